@@ -0,0 +1,133 @@
+//! Parsing, formatting and linting benchmarks.
+//!
+//! Run with `cargo bench --bench format`. To measure a change, save a
+//! baseline before it and compare against it after; criterion reports the
+//! regressions:
+//!
+//! ```sh
+//! cargo bench --bench format -- --save-baseline before
+//! cargo bench --bench format -- --baseline before
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use tree_sitter_validatetest::arena::ArenaDocument;
+use tree_sitter_validatetest::format::{format_source, FormatterConfig};
+use tree_sitter_validatetest::lint::lint;
+use tree_sitter_validatetest::Document;
+
+/// A typical scenario, with metadata, comments and a few actions.
+const SMALL: &str = r#"# Seek around while playing
+meta,
+    handles-states=true,
+    args={
+        "videotestsrc num-buffers=300 ! video/x-raw,framerate=30/1 ! fakesink sync=true",
+    },
+    configs={
+        "$(validateflow), pad=fakesink0:sink, buffers-checksum=true",
+    },
+    expected-issues={
+        [expected-issue, issue-id=event::seek-not-handled, sometimes=true],
+    }
+
+pause
+seek, start=1.0, stop=5.0, flags=accurate+flush  # to the middle
+set-properties, element=fakesink0, sync=false, max-lateness=(gint64)-1
+play
+wait, duration=0.5
+check-position, expected-position=1.5
+stop
+"#;
+
+/// A long scenario: `SMALL` with its actions repeated `repeat` times.
+fn large(repeat: usize) -> String {
+    let (meta, actions) = SMALL.split_at(SMALL.find("\npause").unwrap() + 1);
+    let mut source = meta.to_string();
+    for i in 0..repeat {
+        source.push_str(&format!("\n# Round {}\n", i));
+        source.push_str(actions);
+    }
+    source
+}
+
+/// A field holding blocks of array structures nested `depth` times.
+fn nested(depth: usize) -> String {
+    let mut value = "{1, 2, 3}".to_string();
+    for i in 0..depth {
+        value = format!(
+            "{{[level, index={}, values=<{}, {}>, inner={}]}}",
+            i,
+            i,
+            i + 1,
+            value
+        );
+    }
+    format!("meta, args={}\nplay\n", value)
+}
+
+fn inputs() -> Vec<(&'static str, String)> {
+    vec![
+        ("small", SMALL.to_string()),
+        ("large", large(200)),
+        ("nested", nested(8)),
+    ]
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in inputs() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", name), &source, |b, s| {
+            b.iter(|| Document::parse(black_box(s)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("arena", name), &source, |b, s| {
+            b.iter(|| ArenaDocument::parse(black_box(s)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_format(c: &mut Criterion) {
+    let config = FormatterConfig::default();
+    let mut group = c.benchmark_group("format");
+    for (name, source) in inputs() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, s| {
+            b.iter(|| format_source(black_box(s), &config).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Formatting time by nesting depth, which should grow linearly.
+fn bench_format_depth(c: &mut Criterion) {
+    let config = FormatterConfig::default();
+    let mut group = c.benchmark_group("format-depth");
+    for depth in [2, 4, 8, 16] {
+        let source = nested(depth);
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &source, |b, s| {
+            b.iter(|| format_source(black_box(s), &config).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_lint(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lint");
+    for (name, source) in inputs() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, s| {
+            b.iter(|| lint(black_box(s)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_format,
+    bench_format_depth,
+    bench_lint
+);
+criterion_main!(benches);
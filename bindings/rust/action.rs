@@ -0,0 +1,164 @@
+//! The gst-validate actions known at compile time.
+//!
+//! [`KnownAction`] is generated from the built-in registry snapshot,
+//! `actions.toml`, with the parameters of each action, and
+//! [`parameters`] holds their names as constants. Matching on an [`Action`]
+//! checks action and parameter names at compile time, while actions missing
+//! from the snapshot stay plain structures:
+//!
+//! ```
+//! use tree_sitter_validatetest::action::{parameters, Action, KnownAction};
+//! use tree_sitter_validatetest::Document;
+//!
+//! let document = Document::parse("seek, start=1.0, flags=flush\nmy-action, x=1\n")?;
+//! for structure in document.structures {
+//!     match Action::from(structure) {
+//!         Action::Known(KnownAction::Seek, seek) => {
+//!             assert_eq!(seek.get_double(parameters::seek::START)?, 1.0);
+//!         }
+//!         Action::Known(..) => {}
+//!         Action::Unknown(other) => assert_eq!(other.name, "my-action"),
+//!     }
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The [`ActionRegistry`](crate::registry::ActionRegistry) describes the same
+//! actions at runtime, completed by the dumps of the installed gst-validate.
+
+use std::fmt;
+
+use crate::document::Structure;
+
+include!(concat!(env!("OUT_DIR"), "/known_actions.rs"));
+
+/// A parameter of a [`KnownAction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Whether the action fails without this parameter.
+    pub mandatory: bool,
+    /// Human readable description of the accepted types.
+    pub types: &'static str,
+    /// Value used when the parameter is not given.
+    pub default: Option<&'static str>,
+}
+
+impl KnownAction {
+    /// Look up a parameter by name, including the common ones.
+    pub fn parameter(self, name: &str) -> Option<&'static Parameter> {
+        self.parameters()
+            .iter()
+            .chain(COMMON_PARAMETERS)
+            .find(|parameter| parameter.name == name)
+    }
+}
+
+impl fmt::Display for KnownAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A structure of a scenario, with its action when known.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Known(KnownAction, Structure),
+    /// An action missing from the built-in registry, or a structure that is
+    /// not an action.
+    Unknown(Structure),
+}
+
+impl Action {
+    pub fn known(&self) -> Option<KnownAction> {
+        match self {
+            Action::Known(action, _) => Some(*action),
+            Action::Unknown(_) => None,
+        }
+    }
+
+    pub fn structure(&self) -> &Structure {
+        match self {
+            Action::Known(_, structure) | Action::Unknown(structure) => structure,
+        }
+    }
+
+    /// The mandatory parameters of the action that the structure lacks.
+    pub fn missing_parameters(&self) -> Vec<&'static str> {
+        let Action::Known(action, structure) = self else {
+            return Vec::new();
+        };
+        action
+            .parameters()
+            .iter()
+            .filter(|parameter| parameter.mandatory && structure.field(parameter.name).is_none())
+            .map(|parameter| parameter.name)
+            .collect()
+    }
+}
+
+impl From<Structure> for Action {
+    fn from(structure: Structure) -> Self {
+        match KnownAction::from_name(&structure.name) {
+            Some(action) => Action::Known(action, structure),
+            None => Action::Unknown(structure),
+        }
+    }
+}
+
+impl From<Action> for Structure {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Known(_, structure) | Action::Unknown(structure) => structure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::ActionRegistry;
+    use crate::Document;
+
+    #[test]
+    fn test_known_actions() {
+        assert_eq!(
+            KnownAction::from_name("set-vars"),
+            Some(KnownAction::SetVars)
+        );
+        assert_eq!(KnownAction::SetVars.to_string(), "set-vars");
+        assert_eq!(KnownAction::from_name("my-action"), None);
+        assert_eq!(parameters::seek::START_TYPE, "start_type");
+        assert_eq!(parameters::PLAYBACK_TIME, "playback-time");
+        assert!(KnownAction::Seek.parameter("flags").unwrap().mandatory);
+        assert!(KnownAction::Seek.parameter("playback-time").is_some());
+
+        // Generated from the same snapshot as the built-in registry
+        let registry = ActionRegistry::builtin();
+        assert_eq!(KnownAction::ALL.len(), registry.actions().count());
+        for action in KnownAction::ALL {
+            let definition = registry.get(action.name()).unwrap();
+            assert_eq!(action.description(), definition.description);
+            let names: Vec<_> = action.parameters().iter().map(|p| p.name).collect();
+            let expected: Vec<_> = definition.parameters.iter().map(|p| &p.name).collect();
+            assert_eq!(names, expected);
+        }
+        assert_eq!(COMMON_PARAMETERS.len(), registry.common_parameters().len());
+    }
+
+    #[test]
+    fn test_action() {
+        let document = Document::parse("seek, flags=flush\nmy-action, x=1\n").unwrap();
+        let [seek, other] = document.structures.clone().try_into().unwrap();
+        let seek = Action::from(seek);
+        assert_eq!(seek.known(), Some(KnownAction::Seek));
+        assert_eq!(seek.missing_parameters(), ["start"]);
+
+        let other = Action::from(other);
+        assert_eq!(other.known(), None);
+        assert!(other.missing_parameters().is_empty());
+        let structure = Structure::from(other);
+        assert!(structure.semantically_eq(&document.structures[1]));
+    }
+}
@@ -0,0 +1,367 @@
+//! Arena-allocated documents, for batch jobs over many files.
+//!
+//! A [`Document`] owns its text and boxes its values: every name, value and
+//! list is an allocation of its own. An [`ArenaDocument`] holds the same
+//! content in a few flat vectors, its nodes referring to each other by
+//! index and its texts being ranges of the borrowed source, so parsing a
+//! file takes a handful of allocations however large it is.
+//! [`ArenaDocument::to_document`] and friends convert it to the owned form
+//! on demand.
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use crate::document::{parse_tree, Document, Field, FieldValue, Scalar, ScalarKind, Structure};
+use crate::ParseError;
+
+/// Index of a structure of an [`ArenaDocument`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StructureId(u32);
+
+/// Index of a value of an [`ArenaDocument`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueId(u32);
+
+/// A structure, see [`Structure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaStructure {
+    /// Byte range of the name in the source.
+    pub name: Range<usize>,
+    fields: Range<u32>,
+    /// Whether the structure is terminated by a `;`.
+    pub terminated: bool,
+    /// Byte range of the whole structure in the source.
+    pub span: Range<usize>,
+}
+
+/// A `name=value` pair, see [`Field`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaField {
+    /// Byte range of the name in the source.
+    pub name: Range<usize>,
+    pub value: ValueId,
+    /// Byte range of the whole field in the source.
+    pub span: Range<usize>,
+}
+
+/// A value, see [`FieldValue`]. Lists are ranges of the elements of the
+/// document, read with [`ArenaDocument::elements`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArenaValue {
+    /// A single value, as the byte range of its source text.
+    Scalar {
+        kind: ScalarKind,
+        span: Range<usize>,
+    },
+    /// A type cast, with the byte range of the type name.
+    Typed {
+        type_name: Range<usize>,
+        value: ValueId,
+    },
+    /// A `[...]` array.
+    Array(Range<u32>),
+    /// A `<...>` GstValueArray.
+    ValueArray(Range<u32>),
+    /// A `{...}` block.
+    Block(Range<u32>),
+    /// A structure nested in an array or block.
+    Structure(StructureId),
+}
+
+/// A parsed ValidateTest file, borrowing its source.
+#[derive(Debug, Clone, Default)]
+pub struct ArenaDocument<'s> {
+    source: &'s str,
+    roots: Vec<StructureId>,
+    structures: Vec<ArenaStructure>,
+    fields: Vec<ArenaField>,
+    values: Vec<ArenaValue>,
+    elements: Vec<ValueId>,
+    /// Elements and fields of the lists being built, moved to `elements`
+    /// and `fields` once complete so that those of a list are contiguous.
+    pending_elements: Vec<ValueId>,
+    pending_fields: Vec<ArenaField>,
+}
+
+impl<'s> ArenaDocument<'s> {
+    /// Parse a document from source text, failing on any syntax error.
+    pub fn parse(source: &'s str) -> Result<Self, ParseError> {
+        let tree = parse_tree(source)?;
+        Ok(Self::from_node(tree.root_node(), source))
+    }
+
+    /// Build a document from an already parsed `source_file` node.
+    pub fn from_node(root: Node, source: &'s str) -> Self {
+        let mut document = Self {
+            source,
+            ..Self::default()
+        };
+        let mut cursor = root.walk();
+        for child in root.children(&mut cursor) {
+            if child.kind() == "structure" {
+                let id = document.build_structure(child);
+                document.roots.push(id);
+            }
+        }
+        document.pending_elements = Vec::new();
+        document.pending_fields = Vec::new();
+        document
+    }
+
+    fn build_structure(&mut self, node: Node) -> StructureId {
+        let mut name = node.start_byte()..node.start_byte();
+        let mut terminated = false;
+        let start = self.pending_fields.len();
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "structure_name" => name = child.byte_range(),
+                "field_list" => {
+                    let mut field_cursor = child.walk();
+                    for field in child.children(&mut field_cursor) {
+                        if field.kind() == "field" {
+                            let field = self.build_field(field);
+                            self.pending_fields.push(field);
+                        }
+                    }
+                }
+                ";" => terminated = true,
+                _ => {}
+            }
+        }
+
+        let first = self.fields.len() as u32;
+        self.fields.extend(self.pending_fields.drain(start..));
+        self.structures.push(ArenaStructure {
+            name,
+            fields: first..self.fields.len() as u32,
+            terminated,
+            span: node.byte_range(),
+        });
+        StructureId(self.structures.len() as u32 - 1)
+    }
+
+    fn build_field(&mut self, node: Node) -> ArenaField {
+        let name = node
+            .child_by_field_name("name")
+            .map_or(node.start_byte()..node.start_byte(), |n| n.byte_range());
+        let value = node
+            .child_by_field_name("value")
+            .and_then(|v| self.build_value(v))
+            .unwrap_or_else(|| {
+                self.push(ArenaValue::Scalar {
+                    kind: ScalarKind::Unquoted,
+                    span: node.end_byte()..node.end_byte(),
+                })
+            });
+        ArenaField {
+            name,
+            value,
+            span: node.byte_range(),
+        }
+    }
+
+    fn push(&mut self, value: ArenaValue) -> ValueId {
+        self.values.push(value);
+        ValueId(self.values.len() as u32 - 1)
+    }
+
+    fn build_value(&mut self, node: Node) -> Option<ValueId> {
+        let value = match node.kind() {
+            // Wrapper nodes holding exactly one meaningful child
+            "field_value" | "value" | "array_value" | "array_element" => {
+                return (0..node.named_child_count() as u32)
+                    .filter_map(|i| node.named_child(i))
+                    .filter(|c| c.kind() != "comment")
+                    .find_map(|c| self.build_value(c));
+            }
+            "typed_value" => {
+                let type_name = node.child_by_field_name("type")?.byte_range();
+                let value = self.build_value(node.child_by_field_name("value")?)?;
+                ArenaValue::Typed { type_name, value }
+            }
+            "array" => ArenaValue::Array(self.build_elements(node)),
+            "angle_bracket_array" => ArenaValue::ValueArray(self.build_elements(node)),
+            "nested_structure_block" => ArenaValue::Block(self.build_elements(node)),
+            "structure" | "array_structure" => ArenaValue::Structure(self.build_structure(node)),
+            kind => ArenaValue::Scalar {
+                kind: ScalarKind::from_node_kind(kind)?,
+                span: node.byte_range(),
+            },
+        };
+        Some(self.push(value))
+    }
+
+    fn build_elements(&mut self, node: Node) -> Range<u32> {
+        let start = self.pending_elements.len();
+        for child in (0..node.named_child_count() as u32).filter_map(|i| node.named_child(i)) {
+            if child.kind() == "comment" {
+                continue;
+            }
+            if let Some(id) = self.build_value(child) {
+                self.pending_elements.push(id);
+            }
+        }
+        let first = self.elements.len() as u32;
+        self.elements.extend(self.pending_elements.drain(start..));
+        first..self.elements.len() as u32
+    }
+
+    /// The source the document borrows its text from.
+    pub fn source(&self) -> &'s str {
+        self.source
+    }
+
+    /// Text of `span` of the source.
+    pub fn text(&self, span: &Range<usize>) -> &'s str {
+        &self.source[span.clone()]
+    }
+
+    /// The top-level structures of the file, in order.
+    pub fn structures(&self) -> &[StructureId] {
+        &self.roots
+    }
+
+    pub fn structure(&self, id: StructureId) -> &ArenaStructure {
+        &self.structures[id.0 as usize]
+    }
+
+    /// Name of the structure `id`.
+    pub fn name(&self, id: StructureId) -> &'s str {
+        self.text(&self.structure(id).name)
+    }
+
+    /// Fields of the structure `id`, in order.
+    pub fn fields(&self, id: StructureId) -> &[ArenaField] {
+        let fields = &self.structure(id).fields;
+        &self.fields[fields.start as usize..fields.end as usize]
+    }
+
+    /// Look up the first field of the structure `id` with the given name.
+    pub fn field(&self, id: StructureId, name: &str) -> Option<&ArenaField> {
+        self.fields(id).iter().find(|f| self.text(&f.name) == name)
+    }
+
+    pub fn value(&self, id: ValueId) -> &ArenaValue {
+        &self.values[id.0 as usize]
+    }
+
+    /// Elements of the array or block `id`, empty for other values.
+    pub fn elements(&self, id: ValueId) -> &[ValueId] {
+        match self.value(id) {
+            ArenaValue::Array(range) | ArenaValue::ValueArray(range) | ArenaValue::Block(range) => {
+                &self.elements[range.start as usize..range.end as usize]
+            }
+            _ => &[],
+        }
+    }
+
+    /// The owned form of the whole document, without its comments.
+    pub fn to_document(&self) -> Document {
+        Document {
+            structures: self.roots.iter().map(|id| self.to_structure(*id)).collect(),
+        }
+    }
+
+    /// The owned form of the structure `id`.
+    pub fn to_structure(&self, id: StructureId) -> Structure {
+        let structure = self.structure(id);
+        Structure {
+            name: self.text(&structure.name).to_string(),
+            fields: self
+                .fields(id)
+                .iter()
+                .map(|field| Field {
+                    name: self.text(&field.name).to_string(),
+                    value: self.to_value(field.value),
+                    span: field.span.clone(),
+                    leading_comments: Vec::new(),
+                    trailing_comment: None,
+                })
+                .collect(),
+            terminated: structure.terminated,
+            span: structure.span.clone(),
+            leading_comments: Vec::new(),
+            trailing_comment: None,
+        }
+    }
+
+    /// The owned form of the value `id`.
+    pub fn to_value(&self, id: ValueId) -> FieldValue {
+        let elements = || {
+            self.elements(id)
+                .iter()
+                .map(|e| self.to_value(*e))
+                .collect()
+        };
+        match self.value(id) {
+            ArenaValue::Scalar { kind, span } => FieldValue::Scalar(Scalar {
+                kind: *kind,
+                text: self.text(span).to_string(),
+                span: span.clone(),
+            }),
+            ArenaValue::Typed { type_name, value } => FieldValue::Typed {
+                type_name: self.text(type_name).to_string(),
+                value: Box::new(self.to_value(*value)),
+            },
+            ArenaValue::Array(_) => FieldValue::Array(elements()),
+            ArenaValue::ValueArray(_) => FieldValue::ValueArray(elements()),
+            ArenaValue::Block(_) => FieldValue::Block(elements()),
+            ArenaValue::Structure(structure) => {
+                FieldValue::Structure(self.to_structure(*structure))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_document() {
+        let source = "meta, args={\"a\", -t}, # comment\n    configs=<1, (int)2>\n\
+                      seek, start=1.0, flags=flush+accurate;\n\
+                      foo, issues={[expected-issue, id=a::b, sometimes=true], [x]}, empty=[]\n";
+        let document = ArenaDocument::parse(source).unwrap();
+        assert_eq!(document.structures().len(), 3);
+
+        let seek = document.structures()[1];
+        assert_eq!(document.name(seek), "seek");
+        assert!(document.structure(seek).terminated);
+        let flags = document.field(seek, "flags").unwrap();
+        assert_eq!(
+            document.value(flags.value),
+            &ArenaValue::Scalar {
+                kind: ScalarKind::Flags,
+                span: source.find("flush").unwrap()..source.find(";").unwrap(),
+            }
+        );
+
+        // Lists of a nested structure stay contiguous
+        let foo = document.structures()[2];
+        let issues = document.elements(document.field(foo, "issues").unwrap().value);
+        assert_eq!(issues.len(), 2);
+        // `[expected-issue, ...]`, an array holding a structure
+        let ArenaValue::Structure(issue) = document.value(document.elements(issues[0])[0]) else {
+            panic!("not a structure");
+        };
+        assert_eq!(document.fields(*issue).len(), 2);
+
+        let mut owned = Document::parse(source).unwrap();
+        // The arena does not keep comments
+        assert_eq!(
+            owned.structures[0].fields[0]
+                .trailing_comment
+                .take()
+                .as_deref(),
+            Some("comment")
+        );
+        assert_eq!(
+            serde_json::to_value(document.to_document()).unwrap(),
+            serde_json::to_value(&owned).unwrap()
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
 fn main() {
     let src_dir = std::path::Path::new("src");
 
@@ -12,4 +15,204 @@ fn main() {
     println!("cargo:rerun-if-changed={}", parser_path.to_str().unwrap());
 
     c_config.compile("tree-sitter-validatetest");
+
+    let actions_path = Path::new("bindings/rust/actions.toml");
+    println!("cargo:rerun-if-changed={}", actions_path.display());
+    let actions = std::fs::read_to_string(actions_path).unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(
+        Path::new(&out_dir).join("known_actions.rs"),
+        known_actions(&actions),
+    )
+    .unwrap();
+}
+
+/// `set-vars` as `SetVars`.
+fn camel_case(name: &str) -> String {
+    name.split(['-', '_'])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect()
+}
+
+/// `playback-time` as `PLAYBACK_TIME`, or as `playback_time` for modules.
+fn snake_case(name: &str, upper: bool) -> String {
+    let name = name.replace(['-', '.', ':'], "_");
+    if upper {
+        name.to_ascii_uppercase()
+    } else {
+        name
+    }
+}
+
+fn text(table: &toml::Table, key: &str) -> String {
+    table
+        .get(key)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The `Parameter` literals of `parameters`, a TOML array of tables.
+fn parameter_literals(parameters: Option<&toml::Value>) -> Vec<(String, String)> {
+    let parameters = parameters.and_then(|p| p.as_array()).cloned();
+    parameters
+        .unwrap_or_default()
+        .iter()
+        .map(|parameter| {
+            let parameter = parameter.as_table().expect("parameters are tables");
+            let name = text(parameter, "name");
+            let default = match parameter.get("default").and_then(|d| d.as_str()) {
+                Some(default) => format!("Some({:?})", default),
+                None => "None".to_string(),
+            };
+            let literal = format!(
+                "Parameter {{ name: {:?}, description: {:?}, mandatory: {}, types: {:?}, default: {} }}",
+                name,
+                text(parameter, "description"),
+                parameter
+                    .get("mandatory")
+                    .and_then(|m| m.as_bool())
+                    .unwrap_or(false),
+                text(parameter, "types"),
+                default,
+            );
+            (name, literal)
+        })
+        .collect()
+}
+
+/// Constants named after `parameters`, indented by `indent`.
+fn parameter_constants(out: &mut String, parameters: &[(String, String)], indent: &str) {
+    let mut seen = Vec::new();
+    for (name, _) in parameters {
+        let constant = snake_case(name, true);
+        assert!(
+            !seen.contains(&constant),
+            "parameters named like {} in actions.toml",
+            constant
+        );
+        writeln!(out, "{}pub const {}: &str = {:?};", indent, constant, name).unwrap();
+        seen.push(constant);
+    }
+}
+
+/// An action name, description and parameter literals.
+type ActionLiterals = (String, String, Vec<(String, String)>);
+
+/// The `KnownAction` enum and parameter names of the built-in registry.
+fn known_actions(source: &str) -> String {
+    let registry: toml::Table = source.parse().expect("actions.toml is valid TOML");
+    let common = parameter_literals(registry.get("common-parameters"));
+    let actions: Vec<ActionLiterals> = registry
+        .get("actions")
+        .and_then(|actions| actions.as_table())
+        .expect("actions.toml has actions")
+        .iter()
+        .map(|(name, action)| {
+            let action = action.as_table().expect("actions are tables");
+            (
+                name.clone(),
+                text(action, "description"),
+                parameter_literals(action.get("parameters")),
+            )
+        })
+        .collect();
+
+    let mut out = String::from("// Generated by build.rs from bindings/rust/actions.toml\n\n");
+    out.push_str("/// The actions of the built-in registry.\n");
+    out.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]\n");
+    out.push_str("#[non_exhaustive]\npub enum KnownAction {\n");
+    for (name, description, _) in &actions {
+        writeln!(out, "    /// `{}`: {}", name, description).unwrap();
+        writeln!(out, "    {},", camel_case(name)).unwrap();
+    }
+    out.push_str("}\n\nimpl KnownAction {\n");
+    out.push_str("    /// Every known action, by name.\n");
+    out.push_str("    pub const ALL: &'static [KnownAction] = &[\n");
+    for (name, ..) in &actions {
+        writeln!(out, "        KnownAction::{},", camel_case(name)).unwrap();
+    }
+    out.push_str("    ];\n\n");
+
+    out.push_str("    /// The name of the action, as written in scenarios.\n");
+    out.push_str("    pub const fn name(self) -> &'static str {\n        match self {\n");
+    for (name, ..) in &actions {
+        writeln!(
+            out,
+            "            KnownAction::{} => {:?},",
+            camel_case(name),
+            name
+        )
+        .unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+
+    out.push_str("    /// The action named `name`.\n");
+    out.push_str("    pub fn from_name(name: &str) -> Option<Self> {\n        Some(match name {\n");
+    for (name, ..) in &actions {
+        writeln!(
+            out,
+            "            {:?} => KnownAction::{},",
+            name,
+            camel_case(name)
+        )
+        .unwrap();
+    }
+    out.push_str("            _ => return None,\n        })\n    }\n\n");
+
+    out.push_str("    /// What the action does.\n");
+    out.push_str("    pub const fn description(self) -> &'static str {\n        match self {\n");
+    for (name, description, _) in &actions {
+        writeln!(
+            out,
+            "            KnownAction::{} => {:?},",
+            camel_case(name),
+            description
+        )
+        .unwrap();
+    }
+    out.push_str("        }\n    }\n\n");
+
+    out.push_str("    /// The parameters specific to the action, without the common ones.\n");
+    out.push_str(
+        "    pub const fn parameters(self) -> &'static [Parameter] {\n        match self {\n",
+    );
+    for (name, _, parameters) in &actions {
+        writeln!(out, "            KnownAction::{} => &[", camel_case(name)).unwrap();
+        for (_, literal) in parameters {
+            writeln!(out, "                {},", literal).unwrap();
+        }
+        out.push_str("            ],\n");
+    }
+    out.push_str("        }\n    }\n}\n\n");
+
+    out.push_str("/// Parameters accepted by every action.\n");
+    out.push_str("pub const COMMON_PARAMETERS: &[Parameter] = &[\n");
+    for (_, literal) in &common {
+        writeln!(out, "    {},", literal).unwrap();
+    }
+    out.push_str("];\n\n");
+
+    out.push_str("/// The names of the parameters of the known actions, as constants.\n");
+    out.push_str(
+        "///\n/// Those accepted by every action are at the top, the others in a module\n",
+    );
+    out.push_str("/// per action.\npub mod parameters {\n");
+    parameter_constants(&mut out, &common, "    ");
+    for (name, _, parameters) in &actions {
+        if parameters.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n    /// Parameters of `{}`.", name).unwrap();
+        writeln!(out, "    pub mod {} {{", snake_case(name, false)).unwrap();
+        parameter_constants(&mut out, parameters, "        ");
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    out
 }
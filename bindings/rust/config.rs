@@ -0,0 +1,454 @@
+//! `.validatetest-fmt.toml` configuration files.
+//!
+//! Settings are looked up from the directory of the formatted file upwards.
+//! Files closer to it override the keys they set in the ones further away,
+//! and a file with `root = true` stops the lookup:
+//!
+//! ```toml
+//! root = true
+//! indent = 2
+//! line-length = 100
+//! sort-expected-issues = true
+//! quoted-structures = ["my-override"]
+//! line-ending = "auto"
+//! array-packing = "one-per-line"
+//!
+//! [field-order]
+//! seek = ["playback-time", "start", "stop", "rate", "flags"]
+//!
+//! [lint]
+//! meta-fields = ["args", "handles-states"]
+//!
+//! [lint.levels]
+//! style = "warn"
+//! unknown-action = "deny"
+//!
+//! [[lint.rules]]
+//! code = "timed-seeks"
+//! structure = "seek"
+//! require = ["playback-time"]
+//! ```
+//!
+//! See [`crate::custom_rules`] for the rules of `[[lint.rules]]` tables.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::custom_rules::DeclaredRule;
+use crate::dialect::Dialect;
+use crate::flow::FlowAlign;
+use crate::format::{
+    ArrayPacking, BraceStyle, ContinuationIndent, FormatterConfig, LineEnding, Semicolon,
+};
+use crate::lint::{Level, LintConfig};
+
+/// Name of the configuration files.
+pub const FILE_NAME: &str = ".validatetest-fmt.toml";
+
+/// Formatter settings of a configuration file, named like the
+/// `validatetest-fmt` options. Unset keys are inherited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FileConfig {
+    /// Do not look for configuration files in parent directories.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub root: bool,
+    pub indent: Option<usize>,
+    pub line_length: Option<usize>,
+    pub sort_expected_issues: Option<bool>,
+    pub keep_quoted_structures: Option<bool>,
+    /// Structure names converted in addition to the default ones and to
+    /// those of the inherited files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quoted_structures: Vec<String>,
+    pub convert_quoted_structures: Option<bool>,
+    pub legacy_quoted_issues: Option<bool>,
+    pub keep_long_strings: Option<bool>,
+    pub split_long_args: Option<bool>,
+    pub line_ending: Option<LineEnding>,
+    pub strip_bom: Option<bool>,
+    pub array_packing: Option<ArrayPacking>,
+    pub array_packing_threshold: Option<usize>,
+    pub brace_style: Option<BraceStyle>,
+    pub no_block_trailing_comma: Option<bool>,
+    pub no_array_trailing_comma: Option<bool>,
+    pub spaces_around_equals: Option<bool>,
+    pub continuation_indent: Option<ContinuationIndent>,
+    pub max_inline_fields: Option<usize>,
+    pub preserve_breaks: Option<bool>,
+    pub minimal_diff: Option<bool>,
+    pub semicolon: Option<Semicolon>,
+    /// The kind of the files, rather than the one told by their extension.
+    pub dialect: Option<Dialect>,
+    pub flow_align: Option<FlowAlign>,
+    /// Canonical field order by structure name, in a `[field-order]`
+    /// table. Lists override the inherited ones of the same structure.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_order: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "LintFileConfig::is_empty")]
+    pub lint: LintFileConfig,
+}
+
+/// Lint settings of a configuration file, in its `[lint]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LintFileConfig {
+    /// Report files without a `meta` structure. Defaults to whether the
+    /// file is a `.validatetest` one.
+    pub require_meta: Option<bool>,
+    /// Fields required in `meta`, replacing the inherited list.
+    pub meta_fields: Option<Vec<String>>,
+    /// Levels by lint code or category, in a `[lint.levels]` table. They
+    /// override the inherited ones of the same lint or category. Unknown
+    /// names are only found once the rules are known, see
+    /// [`LintConfig::unknown_levels`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, Level>,
+    /// Rules of `[[lint.rules]]` tables. They replace the inherited ones of
+    /// the same code.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<DeclaredRule>,
+}
+
+impl LintFileConfig {
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Apply the keys set in `self` to `config`.
+    pub fn apply(&self, config: &mut LintConfig) {
+        if let Some(require) = self.require_meta {
+            config.require_meta = require;
+        }
+        if let Some(fields) = &self.meta_fields {
+            config.meta_fields.clone_from(fields);
+        }
+        config.add_levels(
+            self.levels
+                .iter()
+                .map(|(name, level)| (name.clone(), *level)),
+        );
+        for rule in &self.rules {
+            config.register(rule.clone());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Toml(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl FileConfig {
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        Self::from_toml(&text).map_err(|e| ConfigError::Toml(path.to_owned(), e))
+    }
+
+    /// Override the keys of `self` with those set in `other`.
+    pub fn merge(&mut self, other: &FileConfig) {
+        fn set<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                value.clone_from(other);
+            }
+        }
+        self.root |= other.root;
+        set(&mut self.indent, &other.indent);
+        set(&mut self.line_length, &other.line_length);
+        set(&mut self.sort_expected_issues, &other.sort_expected_issues);
+        set(
+            &mut self.keep_quoted_structures,
+            &other.keep_quoted_structures,
+        );
+        self.quoted_structures
+            .extend(other.quoted_structures.iter().cloned());
+        set(
+            &mut self.convert_quoted_structures,
+            &other.convert_quoted_structures,
+        );
+        set(&mut self.legacy_quoted_issues, &other.legacy_quoted_issues);
+        set(&mut self.keep_long_strings, &other.keep_long_strings);
+        set(&mut self.split_long_args, &other.split_long_args);
+        set(&mut self.line_ending, &other.line_ending);
+        set(&mut self.strip_bom, &other.strip_bom);
+        set(&mut self.array_packing, &other.array_packing);
+        set(
+            &mut self.array_packing_threshold,
+            &other.array_packing_threshold,
+        );
+        set(&mut self.brace_style, &other.brace_style);
+        set(
+            &mut self.no_block_trailing_comma,
+            &other.no_block_trailing_comma,
+        );
+        set(
+            &mut self.no_array_trailing_comma,
+            &other.no_array_trailing_comma,
+        );
+        set(&mut self.spaces_around_equals, &other.spaces_around_equals);
+        set(&mut self.continuation_indent, &other.continuation_indent);
+        set(&mut self.max_inline_fields, &other.max_inline_fields);
+        set(&mut self.preserve_breaks, &other.preserve_breaks);
+        set(&mut self.minimal_diff, &other.minimal_diff);
+        set(&mut self.semicolon, &other.semicolon);
+        set(&mut self.dialect, &other.dialect);
+        set(&mut self.flow_align, &other.flow_align);
+        self.field_order.extend(
+            other
+                .field_order
+                .iter()
+                .map(|(name, order)| (name.clone(), order.clone())),
+        );
+        set(&mut self.lint.require_meta, &other.lint.require_meta);
+        set(&mut self.lint.meta_fields, &other.lint.meta_fields);
+        self.lint.levels.extend(
+            other
+                .lint
+                .levels
+                .iter()
+                .map(|(name, level)| (name.clone(), *level)),
+        );
+        for rule in &other.lint.rules {
+            self.lint.rules.retain(|r| r.code != rule.code);
+            self.lint.rules.push(rule.clone());
+        }
+    }
+
+    /// Apply the keys set in `self` to `config`.
+    pub fn apply(&self, config: &mut FormatterConfig) {
+        if let Some(indent) = self.indent {
+            config.indent_width = indent;
+        }
+        if let Some(length) = self.line_length {
+            config.max_line_length = length;
+        }
+        if let Some(sort) = self.sort_expected_issues {
+            config.sort_expected_issues = sort;
+        }
+        if let Some(keep) = self.keep_quoted_structures {
+            config.convert_quoted_structures = !keep;
+        }
+        for name in &self.quoted_structures {
+            if !config.quoted_structure_names.contains(name) {
+                config.quoted_structure_names.push(name.clone());
+            }
+        }
+        if let Some(convert) = self.convert_quoted_structures {
+            config.convert_any_quoted_structure = convert;
+        }
+        if let Some(legacy) = self.legacy_quoted_issues {
+            config.legacy_quoted_issues = legacy;
+        }
+        if let Some(keep) = self.keep_long_strings {
+            config.wrap_long_strings = !keep;
+        }
+        if let Some(split) = self.split_long_args {
+            config.split_long_args = split;
+        }
+        if let Some(line_ending) = self.line_ending {
+            config.line_ending = line_ending;
+        }
+        if let Some(strip) = self.strip_bom {
+            config.strip_bom = strip;
+        }
+        if let Some(packing) = self.array_packing {
+            config.array_packing = packing;
+        }
+        if let Some(threshold) = self.array_packing_threshold {
+            config.array_packing_threshold = threshold;
+        }
+        if let Some(style) = self.brace_style {
+            config.brace_style = style;
+        }
+        if let Some(no_comma) = self.no_block_trailing_comma {
+            config.block_trailing_comma = !no_comma;
+        }
+        if let Some(no_comma) = self.no_array_trailing_comma {
+            config.array_trailing_comma = !no_comma;
+        }
+        if let Some(spaces) = self.spaces_around_equals {
+            config.spaces_around_equals = spaces;
+        }
+        if let Some(indent) = self.continuation_indent {
+            config.continuation_indent = indent;
+        }
+        if let Some(max) = self.max_inline_fields {
+            config.max_inline_fields = Some(max);
+        }
+        if let Some(preserve) = self.preserve_breaks {
+            config.preserve_breaks = preserve;
+        }
+        if let Some(minimal) = self.minimal_diff {
+            config.minimal_diff = minimal;
+        }
+        if let Some(semicolon) = self.semicolon {
+            config.semicolon = semicolon;
+        }
+        if let Some(dialect) = self.dialect {
+            config.dialect = dialect;
+        }
+        if let Some(align) = self.flow_align {
+            config.flow_align = align;
+        }
+        for (name, order) in &self.field_order {
+            config.field_order.insert(name.clone(), order.clone());
+        }
+    }
+
+    /// Every formatter key of `config`, as written in a configuration file.
+    pub fn effective(config: &FormatterConfig) -> Self {
+        Self {
+            root: false,
+            indent: Some(config.indent_width),
+            line_length: Some(config.max_line_length),
+            sort_expected_issues: Some(config.sort_expected_issues),
+            keep_quoted_structures: Some(!config.convert_quoted_structures),
+            quoted_structures: config.quoted_structure_names.clone(),
+            convert_quoted_structures: Some(config.convert_any_quoted_structure),
+            legacy_quoted_issues: Some(config.legacy_quoted_issues),
+            keep_long_strings: Some(!config.wrap_long_strings),
+            split_long_args: Some(config.split_long_args),
+            line_ending: Some(config.line_ending),
+            strip_bom: Some(config.strip_bom),
+            array_packing: Some(config.array_packing),
+            array_packing_threshold: Some(config.array_packing_threshold),
+            brace_style: Some(config.brace_style),
+            no_block_trailing_comma: Some(!config.block_trailing_comma),
+            no_array_trailing_comma: Some(!config.array_trailing_comma),
+            spaces_around_equals: Some(config.spaces_around_equals),
+            continuation_indent: Some(config.continuation_indent),
+            max_inline_fields: config.max_inline_fields,
+            preserve_breaks: Some(config.preserve_breaks),
+            minimal_diff: Some(config.minimal_diff),
+            semicolon: Some(config.semicolon),
+            dialect: Some(config.dialect),
+            flow_align: Some(config.flow_align),
+            field_order: config.field_order.clone(),
+            lint: LintFileConfig::default(),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("configurations always serialize")
+    }
+}
+
+/// Configuration files applying to the files of `directory`, outermost
+/// first, up to the first one with `root = true`.
+pub fn discover(directory: &Path) -> Result<Vec<(PathBuf, FileConfig)>, ConfigError> {
+    let directory =
+        std::path::absolute(directory).map_err(|e| ConfigError::Io(directory.to_owned(), e))?;
+    let mut files = Vec::new();
+    for dir in directory.ancestors() {
+        let path = dir.join(FILE_NAME);
+        if !path.is_file() {
+            continue;
+        }
+        let config = FileConfig::load(&path)?;
+        let root = config.root;
+        files.push((path, config));
+        if root {
+            break;
+        }
+    }
+    files.reverse();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("validatetest-config-{}", std::process::id()));
+        let nested = root.join("project/tests");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(FILE_NAME), "indent = 8\n").unwrap();
+        fs::write(
+            root.join("project").join(FILE_NAME),
+            "root = true\nindent = 2\nline-length = 100\nquoted-structures = [\"a\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            nested.join(FILE_NAME),
+            "line-length = 80\nquoted-structures = [\"b\"]\n[lint]\nrequire-meta = false\n\
+             [lint.levels]\nstyle = \"warn\"\n",
+        )
+        .unwrap();
+        let chain = discover(&nested);
+        fs::write(nested.join(FILE_NAME), "indnet = 2\n").unwrap();
+        let invalid = discover(&nested);
+        fs::remove_dir_all(&root).unwrap();
+
+        let chain = chain.unwrap();
+        assert_eq!(
+            chain.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(),
+            vec![root.join("project").join(FILE_NAME), nested.join(FILE_NAME)]
+        );
+        let mut merged = FileConfig::default();
+        for (_, config) in &chain {
+            merged.merge(config);
+        }
+        let mut config = FormatterConfig::default();
+        merged.apply(&mut config);
+        assert_eq!(config.indent_width, 2);
+        assert_eq!(config.max_line_length, 80);
+        assert!(config
+            .quoted_structure_names
+            .ends_with(&["a".into(), "b".into()]));
+        let mut lint_config = LintConfig {
+            require_meta: true,
+            ..Default::default()
+        };
+        merged.lint.apply(&mut lint_config);
+        assert!(!lint_config.require_meta);
+        assert_eq!(lint_config.level("trailing-comma"), Some(Level::Warn));
+
+        assert!(matches!(invalid, Err(ConfigError::Toml(..))));
+        let levels = FileConfig::from_toml("[lint.levels]\nstlye = \"warn\"\n").unwrap();
+        levels.lint.apply(&mut lint_config);
+        assert_eq!(lint_config.unknown_levels().collect::<Vec<_>>(), ["stlye"]);
+    }
+
+    #[test]
+    fn test_effective_round_trip() {
+        let config = FormatterConfig {
+            line_ending: LineEnding::Crlf,
+            continuation_indent: ContinuationIndent::Columns(8),
+            dialect: Dialect::MediaInfo,
+            ..Default::default()
+        };
+        let text = FileConfig::effective(&config).to_toml();
+        assert!(text.contains("line-ending = \"crlf\""));
+        assert!(text.contains("continuation-indent = 8"));
+        assert!(text.contains("dialect = \"media-info\""));
+        let mut parsed = FormatterConfig::default();
+        FileConfig::from_toml(&text).unwrap().apply(&mut parsed);
+        assert_eq!(parsed.line_ending, LineEnding::Crlf);
+        assert_eq!(parsed.continuation_indent, ContinuationIndent::Columns(8));
+        assert_eq!(parsed.dialect, Dialect::MediaInfo);
+        assert_eq!(parsed.quoted_structure_names, config.quoted_structure_names);
+    }
+}
@@ -0,0 +1,192 @@
+//! Conversion between `.scenario` and `.validatetest` files.
+//!
+//! Both formats are GstStructure streams holding the same actions. They
+//! differ in their header: `.scenario` files start with a `description`
+//! structure, while `.validatetest` files use `meta`, which additionally
+//! carries the pipeline (`args`), `configs` and `expected-issues`.
+//!
+//! Conversions only rewrite the header, leaving comments and the layout of
+//! the actions untouched.
+
+use tree_sitter::Node;
+
+use crate::document::parse_tree;
+use crate::refactor::{apply_edits, remove_fields, TextEdit};
+use crate::strings::quote;
+use crate::ParseError;
+
+/// `meta` fields with no `description` equivalent.
+const VALIDATETEST_ONLY_FIELDS: &[&str] = &["args", "configs", "expected-issues"];
+
+/// The result of a conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversion {
+    pub source: String,
+    /// Information lost or missing during the conversion.
+    pub warnings: Vec<String>,
+}
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// The first top-level structure named `name`.
+fn find_header<'t>(root: Node<'t>, source: &str, name: &str) -> Option<Node<'t>> {
+    let mut cursor = root.walk();
+    let header = root
+        .children(&mut cursor)
+        .find(|c| c.kind() == "structure" && c.child(0).is_some_and(|n| text(n, source) == name));
+    header
+}
+
+/// Convert a `.scenario` file to the `.validatetest` layout.
+///
+/// The `description` header becomes `meta`. `args` holds the pipeline
+/// arguments to run the scenario with (e.g. `["playbin", "uri=..."]`); when
+/// empty, no `args` field is added and a warning reminds to add one.
+pub fn scenario_to_validatetest(source: &str, args: &[String]) -> Result<Conversion, ParseError> {
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    let mut warnings = Vec::new();
+
+    let args_field = if args.is_empty() {
+        warnings.push("no pipeline arguments given, add an `args` field to `meta`".to_string());
+        None
+    } else {
+        let args: Vec<String> = args.iter().map(|a| quote(a)).collect();
+        Some(format!("args={{{}}}", args.join(", ")))
+    };
+
+    let mut edits = Vec::new();
+    match find_header(root, source, "description") {
+        Some(header) => {
+            let name = header.child(0).expect("structures have a name");
+            edits.push(TextEdit {
+                range: name.byte_range(),
+                new_text: "meta".to_string(),
+            });
+            if let Some(args_field) = args_field {
+                let mut cursor = header.walk();
+                let last_field = header
+                    .children(&mut cursor)
+                    .find(|c| c.kind() == "field_list")
+                    .and_then(|l| {
+                        let mut cursor = l.walk();
+                        let last = l
+                            .children(&mut cursor)
+                            .filter(|c| c.kind() == "field")
+                            .last();
+                        last
+                    });
+                let at = last_field.map_or(name.end_byte(), |f| f.end_byte());
+                edits.push(TextEdit {
+                    range: at..at,
+                    new_text: format!(", {}", args_field),
+                });
+            }
+        }
+        None => {
+            warnings.push("no `description` header found, adding an empty `meta`".to_string());
+            let mut meta = "meta".to_string();
+            if let Some(args_field) = args_field {
+                meta.push_str(", ");
+                meta.push_str(&args_field);
+            }
+            meta.push('\n');
+            edits.push(TextEdit {
+                range: 0..0,
+                new_text: meta,
+            });
+        }
+    }
+
+    Ok(Conversion {
+        source: apply_edits(source, &edits),
+        warnings,
+    })
+}
+
+/// Convert a `.validatetest` file to the `.scenario` layout.
+///
+/// The `meta` header becomes `description`; its `args`, `configs` and
+/// `expected-issues` fields have no scenario equivalent and are dropped with
+/// a warning each.
+pub fn validatetest_to_scenario(source: &str) -> Result<Conversion, ParseError> {
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    let mut warnings = Vec::new();
+
+    let Some(header) = find_header(root, source, "meta") else {
+        warnings.push("no `meta` header found".to_string());
+        return Ok(Conversion {
+            source: source.to_string(),
+            warnings,
+        });
+    };
+
+    let name = header.child(0).expect("structures have a name");
+    let mut edits = vec![TextEdit {
+        range: name.byte_range(),
+        new_text: "description".to_string(),
+    }];
+    edits.extend(remove_fields(header, |field| {
+        let field_name = field
+            .child_by_field_name("name")
+            .map_or("", |n| text(n, source));
+        let dropped = VALIDATETEST_ONLY_FIELDS.contains(&field_name);
+        if dropped {
+            warnings.push(format!(
+                "dropped `{}` which .scenario files do not support",
+                field_name
+            ));
+        }
+        dropped
+    }));
+
+    Ok(Conversion {
+        source: apply_edits(source, &edits),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_to_validatetest() {
+        let source = "# Seek test\ndescription, seek=true, handles-states=true\nseek, start=1.0\n";
+        let converted = scenario_to_validatetest(
+            source,
+            &["playbin".to_string(), "uri=\"file:///a.mp4\"".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            converted.source,
+            "# Seek test\nmeta, seek=true, handles-states=true, \
+             args={\"playbin\", \"uri=\\\"file:///a.mp4\\\"\"}\nseek, start=1.0\n"
+        );
+        assert!(converted.warnings.is_empty());
+
+        let converted = scenario_to_validatetest("play\n", &[]).unwrap();
+        assert_eq!(converted.source, "meta\nplay\n");
+        assert_eq!(converted.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_validatetest_to_scenario() {
+        let source = "meta,\n    seek=true,\n    args={\"playbin\"},\n    \
+                      expected-issues={[expected-issue, issue-id=a]}\nplay\n";
+        let converted = validatetest_to_scenario(source).unwrap();
+        assert_eq!(converted.source, "description,\n    seek=true\nplay\n");
+        assert_eq!(converted.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = "description, summary=\"Plays\", seek=true\nplay\n";
+        let validatetest = scenario_to_validatetest(source, &["playbin".to_string()]).unwrap();
+        let scenario = validatetest_to_scenario(&validatetest.source).unwrap();
+        assert_eq!(scenario.source, source);
+    }
+}
@@ -0,0 +1,254 @@
+//! Lint rules declared in the `[[lint.rules]]` tables of configuration
+//! files, for project conventions that do not need a [`LintRule`] written
+//! in Rust:
+//!
+//! ```toml
+//! [[lint.rules]]
+//! code = "no-unsynced-sinks"
+//! structure = "set-properties"
+//! forbid = ["sync=false"]
+//! message = "sinks must stay synchronized, seeks are checked against the clock"
+//!
+//! [[lint.rules]]
+//! code = "timed-seeks"
+//! structure = "seek"
+//! require = ["playback-time"]
+//! ```
+//!
+//! `forbid` lists fields the structure must not have, any value when
+//! written as a bare name, and `require` fields it must have. Rules check
+//! nested and quoted structures too, and are given levels and suppressed
+//! like the built-in lints.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::document::Structure;
+use crate::lint::{
+    is_lint_name, literal, visit_structures, Category, Diagnostic, LintContext, LintRule, Severity,
+};
+
+/// A rule of a `[[lint.rules]]` table.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DeclaredRule {
+    /// The code of its diagnostics, which must not be a built-in one.
+    pub code: RuleCode,
+    /// Name of the structures it checks.
+    pub structure: String,
+    /// Fields the structure must not have, as `name` or `name=value`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbid: Vec<FieldPattern>,
+    /// Fields the structure must have.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<String>,
+    /// Message of its diagnostics, replacing the one telling which field
+    /// is forbidden or missing.
+    pub message: Option<String>,
+    #[serde(default)]
+    pub category: Category,
+}
+
+/// A field forbidden by a [`DeclaredRule`]: `sync`, or `sync=false` for
+/// that value only.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FieldPattern {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl TryFrom<String> for FieldPattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, String> {
+        let (name, value) = match pattern.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+            None => (pattern.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(format!("expected a field name in '{}'", pattern));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+impl From<FieldPattern> for String {
+    fn from(pattern: FieldPattern) -> Self {
+        pattern.to_string()
+    }
+}
+
+impl fmt::Display for FieldPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.name, value),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// The code of a [`DeclaredRule`], kept for the life of the process as
+/// diagnostics refer to their code statically. Each code is only leaked
+/// once, however many times the configuration is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct RuleCode(pub &'static str);
+
+impl<'de> Deserialize<'de> for RuleCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        static CODES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+        let code = String::deserialize(deserializer)?;
+        if code.is_empty() || is_lint_name(&code) {
+            return Err(de::Error::custom(format!(
+                "'{}' cannot be the code of a rule, it is a built-in lint or category",
+                code
+            )));
+        }
+        let mut codes = CODES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(code) = codes.get(code.as_str()) {
+            return Ok(RuleCode(code));
+        }
+        let code: &'static str = Box::leak(code.into_boxed_str());
+        codes.insert(code);
+        Ok(RuleCode(code))
+    }
+}
+
+impl DeclaredRule {
+    fn check_structure(
+        &self,
+        structure: &Structure,
+        quoted_span: Option<&Range<usize>>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let diagnostic = |range: &Range<usize>, message: String| {
+            Diagnostic::new(
+                quoted_span.unwrap_or(range).clone(),
+                Severity::Warning,
+                self.code.0,
+                self.message.clone().unwrap_or(message),
+            )
+        };
+        for field in &structure.fields {
+            let forbidden = self.forbid.iter().find(|pattern| {
+                pattern.name == field.name
+                    && pattern
+                        .value
+                        .as_ref()
+                        .is_none_or(|value| literal(&field.value).as_ref() == Some(value))
+            });
+            if let Some(pattern) = forbidden {
+                diagnostics.push(diagnostic(
+                    &field.span,
+                    format!("'{}' is forbidden in {}", pattern, structure.name),
+                ));
+            }
+        }
+        let name = structure.span.start..structure.span.start + structure.name.len();
+        for required in &self.require {
+            if !structure.fields.iter().any(|f| &f.name == required) {
+                diagnostics.push(diagnostic(
+                    &name,
+                    format!("{} lacks the field '{}'", structure.name, required),
+                ));
+            }
+        }
+    }
+}
+
+impl LintRule for DeclaredRule {
+    fn code(&self) -> &'static str {
+        self.code.0
+    }
+
+    fn description(&self) -> &str {
+        self.message
+            .as_deref()
+            .unwrap_or("rule of the configuration files")
+    }
+
+    fn category(&self) -> Category {
+        self.category
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for structure in &context.document.structures {
+            visit_structures(structure, None, &mut |s, quoted_span| {
+                if s.name == self.structure {
+                    self.check_structure(s, quoted_span, &mut diagnostics);
+                }
+            });
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::FileConfig;
+    use crate::lint::{lint_with, LintConfig};
+
+    #[test]
+    fn test_declared_rules() {
+        let config = FileConfig::from_toml(
+            r#"
+[[lint.rules]]
+code = "no-unsynced-sinks"
+structure = "set-properties"
+forbid = ["sync=false", "async"]
+
+[[lint.rules]]
+code = "timed-seeks"
+structure = "seek"
+require = ["playback-time"]
+message = "seeks must be timed"
+"#,
+        )
+        .unwrap();
+        let mut lint_config = LintConfig::default();
+        config.lint.apply(&mut lint_config);
+
+        let source = "\
+set-properties, sink::sync=true, sync=(boolean)false, async=true
+seek, start=1.0, flags=flush
+seek, playback-time=1.0, start=2.0, flags=flush
+meta, checks={[set-properties, sync=true]}
+";
+        let diagnostics = lint_with(source, &lint_config).unwrap();
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.code, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    "no-unsynced-sinks",
+                    "'sync=false' is forbidden in set-properties"
+                ),
+                (
+                    "no-unsynced-sinks",
+                    "'async' is forbidden in set-properties"
+                ),
+                ("timed-seeks", "seeks must be timed"),
+            ]
+        );
+        assert_eq!(&source[diagnostics[2].range.clone()], "seek");
+
+        let error = FileConfig::from_toml(
+            "[[lint.rules]]\ncode = \"unknown-action\"\nstructure = \"seek\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("built-in lint"));
+    }
+}
@@ -0,0 +1,92 @@
+//! The kinds of files the tools read.
+//!
+//! Besides scenarios, `gst-validate-media-check-1.0` writes the media info
+//! of a file as structures: a `file` structure with the duration and the
+//! stream topology, then a `frame` structure per buffer:
+//!
+//! ```text
+//! file, uri="file:///clip.mp4", duration=(guint64)10000000000, seekable=true, streams={[stream, type=video, caps="video/x-h264, width=(int)320"]}
+//! frame, stream-id="1c4b/001", pts=(guint64)0, checksum="a8f0d4c2e1b3f5a7c9d1e3f5a7b9c1d3"
+//! ```
+//!
+//! Their caps get a line of their own when formatted, and they are linted
+//! with checks of their own rather than those of scenarios.
+//!
+//! The expectation files of validateflow are not GstStructure streams, see
+//! [`crate::flow`].
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// What a file holds, which decides how it is formatted and linted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    /// `.validatetest` and `.scenario` files.
+    #[default]
+    Scenario,
+    /// `.media_info` files and their `.media_info.push` variant, written by
+    /// `gst-validate-media-check-1.0`.
+    MediaInfo,
+    /// validateflow expectation files, `log-<pad>-expected` in
+    /// `flow-expectations` directories.
+    #[serde(rename = "validateflow")]
+    ValidateFlow,
+}
+
+impl Dialect {
+    /// The dialect of the file at `path`, from its name.
+    pub fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let in_expectations = path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == "flow-expectations");
+        if name.ends_with(".media_info") || name.ends_with(".media_info.push") {
+            Dialect::MediaInfo
+        } else if in_expectations || name.starts_with("log-") && name.ends_with("-expected") {
+            Dialect::ValidateFlow
+        } else {
+            Dialect::Scenario
+        }
+    }
+
+    /// Whether the field `name` holds caps, such as `caps` or
+    /// `streams-caps`.
+    pub fn is_caps_field(self, name: &str) -> bool {
+        self == Dialect::MediaInfo && (name == "caps" || name.ends_with("-caps"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        let dialect = |path: &str| Dialect::from_path(Path::new(path));
+        assert_eq!(dialect("clip.mp4.media_info"), Dialect::MediaInfo);
+        assert_eq!(dialect("dir/clip.mp4.media_info.push"), Dialect::MediaInfo);
+        assert_eq!(dialect("seek.validatetest"), Dialect::Scenario);
+        assert_eq!(dialect("media_info/seek.scenario"), Dialect::Scenario);
+        assert_eq!(dialect("log-sink-sink-expected"), Dialect::ValidateFlow);
+        assert_eq!(
+            dialect("seek/flow-expectations/log-videosink-sink-expected"),
+            Dialect::ValidateFlow
+        );
+        assert_eq!(dialect("flow-expectations/notes"), Dialect::ValidateFlow);
+        assert_eq!(dialect(""), Dialect::Scenario);
+    }
+
+    #[test]
+    fn test_is_caps_field() {
+        assert!(Dialect::MediaInfo.is_caps_field("caps"));
+        assert!(Dialect::MediaInfo.is_caps_field("streams-caps"));
+        assert!(!Dialect::MediaInfo.is_caps_field("capsfilter"));
+        assert!(!Dialect::Scenario.is_caps_field("caps"));
+    }
+}
@@ -0,0 +1,599 @@
+//! Line-based diffing of two versions of a file.
+//!
+//! Used to turn a reformatted file back into the few [`TextEdit`]s that
+//! produce it, so editors and review tools only see the lines that changed,
+//! and to show what `validatetest-fmt --check` would change as a
+//! [`unified_diff`], or a [`colored_unified_diff`] for terminals.
+//! [`touched_lines`] reads unified diffs back, to tell which lines of a file
+//! a patch changed.
+
+use std::ops::Range;
+
+use crate::refactor::TextEdit;
+
+/// Above this edit distance the differing middle of the files is reported as
+/// a single hunk rather than spending quadratic time on an exact diff.
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// A run of lines differing between the old and new text, as 0-based line
+/// ranges of each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Split `text` into lines, keeping their terminators.
+fn lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Runs of matching lines `(old start, new start, length)` of a shortest
+/// edit script between `a` and `b`, in order, using Myers' algorithm.
+fn matching_runs(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let limit = (a.len() + b.len()).min(MAX_EDIT_DISTANCE) as isize;
+    let offset = limit + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // trace[d][k + d]: furthest x reached on diagonal k = x - y with d edits
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=limit {
+        for k in (-d..=d).step_by(2) {
+            let get = |k: isize| v[(k + offset) as usize];
+            let mut x = if moves_down(k, d, get) {
+                get(k + 1)
+            } else {
+                get(k - 1) + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                trace.push((-d..=d).map(|k| v[(k + offset) as usize]).collect());
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push((-d..=d).map(|k| v[(k + offset) as usize]).collect());
+    }
+    None
+}
+
+/// Whether diagonal `k` is reached at step `d` by an insertion from diagonal
+/// `k + 1` rather than by a deletion from `k - 1`.
+fn moves_down(k: isize, d: isize, x_on: impl Fn(isize) -> isize) -> bool {
+    k == -d || (k != d && x_on(k - 1) < x_on(k + 1))
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<(usize, usize, usize)> {
+    let mut runs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let k = x - y;
+        let get = |k: isize| trace[d as usize - 1][(k + d - 1) as usize];
+        let down = moves_down(k, d, get);
+        let previous_k = if down { k + 1 } else { k - 1 };
+        let previous_x = get(previous_k);
+        // Start of the snake following the insertion or deletion
+        let snake_x = if down { previous_x } else { previous_x + 1 };
+        if x > snake_x {
+            runs.push((
+                snake_x as usize,
+                (snake_x - k) as usize,
+                (x - snake_x) as usize,
+            ));
+        }
+        x = previous_x;
+        y = previous_x - previous_k;
+    }
+    if x > 0 {
+        runs.push((0, 0, x as usize));
+    }
+    runs.reverse();
+    runs
+}
+
+/// Runs of matching items `(a start, b start, length)` of `a` and `b`, in
+/// order. Beyond [`MAX_EDIT_DISTANCE`], only their common prefix and suffix
+/// match.
+pub(crate) fn common_runs(a: &[&str], b: &[&str]) -> Vec<(usize, usize, usize)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut runs = vec![(0, 0, prefix)];
+    runs.extend(
+        matching_runs(a_mid, b_mid)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(i, j, len)| (prefix + i, prefix + j, len)),
+    );
+    runs.push((a.len() - suffix, b.len() - suffix, suffix));
+    runs.retain(|(_, _, len)| *len > 0);
+    runs
+}
+
+/// The hunks turning `old` into `new`, in order.
+pub fn line_diff(old: &str, new: &str) -> Vec<Hunk> {
+    let (a, b) = (lines(old), lines(new));
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (run_i, run_j, len) in common_runs(&a, &b)
+        .into_iter()
+        .chain([(a.len(), b.len(), 0)])
+    {
+        if run_i > i || run_j > j {
+            hunks.push(Hunk {
+                old: i..run_i,
+                new: j..run_j,
+            });
+        }
+        i = run_i + len;
+        j = run_j + len;
+    }
+    hunks
+}
+
+/// Edits turning `old` into `new`, one per differing run of lines.
+pub fn diff_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let mut old_offsets = vec![0];
+    old_offsets.extend(lines(old).iter().scan(0, |end, line| {
+        *end += line.len();
+        Some(*end)
+    }));
+    let new_lines = lines(new);
+
+    line_diff(old, new)
+        .into_iter()
+        .map(|hunk| TextEdit {
+            range: old_offsets[hunk.old.start]..old_offsets[hunk.old.end],
+            new_text: new_lines[hunk.new].concat(),
+        })
+        .collect()
+}
+
+/// Append `line` with `prefix`, flagging a missing final newline the way
+/// `diff` does.
+fn push_line(output: &mut String, prefix: char, line: &str) {
+    output.push(prefix);
+    output.push_str(line);
+    if !line.ends_with('\n') {
+        output.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Start and length of a hunk range in a `@@` header, whose start is 1-based
+/// except for empty ranges, which name the line before them.
+fn header_range(range: &Range<usize>) -> String {
+    let start = if range.is_empty() {
+        range.start
+    } else {
+        range.start + 1
+    };
+    format!("{},{}", start, range.len())
+}
+
+/// ANSI escape sequences of [`colored_unified_diff`].
+const BOLD: &str = "\x1b[1m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const REVERSE: &str = "\x1b[7m";
+const NO_REVERSE: &str = "\x1b[27m";
+const RESET: &str = "\x1b[0m";
+
+/// Split `text` into words, runs of blanks, line breaks and single
+/// punctuation characters, the units changes are highlighted by.
+fn tokens(text: &str) -> Vec<&str> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c == ' ' || c == '\t' {
+            1
+        } else {
+            2
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        let joins_next = |next: Option<&(usize, char)>| {
+            next.is_some_and(|&(_, n)| class(c) != 2 && class(n) == class(c))
+        };
+        if !joins_next(chars.peek()) {
+            let end = chars.peek().map_or(text.len(), |&(i, _)| i);
+            tokens.push(&text[start..end]);
+            start = end;
+        }
+    }
+    tokens
+}
+
+/// Write the lines of one side of a hunk, `tokens` with whether each is
+/// missing from the other side, in `color`.
+fn push_colored_lines(output: &mut String, prefix: char, color: &str, tokens: &[(&str, bool)]) {
+    let mut line_start = true;
+    for &(token, changed) in tokens {
+        if line_start {
+            output.push_str(color);
+            output.push(prefix);
+            line_start = false;
+        }
+        if token == "\n" {
+            output.push_str(RESET);
+            output.push('\n');
+            line_start = true;
+        } else if changed {
+            output.push_str(REVERSE);
+            output.push_str(token);
+            output.push_str(NO_REVERSE);
+        } else {
+            output.push_str(token);
+        }
+    }
+    if !line_start {
+        output.push_str(RESET);
+        output.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// The tokens of the old and new lines of a hunk, flagged when they are not
+/// part of the other side.
+fn changed_tokens<'a>(old: &'a str, new: &'a str) -> [Vec<(&'a str, bool)>; 2] {
+    let (a, b) = (tokens(old), tokens(new));
+    let mut old_tokens: Vec<_> = a.iter().map(|t| (*t, true)).collect();
+    let mut new_tokens: Vec<_> = b.iter().map(|t| (*t, true)).collect();
+    for (i, j, len) in common_runs(&a, &b) {
+        old_tokens[i..i + len].iter_mut().for_each(|t| t.1 = false);
+        new_tokens[j..j + len].iter_mut().for_each(|t| t.1 = false);
+    }
+    [old_tokens, new_tokens]
+}
+
+/// Unified diff from `old` to `new` with `context` lines around changes,
+/// empty when they are identical.
+pub fn unified_diff(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> String {
+    write_unified_diff(old, new, old_name, new_name, context, false)
+}
+
+/// [`unified_diff`] with ANSI colors: removed lines in red and added lines in
+/// green, with the words and spaces that changed within them in reverse
+/// video.
+pub fn colored_unified_diff(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> String {
+    write_unified_diff(old, new, old_name, new_name, context, true)
+}
+
+fn write_unified_diff(
+    old: &str,
+    new: &str,
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+    color: bool,
+) -> String {
+    let hunks = line_diff(old, new);
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let (a, b) = (lines(old), lines(new));
+    let mut output = if color {
+        format!(
+            "{}--- {}{}\n{}+++ {}{}\n",
+            BOLD, old_name, RESET, BOLD, new_name, RESET
+        )
+    } else {
+        format!("--- {}\n+++ {}\n", old_name, new_name)
+    };
+
+    // Hunks whose contexts touch are shown together
+    let mut groups: Vec<Vec<Hunk>> = Vec::new();
+    for hunk in hunks {
+        match groups.last_mut() {
+            Some(group)
+                if hunk.old.start - group.last().map_or(0, |h| h.old.end) <= 2 * context =>
+            {
+                group.push(hunk)
+            }
+            _ => groups.push(vec![hunk]),
+        }
+    }
+
+    for group in groups {
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        let before = context.min(first.old.start);
+        let after = context.min(a.len() - last.old.end);
+        let old_range = first.old.start - before..last.old.end + after;
+        let new_range = first.new.start - before..last.new.end + after;
+        let header = format!(
+            "@@ -{} +{} @@",
+            header_range(&old_range),
+            header_range(&new_range)
+        );
+        if color {
+            output.push_str(&format!("{}{}{}\n", CYAN, header, RESET));
+        } else {
+            output.push_str(&header);
+            output.push('\n');
+        }
+
+        let mut position = old_range.start;
+        for hunk in &group {
+            for line in &a[position..hunk.old.start] {
+                push_line(&mut output, ' ', line);
+            }
+            if color {
+                let (removed, added) = (a[hunk.old.clone()].concat(), b[hunk.new.clone()].concat());
+                let [removed, added] = changed_tokens(&removed, &added);
+                push_colored_lines(&mut output, '-', RED, &removed);
+                push_colored_lines(&mut output, '+', GREEN, &added);
+            } else {
+                for line in &a[hunk.old.clone()] {
+                    push_line(&mut output, '-', line);
+                }
+                for line in &b[hunk.new.clone()] {
+                    push_line(&mut output, '+', line);
+                }
+            }
+            position = hunk.old.end;
+        }
+        for line in &a[position..old_range.end] {
+            push_line(&mut output, ' ', line);
+        }
+    }
+    output
+}
+
+/// Start and length of the `12,3` range of a `@@` header.
+fn parse_header_range(range: &str) -> Option<(usize, usize)> {
+    let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+    Some((start.parse().ok()?, len.parse().ok()?))
+}
+
+/// The lines each file of the unified diff `patch` changes, as 0-based line
+/// ranges of its new version, in the order of the patch. Removed lines give
+/// the empty range at the line that follows them.
+///
+/// The `b/` prefix of the paths of git diffs is removed, and deleted files
+/// are left out.
+pub fn touched_lines(patch: &str) -> Vec<(String, Vec<Range<usize>>)> {
+    let mut files: Vec<(String, Vec<Range<usize>>)> = Vec::new();
+    // Whether the lines go to the last file, not to a deleted one
+    let mut in_file = false;
+    // Lines left in the current hunk, on each side
+    let (mut old_left, mut new_left) = (0, 0);
+    // Line of the new version the next hunk line is at
+    let mut line = 0;
+    for text in patch.lines() {
+        if old_left > 0 || new_left > 0 {
+            let touched = match text.as_bytes().first() {
+                Some(b'+') => {
+                    new_left -= 1;
+                    line += 1;
+                    Some(line - 1..line)
+                }
+                Some(b'-') => {
+                    old_left -= 1;
+                    Some(line..line)
+                }
+                Some(b'\\') => None,
+                // Context, whose blank lines may have lost their space
+                _ => {
+                    old_left -= 1;
+                    new_left -= 1;
+                    line += 1;
+                    None
+                }
+            };
+            let ranges = files.last_mut().filter(|_| in_file).map(|file| &mut file.1);
+            if let (Some(ranges), Some(touched)) = (ranges, touched) {
+                // Runs of added lines make one range
+                match ranges.last_mut() {
+                    Some(last)
+                        if last.start < last.end
+                            && touched.start < touched.end
+                            && last.end == touched.start =>
+                    {
+                        last.end = touched.end
+                    }
+                    _ => ranges.push(touched),
+                }
+            }
+        } else if let Some(path) = text.strip_prefix("+++ ") {
+            let path = path.split('\t').next().unwrap_or_default();
+            in_file = path != "/dev/null";
+            if in_file {
+                let path = path.strip_prefix("b/").unwrap_or(path);
+                files.push((path.to_string(), Vec::new()));
+            }
+        } else if let Some(header) = text.strip_prefix("@@ -") {
+            let mut ranges = header.split_whitespace();
+            let old = ranges.next().and_then(parse_header_range);
+            let new = ranges
+                .next()
+                .and_then(|range| range.strip_prefix('+'))
+                .and_then(parse_header_range);
+            if let (Some((_, old_len)), Some((start, new_len))) = (old, new) {
+                (old_left, new_left) = (old_len, new_len);
+                // Empty ranges name the line before them
+                line = if new_len == 0 {
+                    start
+                } else {
+                    start.saturating_sub(1)
+                };
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refactor::apply_edits;
+
+    #[test]
+    fn test_line_diff() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\ne\nf\n";
+        assert_eq!(
+            line_diff(old, new),
+            vec![
+                Hunk {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Hunk {
+                    old: 3..4,
+                    new: 3..3
+                },
+                Hunk {
+                    old: 5..5,
+                    new: 4..5
+                },
+            ]
+        );
+        assert!(line_diff(old, old).is_empty());
+        assert_eq!(
+            line_diff("", "a\n"),
+            vec![Hunk {
+                old: 0..0,
+                new: 0..1
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_edits_round_trip() {
+        let cases = [
+            ("meta,\n    a=1\nplay\n", "meta, a=1\nplay\n"),
+            ("x\ny\nz", "x\ny\nz\n"),
+            ("a\nb\na\nb\n", "b\na\nb\na\n"),
+            ("one\n", ""),
+        ];
+        for (old, new) in cases {
+            let edits = diff_edits(old, new);
+            assert_eq!(apply_edits(old, &edits), new, "{old:?} -> {new:?}");
+        }
+
+        let edits = diff_edits("meta,\n    a=1\nplay\n", "meta, a=1\nplay\n");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range, 0..14);
+    }
+
+    #[test]
+    fn test_touched_lines() {
+        let patch = "\
+diff --git a/seek.validatetest b/seek.validatetest
+index 1111111..2222222 100644
+--- a/seek.validatetest
++++ b/seek.validatetest
+@@ -1,4 +1,5 @@
+ meta, handles-states=true
+-play
++play, a=1
++--- not a header
+ 
+ stop
+@@ -10,2 +11,1 @@ stop
+ pause
+-seek, start=1.0
+--- a/gone.validatetest
++++ /dev/null
+@@ -1 +0,0 @@
+-play
+--- plain.validatetest\t2024-01-01 00:00:00
++++ plain.validatetest\t2024-01-01 00:00:00
+@@ -1,2 +1,2 @@
++play
+ stop
+-pause
+\\ No newline at end of file
+";
+        assert_eq!(
+            touched_lines(patch),
+            vec![
+                ("seek.validatetest".to_string(), vec![1..1, 1..3, 11..11]),
+                ("plain.validatetest".to_string(), vec![0..1, 2..2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\n";
+        assert_eq!(
+            unified_diff(old, new, "old", "new", 1),
+            "--- old\n+++ new\n\
+             @@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n\
+             @@ -7,2 +7,1 @@\n g\n-h\n"
+        );
+        // Close changes share a hunk
+        assert_eq!(
+            unified_diff(old, new, "old", "new", 3)
+                .matches("@@ -")
+                .count(),
+            1
+        );
+        assert_eq!(
+            unified_diff("x", "x\n", "old", "new", 3),
+            "--- old\n+++ new\n@@ -1,1 +1,1 @@\n-x\n\\ No newline at end of file\n+x\n"
+        );
+        assert_eq!(unified_diff(old, old, "old", "new", 3), "");
+    }
+
+    #[test]
+    fn test_colored_unified_diff() {
+        assert_eq!(
+            tokens("seek,  start=1.0\n"),
+            ["seek", ",", "  ", "start", "=", "1", ".", "0", "\n"]
+        );
+
+        let old = "play\nseek,start=1.0, flags=flush\nstop\n";
+        let new = "play\nseek, start=2.0,\n    flags=flush\nstop\n";
+        let diff = colored_unified_diff(old, new, "old", "new", 1);
+        assert_eq!(
+            diff,
+            "\x1b[1m--- old\x1b[0m\n\x1b[1m+++ new\x1b[0m\n\
+             \x1b[36m@@ -1,3 +1,4 @@\x1b[0m\n play\n\
+             \x1b[31m-seek,start=\x1b[7m1\x1b[27m.0,\x1b[7m \x1b[27mflags=flush\x1b[0m\n\
+             \x1b[32m+seek,\x1b[7m \x1b[27mstart=\x1b[7m2\x1b[27m.0,\x1b[0m\n\
+             \x1b[32m+\x1b[7m    \x1b[27mflags=flush\x1b[0m\n stop\n"
+        );
+        // The same lines as without colors
+        let plain: String = diff
+            .split('\x1b')
+            .enumerate()
+            .map(|(i, part)| {
+                if i == 0 {
+                    part
+                } else {
+                    &part[part.find('m').unwrap() + 1..]
+                }
+            })
+            .collect();
+        assert_eq!(plain, unified_diff(old, new, "old", "new", 1));
+        assert_eq!(colored_unified_diff(old, old, "old", "new", 3), "");
+    }
+}
@@ -0,0 +1,1464 @@
+//! Typed representation of ValidateTest documents.
+//!
+//! The tree-sitter parse tree is faithful to the source text, which makes it
+//! awkward to answer questions like "do these two files describe the same
+//! scenario?". [`Document`] is a lightweight owned view over the parse tree
+//! that drops formatting (whitespace, line continuations) and keeps only the
+//! structures, their fields and their values.
+//!
+//! Comments are kept with the structure or field they are attached to by
+//! [`Trivia`]: those on the lines before it lead it, the one ending its last
+//! line trails it. Comments after the last item of a list, and those of
+//! values other than structures, are dropped.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use serde::Serialize;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::pool::ParserPool;
+use crate::strings::{quote, unescape};
+use crate::time::ClockTime;
+use crate::trivia::{Comment, Trivia};
+use crate::value::Value;
+
+/// Error returned when a source text cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Position of the first error node in the source (0-based row/column).
+    pub position: Point,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Parse error at line {}, column {}",
+            self.position.row + 1,
+            self.position.column + 1
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Build an error pointing at the deepest error or missing node under `root`.
+    pub fn from_root(root: Node) -> Self {
+        fn find_error_node(node: Node) -> Option<Node> {
+            if node.kind() == "ERROR" || node.is_missing() {
+                return Some(node);
+            }
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.has_error() || child.kind() == "ERROR" || child.is_missing() {
+                    if let Some(error) = find_error_node(child) {
+                        return Some(error);
+                    }
+                }
+            }
+            None
+        }
+
+        let error_node = find_error_node(root);
+        Self {
+            position: error_node.map_or(root.start_position(), |n| n.start_position()),
+        }
+    }
+}
+
+/// The kind of a scalar value, mirroring the grammar's leaf value nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalarKind {
+    String,
+    Number,
+    HexNumber,
+    Fraction,
+    Boolean,
+    Variable,
+    Expression,
+    Flags,
+    NamespacedIdentifier,
+    CliArgument,
+    Unquoted,
+}
+
+impl ScalarKind {
+    pub(crate) fn from_node_kind(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "string" => Self::String,
+            "number" => Self::Number,
+            "hex_number" => Self::HexNumber,
+            "fraction" => Self::Fraction,
+            "boolean" => Self::Boolean,
+            "variable" => Self::Variable,
+            "expression" => Self::Expression,
+            "flags" => Self::Flags,
+            "namespaced_identifier" => Self::NamespacedIdentifier,
+            "cli_argument" => Self::CliArgument,
+            "unquoted_string" | "identifier" => Self::Unquoted,
+            _ => return None,
+        })
+    }
+}
+
+/// A scalar value, kept as written in the source.
+#[derive(Debug, Clone, Serialize)]
+pub struct Scalar {
+    pub kind: ScalarKind,
+    /// Source text, including the surrounding quotes for strings.
+    pub text: String,
+    /// Byte range of the value in the source.
+    pub span: Range<usize>,
+}
+
+impl Scalar {
+    /// The value with string quoting and escaping removed.
+    ///
+    /// A string GStreamer cannot read, with an incomplete octal escape or
+    /// escaping bytes that are not UTF-8, is returned as written between its
+    /// quotes.
+    pub fn unquoted(&self) -> String {
+        if self.kind == ScalarKind::String && self.text.len() >= 2 {
+            let content = &self.text[1..self.text.len() - 1];
+            unescape(content).unwrap_or_else(|_| content.to_string())
+        } else {
+            self.text.clone()
+        }
+    }
+
+    /// Name of the type the value is deserialized to without a type cast,
+    /// such as `int` or `string`.
+    pub fn inferred_type(&self) -> &'static str {
+        match self.kind {
+            ScalarKind::Number if self.text.parse::<i64>().is_ok() => "int",
+            ScalarKind::Number => "double",
+            ScalarKind::HexNumber => "int",
+            ScalarKind::Fraction => "fraction",
+            ScalarKind::Boolean => "boolean",
+            ScalarKind::Flags => "flags",
+            ScalarKind::Variable => "variable",
+            ScalarKind::Expression => "expression",
+            ScalarKind::String
+            | ScalarKind::NamespacedIdentifier
+            | ScalarKind::CliArgument
+            | ScalarKind::Unquoted => "string",
+        }
+    }
+}
+
+/// Values built in Rust, such as with the [`validatetest!`](crate::validatetest)
+/// macro, have an empty span.
+impl FieldValue {
+    /// A scalar written as `text`, such as `FieldValue::scalar(ScalarKind::Flags,
+    /// "flush+accurate")` or `FieldValue::scalar(ScalarKind::Variable, "$(start)")`.
+    pub fn scalar(kind: ScalarKind, text: impl Into<String>) -> Self {
+        FieldValue::Scalar(Scalar {
+            kind,
+            text: text.into(),
+            span: 0..0,
+        })
+    }
+
+    /// `value` cast to `type_name`, such as `(int)5`.
+    pub fn typed(type_name: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        FieldValue::Typed {
+            type_name: type_name.into(),
+            value: Box::new(value.into()),
+        }
+    }
+}
+
+/// Parses a value as [`parse_value_str`] does.
+impl FromStr for FieldValue {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_value_str(text)
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::new();
+        write_value(self, &mut text);
+        f.write_str(&text)
+    }
+}
+
+/// A quoted string.
+impl From<&str> for FieldValue {
+    fn from(text: &str) -> Self {
+        FieldValue::scalar(ScalarKind::String, quote(text))
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(text: String) -> Self {
+        FieldValue::from(text.as_str())
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::scalar(ScalarKind::Boolean, value.to_string())
+    }
+}
+
+macro_rules! integer_field_value {
+    ($($integer:ty),*) => {
+        $(
+            impl From<$integer> for FieldValue {
+                fn from(value: $integer) -> Self {
+                    FieldValue::scalar(ScalarKind::Number, value.to_string())
+                }
+            }
+        )*
+    };
+}
+
+integer_field_value!(i32, i64, u32, u64);
+
+/// A number with a decimal point, read back as a double.
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        let mut text = value.to_string();
+        if text.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+            text.push_str(".0");
+        }
+        FieldValue::scalar(ScalarKind::Number, text)
+    }
+}
+
+impl From<Structure> for FieldValue {
+    fn from(structure: Structure) -> Self {
+        FieldValue::Structure(structure)
+    }
+}
+
+/// A `[...]` array.
+impl<T: Into<FieldValue>> From<Vec<T>> for FieldValue {
+    fn from(values: Vec<T>) -> Self {
+        FieldValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<FieldValue>, const N: usize> From<[T; N]> for FieldValue {
+    fn from(values: [T; N]) -> Self {
+        FieldValue::from(Vec::from(values))
+    }
+}
+
+/// The value of a field, or an element of an array or block.
+///
+/// Serialized as `{"type": "scalar", "value": ...}`, the variant name in
+/// snake case and its content.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum FieldValue {
+    /// A single value such as `42`, `"foo"`, `$(var)` or `flush+accurate`.
+    Scalar(Scalar),
+    /// A type cast such as `(int)42` or `(string)[a, b]`.
+    Typed {
+        type_name: String,
+        value: Box<FieldValue>,
+    },
+    /// A `[...]` array.
+    Array(Vec<FieldValue>),
+    /// A `<...>` GstValueArray.
+    ValueArray(Vec<FieldValue>),
+    /// A `{...}` block.
+    Block(Vec<FieldValue>),
+    /// A structure nested in an array or block.
+    Structure(Structure),
+}
+
+impl FieldValue {
+    /// Build a value from a `field_value` node or any of the value nodes it wraps.
+    pub fn from_node(node: Node, source: &str) -> Option<Self> {
+        build_value(node, source, &Trivia::new(node, source))
+    }
+
+    /// Compare two values, ignoring formatting and the quotes of strings.
+    pub fn semantically_eq(&self, other: &FieldValue) -> bool {
+        values_eq(self, other, &EqOptions::default())
+    }
+
+    /// The structure held by this value, if any.
+    ///
+    /// Besides nested structures, this recognizes the single-element array
+    /// form (`[expected-issue, ...]`) and quoted serialized structures
+    /// (`"expected-issue, ..."`).
+    pub fn as_structure(&self) -> Option<Structure> {
+        match self {
+            FieldValue::Structure(s) => Some(s.clone()),
+            FieldValue::Array(elems) => match elems.as_slice() {
+                [FieldValue::Structure(s)] => Some(s.clone()),
+                _ => None,
+            },
+            FieldValue::Scalar(s) if s.kind == ScalarKind::String => {
+                let mut doc = Document::parse(&s.unquoted()).ok()?;
+                if doc.structures.len() == 1 && !doc.structures[0].fields.is_empty() {
+                    doc.structures.pop()
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A `name=value` pair.
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub value: FieldValue,
+    /// Byte range of the whole field in the source.
+    pub span: Range<usize>,
+    /// Comments on the lines before the field, without their `#`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub leading_comments: Vec<String>,
+    /// Comment ending the last line of the field, without its `#`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_comment: Option<String>,
+}
+
+/// A structure: a name followed by a list of fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct Structure {
+    pub name: String,
+    pub fields: Vec<Field>,
+    /// Whether the structure is terminated by a `;`.
+    pub terminated: bool,
+    /// Byte range of the whole structure in the source.
+    pub span: Range<usize>,
+    /// Comments on the lines before the structure, without their `#`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub leading_comments: Vec<String>,
+    /// Comment ending the last line of the structure, without its `#`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_comment: Option<String>,
+}
+
+impl Structure {
+    /// A structure without fields, to add them with [`Structure::with_field`].
+    pub fn new(name: impl Into<String>) -> Self {
+        Structure {
+            name: name.into(),
+            fields: Vec::new(),
+            terminated: false,
+            span: 0..0,
+            leading_comments: Vec::new(),
+            trailing_comment: None,
+        }
+    }
+
+    /// The structure with a `name=value` field added at the end.
+    pub fn with_field(mut self, name: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        self.fields.push(Field {
+            name: name.into(),
+            value: value.into(),
+            span: 0..0,
+            leading_comments: Vec::new(),
+            trailing_comment: None,
+        });
+        self
+    }
+
+    /// Look up the first field with the given name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// The `playback-time` of the action, when it is a literal time rather
+    /// than a variable or an expression.
+    pub fn playback_time(&self) -> Option<ClockTime> {
+        let mut value = &self.field("playback-time")?.value;
+        while let FieldValue::Typed { value: inner, .. } = value {
+            value = inner;
+        }
+        match value {
+            FieldValue::Scalar(scalar)
+                if !matches!(scalar.kind, ScalarKind::Variable | ScalarKind::Expression)
+                    && !scalar.text.contains("$(") =>
+            {
+                ClockTime::parse_seconds(&scalar.unquoted())
+            }
+            _ => None,
+        }
+    }
+
+    /// Compare two structures, ignoring formatting and the quotes of strings.
+    pub fn semantically_eq(&self, other: &Structure) -> bool {
+        structures_eq(self, other, &EqOptions::default())
+    }
+
+    /// Compare two structures with the given options.
+    pub fn semantically_eq_with(&self, other: &Structure, options: &EqOptions) -> bool {
+        structures_eq(self, other, options)
+    }
+}
+
+/// Parses a single structure, such as `seek, start=1.0`.
+///
+/// Comments around it are allowed; a second structure is reported as a
+/// [`ParseError`] at its position.
+impl FromStr for Structure {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let tree = parse_tree(text)?;
+        let trivia = Trivia::new(tree.root_node(), text);
+        Ok(build_structure(single_structure(&tree)?, text, &trivia))
+    }
+}
+
+/// Writes the structure on one line, with values as written in the source:
+/// `seek, start=1.0, flags=flush`.
+///
+/// A structure nested in a value is written as the single element of an
+/// array, the only place the syntax allows it.
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::new();
+        write_structure(self, &mut text);
+        f.write_str(&text)
+    }
+}
+
+/// A parsed ValidateTest file.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Document {
+    pub structures: Vec<Structure>,
+}
+
+impl FromStr for Document {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Document::parse(source)
+    }
+}
+
+/// Writes each structure on its own line, as [`Structure`] does, without
+/// the comments.
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for structure in &self.structures {
+            writeln!(f, "{}", structure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options controlling [`Document::semantically_eq_with`].
+#[derive(Debug, Clone, Default)]
+pub struct EqOptions {
+    /// Consider structures equal when they have the same fields in a different order.
+    pub ignore_field_order: bool,
+    /// Fields holding a GstClockTime, whose values compare as times:
+    /// `5.0` (seconds) then equals `5000000000` (nanoseconds).
+    pub time_fields: Vec<String>,
+    /// Compare `args` blocks, which gst-validate joins into a command line,
+    /// by their space-separated words: `{"a b"}` then equals `{"a", "b"}`.
+    pub join_args: bool,
+}
+
+impl Document {
+    /// Parse a document from source text, failing on any syntax error.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let tree = parse_tree(source)?;
+        Ok(Self::from_node(tree.root_node(), source))
+    }
+
+    /// Build a document from an already parsed `source_file` node.
+    pub fn from_node(root: Node, source: &str) -> Self {
+        let trivia = Trivia::new(root, source);
+        let mut cursor = root.walk();
+        let structures = root
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "structure")
+            .map(|c| build_structure(c, source, &trivia))
+            .collect();
+        Self { structures }
+    }
+
+    /// The structures named `name`, in file order.
+    pub fn structures_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Structure> {
+        self.structures.iter().filter(move |s| s.name == name)
+    }
+
+    /// The structures named `name`, in file order, to modify them.
+    pub fn structures_named_mut<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a mut Structure> {
+        self.structures.iter_mut().filter(move |s| s.name == name)
+    }
+
+    /// The structures by name, each group in file order.
+    pub fn group_by_action(&self) -> BTreeMap<&str, Vec<&Structure>> {
+        let mut groups: BTreeMap<&str, Vec<&Structure>> = BTreeMap::new();
+        for structure in &self.structures {
+            groups.entry(&structure.name).or_default().push(structure);
+        }
+        groups
+    }
+
+    /// Sort the actions by `playback-time`, keeping the order of actions
+    /// with the same time.
+    ///
+    /// Only runs of consecutive actions with a literal playback time are
+    /// sorted: an action without one runs after those written before it,
+    /// so it stays between them. See
+    /// [`refactor::sort_actions_by_time`](crate::refactor::sort_actions_by_time)
+    /// to sort them in the source text, keeping comments and layout.
+    pub fn sort_actions_by_time(&mut self) {
+        for run in self.timed_runs() {
+            self.structures[run].sort_by_key(|s| s.playback_time());
+        }
+    }
+
+    /// Indices of the runs of consecutive structures with a literal
+    /// playback time, which can be reordered without changing when the
+    /// other actions execute.
+    pub(crate) fn timed_runs(&self) -> Vec<Range<usize>> {
+        let mut runs = Vec::new();
+        let mut start = None;
+        for (i, structure) in self.structures.iter().enumerate() {
+            match (structure.playback_time(), start) {
+                (Some(_), None) => start = Some(i),
+                (None, Some(run_start)) => {
+                    runs.push(run_start..i);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(run_start) = start {
+            runs.push(run_start..self.structures.len());
+        }
+        runs
+    }
+
+    /// Compare two documents, ignoring formatting, comments and the quotes
+    /// of strings.
+    ///
+    /// Values compare the way GstStructure deserializes them: `(int)5`
+    /// equals `5` and `TRUE` equals `true`, but `(double)5` does not equal
+    /// `5`, an int, and `"5"`, a string, does not either.
+    ///
+    /// Field order is significant; see [`Document::semantically_eq_with`]
+    /// to relax that.
+    pub fn semantically_eq(&self, other: &Document) -> bool {
+        self.semantically_eq_with(other, &EqOptions::default())
+    }
+
+    /// Compare two documents with the given options.
+    pub fn semantically_eq_with(&self, other: &Document, options: &EqOptions) -> bool {
+        self.structures.len() == other.structures.len()
+            && self
+                .structures
+                .iter()
+                .zip(&other.structures)
+                .all(|(a, b)| structures_eq(a, b, options))
+    }
+
+    /// A hash of the semantic content of the document, ignoring formatting,
+    /// comments and the quotes of strings, to find duplicate scenarios.
+    ///
+    /// The hash is stable across runs and versions: it is the 64-bit FNV-1a
+    /// hash of a canonical serialization of the structures, where values are
+    /// written the way [`Document::semantically_eq`] compares them.
+    pub fn fingerprint(&self) -> u64 {
+        let mut canonical = String::new();
+        for structure in &self.structures {
+            canonical_structure(structure, &mut canonical);
+            canonical.push('\n');
+        }
+        // FNV-1a
+        canonical.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+}
+
+/// Parse `text` as a single field value, such as `(int)5`, `[a, b]` or
+/// `{"seek, start=1.0"}`.
+///
+/// The spans of the structures it holds, and the position of a syntax error,
+/// are relative to `text`.
+pub fn parse_value_str(text: &str) -> Result<FieldValue, ParseError> {
+    const PREFIX: &str = "v, v=";
+    let in_text = |mut position: Point| {
+        if position.row == 0 {
+            position.column = position.column.saturating_sub(PREFIX.len());
+        }
+        ParseError { position }
+    };
+    let source = format!("{}{}\n", PREFIX, text);
+    let tree = parse_tree(&source).map_err(|e| in_text(e.position))?;
+    let root = tree.root_node();
+    let mut document = Document::from_node(root, &source);
+
+    // Anything after the value is another field or structure
+    let extra = match document.structures.as_slice() {
+        [structure] => structure.fields.get(1).map(|field| field.span.start),
+        structures => structures.get(1).map(|structure| structure.span.start),
+    };
+    if let Some(offset) = extra {
+        let node = root
+            .descendant_for_byte_range(offset, offset)
+            .unwrap_or(root);
+        return Err(in_text(node.start_position()));
+    }
+    let mut value = document.structures[0]
+        .fields
+        .pop()
+        .expect("parsed field")
+        .value;
+    shift_spans(&mut value, PREFIX.len());
+    Ok(value)
+}
+
+/// Move the spans of `value` and of what it holds back by `offset` bytes.
+fn shift_spans(value: &mut FieldValue, offset: usize) {
+    let shift = |span: &mut Range<usize>| *span = span.start - offset..span.end - offset;
+    match value {
+        FieldValue::Scalar(scalar) => shift(&mut scalar.span),
+        FieldValue::Typed { value, .. } => shift_spans(value, offset),
+        FieldValue::Array(values) | FieldValue::ValueArray(values) | FieldValue::Block(values) => {
+            values
+                .iter_mut()
+                .for_each(|value| shift_spans(value, offset))
+        }
+        FieldValue::Structure(structure) => {
+            shift(&mut structure.span);
+            for field in &mut structure.fields {
+                shift(&mut field.span);
+                shift_spans(&mut field.value, offset);
+            }
+        }
+    }
+}
+
+/// Parse `source` into a tree, keeping error nodes.
+pub(crate) fn parse_tree_with_errors(source: &str) -> Tree {
+    ParserPool::global().parse(source)
+}
+
+/// Parse `source` again after `old_tree` has been updated with
+/// [`Tree::edit`], reusing its unchanged parts.
+#[cfg(feature = "lsp")]
+pub(crate) fn reparse(source: &str, old_tree: &Tree) -> Tree {
+    ParserPool::global().reparse(source, Some(old_tree))
+}
+
+/// The only structure of `tree`, or an error at the end of an empty tree or
+/// at the second structure.
+pub(crate) fn single_structure(tree: &Tree) -> Result<Node<'_>, ParseError> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut structures = root
+        .named_children(&mut cursor)
+        .filter(|c| c.kind() == "structure");
+    let structure = structures.next().ok_or(ParseError {
+        position: root.end_position(),
+    })?;
+    if let Some(extra) = structures.next() {
+        return Err(ParseError {
+            position: extra.start_position(),
+        });
+    }
+    Ok(structure)
+}
+
+/// Parse `source` into a tree, failing on any syntax error.
+pub(crate) fn parse_tree(source: &str) -> Result<Tree, ParseError> {
+    let tree = parse_tree_with_errors(source);
+    if tree.root_node().has_error() {
+        return Err(ParseError::from_root(tree.root_node()));
+    }
+    Ok(tree)
+}
+
+fn node_text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// The text of `comment` without its `#` and the space following it.
+fn comment_text(comment: &Comment, source: &str) -> String {
+    let text = node_text(comment.node, source);
+    let text = text.strip_prefix('#').unwrap_or(text);
+    text.strip_prefix(' ')
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
+
+/// The leading and trailing comments of `node`, a field or a structure.
+///
+/// A structure nested in a value is wrapped in an array element, itself
+/// maybe the value of a block: the comments of these wrappers are its own.
+fn comments(node: Node, source: &str, trivia: &Trivia) -> (Vec<String>, Option<String>) {
+    let mut nodes = vec![node];
+    let mut item = node;
+    while let Some(parent) = item.parent() {
+        let single = |list: Node| {
+            let mut cursor = list.walk();
+            let count = list
+                .named_children(&mut cursor)
+                .filter(|c| c.kind() != "comment")
+                .count();
+            count == 1
+        };
+        match parent.kind() {
+            "array_element" | "array_value" | "field_value" => {}
+            "array" if single(parent) => {}
+            _ => break,
+        }
+        nodes.push(parent);
+        item = parent;
+    }
+    let leading = nodes
+        .iter()
+        .rev()
+        .flat_map(|n| trivia.leading(*n))
+        .map(|c| comment_text(c, source))
+        .collect();
+    let trailing = nodes
+        .iter()
+        .find_map(|n| trivia.trailing(*n))
+        .map(|c| comment_text(c, source));
+    (leading, trailing)
+}
+
+fn build_structure(node: Node, source: &str, trivia: &Trivia) -> Structure {
+    let mut name = String::new();
+    let mut fields = Vec::new();
+    let mut terminated = false;
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "structure_name" => name = node_text(child, source).to_string(),
+            "field_list" => {
+                let mut field_cursor = child.walk();
+                fields = child
+                    .children(&mut field_cursor)
+                    .filter(|c| c.kind() == "field")
+                    .map(|c| build_field(c, source, trivia))
+                    .collect();
+            }
+            ";" => terminated = true,
+            _ => {}
+        }
+    }
+
+    let (leading_comments, trailing_comment) = comments(node, source, trivia);
+    Structure {
+        name,
+        fields,
+        terminated,
+        span: node.byte_range(),
+        leading_comments,
+        trailing_comment,
+    }
+}
+
+fn build_field(node: Node, source: &str, trivia: &Trivia) -> Field {
+    let name = node
+        .child_by_field_name("name")
+        .map(|n| node_text(n, source).to_string())
+        .unwrap_or_default();
+    let value = node
+        .child_by_field_name("value")
+        .and_then(|v| build_value(v, source, trivia))
+        .unwrap_or_else(|| {
+            FieldValue::Scalar(Scalar {
+                kind: ScalarKind::Unquoted,
+                text: String::new(),
+                span: node.end_byte()..node.end_byte(),
+            })
+        });
+    let (leading_comments, trailing_comment) = comments(node, source, trivia);
+    Field {
+        name,
+        value,
+        span: node.byte_range(),
+        leading_comments,
+        trailing_comment,
+    }
+}
+
+fn build_value(node: Node, source: &str, trivia: &Trivia) -> Option<FieldValue> {
+    match node.kind() {
+        // Wrapper nodes holding exactly one meaningful child
+        "field_value" | "value" | "array_value" | "array_element" => {
+            let mut cursor = node.walk();
+            let result = node
+                .named_children(&mut cursor)
+                .filter(|c| c.kind() != "comment")
+                .find_map(|c| build_value(c, source, trivia));
+            result
+        }
+        "typed_value" => {
+            let type_name = node_text(node.child_by_field_name("type")?, source).to_string();
+            let value = build_value(node.child_by_field_name("value")?, source, trivia)?;
+            Some(FieldValue::Typed {
+                type_name,
+                value: Box::new(value),
+            })
+        }
+        "array" => Some(FieldValue::Array(build_children(node, source, trivia))),
+        "angle_bracket_array" => Some(FieldValue::ValueArray(build_children(node, source, trivia))),
+        "nested_structure_block" => Some(FieldValue::Block(build_children(node, source, trivia))),
+        "structure" | "array_structure" => {
+            Some(FieldValue::Structure(build_structure(node, source, trivia)))
+        }
+        kind => ScalarKind::from_node_kind(kind).map(|kind| {
+            FieldValue::Scalar(Scalar {
+                kind,
+                text: node_text(node, source).to_string(),
+                span: node.byte_range(),
+            })
+        }),
+    }
+}
+
+fn build_children(node: Node, source: &str, trivia: &Trivia) -> Vec<FieldValue> {
+    let mut cursor = node.walk();
+    let children: Vec<_> = node.named_children(&mut cursor).collect();
+    children
+        .into_iter()
+        .filter(|c| c.kind() != "comment")
+        .filter_map(|c| build_value(c, source, trivia))
+        .collect()
+}
+
+fn structures_eq(a: &Structure, b: &Structure, options: &EqOptions) -> bool {
+    if a.name != b.name || a.fields.len() != b.fields.len() {
+        return false;
+    }
+
+    if !options.ignore_field_order {
+        return a
+            .fields
+            .iter()
+            .zip(&b.fields)
+            .all(|(fa, fb)| fields_eq(fa, fb, options));
+    }
+
+    let mut used = vec![false; b.fields.len()];
+    a.fields.iter().all(|fa| {
+        let found = b
+            .fields
+            .iter()
+            .enumerate()
+            .position(|(i, fb)| !used[i] && fields_eq(fa, fb, options));
+        match found {
+            Some(i) => {
+                used[i] = true;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+fn fields_eq(a: &Field, b: &Field, options: &EqOptions) -> bool {
+    if a.name != b.name {
+        return false;
+    }
+    if options.join_args && a.name == "args" {
+        if let (Some(wa), Some(wb)) = (args_words(&a.value), args_words(&b.value)) {
+            return wa == wb;
+        }
+    }
+    if options.time_fields.contains(&a.name) {
+        if let (Some(ta), Some(tb)) = (clock_time(&a.value), clock_time(&b.value)) {
+            return ta == tb;
+        }
+    }
+    values_eq(&a.value, &b.value, options)
+}
+
+/// The time held by a time field value: integers are nanoseconds, doubles
+/// seconds, and strings `H:MM:SS.fraction` clock times.
+fn clock_time(value: &FieldValue) -> Option<ClockTime> {
+    match Value::from_field_value(value).ok()? {
+        Value::Int(n) => Some(ClockTime::from_nseconds(u64::try_from(n).ok()?)),
+        Value::UInt(n) => Some(ClockTime::from_nseconds(n)),
+        Value::ClockTime(time) => Some(time),
+        Value::Double(seconds) => ClockTime::from_seconds_f64(seconds),
+        Value::Str(text) => ClockTime::parse_clock(&text),
+        _ => None,
+    }
+}
+
+/// The space-separated words of an `args` block of scalars.
+fn args_words(value: &FieldValue) -> Option<Vec<String>> {
+    let FieldValue::Block(elements) = value else {
+        return None;
+    };
+    let mut words = Vec::new();
+    for element in elements {
+        let FieldValue::Scalar(scalar) = element else {
+            return None;
+        };
+        words.extend(scalar.unquoted().split(' ').map(str::to_string));
+    }
+    Some(words)
+}
+
+/// The value of a scalar, possibly cast to a type, as GstStructure
+/// deserializes it. Quoted values are strings: `"5"` is not the int 5.
+fn scalar_value(value: &FieldValue) -> Option<Value> {
+    match value {
+        FieldValue::Scalar(_) => Value::from_field_value(value).ok(),
+        // Values invalid for their type compare as written
+        FieldValue::Typed { value: inner, .. } if matches!(**inner, FieldValue::Scalar(_)) => {
+            Value::from_field_value(value).ok()
+        }
+        _ => None,
+    }
+}
+
+fn values_eq(a: &FieldValue, b: &FieldValue, options: &EqOptions) -> bool {
+    if let (Some(va), Some(vb)) = (scalar_value(a), scalar_value(b)) {
+        return va == vb;
+    }
+    match (a, b) {
+        (FieldValue::Scalar(sa), FieldValue::Scalar(sb)) => sa.unquoted() == sb.unquoted(),
+        (
+            FieldValue::Typed {
+                type_name: ta,
+                value: va,
+            },
+            FieldValue::Typed {
+                type_name: tb,
+                value: vb,
+            },
+        ) => ta == tb && values_eq(va, vb, options),
+        (FieldValue::Array(ea), FieldValue::Array(eb))
+        | (FieldValue::ValueArray(ea), FieldValue::ValueArray(eb))
+        | (FieldValue::Block(ea), FieldValue::Block(eb)) => {
+            ea.len() == eb.len() && ea.iter().zip(eb).all(|(va, vb)| values_eq(va, vb, options))
+        }
+        (FieldValue::Structure(sa), FieldValue::Structure(sb)) => structures_eq(sa, sb, options),
+        (FieldValue::Scalar(s), FieldValue::Structure(st))
+        | (FieldValue::Structure(st), FieldValue::Scalar(s)) => scalar_eq_structure(s, st, options),
+        // A quoted serialized structure is equivalent to the array structure
+        // the formatter rewrites it to (`"expected-issue, ..."` -> `[expected-issue, ...]`).
+        (FieldValue::Scalar(s), FieldValue::Array(elems))
+        | (FieldValue::Array(elems), FieldValue::Scalar(s)) => match elems.as_slice() {
+            [FieldValue::Structure(st)] if s.kind == ScalarKind::String => {
+                scalar_eq_structure(s, st, options)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn scalar_eq_structure(scalar: &Scalar, structure: &Structure, options: &EqOptions) -> bool {
+    match scalar.kind {
+        // A bare word inside a block can parse either as a value or as a
+        // structure without fields.
+        ScalarKind::Unquoted => structure.fields.is_empty() && scalar.text == structure.name,
+        ScalarKind::String => match Document::parse(&scalar.unquoted()) {
+            Ok(doc) => {
+                doc.structures.len() == 1 && structures_eq(&doc.structures[0], structure, options)
+            }
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// `structure` on a single line, as `name, field=value, ...`.
+/// Write `text` as a comment.
+fn write_comment(text: &str, out: &mut String) {
+    out.push('#');
+    if !text.is_empty() {
+        out.push(' ');
+        out.push_str(text);
+    }
+}
+
+/// Write the structures of `document` with their comments and those of
+/// their fields, one structure per line unless its fields have comments.
+pub(crate) fn write_commented(document: &Document) -> String {
+    let mut out = String::new();
+    for structure in &document.structures {
+        for comment in &structure.leading_comments {
+            write_comment(comment, &mut out);
+            out.push('\n');
+        }
+        let split = structure
+            .fields
+            .iter()
+            .any(|f| !f.leading_comments.is_empty() || f.trailing_comment.is_some());
+        if !split {
+            write_structure(structure, &mut out);
+            if let Some(comment) = &structure.trailing_comment {
+                out.push(' ');
+                write_comment(comment, &mut out);
+            }
+            out.push('\n');
+            continue;
+        }
+        out.push_str(&structure.name);
+        out.push(',');
+        if let Some(comment) = &structure.trailing_comment {
+            out.push(' ');
+            write_comment(comment, &mut out);
+        }
+        out.push('\n');
+        for (i, field) in structure.fields.iter().enumerate() {
+            for comment in &field.leading_comments {
+                out.push_str("    ");
+                write_comment(comment, &mut out);
+                out.push('\n');
+            }
+            out.push_str("    ");
+            out.push_str(&field.name);
+            out.push('=');
+            write_value(&field.value, &mut out);
+            if i + 1 < structure.fields.len() {
+                out.push(',');
+            } else if structure.terminated {
+                out.push(';');
+            }
+            if let Some(comment) = &field.trailing_comment {
+                out.push(' ');
+                write_comment(comment, &mut out);
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_structure(structure: &Structure, out: &mut String) {
+    out.push_str(&structure.name);
+    for field in &structure.fields {
+        out.push_str(", ");
+        out.push_str(&field.name);
+        out.push('=');
+        write_value(&field.value, out);
+    }
+    if structure.terminated {
+        out.push(';');
+    }
+}
+
+fn write_value(value: &FieldValue, out: &mut String) {
+    let elements = |open: char, values: &[FieldValue], close: char, out: &mut String| {
+        out.push(open);
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            match value {
+                // Array elements parse as structures as they are
+                FieldValue::Structure(structure) if open == '[' => write_structure(structure, out),
+                _ => write_value(value, out),
+            }
+        }
+        out.push(close);
+    };
+    match value {
+        FieldValue::Scalar(scalar) => out.push_str(&scalar.text),
+        FieldValue::Typed { type_name, value } => {
+            out.push_str(&format!("({})", type_name));
+            write_value(value, out);
+        }
+        FieldValue::Array(values) => elements('[', values, ']', out),
+        FieldValue::ValueArray(values) => elements('<', values, '>', out),
+        FieldValue::Block(values) => elements('{', values, '}', out),
+        // Elsewhere, a structure is written as the single element of an array
+        FieldValue::Structure(structure) => {
+            out.push('[');
+            write_structure(structure, out);
+            out.push(']');
+        }
+    }
+}
+
+/// `text` between double quotes, escaping quotes and backslashes.
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn canonical_structure(structure: &Structure, out: &mut String) {
+    out.push_str(&quoted(&structure.name));
+    for field in &structure.fields {
+        out.push_str(&format!(", {}=", quoted(&field.name)));
+        canonical_value(&field.value, out);
+    }
+}
+
+/// A deserialized value written with its type, such as `int:5`.
+fn canonical_scalar(value: &Value) -> String {
+    let elements = |values: &[Value]| {
+        values
+            .iter()
+            .map(canonical_scalar)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match value {
+        Value::Int(n) => format!("int:{}", n),
+        Value::UInt(n) => format!("uint:{}", n),
+        Value::Double(d) => format!("double:{}", d),
+        Value::Bool(b) => format!("boolean:{}", b),
+        Value::Str(s) => format!("string:{}", quoted(s)),
+        Value::Fraction(n, d) => format!("fraction:{}/{}", n, d),
+        Value::ClockTime(time) => format!("time:{}", time.nseconds()),
+        Value::List(values) => format!("{{{}}}", elements(values)),
+        Value::Array(values) => format!("<{}>", elements(values)),
+        Value::Structure(structure) => {
+            let mut out = String::new();
+            canonical_structure(structure, &mut out);
+            out
+        }
+        Value::Other { type_name, text } => format!("{}:{}", quoted(type_name), quoted(text)),
+    }
+}
+
+fn canonical_value(value: &FieldValue, out: &mut String) {
+    let elements = |open: char, values: &[FieldValue], close: char, out: &mut String| {
+        out.push(open);
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            canonical_value(value, out);
+        }
+        out.push(close);
+    };
+    match value {
+        // Quoted structures are equivalent to the array structures the
+        // formatter rewrites them to
+        FieldValue::Scalar(scalar)
+            if scalar.kind == ScalarKind::String && value.as_structure().is_some() =>
+        {
+            let structure = value.as_structure().unwrap();
+            out.push('[');
+            canonical_structure(&structure, out);
+            out.push(']');
+        }
+        FieldValue::Scalar(_) | FieldValue::Typed { .. } => match scalar_value(value) {
+            Some(value) => out.push_str(&canonical_scalar(&value)),
+            // A cast of a list applies to its elements
+            None => {
+                let FieldValue::Typed { type_name, value } = value else {
+                    unreachable!("untyped scalars always have a value");
+                };
+                out.push_str(&format!("({})", quoted(type_name)));
+                canonical_value(value, out);
+            }
+        },
+        FieldValue::Array(values) => elements('[', values, ']', out),
+        FieldValue::ValueArray(values) => elements('<', values, '>', out),
+        FieldValue::Block(values) => elements('{', values, '}', out),
+        // A bare word in a block may parse as a structure without fields
+        FieldValue::Structure(structure) if structure.fields.is_empty() => {
+            out.push_str(&canonical_scalar(&Value::parse_untyped(&structure.name)))
+        }
+        FieldValue::Structure(structure) => canonical_structure(structure, out),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(source: &str) -> Document {
+        Document::parse(source).unwrap()
+    }
+
+    #[test]
+    fn test_parse_structure() {
+        let d = doc("seek, start=1.0, flags=flush+accurate;\nstop\n");
+        assert_eq!(d.structures.len(), 2);
+        let seek = &d.structures[0];
+        assert_eq!(seek.name, "seek");
+        assert!(seek.terminated);
+        assert_eq!(seek.fields.len(), 2);
+        match &seek.field("flags").unwrap().value {
+            FieldValue::Scalar(s) => assert_eq!(s.kind, ScalarKind::Flags),
+            v => panic!("unexpected value {v:?}"),
+        }
+        assert_eq!(d.structures[1].name, "stop");
+    }
+
+    #[test]
+    fn test_parse_value_str() {
+        match parse_value_str("(int)5").unwrap() {
+            FieldValue::Typed { type_name, value } => {
+                assert_eq!(type_name, "int");
+                assert!(
+                    matches!(*value, FieldValue::Scalar(s) if s.text == "5" && s.span == (5..6))
+                );
+            }
+            v => panic!("unexpected value {v:?}"),
+        }
+        let value = parse_value_str("{\"seek, start=1.0\", [wait, duration=1.0]} # Steps").unwrap();
+        let FieldValue::Block(values) = &value else {
+            panic!("unexpected value {value:?}");
+        };
+        let wait = values[1].as_structure().unwrap();
+        assert_eq!(wait.name, "wait");
+        assert_eq!(wait.span, 21..39);
+        assert_eq!(wait.fields[0].span, 27..39);
+
+        assert!(parse_value_str("[a, b").is_err());
+        assert_eq!(
+            parse_value_str("1, b=2").unwrap_err().position,
+            Point::new(0, 3)
+        );
+        assert_eq!(
+            parse_value_str("1\nplay").unwrap_err().position,
+            Point::new(1, 0)
+        );
+        assert!(parse_value_str("").is_err());
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        let source = "meta,\n    # Issues\n    expected-issues={\n        [expected-issue, details=\"a \\\"b\\\"\"],\n    }\n\nseek, start=(double)1, flags=flush+accurate;\nset-vars, v=<1, 2>, l=[a, \"b c\"]\n";
+        let document: Document = source.parse().unwrap();
+        let compact = document.to_string();
+        assert_eq!(
+            compact,
+            "meta, expected-issues={[expected-issue, details=\"a \\\"b\\\"\"]}\n\
+             seek, start=(double)1, flags=flush+accurate;\n\
+             set-vars, v=<1, 2>, l=[a, \"b c\"]\n"
+        );
+        assert!(doc(&compact).semantically_eq(&document));
+        assert_eq!(
+            document.structures[2].to_string(),
+            "set-vars, v=<1, 2>, l=[a, \"b c\"]"
+        );
+
+        let seek: Structure = "# Seek\nseek,start=1.0 # Start\n".parse().unwrap();
+        assert_eq!(seek.to_string(), "seek, start=1.0");
+        assert_eq!(
+            "seek\nplay".parse::<Structure>().unwrap_err().position,
+            Point::new(1, 0)
+        );
+        assert!("".parse::<Structure>().is_err());
+        assert!("seek, start=".parse::<Structure>().is_err());
+
+        let value: FieldValue = "{ \"a\",(int) 5 }".parse().unwrap();
+        assert_eq!(value.to_string(), "{\"a\", (int)5}");
+        // Nested structures are written as arrays
+        let issue = Structure::new("expected-issue").with_field("id", 1);
+        assert_eq!(
+            Structure::new("meta")
+                .with_field("issue", issue)
+                .to_string(),
+            "meta, issue=[expected-issue, id=1]"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "# Seek to the middle\n#\nseek, start=1.0 # fast\n\nmeta,\n    # The pipeline\n    args=\"fakesrc ! fakesink\", #  trailing\n    expected-issues={\n        # First\n        [expected-issue, issue-id=a::b], # known\n    }\n# dangling\n";
+        let document = doc(source);
+        let seek = &document.structures[0];
+        assert_eq!(seek.leading_comments, ["Seek to the middle", ""]);
+        assert_eq!(seek.trailing_comment.as_deref(), Some("fast"));
+        let meta = &document.structures[1];
+        assert!(meta.leading_comments.is_empty() && meta.trailing_comment.is_none());
+        let args = meta.field("args").unwrap();
+        assert_eq!(args.leading_comments, ["The pipeline"]);
+        assert_eq!(args.trailing_comment.as_deref(), Some(" trailing"));
+        let FieldValue::Block(issues) = &meta.field("expected-issues").unwrap().value else {
+            panic!("expected-issues is not a block");
+        };
+        let issue = issues[0].as_structure().unwrap();
+        assert_eq!(issue.leading_comments, ["First"]);
+        assert_eq!(issue.trailing_comment.as_deref(), Some("known"));
+
+        assert_eq!(
+            write_commented(&document),
+            "# Seek to the middle\n#\nseek, start=1.0 # fast\nmeta,\n    # The pipeline\n    args=\"fakesrc ! fakesink\", #  trailing\n    expected-issues={[expected-issue, issue-id=a::b]}\n"
+        );
+        assert!(doc(&write_commented(&document)).semantically_eq(&document));
+    }
+
+    #[test]
+    fn test_group_and_sort() {
+        let mut d = doc("meta\n\
+             play, playback-time=3\n\
+             seek, playback-time=1.5, start=1.0\n\
+             pause, playback-time=(string)\"0:00:01\"\n\
+             seek, playback-time=$(t), start=2.0\n\
+             stop, playback-time=2.0\n\
+             eos, playback-time=1.0\n");
+        let seeks: Vec<_> = d.structures_named("seek").collect();
+        assert_eq!(seeks.len(), 2);
+        assert_eq!(seeks[0].playback_time(), ClockTime::from_seconds_f64(1.5));
+        assert_eq!(seeks[1].playback_time(), None);
+
+        let groups = d.group_by_action();
+        assert_eq!(
+            groups.keys().copied().collect::<Vec<_>>(),
+            ["eos", "meta", "pause", "play", "seek", "stop"]
+        );
+        assert!(std::ptr::eq(groups["seek"][1], &d.structures[4]));
+
+        d.sort_actions_by_time();
+        let names: Vec<_> = d.structures.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["meta", "pause", "seek", "play", "seek", "eos", "stop"]
+        );
+
+        for stop in d.structures_named_mut("stop") {
+            stop.fields.clear();
+        }
+        assert!(d.structures[6].fields.is_empty());
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = Document::parse("meta, foo=\n  bar=[").unwrap_err();
+        assert!(err.to_string().starts_with("Parse error at line"));
+    }
+
+    #[test]
+    fn test_semantically_eq_ignores_formatting() {
+        let a = doc("meta, args={-t, video}, handles-states=true # comment\n");
+        let b = doc("meta,\n    args={\n        -t, video,\n    },\n    handles-states=true\n");
+        assert!(a.semantically_eq(&b));
+    }
+
+    #[test]
+    fn test_semantically_eq_ignores_quoting() {
+        let a = doc("set-state, state=\"playing\"");
+        let b = doc("set-state, state=playing");
+        assert!(a.semantically_eq(&b));
+        assert!(!a.semantically_eq(&doc("set-state, state=paused")));
+    }
+
+    #[test]
+    fn test_semantically_eq_field_order() {
+        let a = doc("seek, start=1.0, stop=2.0");
+        let b = doc("seek, stop=2.0, start=1.0");
+        assert!(!a.semantically_eq(&b));
+        let options = EqOptions {
+            ignore_field_order: true,
+            ..Default::default()
+        };
+        assert!(a.semantically_eq_with(&b, &options));
+    }
+
+    #[test]
+    fn test_semantically_eq_quoted_structure() {
+        let a = doc(r#"meta, expected-issues={"expected-issue, issue-id=foo, details=\"a b\""}"#);
+        let b = doc(r#"meta, expected-issues={[expected-issue, issue-id=foo, details="a b"]}"#);
+        assert!(a.semantically_eq(&b));
+    }
+
+    #[test]
+    fn test_semantically_eq_args() {
+        let a = doc(r#"meta, args={"videotestsrc ! fakesink", -t}"#);
+        assert!(a.semantically_eq(&doc(r#"meta, args={"videotestsrc ! fakesink", "-t"}"#)));
+        // Elements are compared as written, not split into words
+        let b = doc(r#"meta, args={"videotestsrc !", "fakesink", -t}"#);
+        assert!(!a.semantically_eq(&b));
+        assert_ne!(a.fingerprint(), b.fingerprint());
+
+        let options = EqOptions {
+            join_args: true,
+            ..Default::default()
+        };
+        assert!(a.semantically_eq_with(&b, &options));
+        assert!(
+            !a.semantically_eq_with(&doc(r#"meta, args={"videotestsrc ! fakesink"}"#), &options)
+        );
+        // Only in args
+        let c = doc(r#"meta, other={"videotestsrc !", "fakesink", -t}"#);
+        let d = doc(r#"meta, other={"videotestsrc ! fakesink", -t}"#);
+        assert!(!c.semantically_eq_with(&d, &options));
+    }
+
+    #[test]
+    fn test_semantically_eq_values() {
+        assert!(
+            doc("foo, a=(int)5, b=TRUE, c=5.0").semantically_eq(&doc("foo, a=5, b=true, c=5.00"))
+        );
+        assert!(!doc("foo, a=(double)5").semantically_eq(&doc("foo, a=5")));
+        assert!(!doc("seek, start=5.0").semantically_eq(&doc("seek, start=5000000000")));
+
+        let options = EqOptions {
+            time_fields: vec!["start".to_string()],
+            ..Default::default()
+        };
+        let seconds = doc("seek, start=5.0, rate=5.0");
+        assert!(seconds.semantically_eq_with(&doc("seek, start=5000000000, rate=5.0"), &options));
+        assert!(seconds.semantically_eq_with(&doc("seek, start=\"0:00:05\", rate=5.0"), &options));
+        assert!(!seconds.semantically_eq_with(&doc("seek, start=5.0, rate=5000000000"), &options));
+    }
+
+    #[test]
+    fn test_quoted_scalars_are_strings() {
+        // Quoted and unquoted values with the same text, and whether they
+        // are the same value
+        let cases = [
+            ("foo, a=\"5\"", "foo, a=5", false),
+            ("foo, a=\"1.5\"", "foo, a=1.5", false),
+            ("foo, a=\"true\"", "foo, a=true", false),
+            ("foo, a=\"5\"", "foo, a=(string)5", true),
+            (
+                "set-state, state=\"playing\"",
+                "set-state, state=playing",
+                true,
+            ),
+        ];
+        for (quoted, unquoted, equal) in cases {
+            let (a, b) = (doc(quoted), doc(unquoted));
+            assert_eq!(a.semantically_eq(&b), equal, "{} and {}", quoted, unquoted);
+            assert_eq!(
+                a.fingerprint() == b.fingerprint(),
+                equal,
+                "fingerprints of {} and {}",
+                quoted,
+                unquoted
+            );
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = doc("meta, args={\"videotestsrc ! fakesink\"}, expected-issues={\"expected-issue, issue-id=a::b\"}\n\
+                     # Comment\n\
+                     seek, start=(double)1.0, flags=flush\n");
+        let b = doc("meta,\n    args = { \"videotestsrc ! fakesink\" },\n    expected-issues = {\n        [expected-issue, issue-id=a::b],\n    }\n\
+                     seek, start=(double)1, flags=\"flush\"\n");
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(
+            a.fingerprint(),
+            doc("meta, args={\"videotestsrc ! fakesink\"}\nseek, start=1.0, flags=flush\n")
+                .fingerprint()
+        );
+        // Stable across versions
+        assert_eq!(doc("play\n").fingerprint(), 0x29ec_0f34_3a33_6c5b);
+    }
+
+    #[test]
+    fn test_semantically_eq_detects_differences() {
+        assert!(!doc("play").semantically_eq(&doc("stop")));
+        assert!(!doc("play\nstop").semantically_eq(&doc("play")));
+        assert!(!doc("foo, a=(int)1").semantically_eq(&doc("foo, a=(uint)1")));
+    }
+}
@@ -0,0 +1,143 @@
+//! Dumps of the parse tree, for grammar contributors and lint authors, and
+//! of the typed [`Document`], for tools that do not link this crate.
+
+use std::fmt::Write;
+
+use tree_sitter::{Node, TreeCursor};
+
+use crate::document::parse_tree_with_errors;
+use crate::Document;
+
+/// The concrete syntax tree of `source` as an S-expression, as
+/// `tree-sitter parse` prints it: named nodes only, with their field names.
+pub fn cst_sexp(source: &str) -> String {
+    let mut sexp = parse_tree_with_errors(source).root_node().to_sexp();
+    sexp.push('\n');
+    sexp
+}
+
+/// One line per node of the concrete syntax tree of `source`, indented by
+/// depth, with its field name, range (0-based `[row, column]`) and, for
+/// leaves, its text. Anonymous nodes such as `,` are included, quoted.
+///
+/// ```text
+/// source_file [0, 0] - [1, 0]
+///   structure [0, 0] - [0, 4]
+///     structure_name [0, 0] - [0, 4]
+///       identifier [0, 0] - [0, 4] "play"
+/// ```
+pub fn cst_pretty(source: &str) -> String {
+    let tree = parse_tree_with_errors(source);
+    let mut output = String::new();
+    write_node(&mut tree.walk(), source, 0, &mut output);
+    output
+}
+
+fn write_node(cursor: &mut TreeCursor, source: &str, depth: usize, output: &mut String) {
+    let node: Node = cursor.node();
+    let _ = write!(output, "{:indent$}", "", indent = depth * 2);
+    if let Some(field) = cursor.field_name() {
+        let _ = write!(output, "{}: ", field);
+    }
+    if node.is_missing() {
+        output.push_str("MISSING ");
+    }
+    if node.is_named() {
+        output.push_str(node.kind());
+    } else {
+        let _ = write!(output, "{:?}", node.kind());
+    }
+    let (start, end) = (node.start_position(), node.end_position());
+    let _ = write!(
+        output,
+        " [{}, {}] - [{}, {}]",
+        start.row, start.column, end.row, end.column
+    );
+    if node.is_named() && node.child_count() == 0 && !node.is_missing() {
+        let _ = write!(
+            output,
+            " {:?}",
+            node.utf8_text(source.as_bytes()).unwrap_or("")
+        );
+    }
+    output.push('\n');
+
+    if cursor.goto_first_child() {
+        loop {
+            write_node(cursor, source, depth + 1, output);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+    }
+}
+
+/// `document` as pretty-printed JSON. Spans are byte ranges of the source,
+/// `{"start": 0, "end": 4}`, and values are tagged with their type:
+///
+/// ```json
+/// {"name": "start", "value": {"type": "scalar", "value": {"kind": "number", "text": "1.0", ...}}, ...}
+/// ```
+pub fn ast_json(document: &Document) -> String {
+    let mut json = serde_json::to_string_pretty(document).expect("documents serialize to JSON");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let source = "seek, start=1.0\n";
+        assert_eq!(
+            cst_sexp(source),
+            "(source_file (structure (structure_name (identifier)) (field_list (field name: (field_name (identifier)) value: (field_value (value (number)))))))\n"
+        );
+        assert_eq!(
+            cst_pretty(source),
+            r#"source_file [0, 0] - [1, 0]
+  structure [0, 0] - [0, 15]
+    structure_name [0, 0] - [0, 4]
+      identifier [0, 0] - [0, 4] "seek"
+    "," [0, 4] - [0, 5]
+    field_list [0, 6] - [0, 15]
+      field [0, 6] - [0, 15]
+        name: field_name [0, 6] - [0, 11]
+          identifier [0, 6] - [0, 11] "start"
+        "=" [0, 11] - [0, 12]
+        value: field_value [0, 12] - [0, 15]
+          value [0, 12] - [0, 15]
+            number [0, 12] - [0, 15] "1.0"
+"#
+        );
+
+        // Trees with errors are dumped too
+        assert!(cst_pretty("seek, start=\n").contains("MISSING cli_argument [0, 12] - [0, 12]\n"));
+    }
+
+    #[test]
+    fn test_ast_json() {
+        let source = "seek, start=(double)1.0, flags=<a, [b, c=1]>;\n";
+        let json: serde_json::Value =
+            serde_json::from_str(&ast_json(&Document::parse(source).unwrap())).unwrap();
+        let seek = &json["structures"][0];
+        assert_eq!(seek["name"], "seek");
+        assert_eq!(seek["terminated"], true);
+        assert_eq!(seek["span"], serde_json::json!({"start": 0, "end": 45}));
+        assert_eq!(
+            seek["fields"][0]["value"],
+            serde_json::json!({"type": "typed", "value": {"type_name": "double", "value": {
+                "type": "scalar",
+                "value": {"kind": "number", "text": "1.0", "span": {"start": 20, "end": 23}}
+            }}})
+        );
+        let flags = &seek["fields"][1]["value"];
+        assert_eq!(flags["type"], "value_array");
+        assert_eq!(flags["value"][1]["type"], "array");
+        assert_eq!(flags["value"][1]["value"][0]["type"], "structure");
+        assert_eq!(flags["value"][1]["value"][0]["value"]["name"], "b");
+    }
+}
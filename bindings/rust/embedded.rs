@@ -0,0 +1,289 @@
+//! Formatting of scenarios embedded in C and Python string literals.
+//!
+//! gst-validate tests often hold their scenarios in string literals, such as
+//! `"description, seek=true\n" "seek, start=1.0\n"` in C. [`format_embedded`]
+//! finds the literals (or runs of adjacent literals) holding a scenario,
+//! formats it and writes it back with the escaping of the host language.
+
+use std::ops::Range;
+
+use crate::format::{format_source, FormatterConfig};
+use crate::refactor::{apply_edits, TextEdit};
+use crate::Document;
+
+/// Language of a file embedding scenarios.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostLanguage {
+    C,
+    Python,
+}
+
+/// Names of the first structure of the strings considered scenarios.
+const SCENARIO_STARTS: &[&str] = &["description", "meta"];
+
+/// A string literal of the host file.
+struct Literal {
+    /// Byte range of the whole literal, quotes included.
+    range: Range<usize>,
+    /// Byte range of its content.
+    content: Range<usize>,
+    quote: char,
+    /// A Python `"""` or `'''` string.
+    triple: bool,
+    /// Prefixed with `r`, `b` or `f` in Python: not handled.
+    unsupported: bool,
+}
+
+/// End of the quoted literal whose content starts at `start`, just past the
+/// closing `quote` (repeated `count` times).
+fn literal_end(source: &str, start: usize, quote: char, count: usize) -> Option<usize> {
+    let closing = quote.to_string().repeat(count);
+    let mut chars = source[start..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if source[start + i..].starts_with(&closing) {
+            return Some(start + i + closing.len());
+        } else if c == '\n' && count == 1 {
+            // Unterminated literal
+            return None;
+        }
+    }
+    None
+}
+
+/// The string literals of `source`, skipping comments and C character
+/// literals.
+fn literals(source: &str, language: HostLanguage) -> Vec<Literal> {
+    let mut literals = Vec::new();
+    let mut i = 0;
+    while i < source.len() {
+        let rest = &source[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        let comment_end = match language {
+            HostLanguage::C if rest.starts_with("//") => {
+                Some(rest.find('\n').unwrap_or(rest.len()))
+            }
+            HostLanguage::C if rest.starts_with("/*") => {
+                Some(rest.find("*/").map_or(rest.len(), |e| e + 2))
+            }
+            HostLanguage::Python if c == '#' => Some(rest.find('\n').unwrap_or(rest.len())),
+            _ => None,
+        };
+        if let Some(end) = comment_end {
+            i += end;
+            continue;
+        }
+
+        if c == '\'' && language == HostLanguage::C {
+            i = literal_end(source, i + 1, '\'', 1).unwrap_or(i + 1);
+            continue;
+        }
+        if c == '"' || (c == '\'' && language == HostLanguage::Python) {
+            let triple =
+                language == HostLanguage::Python && rest.starts_with(&c.to_string().repeat(3));
+            let count = if triple { 3 } else { 1 };
+            let start = i + count;
+            let Some(end) = literal_end(source, start, c, count) else {
+                i = start;
+                continue;
+            };
+            let prefix = source[..i]
+                .chars()
+                .rev()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect::<String>()
+                .to_ascii_lowercase();
+            literals.push(Literal {
+                range: i..end,
+                content: start..end - count,
+                quote: c,
+                triple,
+                unsupported: language == HostLanguage::Python && prefix.contains(['r', 'b', 'f']),
+            });
+            i = end;
+            continue;
+        }
+        i += c.len_utf8();
+    }
+    literals
+}
+
+/// Content of a literal with its escapes processed, or `None` if it uses
+/// escapes that could not be written back the same way.
+fn unescape(content: &str) -> Option<String> {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            c @ ('\\' | '"' | '\'') => result.push(c),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+/// `text` escaped for a literal quoted with `quote`.
+fn escape(text: &str, quote: char) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c if c == quote => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Whether `text` is a scenario: it parses and starts with one of
+/// [`SCENARIO_STARTS`].
+fn is_scenario(text: &str) -> bool {
+    Document::parse(text).is_ok_and(|doc| {
+        doc.structures
+            .first()
+            .is_some_and(|s| SCENARIO_STARTS.contains(&s.name.as_str()))
+    })
+}
+
+/// The edit rewriting the run of adjacent `group` literals, if it holds a
+/// scenario the formatter changes.
+fn format_group(
+    source: &str,
+    group: &[Literal],
+    language: HostLanguage,
+    config: &FormatterConfig,
+) -> Option<TextEdit> {
+    if group.iter().any(|l| l.unsupported) {
+        return None;
+    }
+    let mut text = String::new();
+    for literal in group {
+        text.push_str(&unescape(&source[literal.content.clone()])?);
+    }
+    if !is_scenario(&text) {
+        return None;
+    }
+
+    // Keep the whitespace around the scenario, such as the newline after
+    // an opening `"""`
+    let formatted = format_source(&text, config).ok()?;
+    let leading = &text[..text.len() - text.trim_start().len()];
+    let trailing = &text[text.trim_end().len()..];
+    let formatted = format!("{}{}{}", leading, formatted.trim(), trailing);
+    if formatted == text {
+        return None;
+    }
+
+    let first = &group[0];
+    let new_text = if first.triple {
+        let quotes = first.quote.to_string().repeat(3);
+        // Newlines stay as they are; only quotes that could end the
+        // literal need escaping
+        let escaped = formatted
+            .replace('\\', "\\\\")
+            .replace(&quotes, &format!("\\{0}\\{0}\\{0}", first.quote));
+        let escaped = match escaped.strip_suffix(first.quote) {
+            Some(rest) if !rest.ends_with('\\') => format!("{}\\{}", rest, first.quote),
+            _ => escaped,
+        };
+        format!("{}{}{}", quotes, escaped, quotes)
+    } else if language == HostLanguage::Python && group.len() == 1 {
+        // Adjacent Python literals are only valid within parentheses
+        format!("{0}{1}{0}", first.quote, escape(&formatted, first.quote))
+    } else {
+        // One literal per line, aligned on the first one
+        let line_start = source[..first.range.start].rfind('\n').map_or(0, |i| i + 1);
+        let before = &source[line_start..first.range.start];
+        let indent: String = before
+            .chars()
+            .map(|c| if c.is_whitespace() { c } else { ' ' })
+            .collect();
+        formatted
+            .split_inclusive('\n')
+            .map(|line| format!("{0}{1}{0}", first.quote, escape(line, first.quote)))
+            .collect::<Vec<_>>()
+            .join(&format!("\n{}", indent))
+    };
+    Some(TextEdit {
+        range: first.range.start..group[group.len() - 1].range.end,
+        new_text,
+    })
+}
+
+/// Format the scenarios held by the string literals of `source`, a C or
+/// Python file, leaving the rest of the file untouched.
+///
+/// Adjacent literals, separated only by whitespace, are read as one string.
+/// Literals using escapes other than `\n`, `\t`, `\\` and quotes, and
+/// Python raw, byte and f-strings, are skipped.
+pub fn format_embedded(source: &str, language: HostLanguage, config: &FormatterConfig) -> String {
+    let mut groups: Vec<Vec<Literal>> = Vec::new();
+    for literal in literals(source, language) {
+        match groups.last_mut() {
+            Some(group)
+                if !literal.triple
+                    && !group[0].triple
+                    && source[group[group.len() - 1].range.end..literal.range.start]
+                        .trim()
+                        .is_empty() =>
+            {
+                group.push(literal)
+            }
+            _ => groups.push(vec![literal]),
+        }
+    }
+
+    let edits: Vec<TextEdit> = groups
+        .iter()
+        .filter_map(|group| format_group(source, group, language, config))
+        .collect();
+    apply_edits(source, &edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(source: &str, language: HostLanguage) -> String {
+        format_embedded(source, language, &FormatterConfig::default())
+    }
+
+    #[test]
+    fn test_c() {
+        let source = r#"static const gchar *scenario =
+    "description,   seek=true,handles-states=true\n"
+    "seek, start=1.0, flags=\"flush\"\n";  /* "description, x=1" */
+const gchar *name = "description";
+"#;
+        let expected = r#"static const gchar *scenario =
+    "description, seek=true, handles-states=true\n"
+    "seek, start=1.0, flags=\"flush\"\n";  /* "description, x=1" */
+const gchar *name = "description";
+"#;
+        assert_eq!(fmt(source, HostLanguage::C), expected);
+        assert_eq!(fmt(expected, HostLanguage::C), expected);
+        // Not a scenario, or escapes that cannot be written back
+        let other = "f (\"play,  x=1\\n\");\ng (\"description,  x=\\x41\");\n";
+        assert_eq!(fmt(other, HostLanguage::C), other);
+    }
+
+    #[test]
+    fn test_python() {
+        let source = "SCENARIO = \"\"\"\ndescription,   seek=true\nseek, start=1.0,   flags=flush\n\"\"\"\nother = 'description,  seek=true\\nplay'\nraw = r\"description,  x=1\"\n";
+        let expected = "SCENARIO = \"\"\"\ndescription, seek=true\nseek, start=1.0, flags=flush\n\"\"\"\nother = 'description, seek=true\\nplay'\nraw = r\"description,  x=1\"\n";
+        assert_eq!(fmt(source, HostLanguage::Python), expected);
+        assert_eq!(fmt(expected, HostLanguage::Python), expected);
+    }
+}
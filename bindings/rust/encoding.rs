@@ -0,0 +1,73 @@
+//! Decoding of test files read as bytes.
+
+use std::fmt;
+
+use crate::lint::line_col;
+
+/// The byte order mark some editors write at the start of UTF-8 files.
+pub const BOM: char = '\u{feff}';
+
+/// Position of the first invalid UTF-8 sequence of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// Byte offset of the invalid sequence.
+    pub offset: usize,
+    /// 0-based line and column (in bytes).
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid UTF-8 at line {}, column {} (byte {})",
+            self.line + 1,
+            self.column + 1,
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for InvalidUtf8 {}
+
+/// The text of `bytes`, or the position of its first invalid sequence.
+///
+/// A leading [`BOM`] is kept: the parser skips it and the formatter writes
+/// it back.
+pub fn decode(bytes: &[u8]) -> Result<&str, InvalidUtf8> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        let offset = e.valid_up_to();
+        // The bytes before the error are valid
+        let valid = std::str::from_utf8(&bytes[..offset]).unwrap_or("");
+        let (line, column) = line_col(valid, offset);
+        InvalidUtf8 {
+            offset,
+            line,
+            column,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("\u{feff}meta\n".as_bytes()), Ok("\u{feff}meta\n"));
+        let error = decode(b"meta\nplay, a=\"\xe9t\xe9\"\n").unwrap_err();
+        assert_eq!(
+            error,
+            InvalidUtf8 {
+                offset: 14,
+                line: 1,
+                column: 9
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid UTF-8 at line 2, column 10 (byte 14)"
+        );
+    }
+}
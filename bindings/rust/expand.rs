@@ -0,0 +1,375 @@
+//! Static expansion of scenarios into the actions gst-validate runs.
+//!
+//! `foreach` loops are unrolled, actions with a `repeat` count are copied
+//! and `$(variables)` defined by `set-vars` (or by the loops) are replaced
+//! by their value. What depends on the running pipeline, such as `expr()`
+//! or built-in variables, is kept as written.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::document::{FieldValue, ScalarKind, Structure};
+use crate::format::{format_source, FormatterConfig};
+use crate::variables::foreach_iterators;
+use crate::{Document, ParseError};
+
+/// Structures configuring the scenario rather than being actions.
+const HEADER_STRUCTURES: &[&str] = &["meta", "description"];
+
+/// The result of an expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    /// The expanded actions, one structure per line.
+    pub source: String,
+    /// Loops or repeats that could not be expanded, kept as written.
+    pub warnings: Vec<String>,
+}
+
+/// Why a scenario could not be expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpandError {
+    /// The scenario does not parse.
+    Parse(ParseError),
+    /// The expanded actions do not parse, the error pointing in `source`.
+    Expanded { error: ParseError, source: String },
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => e.fmt(f),
+            Self::Expanded { error, .. } => {
+                write!(f, "the expanded actions do not parse: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpandError {}
+
+impl From<ParseError> for ExpandError {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+/// The value of a variable: as written, quotes included, for standalone
+/// references, and without the quotes for those embedded in strings, as
+/// `inline-var` does.
+#[derive(Debug, Clone)]
+struct Var {
+    written: String,
+    content: String,
+}
+
+impl Var {
+    fn plain(text: String) -> Self {
+        Self {
+            content: text.clone(),
+            written: text,
+        }
+    }
+
+    /// The variable `value` defines, its references replaced with `vars`.
+    fn new(value: &FieldValue, vars: &BTreeMap<String, Var>) -> Option<Self> {
+        match value {
+            FieldValue::Scalar(scalar) if scalar.kind == ScalarKind::String => {
+                let written = substitute(&scalar.text, vars);
+                Some(Self {
+                    content: written[1..written.len() - 1].to_string(),
+                    written,
+                })
+            }
+            FieldValue::Scalar(scalar) => Some(Self::plain(substitute(&scalar.text, vars))),
+            FieldValue::Typed { value, .. } => Self::new(value, vars),
+            _ => None,
+        }
+    }
+}
+
+struct Expander {
+    actions: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// Replace the `$(name)` references of `text` with the values of `vars`,
+/// leaving unknown variables as they are.
+fn substitute(text: &str, vars: &BTreeMap<String, Var>) -> String {
+    let bytes = text.as_bytes();
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut in_string = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_string => i += 1,
+            b'"' => in_string = !in_string,
+            b'$' if bytes.get(i + 1) == Some(&b'(') => {
+                let Some(end) = text[i..].find(')').map(|e| i + e) else {
+                    break;
+                };
+                if let Some(var) = vars.get(&text[i + 2..end]) {
+                    result.push_str(&text[copied..i]);
+                    result.push_str(if in_string {
+                        &var.content
+                    } else {
+                        &var.written
+                    });
+                    copied = end + 1;
+                }
+                i = end;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+/// `structure` written back from `base`, the text its spans refer to,
+/// without the fields named in `skip`.
+fn structure_text(structure: &Structure, base: &str, skip: &[&str]) -> String {
+    let mut text = structure.name.clone();
+    for field in structure
+        .fields
+        .iter()
+        .filter(|f| !skip.contains(&f.name.as_str()))
+    {
+        text.push_str(", ");
+        text.push_str(&base[field.span.clone()]);
+    }
+    text
+}
+
+/// Values a `foreach` iterator takes: `[start, stop]` and
+/// `[start, stop, step]` ranges, stop excluded, or the elements of a list.
+fn iterations(value: &FieldValue, vars: &BTreeMap<String, Var>) -> Option<Vec<Var>> {
+    let elements = match value {
+        FieldValue::Array(elements) => {
+            let numbers: Option<Vec<i64>> = elements
+                .iter()
+                .map(|e| Var::new(e, vars)?.content.parse().ok())
+                .collect();
+            let numbers: Vec<i64> = match *numbers?.as_slice() {
+                [start, stop] => (start..stop).collect(),
+                [start, stop, step] if step > 0 => (start..stop).step_by(step as usize).collect(),
+                _ => return None,
+            };
+            return Some(numbers.iter().map(|i| Var::plain(i.to_string())).collect());
+        }
+        FieldValue::ValueArray(elements) | FieldValue::Block(elements) => elements,
+        _ => return None,
+    };
+    elements.iter().map(|e| Var::new(e, vars)).collect()
+}
+
+impl Expander {
+    fn structure(&mut self, structure: &Structure, base: &str, vars: &mut BTreeMap<String, Var>) {
+        match structure.name.as_str() {
+            name if HEADER_STRUCTURES.contains(&name) => {}
+            "set-vars" => {
+                for field in &structure.fields {
+                    if let Some(var) = Var::new(&field.value, vars) {
+                        vars.insert(field.name.clone(), var);
+                    }
+                }
+            }
+            "foreach" => {
+                let iterators: Vec<&str> = foreach_iterators(structure)
+                    .map(|f| f.name.as_str())
+                    .collect();
+                if iterators.len() > 1 {
+                    self.warnings.push(format!(
+                        "'foreach' has several iterators ({}), gst-validate only allows one: '{}'",
+                        iterators.join(", "),
+                        &base[structure.span.clone()]
+                    ));
+                    self.actions
+                        .push(substitute(&structure_text(structure, base, &[]), vars));
+                } else if self.foreach(structure, base, vars).is_none() {
+                    self.warnings.push(format!(
+                        "could not expand '{}'",
+                        &base[structure.span.clone()]
+                    ));
+                    self.actions
+                        .push(substitute(&structure_text(structure, base, &[]), vars));
+                }
+            }
+            _ => self.action(structure, base, vars),
+        }
+    }
+
+    /// Unroll a `foreach` loop, `None` if its iterator or actions are not
+    /// known statically.
+    fn foreach(
+        &mut self,
+        foreach: &Structure,
+        base: &str,
+        vars: &BTreeMap<String, Var>,
+    ) -> Option<()> {
+        let actions = match &foreach.field("actions")?.value {
+            FieldValue::Block(actions) | FieldValue::Array(actions) => actions,
+            _ => return None,
+        };
+        let iterator = foreach_iterators(foreach).next()?;
+        let values = iterations(&iterator.value, vars)?;
+
+        // Actions given as strings are parsed once, with spans relative to
+        // their own text
+        let mut parsed: Vec<(Structure, String)> = Vec::new();
+        for action in actions {
+            match action {
+                FieldValue::Structure(s) => parsed.push((s.clone(), base.to_string())),
+                FieldValue::Array(elements) => match elements.as_slice() {
+                    [FieldValue::Structure(s)] => parsed.push((s.clone(), base.to_string())),
+                    _ => return None,
+                },
+                FieldValue::Scalar(s) if s.kind == ScalarKind::String => {
+                    let text = s.unquoted();
+                    let document = Document::parse(&text).ok()?;
+                    for structure in document.structures {
+                        parsed.push((structure, text.clone()));
+                    }
+                }
+                _ => return None,
+            }
+        }
+
+        for value in values {
+            let mut vars = vars.clone();
+            vars.insert(iterator.name.clone(), value);
+            for (action, text) in &parsed {
+                self.structure(action, text, &mut vars);
+            }
+        }
+        Some(())
+    }
+
+    fn action(&mut self, action: &Structure, base: &str, vars: &BTreeMap<String, Var>) {
+        let Some(repeat) = action.field("repeat") else {
+            self.actions
+                .push(substitute(&structure_text(action, base, &[]), vars));
+            return;
+        };
+        let count = Var::new(&repeat.value, vars).and_then(|count| count.content.parse().ok());
+        let Some(count) = count else {
+            self.warnings.push(format!(
+                "could not expand the repeat count of '{}'",
+                &base[action.span.clone()]
+            ));
+            self.actions
+                .push(substitute(&structure_text(action, base, &[]), vars));
+            return;
+        };
+        let text = substitute(&structure_text(action, base, &["repeat"]), vars);
+        for _ in 0..count {
+            self.actions.push(text.clone());
+        }
+    }
+}
+
+/// Expand the scenario `source` into the actions gst-validate runs, in
+/// order, formatted with `config`.
+///
+/// `foreach` ranges (`i=[0, 10, 2]`) exclude their end, as in gst-validate.
+pub fn expand(source: &str, config: &FormatterConfig) -> Result<Expansion, ExpandError> {
+    let document = Document::parse(source)?;
+    let mut expander = Expander {
+        actions: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut vars = BTreeMap::new();
+    for structure in &document.structures {
+        expander.structure(structure, source, &mut vars);
+    }
+
+    let mut expanded = expander.actions.join("\n");
+    expanded.push('\n');
+    let source = format_source(&expanded, config).map_err(|error| ExpandError::Expanded {
+        error,
+        source: expanded.clone(),
+    })?;
+    Ok(Expansion {
+        source,
+        warnings: expander.warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(source: &str) -> Expansion {
+        expand(source, &FormatterConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_foreach() {
+        let source = r#"meta, handles-states=true
+set-vars, step=2, clip="a b.mp4"
+foreach, i=[0, 5, $(step)], actions={
+    "seek, start=$(i), flags=flush",
+    [wait, duration=0.5],
+}
+foreach, sink=<"fakesink", xvimagesink>, actions={"set-property, target-element-name=$(sink), property-name=sync, property-value=false"}
+play, uri="file:///$(clip)", n=$(TMPDIR)
+"#;
+        let expansion = expand_str(source);
+        assert!(expansion.warnings.is_empty());
+        assert_eq!(
+            expansion.source,
+            "seek, start=0, flags=flush
+wait, duration=0.5
+seek, start=2, flags=flush
+wait, duration=0.5
+seek, start=4, flags=flush
+wait, duration=0.5
+set-property, target-element-name=\"fakesink\", property-name=sync, property-value=false
+set-property, target-element-name=xvimagesink, property-name=sync, property-value=false
+play, uri=\"file:///a b.mp4\", n=$(TMPDIR)
+"
+        );
+    }
+
+    #[test]
+    fn test_substituted_strings_keep_their_quotes() {
+        let source = r#"set-vars, uri="file:///a b.mp4", name="x"
+play, uri=$(uri), description="playing $(uri)"
+foreach, f=<"c d.mp4">, actions={"seek, uri=$(f), flags=\"$(name)\""}
+"#;
+        let expansion = expand_str(source);
+        assert!(expansion.warnings.is_empty());
+        assert_eq!(
+            expansion.source,
+            r#"play, uri="file:///a b.mp4", description="playing file:///a b.mp4"
+seek, uri="c d.mp4", flags="x"
+"#
+        );
+    }
+
+    #[test]
+    fn test_foreach_several_iterators() {
+        let expansion =
+            expand_str("foreach, i=[0, 2], j=[0, 2], actions={\"seek, start=$(i), stop=$(j)\"}\n");
+        assert_eq!(expansion.warnings.len(), 1);
+        assert!(
+            expansion.warnings[0].contains("several iterators (i, j)"),
+            "{:?}",
+            expansion.warnings
+        );
+        // The loop is kept as written
+        assert!(expansion.source.contains("$(j)"), "{}", expansion.source);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let expansion =
+            expand_str("set-vars, n=2\nseek, start=1.0, repeat=$(n)\nstop, repeat=$(later)\n");
+        assert_eq!(
+            expansion.source,
+            "seek, start=1.0\nseek, start=1.0\nstop, repeat=$(later)\n"
+        );
+        assert_eq!(expansion.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,237 @@
+//! validateflow expectation files.
+//!
+//! The `validateflow` plugin of gst-validate logs the events and buffers
+//! going through a pad, one record per line, and compares the log with an
+//! expectation file, usually `flow-expectations/log-<pad>-expected`:
+//!
+//! ```text
+//! event stream-start: GstEventStreamStart, flags=(GstStreamFlags)GST_STREAM_FLAG_NONE, group-id=(uint)1;
+//! event caps: video/x-raw, format=(string)I420, width=(int)320, height=(int)240;
+//! buffer: pts=0:00:00.000000000, dur=0:00:00.033333333, flags=discont
+//! event eos: (no structure)
+//! ```
+//!
+//! Records are not GstStructures, the parser does not read them. As the
+//! comparison is textual, each record stays on its line however long, and
+//! formatting only normalizes the spacing between its items. The
+//! [`FlowAlign`] options align records in columns for reading, at the cost
+//! of matching the log: they are meant for review, not for files
+//! validateflow reads.
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::BOM;
+use crate::format::{with_line_ending, FormatterConfig};
+
+/// Column alignment of validateflow records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlowAlign {
+    /// Items separated by `, `, as validateflow writes them.
+    #[default]
+    None,
+    /// Record bodies start at the same column, after the longest prefix of
+    /// the file.
+    Prefixes,
+    /// Also pad the items of consecutive records of the same kind, so that
+    /// each starts at the same column as the one above.
+    Columns,
+}
+
+/// A line of an expectation file.
+enum Line<'a> {
+    /// A record: its `event caps` or `buffer` prefix and its items.
+    Record(&'a str, Vec<&'a str>),
+    /// Any other line, kept as written.
+    Other(&'a str),
+}
+
+/// Split `line` into its prefix and items, `None` for lines that are not
+/// records or whose brackets or quotes are not balanced.
+fn parse_record(line: &str) -> Option<Line<'_>> {
+    let (prefix, body) = line.split_once(": ")?;
+    if prefix.is_empty() || prefix.contains(['=', '"', ',']) {
+        return None;
+    }
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                items.push(body[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quoted || depth != 0 {
+        return None;
+    }
+    items.push(body[start..].trim());
+    Some(Line::Record(prefix.trim(), items))
+}
+
+/// Format the validateflow expectations `source`.
+///
+/// Lines that are not records, such as blank lines, only lose their
+/// trailing whitespace.
+pub fn format_flow(source: &str, config: &FormatterConfig) -> String {
+    let ending = config.line_ending.resolve(source);
+    let (bom, source) = match source.strip_prefix(BOM) {
+        Some(rest) if !config.strip_bom => (BOM.to_string(), rest),
+        Some(rest) => (String::new(), rest),
+        None => (String::new(), source),
+    };
+    let lines: Vec<Line> = source
+        .lines()
+        .map(|line| parse_record(line).unwrap_or(Line::Other(line.trim_end())))
+        .collect();
+
+    let prefix_width = match config.flow_align {
+        FlowAlign::None => 0,
+        FlowAlign::Prefixes | FlowAlign::Columns => lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Record(prefix, _) => Some(prefix.len()),
+                Line::Other(_) => None,
+            })
+            .max()
+            .unwrap_or(0),
+    };
+    let mut out = bom;
+    let mut widths = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let (prefix, items) = match line {
+            Line::Record(prefix, items) => (prefix, items),
+            Line::Other(text) => {
+                out.push_str(text);
+                out.push('\n');
+                continue;
+            }
+        };
+        // Records of the same kind in a row are aligned together
+        let same_kind = |other: &Line| matches!(other, Line::Record(p, _) if p == prefix);
+        let starts_run = index == 0 || !same_kind(&lines[index - 1]);
+        if starts_run && config.flow_align == FlowAlign::Columns {
+            widths = column_widths(lines[index..].iter().take_while(|l| same_kind(l)));
+        }
+
+        out.push_str(prefix);
+        out.push(':');
+        out.push_str(&" ".repeat(prefix_width.saturating_sub(prefix.len()) + 1));
+        for (column, item) in items.iter().enumerate() {
+            out.push_str(item);
+            if column + 1 < items.len() {
+                out.push(',');
+                let width = widths.get(column).copied().unwrap_or(0);
+                out.push_str(&" ".repeat(width.saturating_sub(item.len()) + 1));
+            }
+        }
+        out.push('\n');
+    }
+    with_line_ending(out, ending)
+}
+
+/// Width of each item column of `records`, the last item of each record
+/// left out as nothing follows it.
+fn column_widths<'a>(records: impl Iterator<Item = &'a Line<'a>>) -> Vec<usize> {
+    let mut widths: Vec<usize> = Vec::new();
+    for record in records {
+        let Line::Record(_, items) = record else {
+            continue;
+        };
+        for (column, item) in items.iter().enumerate().take(items.len() - 1) {
+            match widths.get_mut(column) {
+                Some(width) => *width = (*width).max(item.len()),
+                None => widths.push(item.len()),
+            }
+        }
+    }
+    widths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+event stream-start: GstEventStreamStart, flags=(GstStreamFlags)GST_STREAM_FLAG_NONE, group-id=(uint)1;
+event caps: video/x-raw,format=(string)I420,  width=(int)320, multiview-mode=(string)\"a, b\";
+buffer: pts=0:00:00.000000000, dur=0:00:00.033333333, flags=discont
+buffer: pts=0:00:00.033333333,   dur=0:00:00.033333333   \n\
+buffer: pts=1:00:00.100000000, dur=none, flags=delta-unit
+event eos: (no structure)
+";
+
+    fn format(source: &str, flow_align: FlowAlign) -> String {
+        let config = FormatterConfig {
+            flow_align,
+            ..FormatterConfig::default()
+        };
+        let formatted = format_flow(source, &config);
+        assert_eq!(format_flow(&formatted, &config), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_format_flow() {
+        assert_eq!(
+            format(LOG, FlowAlign::None),
+            "\
+event stream-start: GstEventStreamStart, flags=(GstStreamFlags)GST_STREAM_FLAG_NONE, group-id=(uint)1;
+event caps: video/x-raw, format=(string)I420, width=(int)320, multiview-mode=(string)\"a, b\";
+buffer: pts=0:00:00.000000000, dur=0:00:00.033333333, flags=discont
+buffer: pts=0:00:00.033333333, dur=0:00:00.033333333
+buffer: pts=1:00:00.100000000, dur=none, flags=delta-unit
+event eos: (no structure)
+"
+        );
+        // Unbalanced records and other lines are kept
+        let other = "# comment  \n\nbuffer: pts=(\nno record\n";
+        assert_eq!(
+            format(other, FlowAlign::Columns),
+            "# comment\n\nbuffer: pts=(\nno record\n"
+        );
+    }
+
+    #[test]
+    fn test_flow_align() {
+        assert_eq!(
+            format(LOG, FlowAlign::Prefixes),
+            "\
+event stream-start: GstEventStreamStart, flags=(GstStreamFlags)GST_STREAM_FLAG_NONE, group-id=(uint)1;
+event caps:         video/x-raw, format=(string)I420, width=(int)320, multiview-mode=(string)\"a, b\";
+buffer:             pts=0:00:00.000000000, dur=0:00:00.033333333, flags=discont
+buffer:             pts=0:00:00.033333333, dur=0:00:00.033333333
+buffer:             pts=1:00:00.100000000, dur=none, flags=delta-unit
+event eos:          (no structure)
+"
+        );
+        assert_eq!(
+            format(LOG, FlowAlign::Columns),
+            "\
+event stream-start: GstEventStreamStart, flags=(GstStreamFlags)GST_STREAM_FLAG_NONE, group-id=(uint)1;
+event caps:         video/x-raw, format=(string)I420, width=(int)320, multiview-mode=(string)\"a, b\";
+buffer:             pts=0:00:00.000000000, dur=0:00:00.033333333, flags=discont
+buffer:             pts=0:00:00.033333333, dur=0:00:00.033333333
+buffer:             pts=1:00:00.100000000, dur=none,              flags=delta-unit
+event eos:          (no structure)
+"
+        );
+    }
+}
@@ -0,0 +1,2732 @@
+//! Formatter for ValidateTest files.
+//!
+//! [`format_source`] parses and reformats a whole file according to a
+//! [`FormatterConfig`]; [`format_document`] does the same for an already
+//! parsed tree. [`format_source_mapped`] also maps locations between the
+//! file and its formatted version. [`format_structure`] and [`format_value`]
+//! only reformat one structure or field value of a parsed tree.
+//!
+//! [`format_document_str`] and [`format_structure_str`] parse their input
+//! themselves, for one-off use in scripts and tests.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use tree_sitter::{Node, Tree};
+use unicode_width::UnicodeWidthStr;
+
+use crate::dialect::Dialect;
+use crate::diff::diff_edits;
+use crate::document::{
+    parse_tree, parse_tree_with_errors, single_structure, write_commented, Document, EqOptions,
+};
+use crate::encoding::BOM;
+use crate::flow::{format_flow, FlowAlign};
+use crate::layout::{self, Breaking, Doc};
+use crate::refactor::{apply_edits, expected_issues_edits, TextEdit};
+use crate::source_map::SourceMap;
+use crate::strings::{quote, unescape};
+use crate::trivia::{Comment, Trivia};
+use crate::ParseError;
+
+/// Default indentation width.
+pub const DEFAULT_INDENT: usize = 4;
+/// Default maximum line length.
+pub const DEFAULT_LINE_LENGTH: usize = 120;
+
+/// Default number of values up to which [`ArrayPacking::Auto`] writes one
+/// per line.
+pub const DEFAULT_ARRAY_PACKING_THRESHOLD: usize = 8;
+
+/// Structures rewritten from quoted strings to array structures by default.
+pub const DEFAULT_QUOTED_STRUCTURE_NAMES: &[&str] = &["expected-issue", "change-severity"];
+
+/// Display width of `text`, as counted against the maximum line length: wide
+/// characters take two columns and combining ones none.
+pub(crate) fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Builds the [`Doc`] describing the formatted file, laid out by
+/// [`layout::render`].
+struct Formatter<'a> {
+    source: &'a [u8],
+    indent_width: usize,
+    max_line_length: usize,
+    /// Indentation of the lines of the node being described, when split.
+    current_indent: usize,
+    config: &'a FormatterConfig,
+    /// Comments by owner node, set by [`Formatter::format`].
+    trivia: Trivia<'a>,
+}
+
+/// The items of a split list, as the parts of a [`Doc::fill`]: each goes on
+/// the line of the previous one when it fits and neither needs its own
+/// line.
+#[derive(Default)]
+struct Items {
+    parts: Vec<Doc>,
+    /// Whether the next item goes on a new line.
+    break_next: bool,
+}
+
+impl Items {
+    fn push(&mut self, item: Doc, own_line: bool, ends_line: bool) {
+        if !self.parts.is_empty() {
+            self.parts.push(if own_line || self.break_next {
+                Doc::hard_line()
+            } else {
+                Doc::line()
+            });
+        }
+        self.parts.push(item);
+        self.break_next = ends_line;
+    }
+
+    /// Push `item` on a new line or after a space on the line of the item
+    /// before it, as it is written in the source.
+    fn push_kept(&mut self, item: Doc, new_line: bool) {
+        if !self.parts.is_empty() {
+            self.parts.push(if new_line {
+                Doc::hard_line()
+            } else {
+                Doc::text(" ")
+            });
+        }
+        self.parts.push(item);
+    }
+
+    fn into_doc(self) -> Doc {
+        Doc::fill(self.parts)
+    }
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, config: &'a FormatterConfig) -> Self {
+        Self {
+            source: source.as_bytes(),
+            indent_width: config.indent_width,
+            max_line_length: config.max_line_length,
+            current_indent: 0,
+            config,
+            trivia: Trivia::default(),
+        }
+    }
+
+    /// Indentation of the values of the brackets opened by the value of
+    /// `field`: its continuation indent.
+    fn continuation(&self, field: Node<'a>) -> usize {
+        match self.config.continuation_indent {
+            ContinuationIndent::Block => self.indent_width,
+            ContinuationIndent::Columns(columns) => columns,
+            ContinuationIndent::Equals => field
+                .child_by_field_name("name")
+                .map_or(self.indent_width, |name| {
+                    display_width(&self.node_text(name)) + self.equals().find('=').unwrap_or(0)
+                }),
+        }
+    }
+
+    /// The fields of `field_list` in the order to write them: those listed
+    /// in [`FormatterConfig::field_order`] for its structure first, then the
+    /// others as written.
+    fn ordered_fields(&self, field_list: Node<'a>) -> Vec<Node<'a>> {
+        let mut cursor = field_list.walk();
+        let mut fields: Vec<_> = field_list
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "field")
+            .collect();
+        let order = field_list
+            .parent()
+            .and_then(|s| s.child(0))
+            .filter(|n| n.kind() == "structure_name")
+            .and_then(|n| self.config.field_order.get(&self.node_text(n)));
+        if let Some(order) = order {
+            fields.sort_by_key(|field| {
+                let name = field
+                    .child_by_field_name("name")
+                    .map(|n| self.node_text(n))
+                    .unwrap_or_default();
+                order.iter().position(|o| *o == name).unwrap_or(order.len())
+            });
+        }
+        fields
+    }
+
+    /// Whether to end the structure `node` with a `;`.
+    fn ends_with_semicolon(&self, node: Node<'a>) -> bool {
+        let mut cursor = node.walk();
+        let written = node.children(&mut cursor).any(|c| c.kind() == ";");
+        let top_level = node.parent().is_some_and(|p| p.kind() == "source_file");
+        match self.config.semicolon {
+            Semicolon::Always if top_level => true,
+            Semicolon::Never if top_level => false,
+            _ => written,
+        }
+    }
+
+    /// The separator between field names and values.
+    fn equals(&self) -> &'static str {
+        if self.config.spaces_around_equals {
+            " = "
+        } else {
+            "="
+        }
+    }
+
+    /// Whether several of the `count` values of a split array or block can
+    /// share a line.
+    fn packs(&self, count: usize) -> bool {
+        match self.config.array_packing {
+            ArrayPacking::Packed => true,
+            ArrayPacking::OnePerLine => false,
+            ArrayPacking::Auto => count > self.config.array_packing_threshold,
+        }
+    }
+
+    fn format(mut self, root: Node<'a>) -> String {
+        let mut output = String::with_capacity(self.source.len());
+        // The parser skips the byte order mark; keep it unless asked not to
+        if !self.config.strip_bom && self.source.starts_with(BOM.to_string().as_bytes()) {
+            output.push(BOM);
+        }
+        self.trivia = Trivia::new(root, std::str::from_utf8(self.source).unwrap_or_default());
+        let doc = self.format_source_file(root);
+        output.push_str(&layout::render(&doc, self.max_line_length));
+        // Ensure file ends with newline
+        if !output.ends_with('\n') {
+            output.push('\n');
+        }
+        output
+    }
+
+    fn node_text(&self, node: Node) -> String {
+        node.utf8_text(self.source).unwrap_or("").to_string()
+    }
+
+    fn format_source_file(&mut self, node: Node<'a>) -> Doc {
+        let mut cursor = node.walk();
+        let structures: Vec<_> = node
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "structure")
+            .collect();
+
+        let mut docs = Vec::new();
+        for structure in structures {
+            docs.push(self.comment_lines(self.trivia.leading(structure)));
+            // Preserve blank lines from source
+            for _ in 0..self.trivia.blank_lines_before(structure) {
+                docs.push(Doc::hard_line());
+            }
+            docs.push(self.format_structure(structure));
+            docs.push(self.trailing_comment(structure));
+            docs.push(Doc::hard_line());
+        }
+        docs.push(self.comment_lines(self.trivia.dangling(node)));
+        Doc::concat(docs)
+    }
+
+    /// `comments` on their own lines, with the blank lines preceding them,
+    /// starting at the beginning of a line.
+    fn comment_lines(&self, comments: &[Comment<'a>]) -> Doc {
+        let mut docs = Vec::new();
+        for comment in comments {
+            for _ in 0..comment.blank_lines_before {
+                docs.push(Doc::hard_line());
+            }
+            docs.push(Doc::comment(self.node_text(comment.node)));
+            docs.push(Doc::hard_line());
+        }
+        Doc::concat(docs)
+    }
+
+    /// The comments after the last item of the list `node` on their own
+    /// lines, following that item.
+    fn dangling_lines(&self, node: Node<'a>) -> Doc {
+        let mut docs = Vec::new();
+        for comment in self.trivia.dangling(node) {
+            for _ in 0..=comment.blank_lines_before {
+                docs.push(Doc::hard_line());
+            }
+            docs.push(Doc::comment(self.node_text(comment.node)));
+        }
+        Doc::concat(docs)
+    }
+
+    /// The comment ending the last line of `node`, moved to the next line
+    /// when it does not fit.
+    fn trailing_comment(&self, node: Node<'a>) -> Doc {
+        match self.trivia.trailing(node) {
+            Some(comment) => Doc::trailing_comment(self.node_text(comment.node)),
+            None => Doc::nil(),
+        }
+    }
+
+    /// When to split the structure `node`, which may also be written on
+    /// one line.
+    /// The group of `doc`, the layout of `node`. With `preserve_breaks` it
+    /// is laid out as `node` is written rather than as `breaking` decides:
+    /// on one line if it is, else split.
+    fn group_kept(&self, node: Node<'a>, breaking: Breaking, doc: Doc) -> Doc {
+        if !self.config.preserve_breaks {
+            Doc::group(breaking, doc)
+        } else if node.start_position().row == node.end_position().row {
+            Doc::flat(doc)
+        } else {
+            Doc::group(Breaking::Split, doc)
+        }
+    }
+
+    /// Whether `node` starts on a line after the end of `previous`.
+    fn starts_line_after(node: Node<'a>, previous: Option<Node<'a>>) -> bool {
+        previous.is_none_or(|previous| node.start_position().row > previous.end_position().row)
+    }
+
+    fn structure_breaking(&self, node: Node<'a>) -> Breaking {
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        let split = |reason: &str| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                line = node.start_position().row + 1,
+                "structure split: {}",
+                reason
+            );
+            Breaking::Split
+        };
+        // If structure contains any nested blocks or comments, always split
+        if self.contains_nested_block(node) {
+            return split("it holds a nested block");
+        }
+        if self.trivia.has_comments(node) {
+            return split("it holds comments");
+        }
+        if self.holds_caps(node) {
+            return split("media info caps get a line of their own");
+        }
+        if let Some(max) = self.config.max_inline_fields {
+            let mut cursor = node.walk();
+            let fields: usize = node
+                .children(&mut cursor)
+                .filter(|c| c.kind() == "field_list")
+                .map(|list| {
+                    let mut cursor = list.walk();
+                    let count = list
+                        .children(&mut cursor)
+                        .filter(|c| c.kind() == "field")
+                        .count();
+                    count
+                })
+                .sum();
+            if fields > max {
+                return split("more fields than max-inline-fields");
+            }
+        }
+        // Property-related actions should always be multiline for readability
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() == "structure_name" {
+                let name = self.node_text(child);
+                if name == "check-properties"
+                    || name == "check-child-properties"
+                    || name == "set-child-properties"
+                    || name == "set-properties"
+                    || name == "expected-issue"
+                {
+                    return split("property actions and expected issues are always split");
+                }
+                break;
+            }
+        }
+        Breaking::Fit
+    }
+
+    /// Whether the structure `node` has a caps field of the media info
+    /// dialect.
+    fn holds_caps(&self, node: Node<'a>) -> bool {
+        let mut cursor = node.walk();
+        let lists: Vec<_> = node
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "field_list")
+            .collect();
+        lists.into_iter().any(|list| {
+            let mut cursor = list.walk();
+            let holds = list
+                .children(&mut cursor)
+                .filter(|c| c.kind() == "field")
+                .filter_map(|field| field.child_by_field_name("name"))
+                .any(|name| self.config.dialect.is_caps_field(&self.node_text(name)));
+            holds
+        })
+    }
+
+    fn contains_nested_block(&self, node: Node<'a>) -> bool {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() == "nested_structure_block" {
+                return true;
+            }
+            if (child.kind() == "field_list"
+                || child.kind() == "field"
+                || child.kind() == "field_value")
+                && self.contains_nested_block(child)
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn format_structure(&mut self, node: Node<'a>) -> Doc {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+
+        let name = children
+            .iter()
+            .find(|c| c.kind() == "structure_name")
+            .map(|c| self.node_text(*c))
+            .unwrap_or_default();
+        let semicolon = Doc::text(if self.ends_with_semicolon(node) {
+            ";"
+        } else {
+            ""
+        });
+        let Some(field_list) = children.iter().find(|c| c.kind() == "field_list") else {
+            return Doc::concat([Doc::text(name), semicolon]);
+        };
+
+        let (inline, split) = self.format_field_list(*field_list, self.indent_width);
+        let broken = Doc::concat([
+            self.structure_head(node, &name, *field_list, self.indent_width),
+            Doc::nest(self.indent_width, split),
+            semicolon.clone(),
+        ]);
+        let inline = Doc::concat([Doc::text(format!("{}, ", name)), inline, semicolon]);
+        self.group_kept(
+            node,
+            self.structure_breaking(node),
+            Doc::if_break(broken, inline),
+        )
+    }
+
+    /// The name of a structure split over several lines and what separates
+    /// it from its first field: a line break, or a space when
+    /// `preserve_breaks` keeps the field on the line of the name.
+    fn structure_head(&self, node: Node<'a>, name: &str, field_list: Node<'a>, step: usize) -> Doc {
+        let on_name_line = self.config.preserve_breaks
+            && self
+                .ordered_fields(field_list)
+                .first()
+                .is_some_and(|first| {
+                    first.start_position().row == node.start_position().row
+                        && self.trivia.leading(*first).is_empty()
+                });
+        if on_name_line {
+            Doc::text(format!("{}, ", name))
+        } else {
+            Doc::concat([
+                Doc::text(format!("{},", name)),
+                Doc::nest(step, Doc::hard_line()),
+            ])
+        }
+    }
+
+    /// The fields of `node` on one line, and split one per line indented by
+    /// `step`.
+    fn format_field_list(&mut self, node: Node<'a>, step: usize) -> (Doc, Doc) {
+        let fields = self.ordered_fields(node);
+        self.current_indent += step;
+        let docs: Vec<Doc> = fields.iter().map(|f| self.format_field(*f)).collect();
+        self.current_indent -= step;
+
+        let mut split = Vec::new();
+        for (i, (field, doc)) in fields.iter().zip(&docs).enumerate() {
+            let is_last = i == fields.len() - 1;
+            split.push(self.comment_lines(self.trivia.leading(*field)));
+            if is_last {
+                // Nothing can follow the last field on its line, its
+                // comments go before it
+                let mut comments = self.trivia.dangling(node).to_vec();
+                comments.extend(self.trivia.trailing(*field).copied());
+                split.push(self.comment_lines(&comments));
+            }
+            split.push(doc.clone());
+            if !is_last {
+                split.push(Doc::text(","));
+                split.push(self.trailing_comment(*field));
+                let kept_on_line = self.config.preserve_breaks
+                    && !Self::starts_line_after(fields[i + 1], Some(*field));
+                split.push(if kept_on_line {
+                    Doc::text(" ")
+                } else {
+                    Doc::hard_line()
+                });
+            }
+        }
+        (Doc::join(docs, Doc::text(", ")), Doc::concat(split))
+    }
+
+    fn format_field(&mut self, node: Node<'a>) -> Doc {
+        let name = node
+            .child_by_field_name("name")
+            .map(|n| self.node_text(n))
+            .unwrap_or_default();
+        let Some(value) = node.child_by_field_name("value") else {
+            return Doc::text(format!("{}{}", name, self.equals()));
+        };
+        let doc = self.format_field_value(value, self.continuation(node), true);
+
+        // Strings, possibly with a type cast, move to their own line rather
+        // than overflowing it. That only shortens the line when the name is
+        // longer than the extra indentation.
+        let is_string = value
+            .named_child(0)
+            .is_some_and(|c| matches!(c.kind(), "value" | "typed_value"));
+        // Caps already get their own line through their structure
+        let wrap = self.config.wrap_long_strings && !self.config.dialect.is_caps_field(&name);
+        if wrap && is_string && display_width(&name) >= self.indent_width {
+            let text = layout::flat(&doc);
+            if text.ends_with('"') && !text.contains('\n') {
+                return Doc::group(
+                    Breaking::Fit,
+                    Doc::if_break(
+                        Doc::concat([
+                            Doc::text(format!("{}{}", name, self.equals().trim_end())),
+                            Doc::nest(
+                                self.indent_width,
+                                Doc::concat([Doc::hard_line(), doc.clone()]),
+                            ),
+                        ]),
+                        Doc::concat([Doc::text(format!("{}{}", name, self.equals())), doc]),
+                    ),
+                );
+            }
+        }
+
+        // A brace moved to the next line leaves no space after the `=`
+        let is_block = value
+            .named_child(0)
+            .is_some_and(|c| c.kind() == "nested_structure_block");
+        let equals = if is_block && self.config.brace_style == BraceStyle::NextLine {
+            Doc::if_break(
+                Doc::text(self.equals().trim_end()),
+                Doc::text(self.equals()),
+            )
+        } else {
+            Doc::text(self.equals())
+        };
+        Doc::concat([Doc::text(name), equals, doc])
+    }
+
+    /// The `args` elements replacing the field value `node` of a nested
+    /// block: a long string is split at spaces, which gst-validate joins
+    /// back when building the command line.
+    fn args_elements(&self, node: Node<'a>, value: String) -> Vec<String> {
+        let is_args = node
+            .parent()
+            .and_then(|block| block.parent()?.parent())
+            .filter(|field| field.kind() == "field")
+            .and_then(|field| field.child_by_field_name("name"))
+            .is_some_and(|name| self.node_text(name) == "args");
+        let is_string =
+            node.child(0).is_some_and(|c| c.kind() == "value") && value.starts_with('"');
+        // Room left on a line, minus the trailing comma
+        let width = self.max_line_length.saturating_sub(self.current_indent + 1);
+        if !self.config.split_long_args || !is_args || !is_string || display_width(&value) <= width
+        {
+            return vec![value];
+        }
+        split_quoted_string(&value, width).unwrap_or_else(|| vec![value])
+    }
+
+    /// The value `node`, its brackets indenting their content by `step`
+    /// when split. Only a block following a field's `=`, `after_equals`, can
+    /// have its brace moved to the next line.
+    fn format_field_value(&mut self, node: Node<'a>, step: usize, after_equals: bool) -> Doc {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+
+        for child in children {
+            match child.kind() {
+                "nested_structure_block" => {
+                    return self.format_nested_block(child, step, after_equals)
+                }
+                "array" => return self.format_array(child, step),
+                "angle_bracket_array" => return self.format_angle_bracket_array(child, step),
+                "typed_value" => return self.format_typed_value(child, step),
+                "value" => return self.format_value(child, step),
+                _ => {}
+            }
+        }
+        Doc::nil()
+    }
+
+    fn format_typed_value(&mut self, node: Node<'a>, step: usize) -> Doc {
+        let type_name = node
+            .child_by_field_name("type")
+            .map(|t| self.node_text(t))
+            .unwrap_or_default();
+        let value = match node.child_by_field_name("value") {
+            Some(value) if value.kind() == "array" => self.format_array(value, step),
+            Some(value) if value.kind() == "angle_bracket_array" => {
+                self.format_angle_bracket_array(value, step)
+            }
+            // A cast string keeps its type, it is never converted
+            Some(value) => Doc::text(self.node_text(value)),
+            None => Doc::nil(),
+        };
+        Doc::concat([Doc::text(format!("({})", type_name)), value])
+    }
+
+    fn format_value(&self, node: Node<'a>, step: usize) -> Doc {
+        let text = self.node_text(node);
+
+        // Check if this is a quoted string that should be converted to array structure
+        if let Some(converted) = self.try_convert_quoted_structure(&text, step) {
+            return converted;
+        }
+
+        Doc::text(text)
+    }
+
+    /// Check if a quoted string contains a structure that should be converted to array format
+    fn try_convert_quoted_structure(&self, text: &str, step: usize) -> Option<Doc> {
+        // Must be a quoted string
+        if !self.config.convert_quoted_structures
+            || self.config.legacy_quoted_issues
+            || text.len() < 2
+            || !text.starts_with('"')
+            || !text.ends_with('"')
+        {
+            return None;
+        }
+
+        // Check if the content starts with a convertible structure name
+        let inner = &text[1..text.len() - 1]; // Remove quotes
+        let is_convertible = self.config.quoted_structure_names.iter().any(|name| {
+            inner
+                .strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.starts_with(','))
+        });
+
+        // A string GStreamer cannot read is left alone
+        let unescaped = unescape(inner).ok()?;
+
+        // Any other string holding exactly one structure with fields, such as
+        // `config="validate, ..."`, when enabled
+        let is_convertible = is_convertible
+            || (self.config.convert_any_quoted_structure
+                && Document::parse(&unescaped).is_ok_and(
+                    |doc| matches!(doc.structures.as_slice(), [s] if !s.fields.is_empty()),
+                ));
+
+        if !is_convertible {
+            return None;
+        }
+
+        // Parse and format as array structure
+        self.parse_and_format_as_array_structure(&unescaped, step)
+    }
+
+    /// Parse a structure string and format it as an array structure [name, fields...],
+    /// its fields indented by `step` when split
+    fn parse_and_format_as_array_structure(&self, content: &str, step: usize) -> Option<Doc> {
+        // Parse the content as a structure
+        let tree = parse_tree(content).ok()?;
+        let root = tree.root_node();
+
+        // Find the structure node
+        let structure_node = if root.kind() == "source_file" {
+            root.child(0)?
+        } else {
+            root
+        };
+
+        if structure_node.kind() != "structure" {
+            return None;
+        }
+
+        // Laid out like array structures
+        let mut formatter = Formatter::new(content, self.config);
+        Some(Doc::concat([
+            Doc::text("["),
+            formatter.format_array_structure(structure_node, step),
+            Doc::text("]"),
+        ]))
+    }
+
+    fn field_value_has_nested_block(&self, node: Node<'a>) -> bool {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            match child.kind() {
+                "nested_structure_block" => return true,
+                "array" => {
+                    // Check if any element in the array has nested blocks
+                    let mut arr_cursor = child.walk();
+                    for arr_child in child.children(&mut arr_cursor) {
+                        if arr_child.kind() == "array_element"
+                            && self.array_element_has_nested_block(arr_child)
+                        {
+                            return true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn field_value_has_array_structure(&self, node: Node<'a>) -> bool {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() == "array" {
+                let mut arr_cursor = child.walk();
+                for arr_child in child.children(&mut arr_cursor) {
+                    if arr_child.kind() == "array_element" {
+                        let mut elem_cursor = arr_child.walk();
+                        for elem_child in arr_child.children(&mut elem_cursor) {
+                            if elem_child.kind() == "array_structure" {
+                                return true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// The block `node`, always split unless written within a line, its
+    /// values indented by `step`.
+    fn format_nested_block(&mut self, node: Node<'a>, step: usize, after_equals: bool) -> Doc {
+        // Items with their trailing comment, and standalone comments
+        let mut cursor = node.walk();
+        let mut items: Vec<(Node<'a>, Option<Node<'a>>)> = Vec::new();
+        for child in node.named_children(&mut cursor) {
+            if matches!(child.kind(), "comment" | "line_continuation") {
+                continue;
+            }
+            for comment in self.trivia.leading(child) {
+                items.push((comment.node, None));
+            }
+            items.push((child, self.trivia.trailing(child).map(|c| c.node)));
+        }
+        for comment in self.trivia.dangling(node) {
+            items.push((comment.node, None));
+        }
+
+        // Check if any item is complex (structure, has nested blocks, or contains array structures)
+        // If so, put each item on its own line
+        let has_complex_items = items.iter().any(|(child, _)| {
+            child.kind() == "structure"
+                || (child.kind() == "field_value" && self.field_value_has_nested_block(*child))
+                || (child.kind() == "field_value" && self.field_value_has_array_structure(*child))
+                || (child.kind() == "field_value" && self.trivia.has_comments(*child))
+        });
+
+        let values = items.iter().filter(|(c, _)| c.kind() != "comment").count();
+        let one_per_line = has_complex_items || !self.packs(values);
+
+        self.current_indent += step;
+        let mut inline = Vec::new();
+        let mut split = Items::default();
+        let last_value = items.iter().rposition(|(c, _)| c.kind() != "comment");
+        let preserve_breaks = self.config.preserve_breaks;
+        let mut previous = None;
+        for (idx, (child, trailing_comment)) in items.iter().enumerate() {
+            let is_last = Some(idx) == last_value;
+            // Items stay on the lines they are written on with
+            // `preserve_breaks`
+            let new_line = Self::starts_line_after(*child, previous);
+            previous = Some(*child);
+            let push = |split: &mut Items, item, own_line, ends_line| {
+                if preserve_breaks {
+                    split.push_kept(item, new_line);
+                } else {
+                    split.push(item, own_line, ends_line);
+                }
+            };
+            let comma = Doc::text(if is_last && !self.config.block_trailing_comma {
+                ""
+            } else {
+                ","
+            });
+            let comment_text = trailing_comment.map(|c| self.node_text(c));
+
+            match child.kind() {
+                "structure" => {
+                    let doc = self.format_structure(*child);
+                    inline.push(doc.clone());
+                    let comment =
+                        comment_text.map_or(Doc::nil(), |c| Doc::text(format!("  {}", c)));
+                    push(&mut split, Doc::concat([doc, comma, comment]), true, true);
+                }
+                "field_value" => {
+                    let doc = self.format_field_value(*child, self.indent_width, false);
+                    inline.push(doc.clone());
+
+                    // Values containing nested blocks or comments go on
+                    // their own line
+                    if self.field_value_has_nested_block(*child) || self.trivia.has_comments(*child)
+                    {
+                        let comment =
+                            comment_text.map_or(Doc::nil(), |c| Doc::text(format!("  {}", c)));
+                        push(&mut split, Doc::concat([doc, comma, comment]), true, true);
+                        continue;
+                    }
+
+                    let value_str = layout::flat(&doc);
+                    let mut pieces = if has_complex_items || preserve_breaks {
+                        vec![value_str]
+                    } else {
+                        self.args_elements(*child, value_str)
+                    };
+                    let value_str = pieces.pop().unwrap_or_default();
+                    // The elements a long string was split into go first
+                    let doc = if pieces.is_empty() {
+                        doc
+                    } else {
+                        for piece in pieces {
+                            split.push(Doc::text(format!("{},", piece)), true, false);
+                        }
+                        Doc::text(value_str.as_str())
+                    };
+
+                    // Check if comment would make line too long - if so, put it before
+                    let comment_on_own_line = !preserve_breaks
+                        && comment_text.as_ref().is_some_and(|ct| {
+                            self.current_indent
+                                + display_width(&value_str)
+                                + 1
+                                + 2
+                                + display_width(ct)
+                                > self.max_line_length
+                        });
+                    let item = match comment_text {
+                        Some(ct) if comment_on_own_line => {
+                            Doc::concat([Doc::comment(ct), Doc::hard_line(), doc, comma])
+                        }
+                        Some(ct) => Doc::concat([doc, comma, Doc::text(format!("  {}", ct))]),
+                        None => Doc::concat([doc, comma]),
+                    };
+                    let ends_line =
+                        one_per_line || (trailing_comment.is_some() && !comment_on_own_line);
+                    push(
+                        &mut split,
+                        item,
+                        one_per_line || comment_on_own_line,
+                        ends_line,
+                    );
+                }
+                "comment" => {
+                    // Standalone comment
+                    push(&mut split, Doc::comment(self.node_text(*child)), true, true);
+                }
+                _ => {}
+            }
+        }
+        self.current_indent -= step;
+
+        let mut broken = Vec::new();
+        if after_equals && self.config.brace_style == BraceStyle::NextLine {
+            broken.push(Doc::hard_line());
+        }
+        broken.push(Doc::text("{"));
+        if !split.parts.is_empty() {
+            broken.push(Doc::nest(
+                step,
+                Doc::concat([Doc::hard_line(), split.into_doc()]),
+            ));
+        }
+        broken.push(Doc::hard_line());
+        broken.push(Doc::text("}"));
+        self.group_kept(
+            node,
+            Breaking::Split,
+            Doc::if_break(
+                Doc::concat(broken),
+                Doc::concat([
+                    Doc::text("{"),
+                    Doc::join(inline, Doc::text(", ")),
+                    Doc::text("}"),
+                ]),
+            ),
+        )
+    }
+
+    fn array_element_has_nested_block(&self, elem: Node<'a>) -> bool {
+        let mut cursor = elem.walk();
+        for child in elem.children(&mut cursor) {
+            if child.kind() == "array_structure" && self.contains_nested_block(child) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if an array element's structure should always be formatted multiline
+    fn array_element_should_be_multiline(&self, elem: Node<'a>) -> bool {
+        let mut cursor = elem.walk();
+        for child in elem.children(&mut cursor) {
+            if child.kind() == "array_structure" {
+                return self.array_structure_always_split(child);
+            }
+        }
+        false
+    }
+
+    fn array_structure_always_split(&self, node: Node<'a>) -> bool {
+        if self.holds_caps(node) {
+            return true;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() == "structure_name" {
+                let name = self.node_text(child);
+                return name == "expected-issue"
+                    || name == "change-severity"
+                    || name == "check-properties"
+                    || name == "check-child-properties"
+                    || name == "set-child-properties"
+                    || name == "set-properties";
+            }
+        }
+        false
+    }
+
+    fn format_array_element(&mut self, elem: Node<'a>) -> Doc {
+        let mut cursor = elem.walk();
+        let children: Vec<_> = elem.children(&mut cursor).collect();
+
+        let mut docs = Vec::new();
+        for child in children {
+            match child.kind() {
+                "array_structure" => {
+                    docs.push(self.format_array_structure(child, self.indent_width))
+                }
+                "typed_value" => docs.push(self.format_typed_value(child, self.indent_width)),
+                "[" | "]" | "," | "comment" | "line_continuation" => {}
+                _ => docs.push(Doc::text(self.node_text(child))),
+            }
+        }
+        Doc::concat(docs)
+    }
+
+    /// The structure `node` of an array, or converted from a string, without
+    /// the brackets, its fields indented by `step` when split.
+    fn format_array_structure(&mut self, node: Node<'a>, step: usize) -> Doc {
+        let mut cursor = node.walk();
+        let children: Vec<_> = node.children(&mut cursor).collect();
+
+        let name = children
+            .iter()
+            .find(|c| c.kind() == "structure_name")
+            .map(|c| self.node_text(*c))
+            .unwrap_or_default();
+        let Some(field_list) = children.iter().find(|c| c.kind() == "field_list") else {
+            return Doc::text(name);
+        };
+
+        // Split if it contains nested blocks or comments, or is always-multiline
+        let breaking = if self.array_structure_always_split(node)
+            || self.contains_nested_block(*field_list)
+            || self.trivia.has_comments(*field_list)
+        {
+            Breaking::Split
+        } else {
+            Breaking::Fit
+        };
+        let (inline, split) = self.format_field_list(*field_list, step);
+        let broken = Doc::concat([
+            self.structure_head(node, &name, *field_list, step),
+            Doc::nest(step, split),
+        ]);
+        let inline = Doc::concat([Doc::text(format!("{}, ", name)), inline]);
+        self.group_kept(node, breaking, Doc::if_break(broken, inline))
+    }
+
+    /// The array `node`, its elements indented by `step` when split.
+    fn format_array(&mut self, node: Node<'a>, step: usize) -> Doc {
+        let mut cursor = node.walk();
+        let elements: Vec<_> = node
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "array_element")
+            .collect();
+
+        if elements.is_empty() {
+            return Doc::text("[]");
+        }
+
+        // Check if any element has nested blocks or should always be multiline
+        let has_nested_blocks = elements
+            .iter()
+            .any(|e| self.array_element_has_nested_block(*e));
+
+        let has_always_multiline = elements
+            .iter()
+            .any(|e| self.array_element_should_be_multiline(*e));
+
+        // Comments around elements need their own lines
+        let has_comments = self.trivia.has_comments(node);
+        let has_element_comments = !self.trivia.dangling(node).is_empty()
+            || elements
+                .iter()
+                .any(|e| !self.trivia.leading(*e).is_empty() || self.trivia.trailing(*e).is_some());
+
+        // Special case: a single structure splits within the brackets
+        if let ([elem], false) = (elements.as_slice(), has_element_comments) {
+            let mut c = elem.walk();
+            let children: Vec<_> = elem.children(&mut c).collect();
+            if let Some(struct_node) = children.iter().find(|c| c.kind() == "array_structure") {
+                return Doc::concat([
+                    Doc::text("["),
+                    self.format_array_structure(*struct_node, step),
+                    Doc::text("]"),
+                ]);
+            }
+        }
+
+        // Multi-line format with packing
+        let packs = self.packs(elements.len());
+        self.current_indent += step;
+        let mut inline = Vec::new();
+        let mut split = Items::default();
+        let mut previous = None;
+        for (i, elem) in elements.iter().enumerate() {
+            let is_last = i == elements.len() - 1;
+            let doc = self.format_array_element(*elem);
+            inline.push(doc.clone());
+
+            // Elements with comments inside are formatted like nested ones,
+            // and those with a structure get their own line too
+            let own_line = self.array_element_has_nested_block(*elem)
+                || self.trivia.has_comments(*elem)
+                || elem
+                    .named_child(0)
+                    .is_some_and(|c| c.kind() == "array_structure");
+            let trailing = self.trivia.trailing(*elem).is_some();
+            let leading = self.trivia.leading(*elem);
+            let item = Doc::concat([
+                self.comment_lines(leading),
+                doc,
+                Doc::text(self.array_comma(is_last)),
+                self.trailing_comment(*elem),
+            ]);
+            if self.config.preserve_breaks {
+                // From its leading comments, if any
+                let start = leading.first().map_or(*elem, |comment| comment.node);
+                split.push_kept(item, Self::starts_line_after(start, previous));
+            } else {
+                split.push(
+                    item,
+                    own_line || !leading.is_empty(),
+                    own_line || is_last || trailing || !packs,
+                );
+            }
+            previous = Some(*elem);
+        }
+        self.current_indent -= step;
+
+        let breaking = if has_nested_blocks || has_always_multiline || has_comments {
+            Breaking::Split
+        } else {
+            Breaking::Fit
+        };
+        self.group_kept(
+            node,
+            breaking,
+            Doc::if_break(
+                Doc::concat([
+                    Doc::text("["),
+                    Doc::nest(
+                        step,
+                        Doc::concat([
+                            Doc::hard_line(),
+                            split.into_doc(),
+                            self.dangling_lines(node),
+                        ]),
+                    ),
+                    Doc::hard_line(),
+                    Doc::text("]"),
+                ]),
+                Doc::concat([
+                    Doc::text("["),
+                    Doc::join(inline, Doc::text(", ")),
+                    Doc::text("]"),
+                ]),
+            ),
+        )
+    }
+
+    /// The comma ending an element of an array split over several lines.
+    fn array_comma(&self, is_last: bool) -> &'static str {
+        if is_last && !self.config.array_trailing_comma {
+            ""
+        } else {
+            ","
+        }
+    }
+
+    /// The value array `node`, written on one line unless it holds comments:
+    /// then one value per line indented by `step`.
+    fn format_angle_bracket_array(&mut self, node: Node<'a>, step: usize) -> Doc {
+        let mut cursor = node.walk();
+        let values: Vec<_> = node
+            .children(&mut cursor)
+            .filter(|c| c.kind() == "field_value")
+            .collect();
+
+        if values.is_empty() {
+            return Doc::text("<>");
+        }
+
+        if !self.trivia.has_comments(node) {
+            let docs: Vec<Doc> = values
+                .into_iter()
+                .map(|v| self.format_field_value(v, self.indent_width, false))
+                .collect();
+            return Doc::flat(Doc::concat([
+                Doc::text("<"),
+                Doc::join(docs, Doc::text(", ")),
+                Doc::text(">"),
+            ]));
+        }
+
+        // One value per line, with their comments
+        self.current_indent += step;
+        let last = values.len() - 1;
+        let mut lines = Vec::new();
+        for (i, val) in values.into_iter().enumerate() {
+            lines.push(Doc::hard_line());
+            lines.push(self.comment_lines(self.trivia.leading(val)));
+            lines.push(self.format_field_value(val, self.indent_width, false));
+            lines.push(Doc::text(self.array_comma(i == last)));
+            lines.push(self.trailing_comment(val));
+        }
+        lines.push(self.dangling_lines(node));
+        self.current_indent -= step;
+        Doc::concat([
+            Doc::text("<"),
+            Doc::nest(step, Doc::concat(lines)),
+            Doc::hard_line(),
+            Doc::text(">"),
+        ])
+    }
+}
+
+/// Settings controlling how files are formatted.
+#[derive(Debug, Clone)]
+pub struct FormatterConfig {
+    pub indent_width: usize,
+    pub max_line_length: usize,
+    /// Sort `expected-issues` lists by issue-id and drop duplicate entries.
+    pub sort_expected_issues: bool,
+    /// Rewrite quoted structures such as `"expected-issue, ..."` to array
+    /// structures (`[expected-issue, ...]`). Some gst-validate branches only
+    /// accept the quoted form.
+    pub convert_quoted_structures: bool,
+    /// Names of the structures rewritten by `convert_quoted_structures`.
+    pub quoted_structure_names: Vec<String>,
+    /// Also rewrite any other quoted string holding a single structure with
+    /// fields, whatever its name. Off by default as not every field accepting
+    /// a serialized structure accepts an array.
+    pub convert_any_quoted_structure: bool,
+    /// Serialize `[expected-issue, ...]` array structures (and the other
+    /// `quoted_structure_names`) back to quoted strings, for gst-validate
+    /// versions predating the array syntax.
+    pub legacy_quoted_issues: bool,
+    /// Move string values overflowing the line length to their own line.
+    pub wrap_long_strings: bool,
+    /// Split `args` strings overflowing the line length at spaces, into
+    /// several elements. gst-validate joins them back into the same command
+    /// line, but the file holds different values, so this is opt-in.
+    pub split_long_args: bool,
+    pub line_ending: LineEnding,
+    /// Drop the UTF-8 byte order mark of files starting with one.
+    pub strip_bom: bool,
+    /// Layout of the values of arrays and blocks split over several lines.
+    pub array_packing: ArrayPacking,
+    /// Number of values up to which [`ArrayPacking::Auto`] writes one per
+    /// line.
+    pub array_packing_threshold: usize,
+    /// Where the `{` opening a nested block split over several lines goes.
+    pub brace_style: BraceStyle,
+    /// End the last value of nested blocks with a comma.
+    pub block_trailing_comma: bool,
+    /// End the last element of arrays split over several lines with a comma.
+    pub array_trailing_comma: bool,
+    /// Write `name = value` rather than `name=value`.
+    pub spaces_around_equals: bool,
+    /// Indentation of the values of a field split over several lines.
+    pub continuation_indent: ContinuationIndent,
+    /// Split structures with more fields than this even when they fit on
+    /// one line.
+    pub max_inline_fields: Option<usize>,
+    /// Keep the fields of structures and the elements of blocks and arrays
+    /// on the lines they are written on, only normalizing indentation and
+    /// spacing. Structures written on one line stay on it however long.
+    pub preserve_breaks: bool,
+    /// Only reformat the top-level structures, each through edits of the
+    /// lines of it that change, so reformatting a mostly formatted file
+    /// leaves a small diff. Comments and blank lines between structures,
+    /// line endings and the byte order mark stay as written.
+    pub minimal_diff: bool,
+    /// Canonical order of the fields of structures, by structure name.
+    /// Fields not listed follow in their original order.
+    pub field_order: BTreeMap<String, Vec<String>>,
+    /// Whether top-level structures end with a `;`.
+    pub semicolon: Semicolon,
+    /// The kind of file formatted. Structures of media info files holding
+    /// caps get one field per line, their caps staying after the `=`.
+    pub dialect: Dialect,
+    /// Column alignment of the records of validateflow expectation files.
+    pub flow_align: FlowAlign,
+}
+
+/// The `;` terminating top-level structures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Semicolon {
+    /// Keep them where they are written.
+    #[default]
+    Preserve,
+    /// End every structure with one.
+    Always,
+    /// Remove them.
+    Never,
+}
+
+/// Indentation of the values of an array or block split over several
+/// lines, relative to the field holding it. The closing bracket stays
+/// aligned with the field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "ContinuationRepr", into = "ContinuationRepr")]
+pub enum ContinuationIndent {
+    /// The indentation width, like blocks.
+    #[default]
+    Block,
+    /// This many columns.
+    Columns(usize),
+    /// Under the `=` of the field.
+    Equals,
+}
+
+/// [`ContinuationIndent`] in configuration files: a number of columns,
+/// `"block"` or `"equals"`.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ContinuationRepr {
+    Columns(usize),
+    Name(String),
+}
+
+impl std::str::FromStr for ContinuationIndent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(ContinuationIndent::Block),
+            "equals" => Ok(ContinuationIndent::Equals),
+            _ => s
+                .parse()
+                .map(ContinuationIndent::Columns)
+                .map_err(|_| format!("invalid continuation indent '{}'", s)),
+        }
+    }
+}
+
+impl TryFrom<ContinuationRepr> for ContinuationIndent {
+    type Error = String;
+
+    fn try_from(repr: ContinuationRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ContinuationRepr::Columns(columns) => Ok(ContinuationIndent::Columns(columns)),
+            ContinuationRepr::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl From<ContinuationIndent> for ContinuationRepr {
+    fn from(indent: ContinuationIndent) -> Self {
+        match indent {
+            ContinuationIndent::Block => ContinuationRepr::Name("block".to_string()),
+            ContinuationIndent::Columns(columns) => ContinuationRepr::Columns(columns),
+            ContinuationIndent::Equals => ContinuationRepr::Name("equals".to_string()),
+        }
+    }
+}
+
+/// Placement of the `{` of a nested block split over several lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BraceStyle {
+    /// After the `=` of its field: `args={`.
+    #[default]
+    SameLine,
+    /// On its own line, indented like the field and the closing `}`.
+    NextLine,
+}
+
+/// How the values of a `[...]` array or `{...}` block that does not fit on
+/// one line are laid out. Structures and nested blocks always get their own
+/// lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrayPacking {
+    /// As many values on each line as fit.
+    #[default]
+    Packed,
+    /// One value per line, so that adding one changes a single line.
+    OnePerLine,
+    /// One value per line up to the threshold, packed beyond.
+    Auto,
+}
+
+/// Line ending of the formatted output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// CRLF on Windows, LF elsewhere.
+    Native,
+    /// Keep the ending used by most lines of the input.
+    Auto,
+}
+
+impl LineEnding {
+    /// The line ending to write when formatting `source`.
+    pub fn resolve(self, source: &str) -> &'static str {
+        let crlf = match self {
+            LineEnding::Lf => false,
+            LineEnding::Crlf => true,
+            LineEnding::Native => cfg!(windows),
+            LineEnding::Auto => {
+                let crlf = source.matches("\r\n").count();
+                crlf > source.matches('\n').count() - crlf
+            }
+        };
+        if crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+}
+
+/// `text` with every line ending replaced by `ending`.
+pub(crate) fn with_line_ending(text: String, ending: &str) -> String {
+    let text = if text.contains('\r') {
+        text.replace("\r\n", "\n")
+    } else {
+        text
+    };
+    if ending == "\n" {
+        text
+    } else {
+        text.replace('\n', ending)
+    }
+}
+
+impl Default for FormatterConfig {
+    fn default() -> Self {
+        Self {
+            indent_width: DEFAULT_INDENT,
+            max_line_length: DEFAULT_LINE_LENGTH,
+            sort_expected_issues: false,
+            convert_quoted_structures: true,
+            quoted_structure_names: DEFAULT_QUOTED_STRUCTURE_NAMES
+                .iter()
+                .map(|n| n.to_string())
+                .collect(),
+            convert_any_quoted_structure: false,
+            legacy_quoted_issues: false,
+            wrap_long_strings: true,
+            split_long_args: false,
+            line_ending: LineEnding::default(),
+            strip_bom: false,
+            array_packing: ArrayPacking::default(),
+            array_packing_threshold: DEFAULT_ARRAY_PACKING_THRESHOLD,
+            brace_style: BraceStyle::default(),
+            block_trailing_comma: true,
+            array_trailing_comma: true,
+            spaces_around_equals: false,
+            continuation_indent: ContinuationIndent::default(),
+            max_inline_fields: None,
+            preserve_breaks: false,
+            minimal_diff: false,
+            field_order: BTreeMap::new(),
+            semicolon: Semicolon::default(),
+            dialect: Dialect::default(),
+            flow_align: FlowAlign::default(),
+        }
+    }
+}
+
+/// Split the quoted string `text` at spaces into quoted strings of at most
+/// `width` columns, or `None` if it holds a single word.
+///
+/// Spaces within escaped quotes, as in `\"a b\"`, are kept.
+fn split_quoted_string(text: &str, width: usize) -> Option<Vec<String>> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut breaks = Vec::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in inner.char_indices() {
+        if escaped {
+            escaped = false;
+            in_quotes ^= c == '"';
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ' ' && !in_quotes {
+            breaks.push(i);
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<usize> = None;
+    for end in breaks.into_iter().chain([inner.len()]) {
+        // Close the piece at the previous space once the next word overflows
+        if display_width(&inner[start..end]) + 2 > width {
+            if let Some(previous) = previous.filter(|&p| p > start) {
+                pieces.push(format!("\"{}\"", &inner[start..previous]));
+                start = previous + 1;
+            }
+        }
+        previous = Some(end);
+    }
+    pieces.push(format!("\"{}\"", &inner[start..]));
+    (pieces.len() > 1).then_some(pieces)
+}
+
+/// Edits replacing `[name, ...]` arrays holding a single structure listed in
+/// `config.quoted_structure_names` with the equivalent quoted string.
+fn legacy_quoted_edits(root: Node, source: &str, config: &FormatterConfig) -> Vec<TextEdit> {
+    let mut formatter = Formatter::new(source, config);
+    let trivia = Trivia::new(root, source);
+    let mut edits = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        // Strings cannot hold comments
+        if node.kind() == "array" && !trivia.has_comments(node) {
+            let mut cursor = node.walk();
+            let elements: Vec<_> = node
+                .children(&mut cursor)
+                .filter(|c| c.kind() == "array_element")
+                .collect();
+            let structure = match elements.as_slice() {
+                [element] => element
+                    .named_child(0)
+                    .filter(|c| c.kind() == "array_structure"),
+                _ => None,
+            };
+            let name = structure
+                .and_then(|s| s.child(0).filter(|n| n.kind() == "structure_name"))
+                .map(|n| formatter.node_text(n));
+            if let (Some(structure), Some(name)) = (structure, name) {
+                if config.quoted_structure_names.contains(&name) {
+                    edits.push(TextEdit {
+                        range: node.byte_range(),
+                        new_text: quote(&layout::flat(
+                            &formatter.format_array_structure(structure, config.indent_width),
+                        )),
+                    });
+                    continue;
+                }
+            }
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    edits
+}
+
+/// Format an already parsed document.
+///
+/// `root` must be the error-free `source_file` node of `source`. Source-level
+/// rewrites such as [`FormatterConfig::sort_expected_issues`] are only
+/// applied by [`format_source`].
+pub fn format_document(root: Node, source: &str, config: &FormatterConfig) -> String {
+    let formatter = Formatter::new(source, config);
+    with_line_ending(formatter.format(root), config.line_ending.resolve(source))
+}
+
+/// Parse and format `source` as [`format_document`] does, without the
+/// source-level rewrites of [`format_source`].
+pub fn format_document_str(source: &str, config: &FormatterConfig) -> Result<String, ParseError> {
+    let tree = parse_tree(source)?;
+    Ok(format_document(tree.root_node(), source, config))
+}
+
+/// Format a document built in Rust, such as with the
+/// [`validatetest!`](crate::validatetest) macro.
+///
+/// The document is written the way it would be parsed back, with the
+/// comments of its structures and fields, then formatted as
+/// [`format_document_str`] does, which fails when a name, or the text of a
+/// [`FieldValue::scalar`](crate::document::FieldValue::scalar), is not valid
+/// syntax.
+pub fn format_ast(document: &Document, config: &FormatterConfig) -> Result<String, ParseError> {
+    format_document_str(&write_commented(document), config)
+}
+
+/// Parse and format `text` holding a single structure, such as
+/// `"seek,start=1.0"`, without a final newline.
+///
+/// Comments around the structure are dropped. A second structure is reported
+/// as a [`ParseError`] at its position.
+pub fn format_structure_str(text: &str, config: &FormatterConfig) -> Result<String, ParseError> {
+    let tree = parse_tree(text)?;
+    let structure = single_structure(&tree)?;
+    Ok(format_structure(structure, text, config).expect("error-free structure"))
+}
+
+/// The formatted text replacing the top-level `structure` node of `source`,
+/// as formatting the whole file would write it.
+///
+/// Comments before the structure and after its end are left out. Returns
+/// `None` for other nodes and for structures with syntax errors, which the
+/// rest of the tree may have.
+pub fn format_structure(node: Node, source: &str, config: &FormatterConfig) -> Option<String> {
+    if node.kind() != "structure" || node.has_error() {
+        return None;
+    }
+    Some(format_subtree(node, source, config, |formatter| {
+        formatter.format_structure(node)
+    }))
+}
+
+/// The formatted text replacing the `field_value` node of `source`, the
+/// value of a field or an element of an array or block, laid out from the
+/// column it starts at.
+///
+/// Returns `None` for other nodes and for values with syntax errors.
+pub fn format_value(node: Node, source: &str, config: &FormatterConfig) -> Option<String> {
+    if node.kind() != "field_value" || node.has_error() {
+        return None;
+    }
+    Some(format_subtree(node, source, config, |formatter| {
+        let Some(field) = node.parent().filter(|parent| parent.kind() == "field") else {
+            return formatter.format_field_value(node, formatter.indent_width, false);
+        };
+        formatter.format_field_value(node, formatter.continuation(field), true)
+    }))
+}
+
+/// Render the [`Doc`] that `build` describes for `node`, indented as the line
+/// of `source` holding it and starting at its column.
+fn format_subtree<'a>(
+    node: Node<'a>,
+    source: &'a str,
+    config: &'a FormatterConfig,
+    build: impl FnOnce(&mut Formatter<'a>) -> Doc,
+) -> String {
+    let line_start = source[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    let before = &source[line_start..node.start_byte()];
+    let column = display_width(before);
+    let indent = column - display_width(before.trim_start());
+
+    let mut root = node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let mut formatter = Formatter::new(source, config);
+    formatter.trivia = Trivia::new(root, source);
+    formatter.current_indent = indent;
+    let doc = build(&mut formatter);
+
+    // The node continues a line the layout has to count
+    let doc = Doc::concat([Doc::text(" ".repeat(column)), Doc::nest(indent, doc)]);
+    let text = layout::render(&doc, formatter.max_line_length);
+    let mut text = &text[column..];
+    // A block brace already on its own line stays there
+    if before.trim().is_empty() {
+        text = text.trim_start_matches(['\n', ' ']);
+    }
+    with_line_ending(text.to_string(), config.line_ending.resolve(source))
+}
+
+/// Parse and format `source`.
+///
+/// validateflow expectations, which do not parse, are formatted by
+/// [`format_flow`] instead.
+pub fn format_source(source: &str, config: &FormatterConfig) -> Result<String, ParseError> {
+    if config.dialect == Dialect::ValidateFlow {
+        return Ok(format_flow(source, config));
+    }
+    if config.minimal_diff {
+        return format_minimal(source, config);
+    }
+    let ending = config.line_ending.resolve(source);
+    // CRLF files are formatted as LF ones, so that comments and multi-line
+    // strings do not keep a stray `\r`
+    let normalized;
+    let mut source = source;
+    if source.contains("\r\n") {
+        normalized = source.replace("\r\n", "\n");
+        source = &normalized;
+    }
+    let (source, tree) = rewrite_expected_issues(source, config)?;
+    let formatted = Formatter::new(&source, config).format(tree.root_node());
+    Ok(with_line_ending(formatted, ending))
+}
+
+/// `source` with its expected-issues sorted and quoted as `config` asks,
+/// and its tree.
+fn rewrite_expected_issues<'s>(
+    source: &'s str,
+    config: &FormatterConfig,
+) -> Result<(Cow<'s, str>, Tree), ParseError> {
+    let mut source = Cow::Borrowed(source);
+    let mut tree = parse_tree(&source)?;
+
+    if config.sort_expected_issues {
+        let edits = expected_issues_edits(tree.root_node(), &source);
+        if !edits.is_empty() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(edits = edits.len(), "sorting expected-issues");
+            source = Cow::Owned(apply_edits(&source, &edits));
+            tree = parse_tree(&source)?;
+        }
+    }
+
+    if config.legacy_quoted_issues {
+        let edits = legacy_quoted_edits(tree.root_node(), &source, config);
+        if !edits.is_empty() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(edits = edits.len(), "quoting expected-issues");
+            source = Cow::Owned(apply_edits(&source, &edits));
+            tree = parse_tree(&source)?;
+        }
+    }
+    Ok((source, tree))
+}
+
+/// Format the top-level structures of `source` one by one, leaving what is
+/// between them as written, for [`FormatterConfig::minimal_diff`].
+fn format_minimal(source: &str, config: &FormatterConfig) -> Result<String, ParseError> {
+    let (source, tree) = rewrite_expected_issues(source, config)?;
+    let edits = structure_edits(tree.root_node(), &source, config, |_| true);
+    Ok(apply_edits(&source, &edits))
+}
+
+/// Format the top-level structures of `source` holding one of the 0-based
+/// `lines`, such as those a patch changed, leaving the others as written.
+/// An empty range, for lines removed there, picks the structures on either
+/// side of it.
+///
+/// Only those structures have to parse. Expected-issues are not sorted or
+/// quoted, and validateflow expectations are formatted whole.
+pub fn format_lines(
+    source: &str,
+    lines: &[Range<usize>],
+    config: &FormatterConfig,
+) -> Result<String, ParseError> {
+    if config.dialect == Dialect::ValidateFlow {
+        return Ok(format_flow(source, config));
+    }
+    let touched = |node: Node| {
+        let (first, last) = (node.start_position().row, node.end_position().row);
+        lines.iter().any(|range| {
+            if range.is_empty() {
+                first <= range.start && range.start <= last + 1
+            } else {
+                range.start <= last && first < range.end
+            }
+        })
+    };
+    let tree = parse_tree_with_errors(source);
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    if let Some(error) = root
+        .named_children(&mut cursor)
+        .find(|node| node.has_error() && touched(*node))
+    {
+        return Err(ParseError::from_root(error));
+    }
+    let edits = structure_edits(root, source, config, touched);
+    Ok(apply_edits(source, &edits))
+}
+
+/// Edits formatting the top-level structures of the tree of `source` that
+/// `select` picks, in the line ending of `source`. Each structure only gets
+/// edits for the lines the formatter changes in it, and structures with
+/// syntax errors are left out.
+pub(crate) fn structure_edits(
+    root: Node,
+    source: &str,
+    config: &FormatterConfig,
+    select: impl Fn(Node) -> bool,
+) -> Vec<TextEdit> {
+    let config = FormatterConfig {
+        line_ending: LineEnding::Auto,
+        ..config.clone()
+    };
+    let mut cursor = root.walk();
+    let mut edits = Vec::new();
+    for structure in root.named_children(&mut cursor) {
+        let Some(formatted) = select(structure)
+            .then(|| format_structure(structure, source, &config))
+            .flatten()
+        else {
+            continue;
+        };
+        let start = structure.start_byte();
+        edits.extend(
+            diff_edits(&source[structure.byte_range()], &formatted)
+                .into_iter()
+                .map(|edit| TextEdit {
+                    range: edit.range.start + start..edit.range.end + start,
+                    new_text: edit.new_text,
+                }),
+        );
+    }
+    edits
+}
+
+/// Parse and format `source`, with the [`SourceMap`] translating locations
+/// between `source` and the formatted text.
+pub fn format_source_mapped(
+    source: &str,
+    config: &FormatterConfig,
+) -> Result<(String, SourceMap), ParseError> {
+    let formatted = format_source(source, config)?;
+    let map = SourceMap::new(source, &formatted);
+    Ok((formatted, map))
+}
+
+/// Why [`verify`] rejected the output of the formatter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The input does not parse.
+    Input(ParseError),
+    /// The formatted output does not parse.
+    Output(ParseError),
+    /// The output means something else than the input, starting with the
+    /// structure at this 0-based line of the input.
+    Changed { line: usize },
+    /// The output has a different number of structures.
+    StructureCount { input: usize, output: usize },
+    /// The output lost comments of the input, or changed their words.
+    Comments,
+    /// Formatting the output again changes it.
+    NotIdempotent,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Input(e) => write!(f, "{}", e),
+            VerifyError::Output(e) => write!(f, "the formatted output does not parse: {}", e),
+            VerifyError::Changed { line } => write!(
+                f,
+                "formatting changes the meaning of the structure at line {}",
+                line + 1
+            ),
+            VerifyError::StructureCount { input, output } => {
+                write!(f, "formatting turns {} structures into {}", input, output)
+            }
+            VerifyError::Comments => write!(f, "formatting loses comments"),
+            VerifyError::NotIdempotent => write!(f, "formatting the output again changes it"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Words of the comments of `source`, sorted: long comments are wrapped,
+/// and comments can move from one side of a separator to the other.
+fn comment_words(source: &str) -> Vec<String> {
+    let tree = parse_tree_with_errors(source);
+    let mut words = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "comment" {
+            let text = node.utf8_text(source.as_bytes()).unwrap_or_default();
+            let text = text.strip_prefix('#').unwrap_or(text);
+            words.extend(text.split_whitespace().map(str::to_string));
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    words.sort_unstable();
+    words
+}
+
+/// Format `source` and check the result: it must parse, mean the same as
+/// the input, keep all of its comments and be left unchanged by a second
+/// pass.
+///
+/// With `sort_expected_issues`, the input is compared once sorted, and
+/// with a `field_order`, regardless of the order of fields. validateflow
+/// expectations are only checked for idempotence.
+pub fn verify(source: &str, config: &FormatterConfig) -> Result<String, VerifyError> {
+    if config.dialect == Dialect::ValidateFlow {
+        let formatted = format_flow(source, config);
+        if format_flow(&formatted, config) != formatted {
+            return Err(VerifyError::NotIdempotent);
+        }
+        return Ok(formatted);
+    }
+    let formatted = format_source(source, config).map_err(VerifyError::Input)?;
+    let output = Document::parse(&formatted).map_err(VerifyError::Output)?;
+
+    let mut reference = source.replace("\r\n", "\n");
+    if config.sort_expected_issues {
+        let tree = parse_tree(&reference).map_err(VerifyError::Input)?;
+        let edits = expected_issues_edits(tree.root_node(), &reference);
+        reference = apply_edits(&reference, &edits);
+    }
+    let input = Document::parse(&reference).map_err(VerifyError::Input)?;
+    if input.structures.len() != output.structures.len() {
+        return Err(VerifyError::StructureCount {
+            input: input.structures.len(),
+            output: output.structures.len(),
+        });
+    }
+    let options = EqOptions {
+        ignore_field_order: !config.field_order.is_empty(),
+        join_args: config.split_long_args,
+        ..EqOptions::default()
+    };
+    if let Some(changed) = input
+        .structures
+        .iter()
+        .zip(&output.structures)
+        .find(|(a, b)| !a.semantically_eq_with(b, &options))
+    {
+        let line = reference[..changed.0.span.start].matches('\n').count();
+        return Err(VerifyError::Changed { line });
+    }
+    if comment_words(&reference) != comment_words(&formatted) {
+        return Err(VerifyError::Comments);
+    }
+
+    if format_source(&formatted, config).map_err(VerifyError::Output)? != formatted {
+        return Err(VerifyError::NotIdempotent);
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(input: &str) -> String {
+        format_source(input, &FormatterConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_simple_structure_inline() {
+        assert_eq!(fmt("action, foo=bar"), "action, foo=bar\n");
+    }
+
+    #[test]
+    fn test_simple_structure_multiline() {
+        assert_eq!(
+            fmt("action, foo=bar, baz=123"),
+            "action, foo=bar, baz=123\n"
+        );
+    }
+
+    #[test]
+    fn test_long_structure_splits() {
+        // This input is >150 chars when formatted, so it should split
+        let input="very-long-action-name-here, field1=\"some long value here\", field2=\"another long value\", field3=\"yet another value\", field4=\"and more values\", field5=\"even more values here to exceed the limit\"";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "Long structure should split to multiple lines"
+        );
+    }
+
+    #[test]
+    fn test_nested_block_packing() {
+        let input = "meta, args={-t, video, --sink, fakesink}";
+        let output = fmt(input);
+        // Short values should be packed on same line
+        assert!(output.contains("-t, video, --sink, fakesink"));
+    }
+
+    #[test]
+    fn test_nested_block_long_value_own_line() {
+        // The nested block content exceeds 150 chars, so the structure should go multiline
+        // and the long string should be on its own line within the block
+        let input = r#"meta, args={-t, video, --sink, "this is a very long string value that definitely exceeds one hundred and fifty characters so it should cause line breaking to occur"}"#;
+        let output = fmt(input);
+        // Structure should split because nested block is long
+        assert!(
+            output.contains("args={\n"),
+            "Should split to multiline when block content is long"
+        );
+    }
+
+    #[test]
+    fn test_preserves_blank_lines() {
+        let input = "action1, foo=bar\n\naction2, baz=123";
+        let output = fmt(input);
+        assert!(
+            output.contains("\n\n"),
+            "Should preserve blank line between structures"
+        );
+    }
+
+    #[test]
+    fn test_no_extra_blank_lines() {
+        let input = "action1, foo=bar\naction2, baz=123";
+        let output = fmt(input);
+        assert!(!output.contains("\n\n"), "Should not add blank lines");
+    }
+
+    #[test]
+    fn test_comment_preserved() {
+        let input = "# This is a comment\naction, foo=bar";
+        let output = fmt(input);
+        assert!(output.starts_with("# This is a comment\n"));
+    }
+
+    #[test]
+    fn test_long_comment_wrapped() {
+        let long_comment="# This is a very long comment that exceeds 150 characters and should be wrapped to multiple lines because we want to keep lines under 150 chars for readability";
+        let input = format!("{}\naction, foo=bar", long_comment);
+        let output = fmt(&input);
+        // Comment should be wrapped to multiple lines
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("# "));
+        assert!(lines[1].starts_with("# "));
+        assert!(lines[0].len() <= DEFAULT_LINE_LENGTH);
+        assert!(lines[1].len() <= DEFAULT_LINE_LENGTH);
+    }
+
+    #[test]
+    fn test_line_length_uses_display_width() {
+        // 117 columns but 197 bytes: fits on a line
+        let summary = "é".repeat(80);
+        let input = format!("meta, summary=\"{}\", handles-states=true\n", summary);
+        assert_eq!(display_width(input.trim_end()), 117);
+        assert_eq!(fmt(&input), input);
+
+        // Wide characters take two columns each
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("e\u{301}"), 1);
+        let input = format!(
+            "meta, summary=\"{}\", handles-states=true\n",
+            "日".repeat(50)
+        );
+        assert!(fmt(&input).starts_with("meta,\n"));
+    }
+
+    #[test]
+    fn test_array_inline_short() {
+        let input = "action, values=[1, 2, 3]";
+        let output = fmt(input);
+        assert_eq!(output, "action, values=[1, 2, 3]\n");
+    }
+
+    #[test]
+    fn test_array_with_structures() {
+        // expected-issue should be multiline
+        let input = "meta, issues={[expected-issue, level=critical, id=foo]}";
+        let output = fmt(input);
+        assert!(
+            output.contains("[expected-issue,\n"),
+            "expected-issue should be multiline: {output}"
+        );
+        assert!(output.contains("level=critical"));
+        assert!(output.contains("id=foo"));
+    }
+
+    #[test]
+    fn test_semicolon_preserved() {
+        let input = "set-vars, foo=\"bar\";";
+        let output = fmt(input);
+        assert!(output.ends_with(";\n"));
+    }
+
+    #[test]
+    fn test_typed_value() {
+        let input = "action, value=(int)42";
+        let output = fmt(input);
+        assert!(output.contains("value=(int)42"));
+    }
+
+    #[test]
+    fn test_spaces_around_equals() {
+        let input = "action,foo=bar,baz=123";
+        let output = fmt(input);
+        assert!(output.contains("foo=bar"));
+        assert!(output.contains("baz=123"));
+    }
+
+    #[test]
+    fn test_spaces_around_equals_option() {
+        let config = FormatterConfig {
+            spaces_around_equals: true,
+            brace_style: BraceStyle::NextLine,
+            ..FormatterConfig::default()
+        };
+        let input = "foreach, start=<0.0, 1.5>, n=[1, 20], actions={[seek, start=$(start)]}\n";
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "foreach,\n    start = <0.0, 1.5>,\n    n = [1, 20],\n    actions =\n    {\n        [seek, start = $(start)],\n    }\n"
+        );
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let input = "meta,\n    handles-states=true,\n    args={\n        \"pipeline\",\n    }\n";
+        let output1 = fmt(input);
+        let output2 = fmt(&output1);
+        assert_eq!(output1, output2, "Formatting should be idempotent");
+    }
+
+    #[test]
+    fn test_file_ends_with_newline() {
+        let input = "action, foo=bar";
+        let output = fmt(input);
+        assert!(output.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_trailing_comment_short_stays_on_line() {
+        let input = "meta, args={\n    value,  # short\n}";
+        let output = fmt(input);
+        assert!(
+            output.contains("value,  # short"),
+            "Short trailing comment should stay on same line"
+        );
+    }
+
+    #[test]
+    fn test_trailing_comment_long_moves_before() {
+        let input = "meta, args={\n    [action-with-long-name, param=\"value\"],  # this is a very very very long trailing comment that exceeds the line length limit and should be moved before\n}";
+        let output = fmt(input);
+        // The comment should appear BEFORE the element it was trailing
+        assert!(
+            output.contains("# this is a very very very long trailing comment"),
+            "Long comment should be preserved"
+        );
+        assert!(
+            output.contains("[action-with-long-name, param=\"value\"],\n"),
+            "Element should have comma and newline after, no trailing comment"
+        );
+        // Verify order: comment comes before element
+        let comment_pos = output.find("# this is a very very").unwrap();
+        let element_pos = output.find("[action-with-long-name").unwrap();
+        assert!(
+            comment_pos < element_pos,
+            "Comment should appear before element when too long"
+        );
+    }
+
+    #[test]
+    fn test_property_actions_always_multiline() {
+        // These short structures should still be multiline
+        let input = "check-properties, foo=bar, baz=123";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "check-properties should always be multiline: {output}"
+        );
+
+        let input = "set-properties, foo=bar";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "set-properties should always be multiline: {output}"
+        );
+
+        let input = "check-child-properties, foo=bar";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "check-child-properties should always be multiline: {output}"
+        );
+
+        let input = "set-child-properties, foo=bar";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "set-child-properties should always be multiline: {output}"
+        );
+    }
+
+    #[test]
+    fn test_expected_issue_always_multiline() {
+        let input = "expected-issue, issue-id=foo, level=critical";
+        let output = fmt(input);
+        assert!(
+            output.contains(",\n    "),
+            "expected-issue should always be multiline: {output}"
+        );
+    }
+
+    #[test]
+    fn test_quoted_string_to_array_structure_conversion() {
+        // Quoted expected-issue strings should be converted to array structures
+        let input = r#"meta, expected-issues={
+    "expected-issue, issue-id=foo, level=critical",
+}"#;
+        let output = fmt(input);
+        assert!(
+            output.contains("[expected-issue,"),
+            "Quoted expected-issue should be converted to array structure: {output}"
+        );
+        assert!(
+            !output.contains("\"expected-issue,"),
+            "Should not contain quoted expected-issue: {output}"
+        );
+    }
+
+    #[test]
+    fn test_quoted_structure_layout() {
+        // Converted structures are laid out like array structures, the
+        // bracket closing after the last field
+        let quoted = r#"meta, expected-issues={
+    "expected-issue, issue-id=runtime::not-negotiated, details=\"some very long details about the failure that overflow\", level=critical",
+}"#;
+        let array = r#"meta, expected-issues={
+    [expected-issue, issue-id=runtime::not-negotiated, details="some very long details about the failure that overflow", level=critical],
+}"#;
+        let expected = r#"meta,
+    expected-issues={
+        [expected-issue,
+            issue-id=runtime::not-negotiated,
+            details="some very long details about the failure that overflow",
+            level=critical],
+    }
+"#;
+        assert_eq!(fmt(quoted), expected);
+        assert_eq!(fmt(array), expected);
+        assert_eq!(fmt(expected), expected);
+    }
+
+    #[test]
+    fn test_quoted_string_escapes_unescaped() {
+        // Escaped quotes and backslashes should be properly unescaped
+        let input = r#"meta, expected-issues={
+    "expected-issue, issue-id=foo, details=\"test\\\\nvalue\"",
+}"#;
+        let output = fmt(input);
+        // The \" should become " and \\\\ should become \\
+        assert!(
+            output.contains(r#"details="test\\nvalue""#),
+            "Escapes should be properly unescaped: {output}"
+        );
+    }
+
+    #[test]
+    fn test_sort_expected_issues_option() {
+        let input = r#"meta, expected-issues={
+    [expected-issue, issue-id=b],
+    [expected-issue, issue-id=a],
+    [expected-issue, issue-id=b],
+}"#;
+        let config = FormatterConfig {
+            sort_expected_issues: true,
+            ..FormatterConfig::default()
+        };
+        let output = format_source(input, &config).unwrap();
+        let a = output.find("issue-id=a").unwrap();
+        let b = output.find("issue-id=b").unwrap();
+        assert!(a < b, "expected-issues should be sorted: {output}");
+        assert_eq!(output.matches("issue-id=b").count(), 1, "{output}");
+
+        // Off by default
+        assert!(fmt(input).find("issue-id=b").unwrap() < fmt(input).find("issue-id=a").unwrap());
+    }
+
+    #[test]
+    fn test_change_severity_conversion() {
+        let input = r#"meta, overrides={
+    "change-severity, issue-id=foo, new-severity=warning",
+}"#;
+        let output = fmt(input);
+        assert!(
+            output.contains("[change-severity,"),
+            "Quoted change-severity should be converted to array structure: {output}"
+        );
+    }
+
+    #[test]
+    fn test_quoted_structure_conversion_config() {
+        let input = r#"meta, expected-issues={
+    "expected-issue, issue-id=foo",
+    "custom-issue, issue-id=bar",
+}"#;
+
+        let config = FormatterConfig {
+            convert_quoted_structures: false,
+            ..FormatterConfig::default()
+        };
+        let output = format_source(input, &config).unwrap();
+        assert!(
+            output.contains("\"expected-issue, issue-id=foo\""),
+            "{output}"
+        );
+
+        let mut config = FormatterConfig::default();
+        config
+            .quoted_structure_names
+            .push("custom-issue".to_string());
+        let output = format_source(input, &config).unwrap();
+        assert!(output.contains("[expected-issue,"), "{output}");
+        assert!(output.contains("[custom-issue, issue-id=bar]"), "{output}");
+
+        // Names must match the whole structure name
+        assert!(fmt(input).contains("\"custom-issue, issue-id=bar\""));
+    }
+
+    #[test]
+    fn test_convert_any_quoted_structure() {
+        let input = r#"set-globals, config="validate, fail-on-missing-plugin=true", label="a, b""#;
+        assert_eq!(fmt(input), format!("{input}\n"));
+
+        let config = FormatterConfig {
+            convert_any_quoted_structure: true,
+            ..FormatterConfig::default()
+        };
+        let output = format_source(input, &config).unwrap();
+        assert!(
+            output.contains("config=[validate, fail-on-missing-plugin=true]"),
+            "{output}"
+        );
+        // Not a structure: `b` has no value
+        assert!(output.contains(r#"label="a, b""#), "{output}");
+    }
+
+    #[test]
+    fn test_legacy_quoted_issues() {
+        let input = r#"meta, expected-issues={
+    [expected-issue, issue-id=a, details="got \"foo\""],
+    [other, x=1],
+}"#;
+        let config = FormatterConfig {
+            legacy_quoted_issues: true,
+            ..FormatterConfig::default()
+        };
+        let output = format_source(input, &config).unwrap();
+        assert!(
+            output.contains(r#""expected-issue, issue-id=a, details=\"got \\\"foo\\\"\"""#),
+            "{output}"
+        );
+        assert!(output.contains("[other, x=1]"), "{output}");
+        assert_eq!(format_source(&output, &config).unwrap(), output);
+        // Converting back yields the original entry
+        let back = Document::parse(&fmt(&output)).unwrap();
+        assert!(back.semantically_eq(&Document::parse(input).unwrap()));
+    }
+
+    #[test]
+    fn test_long_string_own_line() {
+        let summary = format!("\"{}\"", "word ".repeat(25).trim_end());
+        let input = format!("meta,\n    summary={},\n    handles-states=true\n", summary);
+        let output = fmt(&input);
+        assert_eq!(
+            output,
+            format!(
+                "meta,\n    summary=\n        {},\n    handles-states=true\n",
+                summary
+            )
+        );
+        assert_eq!(fmt(&output), output);
+
+        let config = FormatterConfig {
+            wrap_long_strings: false,
+            ..FormatterConfig::default()
+        };
+        assert_eq!(format_source(&input, &config).unwrap(), input);
+    }
+
+    #[test]
+    fn test_long_args_split() {
+        let pipeline =
+            "videotestsrc ! capsfilter caps=\\\"video/x-raw, format=I420\\\" ! fakesink ".repeat(3);
+        let input = format!("meta, args={{\"{}\", -t}}\n", pipeline.trim_end());
+        // Only split on request, it changes the values of the file
+        assert!(fmt(&input).contains(pipeline.trim_end()));
+        let config = FormatterConfig {
+            split_long_args: true,
+            ..FormatterConfig::default()
+        };
+        let fmt = |source: &str| format_source(source, &config).unwrap();
+        let output = fmt(&input);
+        assert!(
+            output.lines().all(|l| l.len() <= DEFAULT_LINE_LENGTH),
+            "{output}"
+        );
+        assert!(
+            output.contains("\"video/x-raw, format=I420\\\""),
+            "{output}"
+        );
+        assert_eq!(output.matches("fakesink").count(), 3, "{output}");
+        assert!(
+            output.ends_with("        \"fakesink\", -t,\n    }\n"),
+            "{output}"
+        );
+        assert_eq!(fmt(&output), output);
+        // The split elements hold the same command line
+        assert_eq!(verify(&input, &config).unwrap(), output);
+
+        assert_eq!(
+            split_quoted_string("\"a b c\"", 4),
+            Some(vec!["\"a\"".into(), "\"b\"".into(), "\"c\"".into()])
+        );
+        assert_eq!(split_quoted_string("\"abcdef\"", 5), None);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let input = "meta,\r\n    summary=\"a\"\r\n# note\r\nplay\r\n";
+        let lf = "meta, summary=\"a\"\n# note\nplay\n";
+        assert_eq!(fmt(input), lf);
+
+        let config = |line_ending| FormatterConfig {
+            line_ending,
+            ..FormatterConfig::default()
+        };
+        let crlf = lf.replace('\n', "\r\n");
+        assert_eq!(
+            format_source(input, &config(LineEnding::Auto)).unwrap(),
+            crlf
+        );
+        assert_eq!(format_source(lf, &config(LineEnding::Auto)).unwrap(), lf);
+        assert_eq!(format_source(lf, &config(LineEnding::Crlf)).unwrap(), crlf);
+        // Mostly LF, with a stray CRLF line
+        assert_eq!(LineEnding::Auto.resolve("meta\r\nplay\nstop\n"), "\n");
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        let input = "\u{feff}meta,   handles-states=true\nplay\n";
+        assert_eq!(fmt(input), "\u{feff}meta, handles-states=true\nplay\n");
+        let config = FormatterConfig {
+            strip_bom: true,
+            ..FormatterConfig::default()
+        };
+        assert_eq!(
+            format_source(input, &config).unwrap(),
+            "meta, handles-states=true\nplay\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_attached() {
+        let input = "seek, start=1.0, \\\n  # the flags\n  flags=flush  # trailing\nplay # go\nset-vars, l=<1, # one\n  2>, x=[1, # first\n  2, 3]\nmeta, args={\n    a, # a\n    b,\n    # last\n}\n# end\n";
+        let expected = "seek,
+    start=1.0,
+    # the flags
+    flags=flush  # trailing
+play  # go
+set-vars,
+    l=<
+        1,  # one
+        2,
+    >,
+    x=[
+        1,  # first
+        2, 3,
+    ]
+meta,
+    args={
+        a,  # a
+        b,
+        # last
+    }
+# end
+";
+        assert_eq!(
+            verify(input, &FormatterConfig::default()).unwrap(),
+            expected
+        );
+
+        assert_eq!(
+            comment_words("a, b=1 # x y\n"),
+            comment_words("# y\n# x\na, b=1\n")
+        );
+    }
+
+    #[test]
+    fn test_array_packing() {
+        let input =
+            "meta, args={\"--aaaa\", \"--bbbb\", \"--cccc\"}, x=[1111111, 2222222, 3333333]\n";
+        let config = |array_packing| FormatterConfig {
+            max_line_length: 28,
+            array_packing,
+            array_packing_threshold: 2,
+            ..FormatterConfig::default()
+        };
+        let packed = "meta,\n    args={\n        \"--aaaa\", \"--bbbb\",\n        \"--cccc\",\n    },\n    x=[\n        1111111, 2222222,\n        3333333,\n    ]\n";
+        let one_per_line = "meta,\n    args={\n        \"--aaaa\",\n        \"--bbbb\",\n        \"--cccc\",\n    },\n    x=[\n        1111111,\n        2222222,\n        3333333,\n    ]\n";
+        let format = |packing| format_source(input, &config(packing)).unwrap();
+        assert_eq!(format(ArrayPacking::Packed), packed);
+        assert_eq!(format(ArrayPacking::OnePerLine), one_per_line);
+        // Three values are above the threshold
+        assert_eq!(format(ArrayPacking::Auto), packed);
+        let config = FormatterConfig {
+            array_packing_threshold: 3,
+            ..config(ArrayPacking::Auto)
+        };
+        assert_eq!(format_source(input, &config).unwrap(), one_per_line);
+    }
+
+    #[test]
+    fn test_brace_style() {
+        let input = "meta, args={\"--a\", {b, c}}\n";
+        let config = FormatterConfig {
+            brace_style: BraceStyle::NextLine,
+            block_trailing_comma: false,
+            ..FormatterConfig::default()
+        };
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "meta,\n    args=\n    {\n        \"--a\",\n        {\n            b, c\n        }\n    }\n"
+        );
+        assert_eq!(
+            fmt(input),
+            "meta,\n    args={\n        \"--a\",\n        {\n            b, c,\n        },\n    }\n"
+        );
+    }
+
+    #[test]
+    fn test_array_trailing_comma() {
+        let input = "meta, a=[1111, 2222, 3333], b=<1, 2,  # two\n>\n";
+        let config = FormatterConfig {
+            array_trailing_comma: false,
+            max_line_length: 20,
+            ..FormatterConfig::default()
+        };
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "meta,\n    a=[\n        1111, 2222,\n        3333\n    ],\n    b=<\n        1,\n        2  # two\n    >\n"
+        );
+        // Arrays written on one line never end with a comma
+        assert_eq!(
+            fmt("meta, a=[1, 2,], b=<1, 2,>\n"),
+            "meta, a=[1, 2], b=<1, 2>\n"
+        );
+    }
+
+    #[test]
+    fn test_continuation_indent() {
+        let input = "meta, args={\"--a\", {b}}, flags=[1111, 2222]\n";
+        let format = |continuation_indent| {
+            let config = FormatterConfig {
+                continuation_indent,
+                max_line_length: 14,
+                ..FormatterConfig::default()
+            };
+            verify(input, &config).unwrap()
+        };
+        assert_eq!(
+            format(ContinuationIndent::Columns(8)),
+            "meta,\n    args={\n            \"--a\",\n            {\n                b,\n            },\n    },\n    flags=[\n            1111,\n            2222,\n    ]\n"
+        );
+        assert_eq!(
+            format(ContinuationIndent::Equals),
+            "meta,\n    args={\n        \"--a\",\n        {\n            b,\n        },\n    },\n    flags=[\n         1111,\n         2222,\n    ]\n"
+        );
+        assert_eq!("equals".parse(), Ok(ContinuationIndent::Equals));
+        assert!("x".parse::<ContinuationIndent>().is_err());
+    }
+
+    #[test]
+    fn test_max_inline_fields() {
+        let config = FormatterConfig {
+            max_inline_fields: Some(2),
+            ..FormatterConfig::default()
+        };
+        let input = "seek, start=1.0, flags=flush\nseek, start=1.0, stop=2.0, flags=flush\n";
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "seek, start=1.0, flags=flush\nseek,\n    start=1.0,\n    stop=2.0,\n    flags=flush\n"
+        );
+    }
+
+    #[test]
+    fn test_preserve_breaks() {
+        let config = FormatterConfig {
+            preserve_breaks: true,
+            ..FormatterConfig::default()
+        };
+        let input = format!(
+            "set-properties,  a=1, b=2,\n  c=3\nseek, {}stop=2.0\nmeta,\n  args = {{ \"--set\", 1,\n   2 }},  x=[ 1,\n 2 ]\n",
+            "start=1.0, ".repeat(10)
+        );
+        let expected = format!(
+            "set-properties, a=1, b=2,\n    c=3\nseek, {}stop=2.0\nmeta,\n    args={{\n        \"--set\", 1,\n        2,\n    }}, x=[\n        1,\n        2,\n    ]\n",
+            "start=1.0, ".repeat(10)
+        );
+        assert_eq!(verify(&input, &config).unwrap(), expected);
+        // Reflowed otherwise
+        let input = "set-properties, a=1, b=2,\n  c=3\n";
+        assert_eq!(
+            verify(input, &FormatterConfig::default()).unwrap(),
+            "set-properties,\n    a=1,\n    b=2,\n    c=3\n"
+        );
+    }
+
+    #[test]
+    fn test_minimal_diff() {
+        let config = FormatterConfig {
+            minimal_diff: true,
+            ..FormatterConfig::default()
+        };
+        let input = "meta,\r\n    handles-states=true\r\n\r\n\r\n\r\n#   note  \r\nseek,   start=1.0,flags=flush # seek\r\nplay\r\n";
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "meta, handles-states=true\r\n\r\n\r\n\r\n#   note  \r\nseek, start=1.0, flags=flush # seek\r\nplay\r\n"
+        );
+        // Every line is rewritten otherwise
+        assert_eq!(
+            verify(input, &FormatterConfig::default()).unwrap(),
+            "meta, handles-states=true\n\n\n\n#   note  \nseek, start=1.0, flags=flush  # seek\nplay\n"
+        );
+    }
+
+    #[test]
+    fn test_format_lines() {
+        let config = FormatterConfig::default();
+        let source = "meta,   handles-states=true\nseek,   start=1.0,\n    flags=flush\nplay,   =1\nstop,   b=1\n";
+        assert_eq!(
+            format_lines(source, &[0..1, 2..3], &config).unwrap(),
+            "meta, handles-states=true\nseek, start=1.0, flags=flush\nplay,   =1\nstop,   b=1\n"
+        );
+        // Lines removed between two structures touch both
+        assert_eq!(
+            format_lines(source, &[1..1, 5..5], &config).unwrap(),
+            "meta, handles-states=true\nseek, start=1.0, flags=flush\nplay,   =1\nstop, b=1\n"
+        );
+        assert_eq!(format_lines(source, &[], &config).unwrap(), source);
+        // Structures with syntax errors can only be left out
+        assert_eq!(
+            format_lines(source, &[0..1, 3..4], &config)
+                .unwrap_err()
+                .position
+                .row,
+            3
+        );
+    }
+
+    #[test]
+    fn test_media_info_dialect() {
+        let config = FormatterConfig {
+            dialect: Dialect::MediaInfo,
+            ..FormatterConfig::default()
+        };
+        let caps = format!(
+            "video/x-raw, format=(string)I420, {}",
+            "width=(int)320, ".repeat(8)
+        );
+        let input = format!(
+            "file, duration=(guint64)10, streams-caps=\"video/quicktime\", streams={{[stream, type=video, caps=\"{}\"]}}\nframe, pts=(guint64)0, checksum=\"a8f0\"\n",
+            caps
+        );
+        let expected = format!(
+            "file,\n    duration=(guint64)10,\n    streams-caps=\"video/quicktime\",\n    streams={{\n        [stream,\n            type=video,\n            caps=\"{}\"],\n    }}\nframe, pts=(guint64)0, checksum=\"a8f0\"\n",
+            caps
+        );
+        assert_eq!(verify(&input, &config).unwrap(), expected);
+        // Scenarios keep structures with caps fields that fit on one line
+        let input = "set-caps, caps=\"video/x-raw\"\n";
+        assert_eq!(verify(input, &FormatterConfig::default()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_field_order() {
+        let mut config = FormatterConfig::default();
+        config.field_order.insert(
+            "seek".to_string(),
+            vec!["start".to_string(), "stop".to_string(), "flags".to_string()],
+        );
+        let input = "seek, flags=flush, x=1, stop=2.0, y=2, start=1.0\nmeta, args={[seek, stop=2.0, start=1.0]}\nplay, b=1, a=2\n";
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "seek, start=1.0, stop=2.0, flags=flush, x=1, y=2\nmeta,\n    args={\n        [seek, start=1.0, stop=2.0],\n    }\nplay, b=1, a=2\n"
+        );
+
+        // Comments move with their field
+        let input = "seek,\n    # end\n    stop=2.0,  # two\n    start=1.0\n";
+        assert_eq!(
+            verify(input, &config).unwrap(),
+            "seek,\n    start=1.0,\n    # end\n    # two\n    stop=2.0\n"
+        );
+    }
+
+    #[test]
+    fn test_semicolon() {
+        let input = "set-vars, a=1;\nseek,\n    start=1.0,\n    flags=flush;\nplay\n";
+        let format = |semicolon| {
+            let config = FormatterConfig {
+                semicolon,
+                max_inline_fields: Some(1),
+                ..FormatterConfig::default()
+            };
+            verify(input, &config).unwrap()
+        };
+        assert_eq!(format(Semicolon::Preserve), input);
+        assert_eq!(
+            format(Semicolon::Always),
+            "set-vars, a=1;\nseek,\n    start=1.0,\n    flags=flush;\nplay;\n"
+        );
+        assert_eq!(
+            format(Semicolon::Never),
+            "set-vars, a=1\nseek,\n    start=1.0,\n    flags=flush\nplay\n"
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // Guards against layout attempts multiplying with the depth, which
+        // the format-depth benchmark measures
+        let mut value = "{1, 2}".to_string();
+        for i in 0..48 {
+            value = format!("{{[level, index={}, inner={}]}}", i, value);
+        }
+        let output = verify(
+            &format!("meta, args={}\n", value),
+            &FormatterConfig::default(),
+        );
+        assert!(output.unwrap().lines().count() > 48);
+    }
+
+    #[test]
+    fn test_same_layout_inline_and_split() {
+        // A node is laid out the same way whether written from a string or
+        // as an array, and wherever it is
+        let config = FormatterConfig {
+            max_line_length: 30,
+            ..FormatterConfig::default()
+        };
+        let quoted = "meta, issues={\"expected-issue, details=\\\"Seek failed: .*\\\"\"}\n";
+        let array = "meta, issues={[expected-issue, details=\"Seek failed: .*\"]}\n";
+        let expected = "meta,\n    issues={\n        [expected-issue,\n            details=\n                \"Seek failed: .*\"],\n    }\n";
+        assert_eq!(format_source(quoted, &config).unwrap(), expected);
+        assert_eq!(format_source(array, &config).unwrap(), expected);
+
+        // The `name=` before an array counts against the line length
+        let input = "check, x=[a, b], caps=(GstCaps)[video/x-raw, format=I420]\n";
+        assert_eq!(
+            format_source(input, &config).unwrap(),
+            "check,\n    x=[a, b],\n    caps=(GstCaps)[video/x-raw,\n        format=I420]\n"
+        );
+    }
+
+    #[test]
+    fn test_format_subtree() {
+        let config = FormatterConfig {
+            max_line_length: 40,
+            ..FormatterConfig::default()
+        };
+        let input = "meta,handles-states=true\nplay\n# Seek\nseek,start=1.0, flags=accurate+flush, rate=2.0; # Twice\nset-vars, l=[  a,b ]\n";
+        let tree = parse_tree(input).unwrap();
+        let root = tree.root_node();
+        let seek = root.named_child(3).unwrap();
+        assert_eq!(
+            format_structure(seek, input, &config).unwrap(),
+            "seek,\n    start=1.0,\n    flags=accurate+flush,\n    rate=2.0;"
+        );
+        assert_eq!(format_structure(root, input, &config), None);
+
+        // Formatting each structure or value formats the file
+        let formatted = format_source(input, &config).unwrap();
+        let tree = parse_tree(&formatted).unwrap();
+        let mut cursor = tree.walk();
+        for structure in tree.root_node().named_children(&mut cursor) {
+            if let Some(text) = format_structure(structure, &formatted, &config) {
+                assert_eq!(text, formatted[structure.byte_range()]);
+            }
+        }
+        let mut stack = vec![tree.root_node()];
+        while let Some(node) = stack.pop() {
+            if let Some(text) = format_value(node, &formatted, &config) {
+                assert_eq!(text, formatted[node.byte_range()]);
+            }
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+        }
+
+        // A value is laid out from its column, inside a tree with errors
+        let input = "play\nseek, flags=accurate+flush+key-unit+snap-before\nset-vars, a=\n";
+        let tree = parse_tree_with_errors(input);
+        let mut cursor = tree.walk();
+        let seek = tree.root_node().named_child(1).unwrap();
+        let value = seek
+            .named_child(1)
+            .and_then(|fields| fields.named_children(&mut cursor).next())
+            .and_then(|field| field.child_by_field_name("value"))
+            .unwrap();
+        let config = FormatterConfig {
+            max_line_length: 30,
+            ..FormatterConfig::default()
+        };
+        assert_eq!(
+            format_value(value, input, &config).unwrap(),
+            "accurate+flush+key-unit+snap-before"
+        );
+        assert_eq!(
+            format_structure(seek, input, &config).unwrap(),
+            "seek,\n    flags=accurate+flush+key-unit+snap-before"
+        );
+        assert!(tree.root_node().has_error());
+    }
+
+    #[test]
+    fn test_format_str() {
+        let config = FormatterConfig::default();
+        assert_eq!(
+            format_document_str("meta,handles-states=true\nplay\n", &config).unwrap(),
+            "meta, handles-states=true\nplay\n"
+        );
+        // Expected issues are only sorted by format_source
+        let config = FormatterConfig {
+            sort_expected_issues: true,
+            ..config
+        };
+        let input =
+            "meta, expected-issues={[expected-issue, issue-id=b], [expected-issue, issue-id=a]}\n";
+        assert_eq!(
+            format_document_str(input, &config).unwrap(),
+            format_document(parse_tree(input).unwrap().root_node(), input, &config)
+        );
+        assert_ne!(
+            format_document_str(input, &config).unwrap(),
+            format_source(input, &config).unwrap()
+        );
+
+        assert_eq!(
+            format_structure_str("  seek,start=1.0 # Start", &config).unwrap(),
+            "seek, start=1.0"
+        );
+        assert_eq!(
+            format_structure_str("play\nstop", &config)
+                .unwrap_err()
+                .position,
+            tree_sitter::Point::new(1, 0)
+        );
+        assert!(format_structure_str("# Nothing\n", &config).is_err());
+        assert!(format_structure_str("seek, start=", &config).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let input = "meta,\n    expected-issues={\"expected-issue, issue-id=b\", [expected-issue, issue-id=a]}\nplay\n";
+        let formatted = verify(input, &FormatterConfig::default()).unwrap();
+        assert_eq!(formatted, fmt(input));
+        let config = FormatterConfig {
+            sort_expected_issues: true,
+            ..FormatterConfig::default()
+        };
+        assert!(verify(input, &config).is_ok());
+
+        assert!(matches!(
+            verify("seek, start=", &FormatterConfig::default()),
+            Err(VerifyError::Input(_))
+        ));
+    }
+}
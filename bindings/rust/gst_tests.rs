@@ -0,0 +1,280 @@
+//! Differential tests of the formatter against GStreamer.
+//!
+//! Built with the `gst-tests` feature, which links the installed
+//! `libgstreamer-1.0`: each structure of a file and of its formatted version
+//! goes through `gst_structure_from_string`, the parser GstValidate uses,
+//! and the results must be equal. This proves formatting does not change
+//! what a test does at runtime, beyond the opt-in rewrites, which are
+//! turned off here: conversion of quoted structures and splitting of long
+//! args strings.
+//!
+//! Besides the samples below, the files of the directories listed in
+//! `VALIDATETEST_GST_TESTS_DIRS` (separated like `PATH`), such as a checkout
+//! of the GstValidate testsuite, are checked.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::Once;
+
+use tree_sitter::Node;
+
+use crate::document::parse_tree;
+use crate::format::{format_source, ArrayPacking, BraceStyle, FormatterConfig};
+use crate::strings::{quote, unquote};
+
+#[repr(C)]
+struct GstStructure {
+    _private: [u8; 0],
+}
+
+#[link(name = "gstreamer-1.0")]
+extern "C" {
+    fn gst_init(argc: *mut c_int, argv: *mut *mut *mut c_char);
+    fn gst_structure_from_string(string: *const c_char, end: *mut *mut c_char)
+        -> *mut GstStructure;
+    fn gst_structure_is_equal(a: *const GstStructure, b: *const GstStructure) -> c_int;
+    fn gst_structure_to_string(structure: *const GstStructure) -> *mut c_char;
+    fn gst_structure_get_string(
+        structure: *const GstStructure,
+        fieldname: *const c_char,
+    ) -> *const c_char;
+    fn gst_structure_free(structure: *mut GstStructure);
+}
+
+#[link(name = "glib-2.0")]
+extern "C" {
+    fn g_free(mem: *mut c_void);
+}
+
+/// A `GstStructure` parsed from a string.
+struct Structure(*mut GstStructure);
+
+impl Structure {
+    fn parse(text: &str) -> Option<Self> {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| unsafe { gst_init(ptr::null_mut(), ptr::null_mut()) });
+        let text = CString::new(text).ok()?;
+        let structure = unsafe { gst_structure_from_string(text.as_ptr(), ptr::null_mut()) };
+        (!structure.is_null()).then_some(Self(structure))
+    }
+}
+
+impl Structure {
+    /// The string field `name`.
+    fn string(&self, name: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let value = unsafe { gst_structure_get_string(self.0, name.as_ptr()) };
+        (!value.is_null()).then(|| {
+            unsafe { CStr::from_ptr(value) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+impl PartialEq for Structure {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { gst_structure_is_equal(self.0, other.0) != 0 }
+    }
+}
+
+impl std::fmt::Debug for Structure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        unsafe {
+            let text = gst_structure_to_string(self.0);
+            let result = f.write_str(&CStr::from_ptr(text).to_string_lossy());
+            g_free(text.cast());
+            result
+        }
+    }
+}
+
+impl Drop for Structure {
+    fn drop(&mut self) {
+        unsafe { gst_structure_free(self.0) }
+    }
+}
+
+/// The top-level structures of `source`, as GstValidate hands them to
+/// `gst_structure_from_string`: without comments, line continuations and
+/// the final `;`.
+fn structure_texts(source: &str) -> Vec<String> {
+    fn strip(node: Node, source: &str, text: &mut String) {
+        if matches!(node.kind(), "comment" | "line_continuation") {
+            text.push(' ');
+        } else if node.child_count() == 0 {
+            text.push_str(&source[node.byte_range()]);
+            text.push(' ');
+        } else if node.kind() == "string" {
+            text.push_str(&source[node.byte_range()]);
+        } else {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                strip(child, source, text);
+            }
+        }
+    }
+
+    let tree = parse_tree(source).unwrap();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let structures: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|c| c.kind() == "structure")
+        .collect();
+    structures
+        .into_iter()
+        .map(|structure| {
+            let mut text = String::new();
+            strip(structure, source, &mut text);
+            text.trim_end().trim_end_matches(';').to_string()
+        })
+        .collect()
+}
+
+/// Check that formatting `source` with `config` keeps its structures equal
+/// for GStreamer. Structures it cannot parse, such as those using
+/// variables, are skipped. Returns the number of structures compared.
+fn check(name: &str, source: &str, config: &FormatterConfig) -> usize {
+    let config = FormatterConfig {
+        convert_quoted_structures: false,
+        split_long_args: false,
+        ..config.clone()
+    };
+    let formatted = format_source(source, &config).unwrap();
+    let (before, after) = (structure_texts(source), structure_texts(&formatted));
+    assert_eq!(before.len(), after.len(), "{}: structures lost", name);
+    let mut compared = 0;
+    for (before, after) in before.iter().zip(&after) {
+        let Some(expected) = Structure::parse(before) else {
+            continue;
+        };
+        let actual = Structure::parse(after);
+        assert_eq!(
+            actual.as_ref(),
+            Some(&expected),
+            "{}: `{}` formatted as `{}`",
+            name,
+            before,
+            after
+        );
+        compared += 1;
+    }
+    compared
+}
+
+/// Configurations exercising the layout options.
+fn configs() -> Vec<FormatterConfig> {
+    vec![
+        FormatterConfig::default(),
+        FormatterConfig {
+            max_line_length: 20,
+            array_packing: ArrayPacking::OnePerLine,
+            brace_style: BraceStyle::NextLine,
+            block_trailing_comma: false,
+            array_trailing_comma: false,
+            spaces_around_equals: true,
+            max_inline_fields: Some(1),
+            ..FormatterConfig::default()
+        },
+    ]
+}
+
+const SAMPLES: &[&str] = &[
+    "seek, start=1.0, stop=(double)5.0, flags=accurate+flush  # seek\nplay;\n",
+    "set-properties, element=sink, sync=true, \\\n    max-lateness=(gint64)-1\n",
+    "meta,\n    args={\n        \"videotestsrc num-buffers=1 ! fakesink\",\n    },\n    \
+     configs={\n        \"$(validateflow), pad=sink:sink, buffers-checksum=true\",\n    }\n",
+    "check-properties, caps=(GstCaps)\"video/x-raw, format=(string)I420\", \
+     framerate=30/1, values=<1, 2, 3>\n",
+    "set-vars, list={a, b, c}, w=<1, 2,>\n",
+    "set-vars, nested={[x, y=1], [z, w=2]}\n",
+    "stop, on-message=eos, name=\"a \\\"quoted\\\" name\"\n",
+];
+
+#[test]
+fn test_samples() {
+    for config in configs() {
+        let compared: usize = SAMPLES
+            .iter()
+            .map(|source| check("sample", source, &config))
+            .sum();
+        assert!(compared >= SAMPLES.len());
+    }
+}
+
+/// Files of `dir` and its subdirectories.
+fn files(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            files(&path, found);
+        } else if path
+            .extension()
+            .is_some_and(|e| e == "validatetest" || e == "scenario")
+        {
+            found.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_testsuite() {
+    let Some(dirs) = std::env::var_os("VALIDATETEST_GST_TESTS_DIRS") else {
+        return;
+    };
+    let mut paths = Vec::new();
+    for dir in std::env::split_paths(&dirs) {
+        files(&dir, &mut paths);
+    }
+    for path in paths {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if parse_tree(&source).is_err() {
+            continue;
+        }
+        for config in configs() {
+            check(&path.display().to_string(), &source, &config);
+        }
+    }
+}
+
+#[test]
+fn test_strings() {
+    // Read the way GStreamer reads them
+    for quoted in [
+        r#""a \"b\" c\\d""#,
+        r#""\n\t\é\$(x)""#,
+        r#""\303\251t\303\251 \0411\4""#,
+        r#""expected-issue, details=\"said \\\"no\\\"\"""#,
+        r#""a\38""#,
+        r#""\30""#,
+        r#""a\""#,
+    ] {
+        let structure = Structure::parse(&format!("s, v={}", quoted));
+        assert_eq!(
+            structure.and_then(|s| s.string("v")),
+            unquote(quoted).ok(),
+            "{}",
+            quoted
+        );
+    }
+    // And written so that GStreamer reads them back
+    for text in [
+        "",
+        "a \"b\"",
+        "\\",
+        "\n\r\u{7f}",
+        "\u{1}é",
+        "\\303",
+        "a, b=c; d",
+    ] {
+        // Before GStreamer 1.22, `v=""` is read as an int
+        let structure = Structure::parse(&format!("s, v=(string){}", quote(text))).unwrap();
+        assert_eq!(structure.string("v").as_deref(), Some(text));
+    }
+}
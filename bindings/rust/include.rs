@@ -0,0 +1,234 @@
+//! Resolution of `include` actions with overrides.
+//!
+//! gst-validate runs the structures of the file an `include,
+//! location=common.meta` action names in its place. Suites use it to share
+//! boilerplate such as a `meta` block; [`resolve_includes`] inlines the
+//! included fragments so the resulting document can be read, linted or
+//! compared as a whole.
+//!
+//! The including file can tweak what it pulls in: a structure named like
+//! one of the fragment, among [`OVERRIDABLE_STRUCTURES`], overrides its
+//! fields instead of being added:
+//!
+//! ```text
+//! include, location=common.meta   # meta, handles-states=true, args={...}
+//! meta, handles-states=false      # resolved: meta, handles-states=false, args={...}
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::document::{FieldValue, ScalarKind, Structure};
+use crate::refactor::{apply_edits, TextEdit};
+use crate::{Document, ParseError};
+
+/// Structures of the including file that override the fields of the
+/// included structure of the same name. Actions are not: a `seek` after an
+/// include adds a seek.
+pub const OVERRIDABLE_STRUCTURES: &[&str] = &["meta", "description", "set-vars"];
+
+#[derive(Debug)]
+pub enum IncludeError {
+    Io(String, io::Error),
+    /// A fragment, named by its location, does not parse.
+    Parse(String, ParseError),
+    /// A fragment includes itself, directly or not.
+    Cycle(String),
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncludeError::Io(location, e) => write!(f, "{}: {}", location, e),
+            IncludeError::Parse(location, e) => write!(f, "{}: {}", location, e),
+            IncludeError::Cycle(location) => write!(f, "{} includes itself", location),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+/// The `location` of an `include` action, if it is a literal.
+fn include_location(structure: &Structure) -> Option<String> {
+    if structure.name != "include" {
+        return None;
+    }
+    match &structure.field("location")?.value {
+        FieldValue::Scalar(scalar)
+            if !matches!(scalar.kind, ScalarKind::Variable | ScalarKind::Expression) =>
+        {
+            Some(scalar.unquoted())
+        }
+        _ => None,
+    }
+}
+
+/// Range of `span` extended over the line break following it.
+fn with_line_break(source: &str, span: &std::ops::Range<usize>) -> std::ops::Range<usize> {
+    let end = if source[span.end..].starts_with('\n') {
+        span.end + 1
+    } else {
+        span.end
+    };
+    span.start..end
+}
+
+/// Edits of `fragment` setting the fields of `overriding`, written in
+/// `source`, on `target`.
+fn override_edits(target: &Structure, overriding: &Structure, source: &str) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    let mut added = String::new();
+    for field in &overriding.fields {
+        let text = &source[field.span.clone()];
+        match target.field(&field.name) {
+            Some(existing) => edits.push(TextEdit {
+                range: existing.span.clone(),
+                new_text: text.to_string(),
+            }),
+            None => {
+                added.push_str(", ");
+                added.push_str(text);
+            }
+        }
+    }
+    if !added.is_empty() {
+        let end = target
+            .fields
+            .last()
+            .map_or(target.span.start + target.name.len(), |f| f.span.end);
+        edits.push(TextEdit {
+            range: end..end,
+            new_text: added,
+        });
+    }
+    edits
+}
+
+fn resolve(
+    source: &str,
+    name: &str,
+    load: &mut dyn FnMut(&str) -> io::Result<String>,
+    stack: &mut Vec<String>,
+) -> Result<String, IncludeError> {
+    let document = Document::parse(source).map_err(|e| IncludeError::Parse(name.to_string(), e))?;
+
+    // Resolved fragments, with the structure including them
+    let mut fragments = Vec::new();
+    for structure in &document.structures {
+        let Some(location) = include_location(structure) else {
+            continue;
+        };
+        if stack.contains(&location) {
+            return Err(IncludeError::Cycle(location));
+        }
+        let text = load(&location).map_err(|e| IncludeError::Io(location.clone(), e))?;
+        stack.push(location.clone());
+        let text = resolve(&text, &location, load, stack)?;
+        stack.pop();
+        let fragment =
+            Document::parse(&text).map_err(|e| IncludeError::Parse(location.clone(), e))?;
+        fragments.push((structure, text, fragment, Vec::new()));
+    }
+    if fragments.is_empty() {
+        return Ok(source.to_string());
+    }
+
+    let mut edits = Vec::new();
+    for overriding in document
+        .structures
+        .iter()
+        .filter(|s| OVERRIDABLE_STRUCTURES.contains(&s.name.as_str()))
+    {
+        let target = fragments.iter_mut().find_map(|(_, _, fragment, edits)| {
+            let target = fragment
+                .structures
+                .iter()
+                .find(|s| s.name == overriding.name)?;
+            Some((target.clone(), edits))
+        });
+        if let Some((target, fragment_edits)) = target {
+            fragment_edits.extend(override_edits(&target, overriding, source));
+            edits.push(TextEdit {
+                range: with_line_break(source, &overriding.span),
+                new_text: String::new(),
+            });
+        }
+    }
+    for (include, text, _, fragment_edits) in &fragments {
+        let resolved = apply_edits(text, fragment_edits);
+        edits.push(TextEdit {
+            range: include.span.clone(),
+            new_text: resolved.trim_end_matches('\n').to_string(),
+        });
+    }
+    Ok(apply_edits(source, &edits))
+}
+
+/// Inline the fragments `include` actions name in `source`, applying the
+/// overrides of the including file. `load` reads a fragment from the
+/// `location` of an include; includes in fragments are resolved too.
+///
+/// Includes whose location is a variable are left as they are.
+pub fn resolve_includes(
+    source: &str,
+    mut load: impl FnMut(&str) -> io::Result<String>,
+) -> Result<String, IncludeError> {
+    resolve(source, "<source>", &mut load, &mut Vec::new())
+}
+
+/// [`resolve_includes`] for the file at `path`, whose include locations
+/// are relative to its directory.
+pub fn resolve_file(path: &Path) -> Result<String, IncludeError> {
+    let source =
+        fs::read_to_string(path).map_err(|e| IncludeError::Io(path.display().to_string(), e))?;
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    resolve_includes(&source, |location| {
+        fs::read_to_string(directory.join(PathBuf::from(location)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(location: &str) -> io::Result<String> {
+        match location {
+            "common.meta" => Ok("# Shared\nmeta, handles-states=true, args={\"videotestsrc ! fakesink\"}\nset-vars, rate=1.0\n".to_string()),
+            "nested.meta" => Ok("include, location=common.meta\nplay\n".to_string()),
+            "loop.meta" => Ok("include, location=loop.meta\n".to_string()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        }
+    }
+
+    #[test]
+    fn test_resolve_includes() {
+        let source = "include, location=common.meta\nmeta, handles-states=false, seek=true\nseek, start=1.0\n";
+        assert_eq!(
+            resolve_includes(source, load).unwrap(),
+            "# Shared\nmeta, handles-states=false, args={\"videotestsrc ! fakesink\"}, seek=true\nset-vars, rate=1.0\nseek, start=1.0\n"
+        );
+
+        // Structures the fragment does not have, and actions, are kept
+        let source = "include, location=nested.meta\ndescription, summary=x\nplay\n";
+        assert_eq!(
+            resolve_includes(source, load).unwrap(),
+            "# Shared\nmeta, handles-states=true, args={\"videotestsrc ! fakesink\"}\nset-vars, rate=1.0\nplay\ndescription, summary=x\nplay\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        assert!(matches!(
+            resolve_includes("include, location=loop.meta\n", load),
+            Err(IncludeError::Cycle(location)) if location == "loop.meta"
+        ));
+        assert!(matches!(
+            resolve_includes("include, location=missing.meta\n", load),
+            Err(IncludeError::Io(..))
+        ));
+        let source = "include, location=$(fragment)\n";
+        assert_eq!(resolve_includes(source, load).unwrap(), source);
+    }
+}
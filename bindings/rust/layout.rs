@@ -0,0 +1,446 @@
+//! Layout of the formatter output within a line length.
+//!
+//! The formatter describes its output as a [`Doc`]: text, places where
+//! lines may break, and groups written either on one line or split. A node
+//! is described once, its one-line and split forms sharing the description
+//! of its children, and [`render`] lays the whole document out in a single
+//! pass: a group is split only when it does not fit on the rest of its line.
+//!
+//! The layout follows Wadler's "prettier printer", with a few additions for
+//! this language: groups that are always split ([`Breaking`]), packed lists
+//! ([`Doc::fill`]), and comments, which are wrapped or moved to their own
+//! line when they do not fit.
+
+use std::rc::Rc;
+
+use crate::format::display_width;
+
+/// When a group is split over several lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Breaking {
+    /// When it does not fit on the rest of the line.
+    Fit,
+    /// Always, unless an enclosing group is written on one line.
+    Split,
+}
+
+/// A document to lay out, cheap to clone: a node's forms share it.
+#[derive(Debug, Clone)]
+pub(crate) struct Doc(Rc<Kind>);
+
+#[derive(Debug)]
+enum Kind {
+    Nil,
+    Text(String),
+    /// A space, or a line break when the enclosing group is split.
+    Line,
+    /// A line break.
+    HardLine,
+    Concat(Vec<Doc>),
+    /// Indent the lines of a document further.
+    Nest(usize, Doc),
+    Group(Breaking, Doc),
+    /// The first document when the enclosing group is split, else the
+    /// second.
+    IfBreak(Doc, Doc),
+    /// Contents and separators, alternating: each separator breaks only
+    /// when the content after it does not fit on the line.
+    Fill(Vec<Doc>),
+    /// A document written on one line.
+    Flat(Doc),
+    /// A comment on its own line, wrapped at the line length.
+    Comment(String),
+    /// A comment ending the line, or on the next one when it does not fit.
+    TrailingComment(String),
+}
+
+impl Doc {
+    fn new(kind: Kind) -> Self {
+        Self(Rc::new(kind))
+    }
+
+    pub(crate) fn nil() -> Self {
+        Self::new(Kind::Nil)
+    }
+
+    pub(crate) fn text(text: impl Into<String>) -> Self {
+        Self::new(Kind::Text(text.into()))
+    }
+
+    pub(crate) fn line() -> Self {
+        Self::new(Kind::Line)
+    }
+
+    pub(crate) fn hard_line() -> Self {
+        Self::new(Kind::HardLine)
+    }
+
+    pub(crate) fn concat(docs: impl IntoIterator<Item = Doc>) -> Self {
+        Self::new(Kind::Concat(docs.into_iter().collect()))
+    }
+
+    /// `docs` with `separator` between them.
+    pub(crate) fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Self {
+        let mut joined = Vec::new();
+        for doc in docs {
+            if !joined.is_empty() {
+                joined.push(separator.clone());
+            }
+            joined.push(doc);
+        }
+        Self::new(Kind::Concat(joined))
+    }
+
+    pub(crate) fn nest(indent: usize, doc: Doc) -> Self {
+        Self::new(Kind::Nest(indent, doc))
+    }
+
+    pub(crate) fn group(breaking: Breaking, doc: Doc) -> Self {
+        Self::new(Kind::Group(breaking, doc))
+    }
+
+    pub(crate) fn if_break(broken: Doc, flat: Doc) -> Self {
+        Self::new(Kind::IfBreak(broken, flat))
+    }
+
+    pub(crate) fn fill(parts: Vec<Doc>) -> Self {
+        Self::new(Kind::Fill(parts))
+    }
+
+    pub(crate) fn flat(doc: Doc) -> Self {
+        Self::new(Kind::Flat(doc))
+    }
+
+    pub(crate) fn comment(text: impl Into<String>) -> Self {
+        Self::new(Kind::Comment(text.into()))
+    }
+
+    pub(crate) fn trailing_comment(text: impl Into<String>) -> Self {
+        Self::new(Kind::TrailingComment(text.into()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// A document to write, or the rest of a fill.
+#[derive(Clone, Copy)]
+enum Cmd<'d> {
+    Doc(&'d Doc),
+    Fill(&'d [Doc]),
+}
+
+/// Commands left to write: indentation, mode and command.
+type Stack<'d> = Vec<(usize, Mode, Cmd<'d>)>;
+
+/// Lay `doc` out within `width` columns.
+pub(crate) fn render(doc: &Doc, width: usize) -> String {
+    Renderer::new(width).run(doc, Mode::Break)
+}
+
+/// `doc` written on one line.
+pub(crate) fn flat(doc: &Doc) -> String {
+    Renderer::new(usize::MAX).run(doc, Mode::Flat)
+}
+
+struct Renderer {
+    output: String,
+    width: usize,
+    /// Display width of the current line.
+    column: usize,
+    /// 0-based number of the current line.
+    line: usize,
+    /// Indentation of the current line, written with its first text so that
+    /// blank lines stay empty.
+    pending_indent: Option<usize>,
+}
+
+impl Renderer {
+    fn new(width: usize) -> Self {
+        Self {
+            output: String::new(),
+            width,
+            column: 0,
+            line: 0,
+            pending_indent: None,
+        }
+    }
+
+    fn run(mut self, doc: &Doc, mode: Mode) -> String {
+        let mut stack: Stack = vec![(0, mode, Cmd::Doc(doc))];
+        while let Some((indent, mode, cmd)) = stack.pop() {
+            let doc = match cmd {
+                Cmd::Doc(doc) => doc,
+                Cmd::Fill(parts) => {
+                    self.fill(indent, mode, parts, &mut stack);
+                    continue;
+                }
+            };
+            match &*doc.0 {
+                Kind::Nil => {}
+                Kind::Text(text) => self.text(text),
+                Kind::Line if mode == Mode::Flat => self.text(" "),
+                Kind::Line | Kind::HardLine => self.newline(indent),
+                Kind::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|d| (indent, mode, Cmd::Doc(d))))
+                }
+                Kind::Nest(extra, doc) => stack.push((indent + extra, mode, Cmd::Doc(doc))),
+                Kind::Group(breaking, doc) => {
+                    let mode = match (breaking, mode) {
+                        (_, Mode::Flat) => Mode::Flat,
+                        (Breaking::Split, Mode::Break) => Mode::Break,
+                        (Breaking::Fit, Mode::Break) => {
+                            let fits = self.fits(&[doc], &stack);
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(
+                                output_line = self.line + 1,
+                                column = self.column,
+                                "group {}",
+                                if fits {
+                                    "fits on the line"
+                                } else {
+                                    "split: too long"
+                                }
+                            );
+                            if fits {
+                                Mode::Flat
+                            } else {
+                                Mode::Break
+                            }
+                        }
+                    };
+                    stack.push((indent, mode, Cmd::Doc(doc)));
+                }
+                Kind::IfBreak(broken, flat) => {
+                    let doc = if mode == Mode::Break { broken } else { flat };
+                    stack.push((indent, mode, Cmd::Doc(doc)));
+                }
+                Kind::Fill(parts) => stack.push((indent, mode, Cmd::Fill(parts))),
+                Kind::Flat(doc) => stack.push((indent, Mode::Flat, Cmd::Doc(doc))),
+                Kind::Comment(text) => self.comment(text, indent),
+                Kind::TrailingComment(text) => {
+                    if self.column + 2 + display_width(text) <= self.width {
+                        self.text("  ");
+                        self.text(text);
+                    } else {
+                        self.newline(indent);
+                        self.comment(text, indent);
+                    }
+                }
+            }
+        }
+        self.output
+    }
+
+    fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(indent) = self.pending_indent.take() {
+            self.output.extend(std::iter::repeat_n(' ', indent));
+        }
+        self.output.push_str(text);
+        match text.rfind('\n') {
+            Some(i) => self.column = display_width(&text[i + 1..]),
+            None => self.column += display_width(text),
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.output.push('\n');
+        self.line += 1;
+        self.column = indent;
+        self.pending_indent = Some(indent);
+    }
+
+    /// Write the comment `text`, wrapping its words over several lines
+    /// starting with `#` when it does not fit.
+    fn comment(&mut self, text: &str, indent: usize) {
+        if indent + display_width(text) <= self.width {
+            self.text(text);
+            return;
+        }
+        let content = text.strip_prefix('#').unwrap_or(text);
+        let content = content.strip_prefix(' ').unwrap_or(content);
+        let max_content_len = self.width.saturating_sub(indent + 2);
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut current_line = String::new();
+        for word in content.split_whitespace() {
+            if current_line.is_empty() {
+                current_line = word.to_string();
+            } else if display_width(&current_line) + 1 + display_width(word) <= max_content_len {
+                current_line.push(' ');
+                current_line.push_str(word);
+            } else {
+                lines.push(std::mem::replace(&mut current_line, word.to_string()));
+            }
+        }
+        if !current_line.is_empty() {
+            lines.push(current_line);
+        }
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.newline(indent);
+            }
+            self.text("# ");
+            self.text(line);
+        }
+    }
+
+    /// Write the contents of a fill, breaking each separator only when the
+    /// content following it does not fit on the line. The contents break as
+    /// their own groups decide.
+    fn fill<'d>(&self, indent: usize, mode: Mode, parts: &'d [Doc], stack: &mut Stack<'d>) {
+        let Some(content) = parts.first() else {
+            return;
+        };
+        if let (Some(separator), Some(next)) = (parts.get(1), parts.get(2)) {
+            let separator_mode =
+                if mode == Mode::Flat || self.fits(&[content, separator, next], &[]) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+            stack.push((indent, mode, Cmd::Fill(&parts[2..])));
+            stack.push((indent, separator_mode, Cmd::Doc(separator)));
+        }
+        stack.push((indent, mode, Cmd::Doc(content)));
+    }
+
+    /// Whether `docs` fit on the rest of the line when written flat, with
+    /// what follows them up to the next line break: `rest`, from its end.
+    fn fits(&self, docs: &[&Doc], rest: &[(usize, Mode, Cmd)]) -> bool {
+        let mut remaining = self.width as isize - self.column as isize;
+        // Mode, command and whether it is part of `docs`
+        let mut stack: Vec<(Mode, Cmd, bool)> = docs
+            .iter()
+            .rev()
+            .map(|d| (Mode::Flat, Cmd::Doc(d), true))
+            .collect();
+        let mut rest = rest.iter().rev();
+        loop {
+            let (mode, cmd, measured) = match stack.pop() {
+                Some(next) => next,
+                None => match rest.next() {
+                    Some((_, mode, cmd)) => (*mode, *cmd, false),
+                    None => return true,
+                },
+            };
+            let doc = match cmd {
+                Cmd::Doc(doc) => doc,
+                Cmd::Fill(parts) => {
+                    stack.extend(parts.iter().rev().map(|d| (mode, Cmd::Doc(d), measured)));
+                    continue;
+                }
+            };
+            match &*doc.0 {
+                Kind::Nil => {}
+                Kind::Text(text) => {
+                    if let Some(i) = text.find('\n') {
+                        // A multi-line string never fits on one line
+                        return !measured && remaining >= display_width(&text[..i]) as isize;
+                    }
+                    remaining -= display_width(text) as isize;
+                }
+                Kind::Line if mode == Mode::Flat => remaining -= 1,
+                Kind::Line => return true,
+                Kind::HardLine | Kind::Comment(_) => return !measured,
+                Kind::TrailingComment(text) => {
+                    if measured {
+                        remaining -= 2 + display_width(text) as isize;
+                    }
+                    return remaining >= 0;
+                }
+                Kind::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|d| (mode, Cmd::Doc(d), measured)))
+                }
+                Kind::Nest(_, doc) => stack.push((mode, Cmd::Doc(doc), measured)),
+                Kind::Group(_, doc) => stack.push((mode, Cmd::Doc(doc), measured)),
+                Kind::IfBreak(broken, flat) => {
+                    let doc = if mode == Mode::Break { broken } else { flat };
+                    stack.push((mode, Cmd::Doc(doc), measured));
+                }
+                Kind::Fill(parts) => {
+                    stack.extend(parts.iter().rev().map(|d| (mode, Cmd::Doc(d), measured)))
+                }
+                Kind::Flat(doc) => stack.push((Mode::Flat, Cmd::Doc(doc), measured)),
+            }
+            if remaining < 0 {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `[a, b, ...]`, its items packed on lines when split.
+    fn list(items: &[&str], breaking: Breaking) -> Doc {
+        let mut parts = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                parts.push(Doc::line());
+            }
+            parts.push(Doc::text(format!("{},", item)));
+        }
+        Doc::group(
+            breaking,
+            Doc::if_break(
+                Doc::concat([
+                    Doc::text("["),
+                    Doc::nest(2, Doc::concat([Doc::hard_line(), Doc::fill(parts)])),
+                    Doc::hard_line(),
+                    Doc::text("]"),
+                ]),
+                Doc::concat([
+                    Doc::text("["),
+                    Doc::join(items.iter().map(|i| Doc::text(*i)), Doc::text(", ")),
+                    Doc::text("]"),
+                ]),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_render() {
+        let doc = list(&["aa", "bb", "cc"], Breaking::Fit);
+        assert_eq!(render(&doc, 12), "[aa, bb, cc]");
+        assert_eq!(render(&doc, 11), "[\n  aa, bb,\n  cc,\n]");
+        // What follows a group on its line counts too
+        let doc = Doc::concat([doc, Doc::text(";")]);
+        assert_eq!(render(&doc, 12), "[\n  aa, bb,\n  cc,\n];");
+
+        // A split group is flat within a flat one
+        let inner = list(&["x"], Breaking::Split);
+        let doc = Doc::group(Breaking::Fit, Doc::concat([Doc::text("a="), inner.clone()]));
+        assert_eq!(render(&doc, 80), "a=[x]");
+        assert_eq!(render(&inner, 80), "[\n  x,\n]");
+        assert_eq!(flat(&inner), "[x]");
+    }
+
+    #[test]
+    fn test_render_comments() {
+        let doc = Doc::concat([
+            Doc::text("a,"),
+            Doc::trailing_comment("# short"),
+            Doc::hard_line(),
+            Doc::text("b,"),
+            Doc::trailing_comment("# a longer one"),
+            Doc::hard_line(),
+            Doc::nest(
+                2,
+                Doc::concat([Doc::hard_line(), Doc::comment("# wrap these words")]),
+            ),
+        ]);
+        assert_eq!(
+            render(&doc, 12),
+            "a,  # short\nb,\n# a longer\n# one\n\n  # wrap\n  # these\n  # words"
+        );
+    }
+}
@@ -23,6 +23,48 @@
 
 use tree_sitter_language::LanguageFn;
 
+pub mod action;
+pub mod arena;
+pub mod config;
+pub mod convert;
+pub mod custom_rules;
+pub mod dialect;
+pub mod diff;
+pub mod document;
+pub mod dump;
+pub mod embedded;
+pub mod encoding;
+pub mod expand;
+pub mod flow;
+pub mod format;
+#[cfg(all(test, feature = "gst-tests"))]
+mod gst_tests;
+pub mod include;
+mod layout;
+pub mod lint;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+mod macros;
+pub mod matrix;
+pub mod media_info;
+pub mod merge;
+pub mod pattern;
+#[cfg(feature = "gstreamer")]
+pub mod pipeline;
+pub mod pool;
+pub mod position;
+pub mod query;
+pub mod refactor;
+pub mod registry;
+pub mod source_map;
+pub mod strings;
+pub mod time;
+pub mod trivia;
+pub mod value;
+pub mod variables;
+
+pub use document::{Document, ParseError};
+
 extern "C" {
     fn tree_sitter_validatetest() -> *const ();
 }
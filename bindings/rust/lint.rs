@@ -0,0 +1,2421 @@
+//! Lints for ValidateTest documents.
+//!
+//! The grammar accepts any GstStructure, but gst-validate gives meaning to
+//! specific structure and field names. Mistakes there are not reported at
+//! runtime: a misspelled `expected-issue` field simply makes the expectation
+//! never match. The checks in this module catch such mistakes statically.
+//!
+//! A comment on the line before an item, or ending its line, suppresses
+//! the diagnostics of lints or categories within the item:
+//!
+//! ```text
+//! # validatetest-lint: disable=unknown-action
+//! my-plugin-action, target=sink
+//! seek, start=1.0, flags=flush, # validatetest-lint: disable=trailing-comma
+//! ```
+//!
+//! Suppressions that match nothing are reported as `unused-suppression`.
+
+#[cfg(feature = "gstreamer")]
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Node, Tree};
+
+use crate::dialect::Dialect;
+use crate::document::{parse_tree, Document, FieldValue, ScalarKind, Structure};
+use crate::format::DEFAULT_QUOTED_STRUCTURE_NAMES;
+use crate::media_info::MediaInfo;
+use crate::position::{offset_to_line_col, ColumnEncoding};
+use crate::refactor::{
+    action_lines, apply_edits, expected_issues_edits, sort_actions_edit, TextEdit,
+};
+use crate::registry::ActionRegistry;
+use crate::time::ClockTime;
+use crate::trivia::Trivia;
+use crate::value::ValueType;
+use crate::variables::{builtin_variable, definitions, references};
+use crate::ParseError;
+
+/// Fields understood by gst-validate in `expected-issue` structures.
+pub const EXPECTED_ISSUE_FIELDS: &[&str] = &[
+    "issue-id",
+    "summary",
+    "details",
+    "level",
+    "sometimes",
+    "can-happen-several-time",
+];
+
+/// Valid values of the `level` field of `expected-issue` structures.
+pub const ISSUE_LEVELS: &[&str] = &["critical", "warning", "issue", "ignore"];
+
+/// Nicks of `GstSeekFlags`, accepted in the `flags` field of `seek` actions.
+pub const SEEK_FLAGS: &[&str] = &[
+    "none",
+    "flush",
+    "accurate",
+    "key-unit",
+    "segment",
+    "trickmode",
+    "skip",
+    "snap-before",
+    "snap-after",
+    "snap-nearest",
+    "trickmode-key-units",
+    "trickmode-no-audio",
+    "trickmode-forward-predicted",
+    "instant-rate-change",
+];
+
+/// Types accepted in casts whose values are not checked.
+const UNCHECKED_TYPES: &[&str] = &[
+    "caps",
+    "GstCaps",
+    "date",
+    "GDate",
+    "datetime",
+    "GstDateTime",
+    "bitmask",
+    "GstBitmask",
+    "flagset",
+    "GstFlagSet",
+    "taglist",
+    "GstTagList",
+    "sample",
+    "GstSample",
+    "type",
+    "GType",
+    "array",
+    "GstValueArray",
+    "list",
+    "GstValueList",
+];
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// The level of a lint, which overrides the severity of its diagnostics.
+///
+/// Levels are set by lint code or by [`Category`], see
+/// [`LintConfig::levels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Level {
+    /// Not reported.
+    Allow,
+    /// Reported as a warning.
+    Warn,
+    /// Reported as an error.
+    Deny,
+}
+
+/// A group of lints whose level can be set at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Mistakes making a scenario fail, or not test what it was written
+    /// for.
+    #[default]
+    Correctness,
+    /// Valid but untidy code, only checked with [`LintConfig::style`] or
+    /// when given a level.
+    Style,
+    /// Actions and elements unknown to the registry or to the installed
+    /// GStreamer, which custom actions and plugins can make legitimate.
+    Registry,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [Category::Correctness, Category::Style, Category::Registry];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Correctness => "correctness",
+            Category::Style => "style",
+            Category::Registry => "registry",
+        }
+    }
+}
+
+/// Every lint code, with its category.
+pub const LINTS: &[(&str, Category)] = &[
+    ("unknown-action", Category::Registry),
+    ("action-missing-parameter", Category::Registry),
+    ("unknown-element", Category::Registry),
+    ("expected-issue-field", Category::Correctness),
+    ("expected-issue-level", Category::Correctness),
+    ("expected-issue-id", Category::Correctness),
+    ("seek-flags", Category::Correctness),
+    ("seek-position", Category::Correctness),
+    ("seek-rate", Category::Correctness),
+    ("playback-time-order", Category::Correctness),
+    ("playback-time-duplicate", Category::Correctness),
+    ("typed-value", Category::Correctness),
+    ("unknown-type", Category::Correctness),
+    ("unreachable-action", Category::Correctness),
+    ("missing-meta", Category::Correctness),
+    ("meta-missing-field", Category::Correctness),
+    ("duplicate-meta", Category::Correctness),
+    ("pipeline-syntax", Category::Correctness),
+    ("seek-beyond-duration", Category::Correctness),
+    ("missing-track", Category::Correctness),
+    ("undefined-variable", Category::Correctness),
+    ("duplicate-field", Category::Correctness),
+    ("media-info-duration", Category::Correctness),
+    ("media-info-checksum", Category::Correctness),
+    ("expected-issues-order", Category::Style),
+    ("quoted-structure", Category::Style),
+    ("unused-variable", Category::Style),
+    ("mixed-semicolons", Category::Style),
+    ("trailing-comma", Category::Style),
+    ("unused-suppression", Category::Correctness),
+];
+
+/// The category of the lint `code`, `None` for unknown codes.
+pub fn category(code: &str) -> Option<Category> {
+    LINTS
+        .iter()
+        .find(|(name, _)| *name == code)
+        .map(|(_, category)| *category)
+}
+
+/// Whether `name` is a lint code or a category name, which levels can be
+/// set for.
+pub fn is_lint_name(name: &str) -> bool {
+    category(name).is_some() || Category::ALL.iter().any(|c| c.name() == name)
+}
+
+/// A problem found by a lint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte range the diagnostic applies to.
+    pub range: Range<usize>,
+    pub severity: Severity,
+    /// Identifier of the lint, e.g. `expected-issue-level`.
+    pub code: &'static str,
+    pub message: String,
+    /// Edits fixing the problem, empty when there is no automatic fix.
+    pub fix: Vec<TextEdit>,
+}
+
+impl Diagnostic {
+    /// A diagnostic without fix.
+    pub fn new(
+        range: Range<usize>,
+        severity: Severity,
+        code: &'static str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            range,
+            severity,
+            code,
+            message: message.into(),
+            fix: Vec::new(),
+        }
+    }
+
+    /// Short description of what the fix does, for editors listing fixes.
+    pub fn fix_title(&self) -> Option<&'static str> {
+        if self.fix.is_empty() {
+            return None;
+        }
+        Some(match self.code {
+            "playback-time-order" => "Sort actions by playback-time",
+            "duplicate-field" => "Remove the overridden field",
+            "expected-issues-order" => "Sort expected-issues by issue-id",
+            "quoted-structure" => "Convert to an array structure",
+            "unreachable-action" if self.fix.len() == 1 => "Remove the redundant ending action",
+            "unreachable-action" => "Move the ending action last",
+            "missing-meta" => "Add a meta structure",
+            "mixed-semicolons" if self.fix[0].new_text.is_empty() => "Remove the ';'",
+            "mixed-semicolons" => "Add a ';'",
+            "trailing-comma" => "Remove the trailing comma",
+            "unused-suppression" => "Remove the unused suppression",
+            _ => "Apply the suggested fix",
+        })
+    }
+}
+
+/// Settings of the lints.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    /// Actions known to gst-validate, see [`ActionRegistry::load`].
+    pub registry: ActionRegistry,
+    /// Also run the style checks (`expected-issues-order`,
+    /// `quoted-structure`, `unused-variable`, `mixed-semicolons`,
+    /// `trailing-comma`), which report valid but untidy code. A level
+    /// given to them or to their category takes precedence.
+    pub style: bool,
+    /// Report a missing `meta` structure, which `.validatetest` files need.
+    /// Files with a `description` are `.scenario` ones and are not flagged.
+    pub require_meta: bool,
+    /// Fields the project requires in `meta`, such as `args` or
+    /// `handles-states`.
+    pub meta_fields: Vec<String>,
+    /// Element factories of the installed GStreamer, see
+    /// [`crate::pipeline::installed_elements`]. Elements of the `args`
+    /// pipeline missing from it are reported when set.
+    #[cfg(feature = "gstreamer")]
+    pub elements: Option<BTreeSet<String>>,
+    /// The media the scenario plays, to check seek positions and tracks
+    /// against.
+    pub media_info: Option<MediaInfo>,
+    /// The kind of file linted. Media info files only get the
+    /// `duplicate-field`, `media-info-checksum` and `media-info-duration`
+    /// checks.
+    pub dialect: Dialect,
+    /// Levels by lint code or category name, later entries taking
+    /// precedence; see [`LintConfig::add_levels`]. Lints without a level
+    /// keep the severity of their diagnostics.
+    pub levels: Vec<(String, Level)>,
+    /// Lints implemented outside of this crate, see
+    /// [`LintConfig::register`].
+    pub rules: Vec<Arc<dyn LintRule>>,
+}
+
+impl LintConfig {
+    /// Run `rule` along with the built-in lints. Its code, which must not
+    /// be one of theirs, can be given a level and suppressed like theirs.
+    pub fn register(&mut self, rule: impl LintRule + 'static) {
+        self.rules.push(Arc::new(rule));
+    }
+
+    /// The category of the lint `code`, built-in or registered.
+    pub fn category(&self, code: &str) -> Option<Category> {
+        category(code).or_else(|| {
+            self.rules
+                .iter()
+                .find(|rule| rule.code() == code)
+                .map(|rule| rule.category())
+        })
+    }
+
+    /// Whether `name` is the code of a built-in or registered lint, or a
+    /// category name.
+    pub fn is_lint_name(&self, name: &str) -> bool {
+        is_lint_name(name) || self.rules.iter().any(|rule| rule.code() == name)
+    }
+
+    /// The codes of the built-in and registered lints.
+    fn codes(&self) -> impl Iterator<Item = &str> {
+        let builtin = LINTS.iter().map(|(code, _)| *code);
+        builtin.chain(self.rules.iter().map(|rule| rule.code()))
+    }
+
+    /// Add `levels`, taking precedence over those already set. Like
+    /// clippy, a lint given by its code takes precedence over its
+    /// category, whatever their order in `levels`.
+    pub fn add_levels(&mut self, levels: impl IntoIterator<Item = (String, Level)>) {
+        let mut levels: Vec<_> = levels.into_iter().collect();
+        levels.sort_by_key(|(name, _)| self.category(name).is_some());
+        self.levels.extend(levels);
+    }
+
+    /// The level of the lint `code`, `None` when neither it nor its
+    /// category has one.
+    pub fn level(&self, code: &str) -> Option<Level> {
+        let category = self.category(code).map(Category::name);
+        self.levels
+            .iter()
+            .rev()
+            .find(|(name, _)| name == code || Some(name.as_str()) == category)
+            .map(|(_, level)| *level)
+    }
+
+    /// The names given a level that are neither lints nor categories.
+    pub fn unknown_levels(&self) -> impl Iterator<Item = &str> {
+        self.levels
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .filter(|name| !self.is_lint_name(name))
+    }
+
+    /// Whether the lint `code` is checked.
+    fn enabled(&self, code: &str) -> bool {
+        match self.level(code) {
+            Some(level) => level != Level::Allow,
+            None => self.style || self.category(code) != Some(Category::Style),
+        }
+    }
+}
+
+/// What a [`LintRule`] checks.
+pub struct LintContext<'a> {
+    pub document: &'a Document,
+    pub source: &'a str,
+    pub config: &'a LintConfig,
+    root: Node<'a>,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(
+        document: &'a Document,
+        root: Node<'a>,
+        source: &'a str,
+        config: &'a LintConfig,
+    ) -> Self {
+        Self {
+            document,
+            source,
+            config,
+            root,
+        }
+    }
+
+    /// The root of the parse tree of the source, for rules looking at the
+    /// syntax rather than at the [`Document`].
+    pub fn root(&self) -> Node<'a> {
+        self.root
+    }
+}
+
+/// A lint implemented outside of this crate, such as a convention of a
+/// test suite, run with the built-in ones once registered with
+/// [`LintConfig::register`]:
+///
+/// ```
+/// use tree_sitter_validatetest::lint::{
+///     lint_with, Diagnostic, LintConfig, LintContext, LintRule, Severity,
+/// };
+///
+/// #[derive(Debug)]
+/// struct NoSleep;
+///
+/// impl LintRule for NoSleep {
+///     fn code(&self) -> &'static str {
+///         "no-sleep"
+///     }
+///
+///     fn description(&self) -> &str {
+///         "wait actions make tests slow, wait for a signal instead"
+///     }
+///
+///     fn check(&self, context: &LintContext) -> Vec<Diagnostic> {
+///         let waits = context.document.structures.iter().filter(|s| {
+///             s.name == "wait" && s.fields.iter().any(|f| f.name == "duration")
+///         });
+///         waits
+///             .map(|s| Diagnostic::new(s.span.clone(), Severity::Warning, self.code(), self.description()))
+///             .collect()
+///     }
+/// }
+///
+/// let mut config = LintConfig::default();
+/// config.register(NoSleep);
+/// let diagnostics = lint_with("play\nwait, duration=1.0\n", &config).unwrap();
+/// assert_eq!(diagnostics[0].code, "no-sleep");
+/// ```
+///
+/// Fixes are given with the diagnostics, in [`Diagnostic::fix`].
+pub trait LintRule: fmt::Debug + Send + Sync {
+    /// The code of the diagnostics of the rule, e.g. `no-sleep`.
+    fn code(&self) -> &'static str;
+
+    /// What the rule reports and why, for documentation and editors.
+    fn description(&self) -> &str;
+
+    /// The category whose level applies to the rule, unless it is given
+    /// one.
+    fn category(&self) -> Category {
+        Category::Correctness
+    }
+
+    /// The diagnostics of the rule in the checked document, in any order.
+    fn check(&self, context: &LintContext) -> Vec<Diagnostic>;
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            registry: ActionRegistry::builtin(),
+            style: false,
+            require_meta: false,
+            meta_fields: Vec::new(),
+            #[cfg(feature = "gstreamer")]
+            elements: None,
+            media_info: None,
+            dialect: Dialect::default(),
+            levels: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Parse and lint `source` with the default configuration.
+pub fn lint(source: &str) -> Result<Vec<Diagnostic>, ParseError> {
+    lint_with(source, &LintConfig::default())
+}
+
+/// Parse and lint `source`.
+///
+/// validateflow expectations, which do not parse, have no lints.
+pub fn lint_with(source: &str, config: &LintConfig) -> Result<Vec<Diagnostic>, ParseError> {
+    if config.dialect == Dialect::ValidateFlow {
+        return Ok(Vec::new());
+    }
+    let tree = parse_tree(source)?;
+    Ok(lint_document(
+        &Document::from_node(tree.root_node(), source),
+        &tree,
+        source,
+        config,
+    ))
+}
+
+/// Lint an already parsed `document` of `source`, built from `tree`,
+/// returning diagnostics in source order.
+pub fn lint_document(
+    document: &Document,
+    tree: &Tree,
+    source: &str,
+    config: &LintConfig,
+) -> Vec<Diagnostic> {
+    let root = tree.root_node();
+    let mut diagnostics = Vec::new();
+    if config.dialect == Dialect::MediaInfo {
+        for structure in &document.structures {
+            visit_structures(structure, None, &mut |s, span| {
+                check_duplicate_fields(s, span, &mut diagnostics);
+            });
+        }
+        check_media_info(document, &mut diagnostics);
+        check_rules(document, root, source, config, &mut diagnostics);
+        apply_suppressions(root, source, config, &mut diagnostics);
+        return apply_levels(diagnostics, config);
+    }
+    for structure in &document.structures {
+        check_action(structure, &config.registry, &mut diagnostics);
+    }
+    for structure in &document.structures {
+        visit_structures(structure, None, &mut |s, span| {
+            if s.name == "expected-issue" {
+                check_expected_issue(s, span, &mut diagnostics);
+            }
+            check_duplicate_fields(s, span, &mut diagnostics);
+        });
+    }
+    for structure in &document.structures {
+        visit_structures(structure, None, &mut |s, span| {
+            check_typed_values(s, span, &config.registry, &mut diagnostics);
+        });
+    }
+    for structure in document.structures.iter().filter(|s| s.name == "seek") {
+        check_seek(structure, &mut diagnostics);
+    }
+    if let Some(media_info) = &config.media_info {
+        check_media(document, media_info, &mut diagnostics);
+    }
+    check_playback_times(document, source, &mut diagnostics);
+    check_unreachable_actions(document, source, &mut diagnostics);
+    check_meta(document, config, &mut diagnostics);
+    check_variables(document, config, &mut diagnostics);
+    #[cfg(feature = "gstreamer")]
+    for meta in document.structures.iter().filter(|s| s.name == "meta") {
+        check_pipeline(meta, config, &mut diagnostics);
+    }
+    if config.enabled("quoted-structure") {
+        for structure in &document.structures {
+            check_quoted_structures(structure, &mut diagnostics);
+        }
+    }
+    if config.enabled("expected-issues-order") {
+        check_expected_issues_order(root, source, &mut diagnostics);
+    }
+    if config.enabled("mixed-semicolons") {
+        check_semicolons(document, source, &mut diagnostics);
+    }
+    if config.enabled("trailing-comma") {
+        check_trailing_commas(root, &mut diagnostics);
+    }
+    check_rules(document, root, source, config, &mut diagnostics);
+    apply_suppressions(root, source, config, &mut diagnostics);
+    apply_levels(diagnostics, config)
+}
+
+/// Run the registered rules that are not allowed.
+fn check_rules(
+    document: &Document,
+    root: Node,
+    source: &str,
+    config: &LintConfig,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let context = LintContext::new(document, root, source, config);
+    for rule in config.rules.iter().filter(|r| config.enabled(r.code())) {
+        diagnostics.extend(rule.check(&context));
+    }
+}
+
+/// Start of the comments suppressing diagnostics.
+const SUPPRESSION: &str = "validatetest-lint:";
+
+/// A `# validatetest-lint: disable=...` comment.
+struct Suppression<'tree> {
+    comment: Node<'tree>,
+    /// The lints and categories it names.
+    names: Vec<String>,
+    /// Byte range of the item it applies to.
+    scope: Range<usize>,
+    /// Whether it ends the line of the item rather than precedes it.
+    trailing: bool,
+}
+
+/// The names of the suppression comment `text`, `None` for other comments.
+fn suppressed_names(text: &str) -> Option<Vec<String>> {
+    let directive = text
+        .trim_start_matches('#')
+        .trim()
+        .strip_prefix(SUPPRESSION)?;
+    let names = directive
+        .trim()
+        .strip_prefix("disable=")
+        .unwrap_or_default();
+    Some(
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// The suppression comments of the tree at `root`, in source order.
+fn suppressions<'tree>(root: Node<'tree>, source: &str) -> Vec<Suppression<'tree>> {
+    let trivia = Trivia::new(root, source);
+    let mut suppressions = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let leading = trivia.leading(node).iter().map(|c| (c, false));
+        let trailing = trivia.trailing(node).map(|c| (c, true));
+        for (comment, trailing) in leading.chain(trailing) {
+            if let Some(names) = suppressed_names(&source[comment.node.byte_range()]) {
+                suppressions.push(Suppression {
+                    comment: comment.node,
+                    names,
+                    scope: node.byte_range(),
+                    trailing,
+                });
+            }
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.named_children(&mut cursor));
+    }
+    suppressions.sort_by_key(|s| s.comment.start_byte());
+    suppressions
+}
+
+/// Whether the lint or category `name` covers the lint `code`.
+fn covers(name: &str, code: &str, config: &LintConfig) -> bool {
+    name == code || config.category(code).is_some_and(|c| c.name() == name)
+}
+
+/// Drop the `diagnostics` suppressed by comments of `source`, parsed at
+/// `root`, and report the suppressions left unused.
+///
+/// A suppression is only unused when the lints it names run with `config`:
+/// disabling a style lint does not get reported when style checks are off.
+fn apply_suppressions(
+    root: Node,
+    source: &str,
+    config: &LintConfig,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if !source.contains(SUPPRESSION) {
+        return;
+    }
+    let suppressions = suppressions(root, source);
+    let mut used: Vec<Vec<&str>> = vec![Vec::new(); suppressions.len()];
+    diagnostics.retain(|diagnostic| {
+        let mut suppressed = false;
+        for (suppression, used) in suppressions.iter().zip(&mut used) {
+            if !suppression.scope.contains(&diagnostic.range.start) {
+                continue;
+            }
+            for name in &suppression.names {
+                if covers(name, diagnostic.code, config) {
+                    used.push(name);
+                    suppressed = true;
+                }
+            }
+        }
+        !suppressed
+    });
+
+    for (suppression, used) in suppressions.iter().zip(&used) {
+        let range = suppression.comment.byte_range();
+        if suppression.names.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                range.clone(),
+                Severity::Warning,
+                "unused-suppression",
+                format!(
+                    "suppression names no lint, expected '# {} disable=NAME'",
+                    SUPPRESSION
+                ),
+            ));
+        }
+        for name in suppression.names.iter().filter(|n| !config.is_lint_name(n)) {
+            diagnostics.push(Diagnostic::new(
+                range.clone(),
+                Severity::Warning,
+                "unused-suppression",
+                format!("unknown lint or category '{}' in suppression", name),
+            ));
+        }
+        let runs = |name: &str| {
+            config
+                .codes()
+                .any(|code| covers(name, code, config) && config.enabled(code))
+        };
+        let (unused, kept): (Vec<&String>, Vec<&String>) =
+            suppression.names.iter().partition(|name| {
+                config.is_lint_name(name) && runs(name) && !used.contains(&name.as_str())
+            });
+        if unused.is_empty() {
+            continue;
+        }
+        let names: Vec<String> = unused.iter().map(|name| format!("'{}'", name)).collect();
+        diagnostics.push(Diagnostic {
+            fix: vec![remove_suppressed_names(source, suppression, &kept)],
+            ..Diagnostic::new(
+                range,
+                Severity::Warning,
+                "unused-suppression",
+                format!("suppression of {} matches no diagnostic", names.join(", ")),
+            )
+        });
+    }
+}
+
+/// The edit keeping only the `kept` names of `suppression`, removing the
+/// comment when there are none.
+fn remove_suppressed_names(source: &str, suppression: &Suppression, kept: &[&String]) -> TextEdit {
+    let range = suppression.comment.byte_range();
+    if !kept.is_empty() {
+        let kept: Vec<&str> = kept.iter().map(|name| name.as_str()).collect();
+        return TextEdit {
+            range,
+            new_text: format!("# {} disable={}", SUPPRESSION, kept.join(", ")),
+        };
+    }
+    let before = &source[..range.start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    if suppression.trailing || !before[line_start..].trim().is_empty() {
+        return TextEdit {
+            range: before.trim_end_matches([' ', '\t']).len()..range.end,
+            new_text: String::new(),
+        };
+    }
+    let rest = &source[range.end..];
+    let newline = if rest.starts_with("\r\n") {
+        2
+    } else {
+        usize::from(rest.starts_with('\n'))
+    };
+    TextEdit {
+        range: line_start..range.end + newline,
+        new_text: String::new(),
+    }
+}
+
+/// Drop the `diagnostics` of allowed lints, give the others the severity
+/// of their level, and sort them in source order.
+fn apply_levels(mut diagnostics: Vec<Diagnostic>, config: &LintConfig) -> Vec<Diagnostic> {
+    diagnostics.retain_mut(|diagnostic| {
+        match config.level(diagnostic.code) {
+            Some(Level::Allow) => return false,
+            Some(Level::Warn) => diagnostic.severity = Severity::Warning,
+            Some(Level::Deny) => diagnostic.severity = Severity::Error,
+            None => {}
+        }
+        true
+    });
+    diagnostics.sort_by_key(|d| (d.range.start, d.range.end));
+    diagnostics
+}
+
+/// Apply the fixes of `diagnostics` to `source`.
+///
+/// Several diagnostics may share the same fix; a fix overlapping one
+/// already applied is skipped, running the lints again picks it up.
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut edits: Vec<TextEdit> = Vec::new();
+    for diagnostic in diagnostics {
+        let overlaps = diagnostic.fix.iter().any(|edit| {
+            edits
+                .iter()
+                .any(|e| e.range.start < edit.range.end && edit.range.start < e.range.end)
+        });
+        if !overlaps {
+            edits.extend(diagnostic.fix.iter().cloned());
+        }
+    }
+    apply_edits(source, &edits)
+}
+
+/// 0-based line and column (in bytes) of `offset` in `source`.
+///
+/// See [`crate::position`] for columns in other units.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let position = offset_to_line_col(source, offset, ColumnEncoding::Utf8);
+    (position.line, position.column)
+}
+
+/// Call `f` on `structure` and every structure nested in its values,
+/// including quoted serialized ones (`"expected-issue, ..."`).
+///
+/// Spans of structures parsed out of a quoted string are relative to that
+/// string, so `f` gets the span of the string itself as `quoted_span`; any
+/// diagnostic must then be reported on it instead.
+pub(crate) fn visit_structures(
+    structure: &Structure,
+    quoted_span: Option<&Range<usize>>,
+    f: &mut impl FnMut(&Structure, Option<&Range<usize>>),
+) {
+    fn visit_value(
+        value: &FieldValue,
+        quoted_span: Option<&Range<usize>>,
+        f: &mut impl FnMut(&Structure, Option<&Range<usize>>),
+    ) {
+        match value {
+            FieldValue::Structure(s) => visit_structures(s, quoted_span, f),
+            FieldValue::Typed { value, .. } => visit_value(value, quoted_span, f),
+            FieldValue::Array(values)
+            | FieldValue::ValueArray(values)
+            | FieldValue::Block(values) => {
+                for value in values {
+                    visit_value(value, quoted_span, f);
+                }
+            }
+            FieldValue::Scalar(scalar) if scalar.kind == ScalarKind::String => {
+                if let Some(s) = value.as_structure() {
+                    visit_structures(&s, Some(quoted_span.unwrap_or(&scalar.span)), f);
+                }
+            }
+            FieldValue::Scalar(_) => {}
+        }
+    }
+
+    f(structure, quoted_span);
+    for field in &structure.fields {
+        visit_value(&field.value, quoted_span, f);
+    }
+}
+
+/// The literal text of a scalar value, or `None` for variables, expressions
+/// and non-scalar values whose value is only known at runtime.
+pub(crate) fn literal(value: &FieldValue) -> Option<String> {
+    match value {
+        FieldValue::Scalar(s) => match s.kind {
+            ScalarKind::Variable | ScalarKind::Expression => None,
+            _ if s.text.contains("$(") => None,
+            _ => Some(s.unquoted()),
+        },
+        FieldValue::Typed { value, .. } => literal(value),
+        _ => None,
+    }
+}
+
+/// Whether `id` looks like a gst-validate issue id: `::`-separated lowercase
+/// segments such as `event::newsegment-not-pushed`.
+pub fn is_valid_issue_id(id: &str) -> bool {
+    let mut segments = 0;
+    for segment in id.split("::") {
+        if segment.is_empty()
+            || !segment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return false;
+        }
+        segments += 1;
+    }
+    segments >= 2
+}
+
+fn check_expected_issue(
+    issue: &Structure,
+    quoted_span: Option<&Range<usize>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let span = |range: &Range<usize>| quoted_span.unwrap_or(range).clone();
+
+    for field in &issue.fields {
+        if !EXPECTED_ISSUE_FIELDS.contains(&field.name.as_str()) {
+            diagnostics.push(Diagnostic::new(
+                span(&field.span),
+                Severity::Warning,
+                "expected-issue-field",
+                format!(
+                    "unknown expected-issue field '{}', expected one of: {}",
+                    field.name,
+                    EXPECTED_ISSUE_FIELDS.join(", ")
+                ),
+            ));
+            continue;
+        }
+
+        let Some(value) = literal(&field.value) else {
+            continue;
+        };
+        match field.name.as_str() {
+            "level" if !ISSUE_LEVELS.contains(&value.as_str()) => {
+                diagnostics.push(Diagnostic::new(
+                    span(&field.span),
+                    Severity::Error,
+                    "expected-issue-level",
+                    format!(
+                        "invalid level '{}', expected one of: {}",
+                        value,
+                        ISSUE_LEVELS.join(", ")
+                    ),
+                ))
+            }
+            "issue-id" if !is_valid_issue_id(&value) => diagnostics.push(Diagnostic::new(
+                span(&field.span),
+                Severity::Error,
+                "expected-issue-id",
+                format!(
+                    "invalid issue-id '{}', expected an id like 'event::newsegment-not-pushed'",
+                    value
+                ),
+            )),
+            _ => {}
+        }
+    }
+}
+
+/// Check `action` against its definition in `registry`.
+fn check_action(action: &Structure, registry: &ActionRegistry, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(definition) = registry.get(&action.name) else {
+        diagnostics.push(Diagnostic::new(
+            action.span.start..action.span.start + action.name.len(),
+            Severity::Warning,
+            "unknown-action",
+            format!("unknown action '{}'", action.name),
+        ));
+        return;
+    };
+
+    let missing: Vec<&str> = definition
+        .parameters
+        .iter()
+        .filter(|p| p.mandatory && action.field(&p.name).is_none())
+        .map(|p| p.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            action.span.clone(),
+            Severity::Error,
+            "action-missing-parameter",
+            format!(
+                "'{}' is missing mandatory parameter(s): {}",
+                action.name,
+                missing.join(", ")
+            ),
+        ));
+    }
+}
+
+/// Check the actions against the media they play: seeks must stay within
+/// its duration, and `switch-track` select tracks it has.
+/// Check the `file` structure and the frame checksums of a media info
+/// file.
+fn check_media_info(document: &Document, diagnostics: &mut Vec<Diagnostic>) {
+    match document.structures.iter().find(|s| s.name == "file") {
+        Some(file) => match file.field("duration") {
+            Some(field) => {
+                if literal(&field.value).is_some_and(|v| v.parse::<u64>().is_err()) {
+                    diagnostics.push(Diagnostic::new(
+                        field.span.clone(),
+                        Severity::Error,
+                        "media-info-duration",
+                        "the duration is not a number of nanoseconds",
+                    ));
+                }
+            }
+            None => diagnostics.push(Diagnostic::new(
+                file.span.start..file.span.start + file.name.len(),
+                Severity::Warning,
+                "media-info-duration",
+                "'file' is missing the duration of the media",
+            )),
+        },
+        None => diagnostics.push(Diagnostic::new(
+            0..0,
+            Severity::Warning,
+            "media-info-duration",
+            "missing 'file' structure giving the duration of the media",
+        )),
+    }
+    for structure in &document.structures {
+        visit_structures(structure, None, &mut |s, _| {
+            // The value read is the last one set
+            let Some(field) = s.fields.iter().rev().find(|f| f.name == "checksum") else {
+                return;
+            };
+            let Some(checksum) = literal(&field.value) else {
+                return;
+            };
+            // MD5, the default of gst-validate-media-check, SHA-1 or SHA-256
+            let is_digest = matches!(checksum.len(), 32 | 40 | 64)
+                && checksum.bytes().all(|b| b.is_ascii_hexdigit());
+            if !is_digest {
+                diagnostics.push(Diagnostic::new(
+                    field.span.clone(),
+                    Severity::Error,
+                    "media-info-checksum",
+                    format!("'{}' is not an MD5, SHA-1 or SHA-256 hex digest", checksum),
+                ));
+            }
+        });
+    }
+}
+
+fn check_media(document: &Document, media: &MediaInfo, diagnostics: &mut Vec<Diagnostic>) {
+    for action in &document.structures {
+        match action.name.as_str() {
+            "seek" => {
+                let Some(duration) = media.duration else {
+                    continue;
+                };
+                for field in &action.fields {
+                    if !matches!(field.name.as_str(), "start" | "stop") {
+                        continue;
+                    }
+                    let Some(position) =
+                        literal(&field.value).and_then(|v| ClockTime::parse_seconds(&v))
+                    else {
+                        continue;
+                    };
+                    if position > duration {
+                        diagnostics.push(Diagnostic::new(
+                            field.span.clone(),
+                            Severity::Warning,
+                            "seek-beyond-duration",
+                            format!(
+                                "seek {} {} is past the end of the media, which lasts {}",
+                                field.name,
+                                position.to_seconds_string(),
+                                duration.to_duration_string()
+                            ),
+                        ));
+                    }
+                }
+            }
+            "switch-track" => {
+                let track_type = action
+                    .field("type")
+                    .and_then(|f| literal(&f.value))
+                    .unwrap_or_else(|| "audio".to_string());
+                let count = media.tracks(&track_type).count();
+                let index = action.field("index").and_then(|f| literal(&f.value));
+                // `+1` and `-1` select relatively to the current track
+                let absolute = index
+                    .as_deref()
+                    .filter(|i| !i.starts_with(['+', '-']))
+                    .and_then(|i| i.parse::<usize>().ok());
+                let message = match absolute {
+                    _ if count == 0 => format!("the media has no {} track", track_type),
+                    Some(index) if index >= count => format!(
+                        "the media has no {} track {}, only {}",
+                        track_type, index, count
+                    ),
+                    _ => continue,
+                };
+                diagnostics.push(Diagnostic::new(
+                    action.span.start..action.span.start + action.name.len(),
+                    Severity::Warning,
+                    "missing-track",
+                    message,
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `flag` is a seek flag, as a nick (`key-unit`) or a full name
+/// (`GST_SEEK_FLAG_KEY_UNIT`).
+fn is_seek_flag(flag: &str) -> bool {
+    let nick = flag
+        .strip_prefix("GST_SEEK_FLAG_")
+        .map(|name| name.to_ascii_lowercase().replace('_', "-"));
+    SEEK_FLAGS.contains(&nick.as_deref().unwrap_or(flag))
+}
+
+fn check_seek(seek: &Structure, diagnostics: &mut Vec<Diagnostic>) {
+    for field in &seek.fields {
+        let Some(value) = literal(&field.value) else {
+            continue;
+        };
+        match field.name.as_str() {
+            "flags" => {
+                let unknown: Vec<&str> = value
+                    .split(['+', '|'])
+                    .map(str::trim)
+                    .filter(|f| !is_seek_flag(f))
+                    .collect();
+                if !unknown.is_empty() {
+                    diagnostics.push(Diagnostic::new(
+                        field.span.clone(),
+                        Severity::Error,
+                        "seek-flags",
+                        format!(
+                            "unknown seek flag(s) {}, expected: {}",
+                            unknown.join(", "),
+                            SEEK_FLAGS.join(", ")
+                        ),
+                    ));
+                }
+            }
+            // `-1` leaves the stop position unchanged
+            "start" | "stop" if value != "-1" && parse_playback_time(&value).is_none() => {
+                diagnostics.push(Diagnostic::new(
+                    field.span.clone(),
+                    Severity::Error,
+                    "seek-position",
+                    format!(
+                        "invalid {} position '{}', expected seconds or a H:MM:SS clock time",
+                        field.name, value
+                    ),
+                ))
+            }
+            "rate" => match value.parse::<f64>() {
+                Ok(rate) if rate != 0.0 && rate.is_finite() => {}
+                _ => diagnostics.push(Diagnostic::new(
+                    field.span.clone(),
+                    Severity::Error,
+                    "seek-rate",
+                    format!("invalid rate '{}', expected a non-zero number", value),
+                )),
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Check the `(type)value` casts of `structure` against the types
+/// GstStructure understands and the enum and flags types of `registry`.
+fn check_typed_values(
+    structure: &Structure,
+    quoted_span: Option<&Range<usize>>,
+    registry: &ActionRegistry,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    fn check_value(
+        value: &FieldValue,
+        quoted_span: Option<&Range<usize>>,
+        registry: &ActionRegistry,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        match value {
+            FieldValue::Typed { type_name, value } => {
+                // A cast applies to each element of a list
+                let elements: Vec<&FieldValue> = match value.as_ref() {
+                    FieldValue::Array(values)
+                    | FieldValue::ValueArray(values)
+                    | FieldValue::Block(values) => values.iter().collect(),
+                    value => vec![value],
+                };
+                for element in elements {
+                    check_typed_value(type_name, element, quoted_span, registry, diagnostics);
+                }
+            }
+            FieldValue::Array(values)
+            | FieldValue::ValueArray(values)
+            | FieldValue::Block(values) => {
+                for value in values {
+                    check_value(value, quoted_span, registry, diagnostics);
+                }
+            }
+            // Nested structures are visited on their own
+            FieldValue::Structure(_) | FieldValue::Scalar(_) => {}
+        }
+    }
+
+    for field in &structure.fields {
+        check_value(&field.value, quoted_span, registry, diagnostics);
+    }
+}
+
+fn check_typed_value(
+    type_name: &str,
+    value: &FieldValue,
+    quoted_span: Option<&Range<usize>>,
+    registry: &ActionRegistry,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let FieldValue::Scalar(scalar) = value else {
+        return;
+    };
+    let range = quoted_span.unwrap_or(&scalar.span).clone();
+    let accepted = if let Some(value_type) = ValueType::from_name(type_name) {
+        literal(value).is_none_or(|v| value_type.parse(type_name, &v).is_some())
+    } else if let Some(value_type) = registry.value_type(type_name) {
+        literal(value).is_none_or(|v| value_type.accepts(&v))
+    } else {
+        if !UNCHECKED_TYPES.contains(&type_name) {
+            diagnostics.push(Diagnostic::new(
+                range,
+                Severity::Warning,
+                "unknown-type",
+                format!("unknown type '{}', its values are not checked", type_name),
+            ));
+        }
+        return;
+    };
+    if !accepted {
+        diagnostics.push(Diagnostic::new(
+            range,
+            Severity::Error,
+            "typed-value",
+            format!("'{}' is not a valid {}", scalar.unquoted(), type_name),
+        ));
+    }
+}
+
+/// Parse a `playback-time` value: seconds as a number, or a
+/// `H:MM:SS.fraction` clock time string.
+pub fn parse_playback_time(value: &str) -> Option<f64> {
+    ClockTime::parse_seconds(value).map(ClockTime::seconds_f64)
+}
+
+/// Warn about fields set more than once in a structure: GstStructure keeps
+/// the last value, the fix removes the others.
+fn check_duplicate_fields(
+    structure: &Structure,
+    quoted_span: Option<&Range<usize>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (i, field) in structure.fields.iter().enumerate() {
+        if !structure.fields[i + 1..]
+            .iter()
+            .any(|f| f.name == field.name)
+        {
+            continue;
+        }
+        let mut diagnostic = Diagnostic::new(
+            quoted_span.unwrap_or(&field.span).clone(),
+            Severity::Warning,
+            "duplicate-field",
+            format!(
+                "'{}' is set again later in '{}', this value is ignored",
+                field.name, structure.name
+            ),
+        );
+        // Edits inside quoted structures would need escaping
+        if quoted_span.is_none() {
+            diagnostic.fix = vec![TextEdit {
+                range: field.span.start..structure.fields[i + 1].span.start,
+                new_text: String::new(),
+            }];
+        }
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Suggest writing quoted `"expected-issue, ..."` strings as array
+/// structures, which need no escaping.
+fn check_quoted_structures(structure: &Structure, diagnostics: &mut Vec<Diagnostic>) {
+    fn visit_value(value: &FieldValue, diagnostics: &mut Vec<Diagnostic>) {
+        match value {
+            FieldValue::Structure(s) => check_quoted_structures(s, diagnostics),
+            FieldValue::Typed { value, .. } => visit_value(value, diagnostics),
+            FieldValue::Array(values)
+            | FieldValue::ValueArray(values)
+            | FieldValue::Block(values) => {
+                for value in values {
+                    visit_value(value, diagnostics);
+                }
+            }
+            FieldValue::Scalar(scalar) if scalar.kind == ScalarKind::String => {
+                let Some(quoted) = value.as_structure() else {
+                    return;
+                };
+                if !DEFAULT_QUOTED_STRUCTURE_NAMES.contains(&quoted.name.as_str()) {
+                    return;
+                }
+                let content = scalar.unquoted();
+                diagnostics.push(Diagnostic {
+                    fix: vec![TextEdit {
+                        range: scalar.span.clone(),
+                        new_text: format!("[{}]", content.trim().trim_end_matches(';')),
+                    }],
+                    ..Diagnostic::new(
+                        scalar.span.clone(),
+                        Severity::Warning,
+                        "quoted-structure",
+                        format!("'{}' can be written as an array structure", quoted.name),
+                    )
+                });
+            }
+            FieldValue::Scalar(_) => {}
+        }
+    }
+
+    for field in &structure.fields {
+        visit_value(&field.value, diagnostics);
+    }
+}
+
+/// Suggest sorting `expected-issues` lists by issue-id, dropping duplicates.
+fn check_expected_issues_order(root: Node, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for edit in expected_issues_edits(root, source) {
+        diagnostics.push(Diagnostic {
+            fix: vec![edit.clone()],
+            ..Diagnostic::new(
+                edit.range,
+                Severity::Warning,
+                "expected-issues-order",
+                "expected-issues are not sorted by issue-id or contain duplicates",
+            )
+        });
+    }
+}
+
+/// Flag the structures of a file ending with a `;` when most do not, or
+/// the other way around. On a tie, the terminated ones are flagged.
+fn check_semicolons(document: &Document, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let terminated = document.structures.iter().filter(|s| s.terminated).count();
+    let unterminated = document.structures.len() - terminated;
+    if terminated == 0 || unterminated == 0 {
+        return;
+    }
+    let flag_terminated = terminated <= unterminated;
+    for structure in &document.structures {
+        if structure.terminated != flag_terminated {
+            continue;
+        }
+        let diagnostic = if flag_terminated {
+            let semicolon = source[..structure.span.end].rfind(';').unwrap_or_default();
+            Diagnostic {
+                fix: vec![TextEdit {
+                    range: semicolon..semicolon + 1,
+                    new_text: String::new(),
+                }],
+                ..Diagnostic::new(
+                    semicolon..semicolon + 1,
+                    Severity::Warning,
+                    "mixed-semicolons",
+                    "unexpected ';', most structures of the file do not end with one",
+                )
+            }
+        } else {
+            let name = structure.span.start..structure.span.start + structure.name.len();
+            Diagnostic {
+                fix: vec![TextEdit {
+                    range: structure.span.end..structure.span.end,
+                    new_text: ";".to_string(),
+                }],
+                ..Diagnostic::new(
+                    name,
+                    Severity::Warning,
+                    "mixed-semicolons",
+                    "missing ';', most structures of the file end with one",
+                )
+            }
+        };
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// The `,` after the last item of the list `node`, if any: after the last
+/// field of a structure, or the last element of an array or block.
+fn trailing_comma(node: Node) -> Option<Node> {
+    let mut cursor = node.walk();
+    let mut tokens: Vec<Node> = node
+        .children(&mut cursor)
+        .filter(|c| !c.is_extra())
+        .collect();
+    match node.kind() {
+        "field_list" => {}
+        "nested_structure_block" | "angle_bracket_array" => {
+            tokens.pop();
+        }
+        // Elements of `[...]` hold the comma following them
+        "array" => {
+            tokens.pop();
+            let element = tokens.pop().filter(|e| e.kind() == "array_element")?;
+            return trailing_comma_of_element(element);
+        }
+        _ => return None,
+    }
+    tokens.pop().filter(|t| t.kind() == ",")
+}
+
+fn trailing_comma_of_element(element: Node) -> Option<Node> {
+    let mut cursor = element.walk();
+    let last = element
+        .children(&mut cursor)
+        .filter(|c| !c.is_extra())
+        .last();
+    last.filter(|t| t.kind() == ",")
+}
+
+/// Flag the commas after the last field of structures, and after the last
+/// element of arrays and blocks written on one line. The formatter removes
+/// them; those of split arrays and blocks follow its options instead.
+fn check_trailing_commas(root: Node, diagnostics: &mut Vec<Diagnostic>) {
+    fn visit(node: Node, diagnostics: &mut Vec<Diagnostic>) {
+        if let Some(comma) = trailing_comma(node) {
+            let one_line =
+                node.kind() == "field_list" || comma.end_position().row == node.end_position().row;
+            if one_line {
+                let message = if node.kind() == "field_list" {
+                    "trailing comma after the last field"
+                } else {
+                    "trailing comma after the last element"
+                };
+                diagnostics.push(Diagnostic {
+                    fix: vec![TextEdit {
+                        range: comma.byte_range(),
+                        new_text: String::new(),
+                    }],
+                    ..Diagnostic::new(
+                        comma.byte_range(),
+                        Severity::Warning,
+                        "trailing-comma",
+                        message,
+                    )
+                });
+            }
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            visit(child, diagnostics);
+        }
+    }
+
+    visit(root, diagnostics);
+}
+
+/// Check the `meta` structure against the conventions of `config`: there
+/// is a single one, holding the required fields.
+fn check_meta(document: &Document, config: &LintConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let metas: Vec<&Structure> = document
+        .structures
+        .iter()
+        .filter(|s| s.name == "meta")
+        .collect();
+    let Some(meta) = metas.first() else {
+        let is_scenario = document.structures.iter().any(|s| s.name == "description");
+        if config.require_meta && !is_scenario {
+            diagnostics.push(Diagnostic {
+                fix: vec![TextEdit {
+                    range: 0..0,
+                    new_text: "meta\n\n".to_string(),
+                }],
+                ..Diagnostic::new(
+                    0..0,
+                    Severity::Warning,
+                    "missing-meta",
+                    "missing 'meta' structure",
+                )
+            });
+        }
+        return;
+    };
+
+    let missing: Vec<&str> = config
+        .meta_fields
+        .iter()
+        .filter(|name| meta.field(name).is_none())
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            meta.span.start..meta.span.start + meta.name.len(),
+            Severity::Warning,
+            "meta-missing-field",
+            format!(
+                "'meta' is missing required field(s): {}",
+                missing.join(", ")
+            ),
+        ));
+    }
+    for duplicate in &metas[1..] {
+        diagnostics.push(Diagnostic::new(
+            duplicate.span.start..duplicate.span.start + duplicate.name.len(),
+            Severity::Warning,
+            "duplicate-meta",
+            "only the first 'meta' structure is used",
+        ));
+    }
+}
+
+/// Report references to variables that are neither defined nor built-in
+/// and, as a style check, variables nothing references.
+fn check_variables(document: &Document, config: &LintConfig, diagnostics: &mut Vec<Diagnostic>) {
+    let definitions = definitions(document);
+    let references = references(document);
+    for reference in &references {
+        if !definitions.contains_key(&reference.name) && builtin_variable(&reference.name).is_none()
+        {
+            diagnostics.push(Diagnostic::new(
+                reference.range.clone(),
+                Severity::Warning,
+                "undefined-variable",
+                format!("variable '{}' is not defined", reference.name),
+            ));
+        }
+    }
+    if !config.enabled("unused-variable") {
+        return;
+    }
+    for (name, range) in &definitions {
+        if !references.iter().any(|r| &r.name == name) {
+            diagnostics.push(Diagnostic::new(
+                range.clone(),
+                Severity::Warning,
+                "unused-variable",
+                format!("variable '{}' is never used", name),
+            ));
+        }
+    }
+}
+
+/// Check the pipeline description of the `args` of `meta`: report its
+/// syntax errors and, when the installed elements are known, the elements
+/// GStreamer does not have.
+#[cfg(feature = "gstreamer")]
+fn check_pipeline(meta: &Structure, config: &LintConfig, diagnostics: &mut Vec<Diagnostic>) {
+    use crate::pipeline::parse_launch;
+
+    let Some(FieldValue::Block(elements)) = meta.field("args").map(|f| &f.value) else {
+        return;
+    };
+    // The description, and where each scalar starts in it
+    let mut description = String::new();
+    let mut scalars = Vec::new();
+    for element in elements {
+        let FieldValue::Scalar(scalar) = element else {
+            return;
+        };
+        if !description.is_empty() {
+            description.push(' ');
+        }
+        scalars.push((description.len(), scalar));
+        description.push_str(&scalar.unquoted());
+    }
+    if scalars.is_empty() {
+        return;
+    }
+    // Source range of `range` of the description: exact within unquoted
+    // scalars and strings without escapes, the whole scalar otherwise. None
+    // when it starts past the end of the scalars.
+    let source_range = |range: &Range<usize>| {
+        let (start, scalar) = scalars.iter().rev().find(|(start, scalar)| {
+            *start <= range.start && range.start <= start + scalar.unquoted().len()
+        })?;
+        let quote = usize::from(scalar.kind == ScalarKind::String);
+        Some(
+            if scalar.text.len() == scalar.unquoted().len() + 2 * quote {
+                let offset = scalar.span.start + quote + range.start - start;
+                offset..(offset + range.len()).min(scalar.span.end - quote)
+            } else {
+                scalar.span.clone()
+            },
+        )
+    };
+
+    match parse_launch(&description) {
+        Err(e) => {
+            if let Some(range) = source_range(&e.range) {
+                diagnostics.push(Diagnostic::new(
+                    range,
+                    Severity::Error,
+                    "pipeline-syntax",
+                    format!("invalid pipeline: {}", e),
+                ));
+            }
+        }
+        Ok(used) => {
+            let Some(installed) = &config.elements else {
+                return;
+            };
+            for element in used.iter().filter(|e| !installed.contains(&e.factory)) {
+                let Some(range) = source_range(&element.range) else {
+                    continue;
+                };
+                diagnostics.push(Diagnostic::new(
+                    range,
+                    Severity::Warning,
+                    "unknown-element",
+                    format!(
+                        "no element '{}' in the installed GStreamer",
+                        element.factory
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Actions ending the scenario: nothing written after them runs.
+const ENDING_ACTIONS: &[&str] = &["stop", "eos"];
+
+/// Structures configuring the scenario rather than running in its timeline.
+const NON_TIMELINE_STRUCTURES: &[&str] = &["meta", "description", "set-vars"];
+
+/// Warn about actions written after one ending the scenario. Actions run in
+/// file order, so they never execute; the fix moves the ending action last,
+/// or removes it when another ending action follows, which then ends the
+/// scenario instead.
+///
+/// Actions run `on-message` are triggered outside the timeline and ignored.
+fn check_unreachable_actions(document: &Document, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let timeline: Vec<&Structure> = document
+        .structures
+        .iter()
+        .filter(|s| {
+            !NON_TIMELINE_STRUCTURES.contains(&s.name.as_str()) && s.field("on-message").is_none()
+        })
+        .collect();
+    let Some(end) = timeline
+        .iter()
+        .position(|s| ENDING_ACTIONS.contains(&s.name.as_str()))
+    else {
+        return;
+    };
+    let ending = timeline[end];
+    let unreachable = &timeline[end + 1..];
+    let Some(last) = unreachable.last() else {
+        return;
+    };
+
+    let moved = action_lines(source, &ending.span);
+    let after = action_lines(source, &last.span);
+    let shares_lines = document.structures.iter().any(|s| {
+        !std::ptr::eq(s, ending) && {
+            let lines = action_lines(source, &s.span);
+            lines.start < moved.end && moved.start < lines.end
+        }
+    });
+    let redundant = unreachable
+        .iter()
+        .any(|s| ENDING_ACTIONS.contains(&s.name.as_str()));
+    // Remove the ending action with its line break, and insert it after the
+    // last action unless a later one already ends the scenario
+    let removed_end = (moved.end + 1).min(source.len());
+    let fix = if shares_lines {
+        Vec::new()
+    } else if redundant {
+        vec![TextEdit {
+            range: moved.start..removed_end,
+            new_text: String::new(),
+        }]
+    } else {
+        vec![
+            TextEdit {
+                range: moved.start..removed_end,
+                new_text: String::new(),
+            },
+            TextEdit {
+                range: after.end..after.end,
+                new_text: format!("\n{}", &source[moved.clone()]),
+            },
+        ]
+    };
+
+    for action in unreachable {
+        diagnostics.push(Diagnostic {
+            fix: fix.clone(),
+            ..Diagnostic::new(
+                action.span.start..action.span.start + action.name.len(),
+                Severity::Warning,
+                "unreachable-action",
+                format!(
+                    "'{}' never runs: it is written after '{}', which ends the scenario",
+                    action.name, ending.name
+                ),
+            )
+        });
+    }
+}
+
+/// Warn about actions whose `playback-time` is earlier than, or equal to,
+/// that of an action written before them.
+fn check_playback_times(document: &Document, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let runs: Vec<Vec<(&Structure, ClockTime)>> = document
+        .timed_runs()
+        .into_iter()
+        .map(|run| {
+            document.structures[run]
+                .iter()
+                .map(|s| (s, s.playback_time().unwrap()))
+                .collect()
+        })
+        .collect();
+
+    let mut latest: Option<(&Structure, ClockTime)> = None;
+    for run in &runs {
+        let sorted = run.windows(2).all(|w| w[0].1 <= w[1].1);
+        let fix: Vec<TextEdit> = if sorted {
+            Vec::new()
+        } else {
+            sort_actions_edit(source, run).into_iter().collect()
+        };
+
+        for &(structure, time) in run {
+            let span = structure.field("playback-time").unwrap().span.clone();
+            match latest {
+                Some((previous, previous_time)) if time < previous_time => {
+                    diagnostics.push(Diagnostic {
+                        fix: fix.clone(),
+                        ..Diagnostic::new(
+                            span,
+                            Severity::Warning,
+                            "playback-time-order",
+                            format!(
+                                "'{}' at {}s is written after '{}' at {}s",
+                                structure.name,
+                                time.seconds_f64(),
+                                previous.name,
+                                previous_time.seconds_f64()
+                            ),
+                        )
+                    });
+                    continue;
+                }
+                Some((previous, previous_time)) if time == previous_time => {
+                    diagnostics.push(Diagnostic::new(
+                        span,
+                        Severity::Warning,
+                        "playback-time-duplicate",
+                        format!(
+                            "'{}' has the same playback-time as '{}', they will run in file order",
+                            structure.name, previous.name
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+            latest = Some((structure, time));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(source: &str) -> Vec<&'static str> {
+        lint(source).unwrap().into_iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn test_valid_expected_issues() {
+        let source = r#"set-vars, issue=event::a, level=warning
+meta, expected-issues={
+    [expected-issue, issue-id=scenario::execution-error, level=critical, details="foo", sometimes=true],
+    "expected-issue, issue-id=runtime::not-negotiated",
+    [expected-issue, issue-id=$(issue), level=$(level)],
+}"#;
+        assert!(codes(source).is_empty());
+    }
+
+    #[test]
+    fn test_expected_issue_schema() {
+        let source = r#"meta, expected-issues={
+    [expected-issue, isue-id=event::eos, level=fatal],
+    "expected-issue, issue-id=not-an-id",
+}"#;
+        let diagnostics = lint(source).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "expected-issue-field",
+                "expected-issue-level",
+                "expected-issue-id"
+            ]
+        );
+        assert_eq!(&source[diagnostics[0].range.clone()], "isue-id=event::eos");
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        // Diagnostics in quoted structures point at the string
+        assert_eq!(
+            &source[diagnostics[2].range.clone()],
+            r#""expected-issue, issue-id=not-an-id""#
+        );
+    }
+
+    #[test]
+    fn test_is_valid_issue_id() {
+        assert!(is_valid_issue_id("event::newsegment-not-pushed"));
+        assert!(is_valid_issue_id("g-ignore::some_issue"));
+        assert!(!is_valid_issue_id("event"));
+        assert!(!is_valid_issue_id("event::"));
+        assert!(!is_valid_issue_id("Event::Foo"));
+    }
+
+    #[test]
+    fn test_line_col() {
+        let source = "meta\nplay\n";
+        assert_eq!(line_col(source, 0), (0, 0));
+        assert_eq!(line_col(source, 7), (1, 2));
+    }
+
+    #[test]
+    fn test_parse_playback_time() {
+        assert_eq!(parse_playback_time("1.5"), Some(1.5));
+        assert_eq!(parse_playback_time("0:01:02.5"), Some(62.5));
+        assert_eq!(parse_playback_time("0:61:00"), None);
+        assert_eq!(parse_playback_time("later"), None);
+    }
+
+    #[test]
+    fn test_playback_time_order() {
+        let source = "meta\n\
+                      play, playback-time=0.0\n\
+                      # Seek back\n\
+                      seek, playback-time=5.0, start=0.0, flags=flush\n\
+                      \n\
+                      pause, playback-time=\"0:00:02\"\n\
+                      stop, playback-time=2.0\n";
+        let diagnostics = lint(source).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["playback-time-order", "playback-time-order"]);
+        assert_eq!(
+            &source[diagnostics[0].range.clone()],
+            "playback-time=\"0:00:02\""
+        );
+
+        let fixed = apply_fixes(source, &diagnostics);
+        assert_eq!(
+            fixed,
+            "meta\n\
+             play, playback-time=0.0\n\
+             pause, playback-time=\"0:00:02\"\n\
+             \n\
+             stop, playback-time=2.0\n\
+             # Seek back\n\
+             seek, playback-time=5.0, start=0.0, flags=flush\n"
+        );
+        // Sorting shows the seek runs after the stop
+        let codes: Vec<_> = lint(&fixed).unwrap().into_iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["playback-time-duplicate", "unreachable-action"]);
+    }
+
+    #[test]
+    fn test_unreachable_actions() {
+        let source = "meta\n\
+                      play\n\
+                      # Done\n\
+                      stop, playback-time=5.0\n\
+                      seek, start=1.0, flags=flush\n\
+                      pause, on-message=eos\n\
+                      set-state, state=null\n";
+        let diagnostics = lint(source).unwrap();
+        let codes_found: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes_found,
+            vec!["unreachable-action", "unreachable-action"]
+        );
+        assert_eq!(&source[diagnostics[1].range.clone()], "set-state");
+
+        let fixed = apply_fixes(source, &diagnostics);
+        assert_eq!(
+            fixed,
+            "meta\n\
+             play\n\
+             seek, start=1.0, flags=flush\n\
+             pause, on-message=eos\n\
+             set-state, state=null\n\
+             # Done\n\
+             stop, playback-time=5.0\n"
+        );
+        assert!(codes(&fixed).is_empty());
+
+        assert_eq!(codes("play\neos\nplay\n"), vec!["unreachable-action"]);
+        // A later ending action ends the scenario, the first one is removed
+        // rather than moved after it
+        let source = "play\neos\nwait, duration=1.0\nstop\n";
+        let diagnostics = lint(source).unwrap();
+        assert_eq!(
+            diagnostics[0].fix_title(),
+            Some("Remove the redundant ending action")
+        );
+        let fixed = apply_fixes(source, &diagnostics);
+        assert_eq!(fixed, "play\nwait, duration=1.0\nstop\n");
+        assert!(codes(&fixed).is_empty());
+        let fixed_twice = apply_fixes(&fixed, &lint(&fixed).unwrap());
+        assert_eq!(fixed_twice, fixed);
+        assert!(codes(&fixed_twice).is_empty());
+        // Actions on one line cannot be moved
+        let diagnostics = lint("play\nstop; play\n").unwrap();
+        assert!(diagnostics[0].fix.is_empty());
+    }
+
+    #[test]
+    fn test_meta() {
+        let config = LintConfig {
+            require_meta: true,
+            meta_fields: vec!["args".into(), "handles-states".into()],
+            ..Default::default()
+        };
+        let codes = |source| {
+            lint_with(source, &config)
+                .unwrap()
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>()
+        };
+        assert!(codes("meta, args={playbin}, handles-states=true\nplay\n").is_empty());
+        assert_eq!(
+            codes("meta, handles-states=true\nplay\nmeta\n"),
+            vec![
+                (
+                    "meta-missing-field",
+                    "'meta' is missing required field(s): args".to_string()
+                ),
+                (
+                    "duplicate-meta",
+                    "only the first 'meta' structure is used".to_string()
+                ),
+            ]
+        );
+        // .scenario files describe themselves with `description` instead
+        assert!(codes("description, seek=true\nplay\n").is_empty());
+
+        let diagnostics = lint_with("play\n", &config).unwrap();
+        assert_eq!(diagnostics[0].code, "missing-meta");
+        assert_eq!(apply_fixes("play\n", &diagnostics), "meta\n\nplay\n");
+        // Not required by default
+        assert!(lint("play\n").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "gstreamer")]
+    #[test]
+    fn test_pipeline() {
+        let source = "meta, args={\"videotestsrc ! fakesnk\", -t}\n";
+        assert!(lint(source).unwrap().is_empty());
+        let config = LintConfig {
+            elements: Some(["videotestsrc".to_string(), "fakesink".to_string()].into()),
+            ..Default::default()
+        };
+        let diagnostics = lint_with(source, &config).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "unknown-element");
+        assert_eq!(&source[diagnostics[0].range.clone()], "fakesnk");
+
+        let source = "meta, args={videotestsrc, \"!\", \"!\", fakesink}\n";
+        let diagnostics = lint(source).unwrap();
+        assert_eq!(diagnostics[0].code, "pipeline-syntax");
+        assert_eq!(
+            diagnostics[0].message,
+            "invalid pipeline: '!' without an element to link from"
+        );
+        assert_eq!(diagnostics[0].range, 32..33);
+    }
+
+    #[cfg(feature = "gstreamer")]
+    #[test]
+    fn test_pipeline_without_scalars() {
+        // The empty description does not parse, but there is no scalar to
+        // report it at: this used to panic
+        assert!(lint("meta, handles-states=true, args={}\n")
+            .unwrap()
+            .is_empty());
+        let config = LintConfig {
+            elements: Some(["fakesink".to_string()].into()),
+            ..Default::default()
+        };
+        assert!(lint_with("meta, args={}\n", &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_media_info_dialect() {
+        let config = LintConfig {
+            dialect: Dialect::MediaInfo,
+            ..Default::default()
+        };
+        let codes = |source| {
+            lint_with(source, &config)
+                .unwrap()
+                .into_iter()
+                .map(|d| (d.code, d.message))
+                .collect::<Vec<_>>()
+        };
+        assert!(codes("file, uri=\"file:///a.mp4\", duration=(guint64)10000000000\nframe, pts=(guint64)0, checksum=\"a8f0d4c2e1b3f5a7c9d1e3f5a7b9c1d3\"\n").is_empty());
+        assert_eq!(
+            codes("file, uri=\"file:///a.mp4\", duration=1.5\nframe, checksum=(string)a8f0, checksum=\"zz\"\n"),
+            vec![
+                ("media-info-duration", "the duration is not a number of nanoseconds".to_string()),
+                ("duplicate-field", "'checksum' is set again later in 'frame', this value is ignored".to_string()),
+                ("media-info-checksum", "'zz' is not an MD5, SHA-1 or SHA-256 hex digest".to_string()),
+            ]
+        );
+        assert_eq!(
+            codes("frame, pts=(guint64)0\n"),
+            vec![(
+                "media-info-duration",
+                "missing 'file' structure giving the duration of the media".to_string()
+            )]
+        );
+        assert_eq!(
+            codes("file, uri=\"file:///a.mp4\"\n")[0].1,
+            "'file' is missing the duration of the media"
+        );
+    }
+
+    #[test]
+    fn test_media_info() {
+        let media_info = MediaInfo::parse(
+            r#"<file duration="10000000000" uri="file:///a.mp4" seekable="true">
+  <streams caps="video/quicktime">
+    <stream type="video" caps="video/x-h264" id="1"/>
+    <stream type="audio" caps="audio/mpeg" id="2"/>
+  </streams>
+</file>"#,
+        );
+        let config = LintConfig {
+            media_info,
+            ..Default::default()
+        };
+        let messages = |source| {
+            lint_with(source, &config)
+                .unwrap()
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>()
+        };
+        assert!(messages("seek, start=5.0, stop=\"0:00:10\", flags=flush\nswitch-track, type=video, index=0\nswitch-track, index=+1\n").is_empty());
+        assert_eq!(
+            messages("seek, start=12.5, stop=-1, flags=flush\nswitch-track, type=audio, index=1\nswitch-track, type=text, index=+1\n"),
+            vec![
+                "seek start 12.5 is past the end of the media, which lasts 10 s",
+                "the media has no audio track 1, only 1",
+                "the media has no text track",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playback_time_order_across_untimed_actions() {
+        let source = "play, playback-time=3.0\nwait, duration=1.0\nstop, playback-time=1.0\n";
+        let diagnostics = lint(source).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        // Sorting would move `stop` across `wait`
+        assert!(diagnostics[0].fix.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_fields() {
+        let source = "set-state, state=paused, state=playing\n\
+                      meta, expected-issues={\"expected-issue, level=critical, level=warning\"}\n";
+        let diagnostics = lint(source).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["duplicate-field", "duplicate-field"]);
+        assert!(diagnostics[1].fix.is_empty());
+        assert_eq!(
+            apply_fixes(source, &diagnostics[..1]),
+            source.replacen("state=paused, ", "", 1)
+        );
+    }
+
+    #[test]
+    fn test_style() {
+        let source = r#"meta, expected-issues={
+    "expected-issue, issue-id=event::b",
+    [expected-issue, issue-id=event::a],
+}
+"#;
+        assert!(lint(source).unwrap().is_empty());
+
+        let config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        let diagnostics = lint_with(source, &config).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["expected-issues-order", "quoted-structure"]);
+        assert_eq!(
+            diagnostics[1].fix_title(),
+            Some("Convert to an array structure")
+        );
+        // The fixes overlap: one is applied at a time
+        let fixed = apply_fixes(source, &diagnostics);
+        let fixed = apply_fixes(&fixed, &lint_with(&fixed, &config).unwrap());
+        assert_eq!(
+            fixed,
+            r#"meta, expected-issues={
+    [expected-issue, issue-id=event::a],
+    [expected-issue, issue-id=event::b],
+}
+"#
+        );
+        assert!(lint_with(&fixed, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_levels() {
+        let source = "sek, flags=flush\nseek, rate=0, start=1.0, flags=flush,\n";
+        let codes = |levels: &[(&str, Level)]| {
+            let mut config = LintConfig::default();
+            config.add_levels(
+                levels
+                    .iter()
+                    .map(|(name, level)| (name.to_string(), *level)),
+            );
+            lint_with(source, &config)
+                .unwrap()
+                .iter()
+                .map(|d| (d.code, d.severity))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            codes(&[]),
+            vec![
+                ("unknown-action", Severity::Warning),
+                ("seek-rate", Severity::Error)
+            ]
+        );
+        assert_eq!(
+            codes(&[("registry", Level::Deny), ("seek-rate", Level::Warn)]),
+            vec![
+                ("unknown-action", Severity::Error),
+                ("seek-rate", Severity::Warning)
+            ]
+        );
+        // A style lint runs once given a level, and the lint wins over its
+        // category whatever the order
+        assert_eq!(
+            codes(&[
+                ("trailing-comma", Level::Deny),
+                ("correctness", Level::Allow),
+                ("registry", Level::Allow)
+            ]),
+            vec![("trailing-comma", Severity::Error)]
+        );
+        assert_eq!(
+            codes(&[("style", Level::Warn), ("correctness", Level::Allow)]),
+            vec![
+                ("unknown-action", Severity::Warning),
+                ("trailing-comma", Severity::Warning)
+            ]
+        );
+
+        assert_eq!(category("unused-variable"), Some(Category::Style));
+        assert!(is_lint_name("registry"));
+        assert!(!is_lint_name("unknown"));
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let source = "\
+# validatetest-lint: disable=unknown-action
+sek, a=1
+sek, b=2 # validatetest-lint: disable=registry, seek-rate
+seek, start=1.0, flags=flush, rate=0
+";
+        let diagnostics = lint(source).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["unused-suppression", "seek-rate"]);
+        assert_eq!(
+            diagnostics[0].message,
+            "suppression of 'seek-rate' matches no diagnostic"
+        );
+        assert_eq!(
+            apply_fixes(source, &diagnostics[..1]),
+            source.replace(", seek-rate", "")
+        );
+
+        // Suppressions of lints that do not run are not reported
+        let source = "\
+seek, start=1.0, flags=flush, # validatetest-lint: disable=trailing-comma
+# validatetest-lint: disable=seek-rate, no-such-lint
+play
+";
+        let diagnostics = lint(source).unwrap();
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "unknown lint or category 'no-such-lint' in suppression",
+                "suppression of 'seek-rate' matches no diagnostic"
+            ]
+        );
+        let config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        assert_eq!(lint_with(source, &config).unwrap().len(), 2);
+        // Unused ones are removed with their line
+        let fixed = apply_fixes(source, &diagnostics[1..]);
+        assert_eq!(
+            fixed,
+            "seek, start=1.0, flags=flush, # validatetest-lint: disable=trailing-comma\n\
+             # validatetest-lint: disable=no-such-lint\nplay\n"
+        );
+        let source = "seek, start=1.0, flags=flush # validatetest-lint: disable=seek-rate\n";
+        let diagnostics = lint(source).unwrap();
+        assert_eq!(
+            apply_fixes(source, &diagnostics),
+            "seek, start=1.0, flags=flush\n"
+        );
+    }
+
+    #[derive(Debug)]
+    struct UppercaseName;
+
+    impl LintRule for UppercaseName {
+        fn code(&self) -> &'static str {
+            "uppercase-name"
+        }
+
+        fn description(&self) -> &str {
+            "structure names are lowercase"
+        }
+
+        fn category(&self) -> Category {
+            Category::Style
+        }
+
+        fn check(&self, context: &LintContext) -> Vec<Diagnostic> {
+            let mut cursor = context.root().walk();
+            let structures: Vec<_> = context.root().named_children(&mut cursor).collect();
+            structures
+                .into_iter()
+                .filter_map(|node| node.named_child(0))
+                .filter(|name| context.source[name.byte_range()].contains(char::is_uppercase))
+                .map(|name| {
+                    Diagnostic::new(
+                        name.byte_range(),
+                        Severity::Warning,
+                        self.code(),
+                        self.description(),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let source = "Play\n# validatetest-lint: disable=style\nStop\n";
+        let mut config = LintConfig::default();
+        config.register(UppercaseName);
+        config.add_levels([("registry".to_string(), Level::Allow)]);
+        assert!(config.is_lint_name("uppercase-name"));
+        // Style rules only run when asked for
+        assert!(lint_with(source, &config).unwrap().is_empty());
+
+        config.add_levels([("uppercase-name".to_string(), Level::Deny)]);
+        let diagnostics = lint_with(source, &config).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, 0..4);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[derive(Debug, Default)]
+    struct SeenRoot(Arc<std::sync::Mutex<Option<usize>>>);
+
+    impl LintRule for SeenRoot {
+        fn code(&self) -> &'static str {
+            "seen-root"
+        }
+
+        fn description(&self) -> &str {
+            "records the root it is given"
+        }
+
+        fn category(&self) -> Category {
+            Category::Correctness
+        }
+
+        fn check(&self, context: &LintContext) -> Vec<Diagnostic> {
+            *self.0.lock().unwrap() = Some(context.root().id());
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_lint_document_tree() {
+        let source = "play, a=1, # validatetest-lint: disable=trailing-comma\nstop, b=[1,]\n";
+        let seen = SeenRoot::default();
+        let root = seen.0.clone();
+        let mut config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        config.register(seen);
+        let tree = parse_tree(source).unwrap();
+        let document = Document::from_node(tree.root_node(), source);
+        let diagnostics = lint_document(&document, &tree, source, &config);
+        // Rules, suppressions and syntax checks all use the given tree
+        assert_eq!(*root.lock().unwrap(), Some(tree.root_node().id()));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "trailing-comma");
+        assert_eq!(diagnostics[0].range.start, source.rfind(',').unwrap());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let source = "seek, start=1.0, flags=flush,\nplay, a=[1, 2,], b=<c, d,>\n\
+                      stop, c={\n    1,\n    2,\n}\n";
+        assert!(lint(source).unwrap().is_empty());
+        let config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        let diagnostics = lint_with(source, &config).unwrap();
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics.iter().all(|d| d.code == "trailing-comma"));
+        assert_eq!(
+            diagnostics[1].message,
+            "trailing comma after the last element"
+        );
+        assert_eq!(
+            diagnostics[0].fix_title(),
+            Some("Remove the trailing comma")
+        );
+        // Those of split blocks are left to the formatter
+        assert_eq!(
+            apply_fixes(source, &diagnostics),
+            "seek, start=1.0, flags=flush\nplay, a=[1, 2], b=<c, d>\nstop, c={\n    1,\n    2,\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_mixed_semicolons() {
+        let source = "set-vars, a=1;\nplay, x=$(a);\nstop\n";
+        assert!(lint(source).unwrap().is_empty());
+        let config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        let diagnostics = lint_with(source, &config).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "mixed-semicolons");
+        assert_eq!(&source[diagnostics[0].range.clone()], "stop");
+        assert_eq!(diagnostics[0].fix_title(), Some("Add a ';'"));
+        assert_eq!(
+            apply_fixes(source, &diagnostics),
+            "set-vars, a=1;\nplay, x=$(a);\nstop;\n"
+        );
+
+        let source = "set-vars, a=1;\nplay, x=$(a)\n";
+        let diagnostics = lint_with(source, &config).unwrap();
+        assert_eq!(diagnostics[0].fix_title(), Some("Remove the ';'"));
+        assert_eq!(
+            apply_fixes(source, &diagnostics),
+            "set-vars, a=1\nplay, x=$(a)\n"
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let source = "set-vars, clip=a.mp4, unused=1\n\
+                      foreach, i=[0, 2], actions={\"seek, start=$(i)\"}\n\
+                      play, uri=\"file://$(TMPDIR)/$(clip)\", n=expr($(position) + $(clips.n))\n";
+        let diagnostics = lint(source).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "undefined-variable");
+        assert_eq!(diagnostics[0].message, "variable 'clips' is not defined");
+        assert_eq!(&source[diagnostics[0].range.clone()], "clips");
+
+        let config = LintConfig {
+            style: true,
+            ..Default::default()
+        };
+        let diagnostics = lint_with(source, &config).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["unused-variable", "undefined-variable"]);
+        assert_eq!(&source[diagnostics[0].range.clone()], "unused");
+    }
+
+    #[test]
+    fn test_seek() {
+        let source = "set-vars, flags=flush, rate=1.0\n\
+                      seek, flags=accurate+flush, start=1.0, stop=-1, rate=2.0\n\
+                      seek, flags=\"GST_SEEK_FLAG_KEY_UNIT | GST_SEEK_FLAG_FLUSH\", start=\"0:00:10\"\n\
+                      seek, flags=$(flags), start=$(position), rate=expr($(rate) * 2)\n";
+        assert!(codes(source).is_empty(), "{:?}", codes(source));
+
+        let source = "seek, flags=accurat+flush, start=soon, rate=0\n";
+        assert_eq!(
+            codes(source),
+            vec!["seek-flags", "seek-position", "seek-rate"]
+        );
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let source =
+            "set-vars, var=1, value=(int)-3, other=(uint)0x10, d=(double)2, b=(boolean)yes\n\
+                      set-vars, f=(fraction)30000/1001, t=(GstClockTime)5000000000, s=(string)foo\n\
+                      set-vars, l=(int)<1, 2>, v=(int)$(var), flags=(GstSeekFlags)flush+accurate\n\
+                      set-vars, c=(GstCaps)\"video/x-raw\"\n";
+        assert!(codes(source).is_empty(), "{:?}", codes(source));
+
+        let source = "set-vars, a=(int)foo, b=(boolean)maybe, c=(uint)-1, d=(int)<1, x>\n\
+                      set-vars, e=(GstSeekFlags)flush+fast, f=(GstFoo)bar\n";
+        assert_eq!(
+            codes(source),
+            vec![
+                "typed-value",
+                "typed-value",
+                "typed-value",
+                "typed-value",
+                "typed-value",
+                "unknown-type"
+            ]
+        );
+
+        let mut config = LintConfig::default();
+        config
+            .registry
+            .merge(ActionRegistry::from_toml("[types.GstFoo]\nvalues = [\"bar\"]").unwrap());
+        assert_eq!(lint_with(source, &config).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_actions() {
+        let source = "set-state, state=playing\nseek, stop=2.0\nfrobnicate, a=1\n";
+        let diagnostics = lint(source).unwrap();
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec!["action-missing-parameter", "unknown-action"]);
+        assert_eq!(
+            diagnostics[0].message,
+            "'seek' is missing mandatory parameter(s): start, flags"
+        );
+        assert_eq!(&source[diagnostics[1].range.clone()], "frobnicate");
+
+        let mut config = LintConfig::default();
+        config.registry.merge(
+            ActionRegistry::from_toml(
+                "[actions.frobnicate]\nparameters = [{ name = \"b\", mandatory = true }]",
+            )
+            .unwrap(),
+        );
+        let codes: Vec<_> = lint_with(source, &config)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.code)
+            .collect();
+        assert_eq!(
+            codes,
+            vec!["action-missing-parameter", "action-missing-parameter"]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Quick fixes applying the automatic fixes of the lints.
+
+use std::ops::Range;
+
+use lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, Uri, WorkspaceEdit};
+use tree_sitter::Tree;
+
+use super::diagnostics::to_lsp_diagnostic;
+use super::to_lsp_edit;
+use crate::document::Document;
+use crate::lint::{lint_document, LintConfig};
+
+/// Quick fixes for the diagnostics of `text` overlapping the byte `range`.
+///
+/// The style checks run here even though they are not published as
+/// diagnostics, so their fixes are offered without cluttering the editor.
+pub(super) fn code_actions(
+    uri: &Uri,
+    text: &str,
+    tree: &Tree,
+    range: Range<usize>,
+    config: &LintConfig,
+) -> Vec<CodeActionOrCommand> {
+    let root = tree.root_node();
+    if root.has_error() {
+        return Vec::new();
+    }
+    let config = LintConfig {
+        style: true,
+        ..config.clone()
+    };
+
+    let mut actions: Vec<CodeAction> = Vec::new();
+    for diagnostic in lint_document(&Document::from_node(root, text), tree, text, &config) {
+        let Some(title) = diagnostic.fix_title() else {
+            continue;
+        };
+        if diagnostic.range.start > range.end || range.start > diagnostic.range.end {
+            continue;
+        }
+        let edits: Vec<_> = diagnostic
+            .fix
+            .iter()
+            .map(|e| to_lsp_edit(text, e))
+            .collect();
+        let lsp_diagnostic = to_lsp_diagnostic(text, &diagnostic);
+        // Diagnostics sharing a fix, such as misordered actions, share the action
+        if let Some(action) = actions.iter_mut().find(|a| {
+            a.edit
+                .as_ref()
+                .and_then(|e| e.changes.as_ref())
+                .and_then(|c| c.get(uri))
+                == Some(&edits)
+        }) {
+            action
+                .diagnostics
+                .get_or_insert_with(Vec::new)
+                .push(lsp_diagnostic);
+            continue;
+        }
+        actions.push(CodeAction {
+            title: title.to_string(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![lsp_diagnostic]),
+            edit: Some(WorkspaceEdit::new([(uri.clone(), edits)].into())),
+            ..Default::default()
+        });
+    }
+    actions
+        .into_iter()
+        .map(CodeActionOrCommand::CodeAction)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors;
+
+    #[test]
+    fn test_code_actions() {
+        let uri: Uri = "file:///a.validatetest".parse().unwrap();
+        let text = "set-state, state=paused, state=playing\n\
+                    play, playback-time=2.0\nplay, playback-time=1.0\npause, playback-time=0.5\n";
+        let tree = parse_tree_with_errors(text);
+        let config = LintConfig::default();
+        let titles = |range: Range<usize>| -> Vec<String> {
+            code_actions(&uri, text, &tree, range, &config)
+                .into_iter()
+                .map(|a| match a {
+                    CodeActionOrCommand::CodeAction(a) => a.title,
+                    CodeActionOrCommand::Command(c) => c.title,
+                })
+                .collect()
+        };
+
+        assert_eq!(titles(12..12), vec!["Remove the overridden field"]);
+        assert_eq!(
+            titles(40..text.len()),
+            vec!["Sort actions by playback-time"]
+        );
+        assert!(titles(text.len()..text.len()).is_empty());
+
+        let CodeActionOrCommand::CodeAction(action) =
+            code_actions(&uri, text, &tree, 0..text.len(), &config).remove(1)
+        else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.diagnostics.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,253 @@
+//! Completion of action and field names.
+//!
+//! Completion runs while the user types, on text that usually does not parse.
+//! The context is therefore found with a lightweight scan of the text rather
+//! than from the parse tree.
+
+use std::ops::Range;
+
+use lsp_types::{CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, TextEdit};
+
+use super::offset_to_position;
+use crate::registry::{ActionRegistry, ParameterDef};
+
+/// What is being typed at the cursor.
+#[derive(Debug, PartialEq, Eq)]
+enum Context<'a> {
+    /// The name of a top-level structure.
+    ActionName { word: Range<usize> },
+    /// A field name of the `action` structure, which already has `present`
+    /// fields.
+    FieldName {
+        action: &'a str,
+        present: Vec<&'a str>,
+        word: Range<usize>,
+    },
+}
+
+/// Start of the top-level structure containing `offset`, or `None` when
+/// `offset` is inside a string, a comment or brackets.
+fn structure_start(text: &str, offset: usize) -> Option<usize> {
+    let mut start = 0;
+    let mut brackets = Vec::new();
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+    // Last character outside of comments and whitespace
+    let mut last = None;
+    let mut previous = '\n';
+
+    for (i, c) in text[..offset].char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '#' if previous.is_whitespace() => in_comment = true,
+                '(' | '[' | '{' => brackets.push(c),
+                // `<` and `>` are also comparison operators in expressions
+                '<' if !brackets.contains(&'(') => brackets.push(c),
+                ')' | ']' | '}' | '>' => {
+                    let open = match c {
+                        ')' => '(',
+                        ']' => '[',
+                        '}' => '{',
+                        _ => '<',
+                    };
+                    if brackets.last() == Some(&open) {
+                        brackets.pop();
+                    }
+                }
+                ';' if brackets.is_empty() => start = i + 1,
+                '\n' if brackets.is_empty() && !matches!(last, Some(',') | Some('\\')) => {
+                    start = i + 1
+                }
+                _ => {}
+            }
+        }
+        if !in_comment && !c.is_whitespace() && c != '#' {
+            last = Some(c);
+        }
+        previous = c;
+    }
+
+    (!in_string && !in_comment && brackets.is_empty()).then_some(start)
+}
+
+fn context(text: &str, offset: usize) -> Option<Context<'_>> {
+    let start = structure_start(text, offset)?;
+    let statement = &text[start..offset];
+
+    let word_start = statement
+        .rfind(|c: char| c == ',' || c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let word = &statement[word_start..];
+    if word.contains('=') {
+        return None;
+    }
+    let word = start + word_start..offset;
+
+    let Some((action, fields)) = statement.split_once(',') else {
+        return (statement.trim_start().len() == offset - word.start)
+            .then_some(Context::ActionName { word });
+    };
+    let present = fields
+        .split(',')
+        .filter_map(|f| f.split_once('=').map(|(name, _)| name.trim()))
+        .collect();
+    Some(Context::FieldName {
+        action: action.trim(),
+        present,
+        word,
+    })
+}
+
+fn field_item(
+    parameter: &ParameterDef,
+    edit: impl Fn(String) -> CompletionTextEdit,
+) -> CompletionItem {
+    let mut detail = parameter.types.clone();
+    if parameter.mandatory {
+        detail.push_str(" (mandatory)");
+    }
+    if let Some(default) = &parameter.default {
+        detail.push_str(&format!(" (default: {})", default));
+    }
+    CompletionItem {
+        label: parameter.name.clone(),
+        kind: Some(CompletionItemKind::FIELD),
+        detail: Some(detail.trim().to_string()).filter(|d| !d.is_empty()),
+        documentation: Some(Documentation::String(parameter.description.clone()))
+            .filter(|_| !parameter.description.is_empty()),
+        // Mandatory parameters first
+        sort_text: Some(format!(
+            "{}{}",
+            u8::from(!parameter.mandatory),
+            parameter.name
+        )),
+        text_edit: Some(edit(format!("{}=", parameter.name))),
+        ..Default::default()
+    }
+}
+
+/// Completion items at byte `offset` of `text`.
+pub(super) fn completions(
+    text: &str,
+    offset: usize,
+    registry: &ActionRegistry,
+) -> Vec<CompletionItem> {
+    let Some(context) = context(text, offset) else {
+        return Vec::new();
+    };
+    let edit = |word: &Range<usize>| {
+        let range = lsp_types::Range::new(
+            offset_to_position(text, word.start),
+            offset_to_position(text, word.end),
+        );
+        move |new_text| CompletionTextEdit::Edit(TextEdit { range, new_text })
+    };
+
+    match context {
+        Context::ActionName { word } => registry
+            .actions()
+            .map(|action| CompletionItem {
+                label: action.name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(action.description.clone()).filter(|d| !d.is_empty()),
+                text_edit: Some(edit(&word)(action.name.clone())),
+                ..Default::default()
+            })
+            .collect(),
+        Context::FieldName {
+            action,
+            present,
+            word,
+        } => {
+            let parameters = registry.get(action).map_or(&[][..], |a| &a.parameters);
+            parameters
+                .iter()
+                .chain(registry.common_parameters())
+                .filter(|p| !present.contains(&p.name.as_str()))
+                .map(|p| field_item(p, edit(&word)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(text: &str) -> Vec<String> {
+        let offset = text.find('|').unwrap();
+        let text = text.replace('|', "");
+        completions(&text, offset, &ActionRegistry::builtin())
+            .into_iter()
+            .map(|i| i.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_context() {
+        assert_eq!(
+            context("meta\nse", 7),
+            Some(Context::ActionName { word: 5..7 })
+        );
+        assert_eq!(
+            context("meta,\n    seek=true,\n    ar", 27),
+            Some(Context::FieldName {
+                action: "meta",
+                present: vec!["seek"],
+                word: 25..27
+            })
+        );
+        // Inside a block, a string or a comment
+        assert_eq!(context("meta, args={\n    ", 17), None);
+        assert_eq!(context("seek, start=\"a, ", 16), None);
+        assert_eq!(context("# play, ", 8), None);
+        // Values
+        assert_eq!(context("seek, start=1", 13), None);
+        // After a terminated structure
+        assert_eq!(
+            context("play; st", 8),
+            Some(Context::ActionName { word: 6..8 })
+        );
+    }
+
+    #[test]
+    fn test_action_completion() {
+        let labels = labels("meta\n|");
+        assert!(labels.contains(&"seek".to_string()));
+        assert!(labels.contains(&"set-state".to_string()));
+    }
+
+    #[test]
+    fn test_field_completion() {
+        let labels = labels("seek, start=1.0, |");
+        assert_eq!(labels[0], "flags");
+        assert!(!labels.contains(&"start".to_string()));
+        assert!(labels.contains(&"rate".to_string()));
+        assert!(labels.contains(&"playback-time".to_string()));
+
+        let item = completions("seek, ", 6, &ActionRegistry::builtin())
+            .into_iter()
+            .find(|i| i.label == "start")
+            .unwrap();
+        assert_eq!(
+            item.detail.as_deref(),
+            Some("double or string (GstClockTime) (mandatory)")
+        );
+        let Some(CompletionTextEdit::Edit(edit)) = item.text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, "start=");
+    }
+}
@@ -0,0 +1,134 @@
+//! Diagnostics from the parser and the lints.
+
+use lsp_types::{DiagnosticSeverity, NumberOrString};
+use tree_sitter::{Node, Tree};
+
+use super::to_lsp_range;
+use crate::dialect::Dialect;
+use crate::document::Document;
+use crate::lint::{self, lint_document, LintConfig, Severity};
+
+const SOURCE: &str = "validatetest";
+
+fn diagnostic(
+    text: &str,
+    range: std::ops::Range<usize>,
+    severity: DiagnosticSeverity,
+    code: &str,
+    message: String,
+) -> lsp_types::Diagnostic {
+    lsp_types::Diagnostic {
+        range: to_lsp_range(text, range),
+        severity: Some(severity),
+        code: Some(NumberOrString::String(code.to_string())),
+        source: Some(SOURCE.to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Error and missing nodes under `node`, outermost first.
+fn syntax_errors(node: Node, text: &str, diagnostics: &mut Vec<lsp_types::Diagnostic>) {
+    if node.is_missing() {
+        diagnostics.push(diagnostic(
+            text,
+            node.byte_range(),
+            DiagnosticSeverity::ERROR,
+            "syntax-error",
+            format!("Missing `{}`", node.kind()),
+        ));
+    } else if node.is_error() {
+        let snippet: String = text[node.byte_range()].chars().take(20).collect();
+        let snippet = snippet.lines().next().unwrap_or("").trim();
+        let message = if snippet.is_empty() {
+            "Syntax error".to_string()
+        } else {
+            format!("Syntax error near `{}`", snippet)
+        };
+        diagnostics.push(diagnostic(
+            text,
+            node.byte_range(),
+            DiagnosticSeverity::ERROR,
+            "syntax-error",
+            message,
+        ));
+    } else if node.has_error() {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            syntax_errors(child, text, diagnostics);
+        }
+    }
+}
+
+/// Diagnostics of `text`, parsed as `tree`.
+///
+/// Lints only run on files without syntax errors, as they would otherwise
+/// report on structures the parser only partially recovered.
+pub(super) fn diagnostics(
+    text: &str,
+    tree: &Tree,
+    config: &LintConfig,
+) -> Vec<lsp_types::Diagnostic> {
+    // validateflow expectations do not parse and have no lints
+    if config.dialect == Dialect::ValidateFlow {
+        return Vec::new();
+    }
+    let root = tree.root_node();
+    let mut diagnostics = Vec::new();
+    if root.has_error() {
+        syntax_errors(root, text, &mut diagnostics);
+        return diagnostics;
+    }
+
+    let document = Document::from_node(root, text);
+    lint_document(&document, tree, text, config)
+        .iter()
+        .map(|d| to_lsp_diagnostic(text, d))
+        .collect()
+}
+
+/// LSP form of a lint diagnostic of `text`.
+pub(super) fn to_lsp_diagnostic(text: &str, d: &lint::Diagnostic) -> lsp_types::Diagnostic {
+    let severity = match d.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    diagnostic(text, d.range.clone(), severity, d.code, d.message.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors;
+
+    fn codes(text: &str) -> Vec<(u32, String)> {
+        diagnostics(text, &parse_tree_with_errors(text), &LintConfig::default())
+            .into_iter()
+            .map(|d| {
+                let Some(NumberOrString::String(code)) = d.code else {
+                    panic!("expected a code");
+                };
+                (d.range.start.line, code)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        assert_eq!(
+            codes("meta, expected-issues={[expected-issue, level=fatal]}\nfrobnicate\n"),
+            vec![
+                (0, "expected-issue-level".to_string()),
+                (1, "unknown-action".to_string())
+            ]
+        );
+        assert!(codes("meta\nplay\n").is_empty());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let codes = codes("meta\nseek, start=1.0, flags=[flush\nfrobnicate\n");
+        assert!(!codes.is_empty());
+        assert!(codes.iter().all(|(_, code)| code == "syntax-error"));
+    }
+}
@@ -0,0 +1,96 @@
+//! Document and range formatting.
+
+use std::ops::Range;
+
+use crate::dialect::Dialect;
+use crate::diff::diff_edits;
+use crate::format::{format_source, structure_edits, FormatterConfig};
+use crate::refactor::TextEdit;
+use tree_sitter::Tree;
+
+/// Edits formatting `text`, parsed as `tree`, or `None` when it does not
+/// parse and there is no `range`.
+///
+/// With a `range`, only the structures overlapping it are formatted, so the
+/// rest of the file may have syntax errors, and they keep the line ending of
+/// the file. They are picked from `tree`, which saves parsing large files
+/// again. Whole files are parsed again by [`format_source`], as its
+/// rewrites of the source need. The edits are minimal: lines the formatter
+/// leaves untouched are not replaced.
+pub(super) fn formatting(
+    text: &str,
+    tree: &Tree,
+    range: Option<Range<usize>>,
+    config: &FormatterConfig,
+) -> Option<Vec<TextEdit>> {
+    // validateflow expectations have no structures to pick from
+    let Some(range) = range.filter(|_| config.dialect != Dialect::ValidateFlow) else {
+        let formatted = format_source(text, config).ok()?;
+        return Some(diff_edits(text, &formatted));
+    };
+
+    let edits = structure_edits(tree.root_node(), text, config, |structure| {
+        structure.start_byte() < range.end && range.start < structure.end_byte()
+            || range.is_empty() && structure.byte_range().contains(&range.start)
+    });
+    Some(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors as parse;
+    use crate::refactor::apply_edits;
+
+    const SOURCE: &str =
+        "meta,\n    handles-states=true\n\nplay\nseek,   start=1.0,flags=flush\nstop\n";
+
+    #[test]
+    fn test_formatting() {
+        let config = FormatterConfig::default();
+        let edits = formatting(SOURCE, &parse(SOURCE), None, &config).unwrap();
+        let formatted = apply_edits(SOURCE, &edits);
+        assert_eq!(formatted, format_source(SOURCE, &config).unwrap());
+        // Unchanged lines are not part of the edits
+        assert!(edits
+            .iter()
+            .all(|e| !SOURCE[e.range.clone()].contains("play")));
+
+        assert!(formatting(&formatted, &parse(&formatted), None, &config)
+            .unwrap()
+            .is_empty());
+        assert!(formatting("seek, start=", &parse("seek, start="), None, &config).is_none());
+    }
+
+    #[test]
+    fn test_range_formatting() {
+        let config = FormatterConfig::default();
+        let all = formatting(SOURCE, &parse(SOURCE), None, &config).unwrap();
+        let seek = SOURCE.find("seek").unwrap();
+
+        let edits = formatting(SOURCE, &parse(SOURCE), Some(seek..seek + 4), &config).unwrap();
+        assert_eq!(edits.len(), 1);
+        assert!(SOURCE[edits[0].range.clone()].starts_with("seek"));
+        assert!(all.len() > edits.len());
+
+        let stop = SOURCE.find("stop").unwrap();
+        assert!(
+            formatting(SOURCE, &parse(SOURCE), Some(stop..stop), &config)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Only the selected structure has to parse
+        let source = "seek,   start=1.0\nplay, a=\n";
+        let edits = formatting(source, &parse(source), Some(0..4), &config).unwrap();
+        assert_eq!(apply_edits(source, &edits), "seek, start=1.0\nplay, a=\n");
+        assert!(formatting(source, &parse(source), None, &config).is_none());
+
+        let source = "seek,\r\n    # Start\r\n    start=1.0,   flags=flush\r\n";
+        let edits = formatting(source, &parse(source), Some(0..4), &config).unwrap();
+        assert_eq!(
+            apply_edits(source, &edits),
+            "seek,\r\n    # Start\r\n    start=1.0,\r\n    flags=flush\r\n"
+        );
+    }
+}
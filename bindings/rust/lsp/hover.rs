@@ -0,0 +1,207 @@
+//! Hover documentation for actions, fields and values.
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use super::rename::reference_at;
+use crate::document::{parse_tree_with_errors, Document, FieldValue};
+use crate::registry::{ActionDef, ActionRegistry, ParameterDef};
+use crate::variables::builtin_variable;
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// Name of the structure `node` belongs to, if it is a top-level one.
+fn action_name<'s>(structure: Node, source: &'s str) -> Option<&'s str> {
+    (structure.kind() == "structure")
+        .then(|| structure.child(0))
+        .flatten()
+        .filter(|n| n.kind() == "structure_name")
+        .map(|n| text(n, source))
+}
+
+fn parameter_doc(parameter: &ParameterDef) -> String {
+    let mut doc = format!("`{}`", parameter.name);
+    if !parameter.types.is_empty() {
+        doc.push_str(&format!(": {}", parameter.types));
+    }
+    if parameter.mandatory {
+        doc.push_str(" *(mandatory)*");
+    }
+    if let Some(default) = &parameter.default {
+        doc.push_str(&format!(" *(default: {})*", default));
+    }
+    if !parameter.description.is_empty() {
+        doc.push_str(&format!(" — {}", parameter.description));
+    }
+    doc
+}
+
+fn action_doc(action: &ActionDef) -> String {
+    let mut doc = format!("**{}**", action.name);
+    if !action.description.is_empty() {
+        doc.push_str("\n\n");
+        doc.push_str(&action.description);
+    }
+    if !action.parameters.is_empty() {
+        doc.push_str("\n\nParameters:\n");
+        for parameter in &action.parameters {
+            doc.push_str(&format!("\n- {}", parameter_doc(parameter)));
+        }
+    }
+    doc
+}
+
+/// A one-line description of a value and its type.
+fn value_doc(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Scalar(scalar) => {
+            format!("`{}` ({})", scalar.unquoted(), scalar.inferred_type())
+        }
+        FieldValue::Typed { type_name, value } => match value.as_ref() {
+            FieldValue::Scalar(scalar) => format!("`{}` ({})", scalar.unquoted(), type_name),
+            other => format!("{} of {}", type_name, value_doc(other)),
+        },
+        FieldValue::Array(values) => format!("array of {} element(s)", values.len()),
+        FieldValue::ValueArray(values) => format!("GstValueArray of {} element(s)", values.len()),
+        FieldValue::Block(values) => format!("list of {} element(s)", values.len()),
+        FieldValue::Structure(structure) => format!("structure `{}`", structure.name),
+    }
+}
+
+/// Documentation of variable `name`: what gst-validate sets built-in
+/// variables to, or the `set-vars` value of user ones.
+fn variable_doc(name: &str, document: &Document) -> String {
+    if let Some(variable) = builtin_variable(name) {
+        return format!("**$({})** *(built-in)*\n\n{}", name, variable.description);
+    }
+    let definition = document
+        .structures
+        .iter()
+        .filter(|s| s.name == "set-vars")
+        .find_map(|s| s.field(name));
+    match definition {
+        Some(field) => format!(
+            "**$({})**\n\nSet by `set-vars` to {}",
+            name,
+            value_doc(&field.value)
+        ),
+        None => format!("**$({})**\n\nNot defined by `set-vars`", name),
+    }
+}
+
+/// Markdown documentation of the element at byte `offset` of `source`,
+/// and the byte range it applies to.
+pub(super) fn hover(
+    source: &str,
+    offset: usize,
+    registry: &ActionRegistry,
+) -> Option<(String, Range<usize>)> {
+    let tree = parse_tree_with_errors(source);
+    let mut node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+
+    loop {
+        match node.kind() {
+            "variable" | "expression" => {
+                let (name, range) = reference_at(node, source, offset)?;
+                let document = Document::from_node(tree.root_node(), source);
+                return Some((variable_doc(&name, &document), range));
+            }
+            "structure_name" => {
+                let name = action_name(node.parent()?, source)?;
+                return Some((action_doc(registry.get(name)?), node.byte_range()));
+            }
+            "field" => break,
+            _ => node = node.parent()?,
+        }
+    }
+
+    let field = node;
+    let name = text(field.child_by_field_name("name")?, source);
+    let mut doc = format!("**{}**", name);
+
+    // The field list, then the structure holding it
+    let structure = field.parent()?.parent()?;
+    let parameter = action_name(structure, source).and_then(|action| {
+        registry
+            .get(action)
+            .and_then(|a| a.parameter(name))
+            .or_else(|| registry.common_parameters().iter().find(|p| p.name == name))
+    });
+    if let Some(parameter) = parameter {
+        doc = parameter_doc(parameter);
+    }
+
+    if let Some(value) = field
+        .child_by_field_name("value")
+        .and_then(|v| FieldValue::from_node(v, source))
+    {
+        doc.push_str(&format!("\n\nValue: {}", value_doc(&value)));
+    }
+    Some((doc, field.byte_range()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hover_at(source: &str, needle: &str) -> Option<String> {
+        let offset = source.find(needle).unwrap();
+        hover(source, offset, &ActionRegistry::builtin()).map(|(doc, _)| doc)
+    }
+
+    #[test]
+    fn test_hover_action() {
+        let doc = hover_at("seek, start=1.0, flags=flush\n", "eek").unwrap();
+        assert!(
+            doc.starts_with("**seek**\n\nSeek into the stream."),
+            "{doc}"
+        );
+        assert!(doc.contains("- `start`: double or string (GstClockTime) *(mandatory)*"));
+        assert!(hover_at("frobnicate, a=1\n", "frob").is_none());
+    }
+
+    #[test]
+    fn test_hover_variable() {
+        let source = "set-vars, clip=\"a.mp4\"\nplay, uri=\"$(TMPDIR)/$(clip)\", n=expr($(position)+$(other))\n";
+        assert_eq!(
+            hover_at(source, "TMPDIR").unwrap(),
+            "**$(TMPDIR)** *(built-in)*\n\nThe temporary directory of the system."
+        );
+        assert_eq!(
+            hover_at(source, "clip)").unwrap(),
+            "**$(clip)**\n\nSet by `set-vars` to `a.mp4` (string)"
+        );
+        assert!(hover_at(source, "position")
+            .unwrap()
+            .contains("playback position"));
+        assert!(hover_at(source, "other")
+            .unwrap()
+            .ends_with("Not defined by `set-vars`"));
+    }
+
+    #[test]
+    fn test_hover_field() {
+        let source = "seek, start=(double)1.5, flags=flush, custom=\"a\\\"b\", n=42\n";
+        let doc = hover_at(source, "start").unwrap();
+        assert!(doc.starts_with("`start`: double"), "{doc}");
+        assert!(doc.ends_with("Value: `1.5` (double)"), "{doc}");
+
+        let doc = hover_at(source, "custom").unwrap();
+        assert_eq!(doc, "**custom**\n\nValue: `a\"b` (string)");
+        assert!(hover_at(source, "42").unwrap().ends_with("`42` (int)"));
+
+        // Common parameters, nested structures
+        let source =
+            "play, playback-time=1.0\nmeta, expected-issues={[expected-issue, level=critical]}\n";
+        assert!(hover_at(source, "playback-time")
+            .unwrap()
+            .contains("The playback time"));
+        assert_eq!(
+            hover_at(source, "level").unwrap(),
+            "**level**\n\nValue: `critical` (string)"
+        );
+    }
+}
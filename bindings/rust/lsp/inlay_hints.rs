@@ -0,0 +1,172 @@
+//! Inlay hints showing decoded times and inferred types of values.
+
+use std::ops::Range;
+
+use lsp_types::{InlayHint, InlayHintKind, InlayHintLabel};
+use tree_sitter::{Node, Tree};
+
+use super::offset_to_position;
+use super::settings::InlayHintSettings;
+use crate::document::{FieldValue, ScalarKind};
+use crate::registry::ActionRegistry;
+use crate::time::ClockTime;
+
+/// Type casts holding a GstClockTime in nanoseconds.
+const NANOSECOND_TYPES: &[&str] = &["guint64", "uint64", "gint64", "int64", "GstClockTime"];
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// Nanoseconds held by `value`, when it is an integer, which gst-validate
+/// reads as a time in nanoseconds (floating point values are seconds).
+fn nanoseconds(value: &FieldValue) -> Option<u64> {
+    let scalar = match value {
+        FieldValue::Scalar(scalar) => scalar,
+        FieldValue::Typed { type_name, value }
+            if NANOSECOND_TYPES.contains(&type_name.as_str()) =>
+        {
+            match value.as_ref() {
+                FieldValue::Scalar(scalar) => scalar,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    (scalar.kind == ScalarKind::Number)
+        .then(|| scalar.text.parse().ok())
+        .flatten()
+}
+
+/// Whether parameter `name` of structure `action` holds a GstClockTime.
+fn is_time_parameter(registry: &ActionRegistry, action: &str, name: &str) -> bool {
+    registry
+        .get(action)
+        .and_then(|a| a.parameter(name))
+        .or_else(|| registry.common_parameters().iter().find(|p| p.name == name))
+        .is_some_and(|p| p.types.contains("GstClockTime"))
+}
+
+fn collect(
+    node: Node,
+    source: &str,
+    registry: &ActionRegistry,
+    settings: &InlayHintSettings,
+    hints: &mut Vec<(usize, String, InlayHintKind)>,
+) {
+    if node.kind() == "field" {
+        if let (Some(name), Some(value_node)) = (
+            node.child_by_field_name("name"),
+            node.child_by_field_name("value"),
+        ) {
+            let action = node
+                .parent()
+                .and_then(|list| list.parent())
+                .and_then(|structure| structure.child(0))
+                .filter(|n| n.kind() == "structure_name")
+                .map_or("", |n| text(n, source));
+            let value = FieldValue::from_node(value_node, source);
+            let end = value_node.end_byte();
+
+            match &value {
+                Some(FieldValue::Scalar(scalar))
+                    if settings.types
+                        && !matches!(
+                            scalar.kind,
+                            ScalarKind::String | ScalarKind::Variable | ScalarKind::Expression
+                        ) =>
+                {
+                    hints.push((
+                        end,
+                        format!(": {}", scalar.inferred_type()),
+                        InlayHintKind::TYPE,
+                    ));
+                }
+                _ => {}
+            }
+            if settings.times && is_time_parameter(registry, action, text(name, source)) {
+                if let Some(ns) = value.as_ref().and_then(nanoseconds).filter(|ns| *ns > 0) {
+                    hints.push((
+                        end,
+                        ClockTime::from_nseconds(ns).to_duration_string(),
+                        InlayHintKind::PARAMETER,
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, source, registry, settings, hints);
+    }
+}
+
+/// Inlay hints for the values of `text`, parsed as `tree`, within the byte
+/// `range`.
+pub(super) fn inlay_hints(
+    text: &str,
+    tree: &Tree,
+    range: Range<usize>,
+    registry: &ActionRegistry,
+    settings: &InlayHintSettings,
+) -> Vec<InlayHint> {
+    let mut hints = Vec::new();
+    collect(tree.root_node(), text, registry, settings, &mut hints);
+    hints
+        .into_iter()
+        .filter(|(offset, _, _)| range.contains(offset) || *offset == range.end)
+        .map(|(offset, label, kind)| InlayHint {
+            position: offset_to_position(text, offset),
+            padding_left: Some(kind == InlayHintKind::PARAMETER),
+            label: InlayHintLabel::String(label),
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_right: None,
+            data: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors;
+
+    fn labels(text: &str, settings: &InlayHintSettings) -> Vec<String> {
+        inlay_hints(
+            text,
+            &parse_tree_with_errors(text),
+            0..text.len(),
+            &ActionRegistry::builtin(),
+            settings,
+        )
+        .into_iter()
+        .map(|h| match h.label {
+            InlayHintLabel::String(label) => label,
+            InlayHintLabel::LabelParts(_) => panic!("expected a string label"),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_inlay_hints() {
+        let text = "seek, start=5000000000, stop=(guint64)250000000, rate=2.0, flags=flush\n\
+                    play, playback-time=1.5, name=\"x\", n=$(n)\n";
+        let times = InlayHintSettings {
+            times: true,
+            types: false,
+        };
+        assert_eq!(labels(text, &times), vec!["5 s", "250 ms"]);
+
+        let types = InlayHintSettings {
+            times: false,
+            types: true,
+        };
+        assert_eq!(
+            labels(text, &types),
+            vec![": int", ": double", ": string", ": double"]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Document links to the media and files referenced by a scenario.
+
+use std::ops::Range;
+
+use lsp_types::{DocumentLink, Uri};
+use tree_sitter::{Node, Tree};
+
+use super::{encode_path, to_lsp_range};
+use crate::document::{FieldValue, ScalarKind};
+
+/// Extensions of the files a relative path value is assumed to point to.
+const LINKED_EXTENSIONS: &[&str] = &[
+    "media_info",
+    "push",
+    "validatetest",
+    "scenario",
+    "xges",
+    "mp4",
+    "m4a",
+    "mkv",
+    "webm",
+    "mov",
+    "avi",
+    "ts",
+    "ogg",
+    "ogv",
+    "oga",
+    "opus",
+    "mp3",
+    "wav",
+    "flac",
+    "png",
+    "jpg",
+    "jpeg",
+    "srt",
+    "vtt",
+];
+
+/// The URI `value` refers to, if it looks like a file, resolving relative
+/// paths against the directory of `document`.
+fn link_target(document: &Uri, value: &str) -> Option<Uri> {
+    if value.contains("$(") {
+        return None;
+    }
+    if value.starts_with("file://") {
+        return value.parse().ok();
+    }
+    if value.contains("://") {
+        return None;
+    }
+    let file_name = value.rsplit('/').next()?;
+    let extension = file_name.rsplit_once('.')?.1;
+    if !LINKED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    if value.starts_with('/') {
+        return format!("file://{}", encode_path(value)).parse().ok();
+    }
+    let base = document.as_str();
+    let directory = &base[..base.rfind('/')? + 1];
+    format!("{}{}", directory, encode_path(value)).parse().ok()
+}
+
+fn collect(node: Node, source: &str, document: &Uri, links: &mut Vec<(Range<usize>, Uri)>) {
+    if node.kind() == "value" {
+        if let Some(FieldValue::Scalar(scalar)) = FieldValue::from_node(node, source) {
+            let range = if scalar.kind == ScalarKind::String {
+                scalar.span.start + 1..scalar.span.end - 1
+            } else {
+                scalar.span.clone()
+            };
+            let is_path = matches!(
+                scalar.kind,
+                ScalarKind::String | ScalarKind::Unquoted | ScalarKind::CliArgument
+            );
+            if let Some(target) = is_path
+                .then(|| link_target(document, &scalar.unquoted()))
+                .flatten()
+            {
+                links.push((range, target));
+            }
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, source, document, links);
+    }
+}
+
+/// Links of `text`, parsed as `tree`, the document at `uri`.
+pub(super) fn document_links(uri: &Uri, text: &str, tree: &Tree) -> Vec<DocumentLink> {
+    let mut links = Vec::new();
+    collect(tree.root_node(), text, uri, &mut links);
+    links
+        .into_iter()
+        .map(|(range, target)| DocumentLink {
+            range: to_lsp_range(text, range),
+            target: Some(target),
+            tooltip: None,
+            data: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors;
+
+    #[test]
+    fn test_link_target() {
+        let document: Uri = "file:///suite/tests/seek.validatetest".parse().unwrap();
+        let target = |value| link_target(&document, value).map(|u| u.as_str().to_string());
+        assert_eq!(
+            target("medias/clip 1.mp4.media_info").as_deref(),
+            Some("file:///suite/tests/medias/clip%201.mp4.media_info")
+        );
+        assert_eq!(
+            target("file:///media/clip.webm").as_deref(),
+            Some("file:///media/clip.webm")
+        );
+        assert_eq!(
+            target("/media/clip.ogg").as_deref(),
+            Some("file:///media/clip.ogg")
+        );
+        assert_eq!(target("$(medias)/clip.mp4"), None);
+        assert_eq!(target("https://example.com/clip.mp4"), None);
+        assert_eq!(target("1.5"), None);
+        assert_eq!(target("video/x-raw"), None);
+    }
+
+    #[test]
+    fn test_document_links() {
+        let uri: Uri = "file:///suite/seek.validatetest".parse().unwrap();
+        let text = "meta, args={\"uri=file:///m/a.mp4\"}\n\
+                    appsrc-push, target-element-name=src, file-name=\"data/buffer.ts\"\n\
+                    seek, start=1.0, flags=flush\n";
+        let links = document_links(&uri, text, &parse_tree_with_errors(text));
+        let targets: Vec<_> = links
+            .iter()
+            .map(|l| l.target.as_ref().unwrap().as_str())
+            .collect();
+        assert_eq!(targets, vec!["file:///suite/data/buffer.ts"]);
+        assert_eq!(
+            links[0].range,
+            to_lsp_range(
+                text,
+                text.find("data/").unwrap()..text.find(".ts").unwrap() + 3
+            )
+        );
+    }
+}
@@ -0,0 +1,841 @@
+//! Language server for ValidateTest files.
+//!
+//! [`run`] serves the Language Server Protocol over stdio. The request
+//! handlers live in submodules and work on plain text and byte offsets; this
+//! module owns the document store and the conversions between byte offsets
+//! and LSP positions.
+//!
+//! Documents are synchronized incrementally and their parse trees updated
+//! with tree-sitter's incremental parsing, which keeps diagnostics and range
+//! formatting fast on large scenarios.
+
+mod code_actions;
+mod completion;
+mod diagnostics;
+mod formatting;
+mod hover;
+mod inlay_hints;
+mod links;
+mod rename;
+mod semantic_tokens;
+mod settings;
+mod workspace;
+
+pub use settings::{FormatSettings, InlayHintSettings, LintSettings, Settings};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeConfiguration, DidChangeTextDocument, DidChangeWorkspaceFolders, DidCloseTextDocument,
+    DidOpenTextDocument, Notification as LspNotification, PublishDiagnostics, ShowMessage,
+};
+use lsp_types::request::{
+    CodeActionRequest, Completion, DocumentLinkRequest, Formatting, HoverRequest, InlayHintRequest,
+    PrepareRenameRequest, RangeFormatting, Rename, Request as LspRequest,
+    SemanticTokensFullRequest,
+};
+use lsp_types::{
+    CodeActionKind, CodeActionOptions, CodeActionProviderCapability, CompletionOptions,
+    CompletionResponse, DocumentLinkOptions, Hover, HoverContents, HoverProviderCapability,
+    InitializeParams, MarkupContent, MarkupKind, MessageType, OneOf, Position,
+    PrepareRenameResponse, PublishDiagnosticsParams, RenameOptions, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, ShowMessageParams,
+    TextDocumentContentChangeEvent, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+    WorkspaceEdit, WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
+};
+use tree_sitter::{InputEdit, Point, Tree};
+
+use crate::config::{self, FileConfig};
+use crate::dialect::Dialect;
+use crate::document::{parse_tree_with_errors, reparse};
+use crate::format::FormatterConfig;
+use crate::lint::LintConfig;
+use crate::position::{line_col_to_offset, offset_to_line_col, ColumnEncoding, LineCol};
+use crate::refactor::TextEdit;
+use crate::registry::{ActionRegistry, RegistryError};
+
+/// Byte offset of an LSP `position` (UTF-16 based) in `text`.
+///
+/// Positions past the end of a line or of the text are clamped.
+pub(crate) fn position_to_offset(text: &str, position: Position) -> usize {
+    line_col_to_offset(
+        text,
+        LineCol::new(position.line as usize, position.character as usize),
+        ColumnEncoding::Utf16,
+    )
+}
+
+/// LSP position (UTF-16 based) of the byte `offset` in `text`.
+pub(crate) fn offset_to_position(text: &str, offset: usize) -> Position {
+    let position = offset_to_line_col(text, offset, ColumnEncoding::Utf16);
+    Position {
+        line: position.line as u32,
+        character: position.column as u32,
+    }
+}
+
+/// LSP range of the byte `range` of `text`.
+pub(crate) fn to_lsp_range(text: &str, range: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(
+        offset_to_position(text, range.start),
+        offset_to_position(text, range.end),
+    )
+}
+
+/// LSP form of an edit of `text`.
+pub(crate) fn to_lsp_edit(text: &str, edit: &TextEdit) -> lsp_types::TextEdit {
+    lsp_types::TextEdit {
+        range: to_lsp_range(text, edit.range.clone()),
+        new_text: edit.new_text.clone(),
+    }
+}
+
+/// Percent-encode the characters of a path that are not allowed in URIs.
+pub(crate) fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'/'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~'
+            | b'+'
+            | b'='
+            | b'@'
+            | b':' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `file://` URI of the absolute `path`.
+pub(crate) fn path_to_uri(path: &Path) -> Option<Uri> {
+    format!("file://{}", encode_path(path.to_str()?))
+        .parse()
+        .ok()
+}
+
+/// The dialect of the document at `uri`, scenarios for documents that are
+/// not files.
+fn dialect(uri: &Uri) -> Dialect {
+    uri_to_path(uri).map_or(Dialect::Scenario, |path| Dialect::from_path(&path))
+}
+
+/// Local path of a `file://` URI.
+pub(crate) fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = uri.as_str().strip_prefix("file://")?;
+    // Drop the authority, usually empty or `localhost`
+    let path = &path[path.find('/')?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// tree-sitter point (row and byte column) of the byte `offset` in `text`.
+fn offset_to_point(text: &str, offset: usize) -> Point {
+    let position = offset_to_line_col(text, offset, ColumnEncoding::Utf8);
+    Point::new(position.line, position.column)
+}
+
+/// An open document and its parse tree.
+struct SourceFile {
+    text: String,
+    tree: Tree,
+}
+
+impl SourceFile {
+    fn new(text: String) -> Self {
+        let tree = parse_tree_with_errors(&text);
+        Self { text, tree }
+    }
+
+    /// Apply a change sent by the client and update the tree incrementally.
+    fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        let Some(range) = change.range else {
+            *self = Self::new(change.text);
+            return;
+        };
+        let start = position_to_offset(&self.text, range.start);
+        let old_end = position_to_offset(&self.text, range.end).max(start);
+        let start_position = offset_to_point(&self.text, start);
+        let old_end_position = offset_to_point(&self.text, old_end);
+
+        self.text.replace_range(start..old_end, &change.text);
+        let new_end = start + change.text.len();
+        self.tree.edit(&InputEdit {
+            start_byte: start,
+            old_end_byte: old_end,
+            new_end_byte: new_end,
+            start_position,
+            old_end_position,
+            new_end_position: offset_to_point(&self.text, new_end),
+        });
+        self.tree = reparse(&self.text, &self.tree);
+    }
+}
+
+/// State of the language server.
+pub struct Server {
+    /// Registry given on the command line, before the client's settings.
+    registry: ActionRegistry,
+    lint_config: LintConfig,
+    format_config: FormatterConfig,
+    settings: Settings,
+    documents: BTreeMap<Uri, SourceFile>,
+    workspace_folders: Vec<PathBuf>,
+    /// Test files found in the workspace folders.
+    workspace_files: Vec<PathBuf>,
+}
+
+impl Server {
+    pub fn new(registry: ActionRegistry) -> Self {
+        Self {
+            lint_config: LintConfig {
+                registry: registry.clone(),
+                ..Default::default()
+            },
+            registry,
+            format_config: FormatterConfig::default(),
+            settings: Settings::default(),
+            documents: BTreeMap::new(),
+            workspace_folders: Vec::new(),
+            workspace_files: Vec::new(),
+        }
+    }
+
+    /// Apply the settings chosen by the client.
+    ///
+    /// When an action file fails to load, the other settings still apply and
+    /// the registry is left unchanged.
+    pub fn set_settings(&mut self, settings: Settings) -> Result<(), RegistryError> {
+        self.format_config = settings.format.to_config();
+        self.lint_config.style = settings.lint.style;
+        self.lint_config.require_meta = settings.lint.require_meta;
+        self.lint_config.meta_fields = settings.lint.meta_fields.clone();
+        self.lint_config.levels.clear();
+        self.lint_config.add_levels(
+            settings
+                .lint
+                .levels
+                .iter()
+                .map(|(name, level)| (name.clone(), *level)),
+        );
+        let base = self.workspace_folders.first();
+        let actions: Vec<PathBuf> = settings
+            .lint
+            .actions
+            .iter()
+            .map(|path| match base {
+                Some(base) if path.is_relative() => base.join(path),
+                _ => path.clone(),
+            })
+            .collect();
+        self.settings = settings;
+
+        let mut registry = self.registry.clone();
+        registry.merge_files(&actions)?;
+        self.lint_config.registry = registry;
+        Ok(())
+    }
+
+    /// Set the workspace folders and look for the test files they hold.
+    pub fn set_workspace_folders(&mut self, folders: Vec<PathBuf>) {
+        self.workspace_files = workspace::scan_folders(&folders);
+        self.workspace_folders = folders;
+    }
+
+    fn text(&self, uri: &Uri) -> Option<&str> {
+        self.documents.get(uri).map(|d| d.text.as_str())
+    }
+
+    /// The capabilities advertised to clients.
+    pub fn capabilities() -> ServerCapabilities {
+        ServerCapabilities {
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec![",".to_string(), " ".to_string()]),
+                ..Default::default()
+            }),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: Some(false),
+                work_done_progress_options: Default::default(),
+            }),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                ..Default::default()
+            })),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: Default::default(),
+            })),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..Default::default()
+                }),
+            ),
+            workspace: Some(WorkspaceServerCapabilities {
+                workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                    supported: Some(true),
+                    change_notifications: Some(OneOf::Left(true)),
+                }),
+                file_operations: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// The `.validatetest-fmt.toml` files applying to the document at
+    /// `uri`, merged. Documents that are not files have none, and invalid
+    /// files are ignored.
+    fn file_config(&self, uri: &Uri) -> Option<FileConfig> {
+        let path = uri_to_path(uri)?;
+        let files = config::discover(path.parent()?).ok()?;
+        if files.is_empty() {
+            return None;
+        }
+        let mut merged = FileConfig::default();
+        for (_, config) in &files {
+            merged.merge(config);
+        }
+        Some(merged)
+    }
+
+    /// Formatter settings of the document at `uri`, in the dialect told by
+    /// its extension. Its configuration files override the client's
+    /// settings.
+    fn format_config(&self, uri: &Uri) -> FormatterConfig {
+        let mut config = FormatterConfig {
+            dialect: dialect(uri),
+            ..self.format_config.clone()
+        };
+        if let Some(file_config) = self.file_config(uri) {
+            file_config.apply(&mut config);
+        }
+        config
+    }
+
+    /// Lint settings of the document at `uri`, in the dialect told by its
+    /// extension. Its configuration files override the client's settings.
+    fn lint_config(&self, uri: &Uri) -> Cow<'_, LintConfig> {
+        let file_config = self.file_config(uri);
+        let dialect = file_config
+            .as_ref()
+            .and_then(|c| c.dialect)
+            .unwrap_or(dialect(uri));
+        match file_config {
+            None if dialect == Dialect::Scenario => Cow::Borrowed(&self.lint_config),
+            file_config => {
+                let mut config = LintConfig {
+                    dialect,
+                    ..self.lint_config.clone()
+                };
+                if let Some(file_config) = file_config {
+                    file_config.lint.apply(&mut config);
+                }
+                Cow::Owned(config)
+            }
+        }
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => respond::<Completion>(request, |params| {
+                let position = params.text_document_position;
+                let text = self.text(&position.text_document.uri)?;
+                let offset = position_to_offset(text, position.position);
+                let items = completion::completions(text, offset, &self.lint_config.registry);
+                Some(CompletionResponse::Array(items))
+            }),
+            HoverRequest::METHOD => respond::<HoverRequest>(request, |params| {
+                let position = params.text_document_position_params;
+                let text = self.text(&position.text_document.uri)?;
+                let offset = position_to_offset(text, position.position);
+                let (value, range) = hover::hover(text, offset, &self.lint_config.registry)?;
+                Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: Some(to_lsp_range(text, range)),
+                })
+            }),
+            // The client's formatting options are ignored so that the result
+            // matches `validatetest-fmt --check`
+            Formatting::METHOD => respond::<Formatting>(request, |params| {
+                let document = self.documents.get(&params.text_document.uri)?;
+                let text = &document.text;
+                let config = self.format_config(&params.text_document.uri);
+                let edits = formatting::formatting(text, &document.tree, None, &config)?;
+                Some(edits.iter().map(|e| to_lsp_edit(text, e)).collect())
+            }),
+            RangeFormatting::METHOD => respond::<RangeFormatting>(request, |params| {
+                let document = self.documents.get(&params.text_document.uri)?;
+                let text = &document.text;
+                let range = position_to_offset(text, params.range.start)
+                    ..position_to_offset(text, params.range.end);
+                let config = self.format_config(&params.text_document.uri);
+                let edits = formatting::formatting(text, &document.tree, Some(range), &config)?;
+                Some(edits.iter().map(|e| to_lsp_edit(text, e)).collect())
+            }),
+            PrepareRenameRequest::METHOD => respond::<PrepareRenameRequest>(request, |params| {
+                let text = self.text(&params.text_document.uri)?;
+                let offset = position_to_offset(text, params.position);
+                let (_, range) = rename::symbol_at(text, offset)?;
+                Some(PrepareRenameResponse::RangeWithPlaceholder {
+                    placeholder: text[range.clone()].to_string(),
+                    range: to_lsp_range(text, range),
+                })
+            }),
+            Rename::METHOD => try_respond::<Rename>(request, |params| {
+                let position = params.text_document_position;
+                let uri = position.text_document.uri;
+                let Some(text) = self.text(&uri) else {
+                    return Ok(None);
+                };
+                let offset = position_to_offset(text, position.position);
+                let Some(edits) = rename::rename(text, offset, &params.new_name) else {
+                    return Ok(None);
+                };
+                let edits = edits.map_err(|e| e.to_string())?;
+                let edits = edits.iter().map(|e| to_lsp_edit(text, e)).collect();
+                Ok(Some(WorkspaceEdit::new([(uri, edits)].into())))
+            }),
+            SemanticTokensFullRequest::METHOD => {
+                respond::<SemanticTokensFullRequest>(request, |params| {
+                    let document = self.documents.get(&params.text_document.uri)?;
+                    Some(SemanticTokensResult::Tokens(SemanticTokens {
+                        result_id: None,
+                        data: semantic_tokens::semantic_tokens(&document.text, &document.tree),
+                    }))
+                })
+            }
+            CodeActionRequest::METHOD => respond::<CodeActionRequest>(request, |params| {
+                let uri = params.text_document.uri;
+                let document = self.documents.get(&uri)?;
+                let text = &document.text;
+                let range = position_to_offset(text, params.range.start)
+                    ..position_to_offset(text, params.range.end);
+                Some(code_actions::code_actions(
+                    &uri,
+                    text,
+                    &document.tree,
+                    range,
+                    &self.lint_config(&uri),
+                ))
+            }),
+            InlayHintRequest::METHOD => respond::<InlayHintRequest>(request, |params| {
+                let document = self.documents.get(&params.text_document.uri)?;
+                let text = &document.text;
+                let range = position_to_offset(text, params.range.start)
+                    ..position_to_offset(text, params.range.end);
+                Some(inlay_hints::inlay_hints(
+                    text,
+                    &document.tree,
+                    range,
+                    &self.lint_config.registry,
+                    &self.settings.inlay_hints,
+                ))
+            }),
+            DocumentLinkRequest::METHOD => respond::<DocumentLinkRequest>(request, |params| {
+                let uri = params.text_document.uri;
+                let document = self.documents.get(&uri)?;
+                Some(links::document_links(&uri, &document.text, &document.tree))
+            }),
+            _ => Response::new_err(
+                request.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", request.method),
+            ),
+        }
+    }
+
+    /// Diagnostics of the document at `uri`.
+    ///
+    /// Closed documents are read from disk when they belong to the
+    /// workspace, and otherwise have no diagnostics.
+    fn publish_diagnostics(&self, uri: Uri) -> Notification {
+        let path = uri_to_path(&uri).filter(|p| self.workspace_files.contains(p));
+        let config = self.lint_config(&uri);
+        let mut diagnostics = match (self.documents.get(&uri), &path) {
+            (Some(document), _) => {
+                diagnostics::diagnostics(&document.text, &document.tree, &config)
+            }
+            (None, Some(path)) => fs::read_to_string(path).map_or_else(
+                |_| Vec::new(),
+                |text| {
+                    let document = SourceFile::new(text);
+                    diagnostics::diagnostics(&document.text, &document.tree, &config)
+                },
+            ),
+            (None, None) => Vec::new(),
+        };
+        if let Some(path) = path {
+            diagnostics.extend(workspace::duplicate_scenarios(&path, &self.workspace_files));
+        }
+        Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            },
+        )
+    }
+
+    /// Diagnostics of the open documents and of every workspace file.
+    fn publish_all_diagnostics(&self) -> Vec<Notification> {
+        let mut uris: Vec<Uri> = self
+            .workspace_files
+            .iter()
+            .filter_map(|path| path_to_uri(path))
+            .collect();
+        uris.extend(self.documents.keys().cloned());
+        uris.sort();
+        uris.dedup();
+        uris.into_iter()
+            .map(|uri| self.publish_diagnostics(uri))
+            .collect()
+    }
+
+    /// Apply `settings`, reporting action files failing to load to the user.
+    fn apply_settings(&mut self, settings: Settings) -> Option<Notification> {
+        self.set_settings(settings).err().map(|e| {
+            Notification::new(
+                ShowMessage::METHOD.to_string(),
+                ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message: format!("Failed to load action definitions: {}", e),
+                },
+            )
+        })
+    }
+
+    /// Handle a notification, returning the notifications to send back.
+    fn handle_notification(&mut self, notification: Notification) -> Vec<Notification> {
+        let uri = match notification.method.as_str() {
+            DidChangeConfiguration::METHOD => {
+                let Some(params) = extract::<DidChangeConfiguration>(notification) else {
+                    return Vec::new();
+                };
+                // Invalid settings are ignored, like at initialization
+                let Ok(settings) = Settings::from_value(params.settings) else {
+                    return Vec::new();
+                };
+                let mut replies: Vec<_> = self.apply_settings(settings).into_iter().collect();
+                replies.extend(self.publish_all_diagnostics());
+                return replies;
+            }
+            DidChangeWorkspaceFolders::METHOD => {
+                let Some(params) = extract::<DidChangeWorkspaceFolders>(notification) else {
+                    return Vec::new();
+                };
+                let removed: Vec<PathBuf> = params
+                    .event
+                    .removed
+                    .iter()
+                    .filter_map(|f| uri_to_path(&f.uri))
+                    .collect();
+                let mut folders = self.workspace_folders.clone();
+                folders.retain(|f| !removed.contains(f));
+                folders.extend(
+                    params
+                        .event
+                        .added
+                        .iter()
+                        .filter_map(|f| uri_to_path(&f.uri)),
+                );
+
+                // Clear the diagnostics of the files leaving the workspace
+                let previous = std::mem::take(&mut self.workspace_files);
+                self.set_workspace_folders(folders);
+                let mut replies: Vec<_> = previous
+                    .iter()
+                    .filter(|p| !self.workspace_files.contains(p))
+                    .filter_map(|p| path_to_uri(p))
+                    .map(|uri| self.publish_diagnostics(uri))
+                    .collect();
+                replies.extend(self.publish_all_diagnostics());
+                return replies;
+            }
+            DidOpenTextDocument::METHOD => {
+                extract::<DidOpenTextDocument>(notification).map(|params| {
+                    let document = params.text_document;
+                    self.documents
+                        .insert(document.uri.clone(), SourceFile::new(document.text));
+                    document.uri
+                })
+            }
+            DidChangeTextDocument::METHOD => extract::<DidChangeTextDocument>(notification)
+                .and_then(|params| {
+                    let uri = params.text_document.uri;
+                    let document = self.documents.get_mut(&uri)?;
+                    for change in params.content_changes {
+                        document.apply_change(change);
+                    }
+                    Some(uri)
+                }),
+            DidCloseTextDocument::METHOD => {
+                extract::<DidCloseTextDocument>(notification).map(|params| {
+                    self.documents.remove(&params.text_document.uri);
+                    params.text_document.uri
+                })
+            }
+            _ => None,
+        };
+        uri.map(|uri| self.publish_diagnostics(uri))
+            .into_iter()
+            .collect()
+    }
+
+    /// Handle messages until the client shuts the server down.
+    pub fn main_loop(
+        &mut self,
+        connection: &Connection,
+    ) -> Result<(), Box<dyn Error + Sync + Send>> {
+        for notification in self.publish_all_diagnostics() {
+            connection
+                .sender
+                .send(Message::Notification(notification))?;
+        }
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    for reply in self.handle_notification(notification) {
+                        connection.sender.send(Message::Notification(reply))?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn respond<R: LspRequest>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    let id = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => Response::new_ok(id, handler(params)),
+        Err(e) => Response::new_err(
+            id,
+            lsp_server::ErrorCode::InvalidParams as i32,
+            e.to_string(),
+        ),
+    }
+}
+
+/// Like [`respond`], replying with an error when `handler` fails.
+fn try_respond<R: LspRequest>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> Result<R::Result, String>,
+) -> Response {
+    let id = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => match handler(params) {
+            Ok(result) => Response::new_ok(id, result),
+            Err(message) => {
+                Response::new_err(id, lsp_server::ErrorCode::RequestFailed as i32, message)
+            }
+        },
+        Err(e) => Response::new_err(
+            id,
+            lsp_server::ErrorCode::InvalidParams as i32,
+            e.to_string(),
+        ),
+    }
+}
+
+fn extract<N: LspNotification>(notification: Notification) -> Option<N::Params> {
+    notification.extract(N::METHOD).ok()
+}
+
+/// Serve the Language Server Protocol on stdio.
+pub fn run(registry: ActionRegistry) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = serde_json::to_value(Server::capabilities())?;
+    let params: InitializeParams = serde_json::from_value(connection.initialize(capabilities)?)?;
+
+    let mut server = Server::new(registry);
+    #[allow(deprecated)]
+    let folders = match (params.workspace_folders, params.root_uri) {
+        (Some(folders), _) => folders.iter().filter_map(|f| uri_to_path(&f.uri)).collect(),
+        (None, Some(root)) => uri_to_path(&root).into_iter().collect(),
+        (None, None) => Vec::new(),
+    };
+    server.set_workspace_folders(folders);
+    // Invalid settings are ignored rather than preventing the server from starting
+    if let Some(settings) = params
+        .initialization_options
+        .and_then(|options| Settings::from_value(options).ok())
+    {
+        if let Some(message) = server.apply_settings(settings) {
+            connection.sender.send(Message::Notification(message))?;
+        }
+    }
+    server.main_loop(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::Level;
+
+    #[test]
+    fn test_position_conversion() {
+        let text = "meta, summary=\"é😀\", a=1\nplay\n";
+        let offset = text.find(", a").unwrap();
+        let position = offset_to_position(text, offset);
+        // é and 😀 take 1 and 2 UTF-16 code units
+        assert_eq!(position, Position::new(0, 19));
+        assert_eq!(position_to_offset(text, position), offset);
+
+        assert_eq!(
+            position_to_offset(text, Position::new(1, 2)),
+            text.len() - 3
+        );
+        assert_eq!(
+            position_to_offset(text, Position::new(1, 99)),
+            text.len() - 1
+        );
+        assert_eq!(position_to_offset(text, Position::new(9, 0)), text.len());
+    }
+
+    #[test]
+    fn test_uri_paths() {
+        let path = Path::new("/tmp/my tests/é.validatetest");
+        let uri = path_to_uri(path).unwrap();
+        assert_eq!(uri.as_str(), "file:///tmp/my%20tests/%C3%A9.validatetest");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+
+        let uri: Uri = "file://localhost/a/b.scenario".parse().unwrap();
+        assert_eq!(uri_to_path(&uri).unwrap(), Path::new("/a/b.scenario"));
+        let uri: Uri = "untitled:Untitled-1".parse().unwrap();
+        assert_eq!(uri_to_path(&uri), None);
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let mut document = SourceFile::new("meta\nseek, start=1.0, flags=flush\n".to_string());
+        let change = |line, start, end, text: &str| TextDocumentContentChangeEvent {
+            range: Some(lsp_types::Range::new(
+                Position::new(line, start),
+                Position::new(line, end),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        };
+        document.apply_change(change(1, 12, 15, "\"é\""));
+        document.apply_change(change(2, 0, 0, "play, playback-time=2.0\n"));
+        let text = "meta\nseek, start=\"é\", flags=flush\nplay, playback-time=2.0\n";
+        assert_eq!(document.text, text);
+        assert_eq!(
+            document.tree.root_node().to_sexp(),
+            parse_tree_with_errors(text).root_node().to_sexp()
+        );
+
+        document.apply_change(TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "play\n".to_string(),
+        });
+        assert_eq!(document.text, "play\n");
+    }
+
+    #[test]
+    fn test_configuration_files() {
+        let root = std::env::temp_dir().join(format!("validatetest-lsp-{}", std::process::id()));
+        let tests = root.join("tests");
+        fs::create_dir_all(&tests).unwrap();
+        fs::write(
+            root.join(config::FILE_NAME),
+            "root = true\nindent = 2\n[lint.levels]\nmissing-meta = \"allow\"\n",
+        )
+        .unwrap();
+        let uri = path_to_uri(&tests.join("seek.validatetest")).unwrap();
+
+        let mut server = Server::new(ActionRegistry::default());
+        server.handle_notification(Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            lsp_types::DidOpenTextDocumentParams {
+                text_document: lsp_types::TextDocumentItem::new(
+                    uri.clone(),
+                    "validatetest".to_string(),
+                    1,
+                    "meta, handles-states=true, args={\"videotestsrc ! fakesink\"}\n".to_string(),
+                ),
+            },
+        ));
+        let response = server.handle_request(Request::new(
+            1.into(),
+            Formatting::METHOD.to_string(),
+            lsp_types::DocumentFormattingParams {
+                text_document: lsp_types::TextDocumentIdentifier::new(uri.clone()),
+                options: Default::default(),
+                work_done_progress_params: Default::default(),
+            },
+        ));
+        let lint_config = server.lint_config(&uri).into_owned();
+        let other = path_to_uri(&std::env::temp_dir().join("a.validatetest")).unwrap();
+        let default_indent = server.format_config(&other).indent_width;
+        fs::remove_dir_all(&root).unwrap();
+
+        let edits: Vec<lsp_types::TextEdit> =
+            serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(
+            edits
+                .iter()
+                .any(|e| e.new_text.contains("\n  handles-states=true")),
+            "{edits:?}"
+        );
+        assert_eq!(lint_config.level("missing-meta"), Some(Level::Allow));
+        assert_eq!(default_indent, 4);
+    }
+}
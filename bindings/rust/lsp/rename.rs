@@ -0,0 +1,137 @@
+//! Renaming of variables and issue-ids.
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use crate::document::{parse_tree_with_errors, FieldValue};
+use crate::refactor::{rename_issue_id, rename_variable, RefactorError, TextEdit};
+
+/// Something that can be renamed.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Symbol {
+    /// A `set-vars` variable, at its definition or a `$(name)` reference.
+    Variable(String),
+    /// The `issue-id` of an expected issue.
+    IssueId(String),
+}
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+/// The `$(name)` reference of `node` (a variable or expression) around byte
+/// `offset`, as the name and its range.
+pub(super) fn reference_at(
+    node: Node,
+    source: &str,
+    offset: usize,
+) -> Option<(String, Range<usize>)> {
+    let node_text = text(node, source);
+    node_text.match_indices("$(").find_map(|(i, _)| {
+        let start = node.start_byte() + i + 2;
+        let len = source[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(source.len() - start);
+        (start..=start + len)
+            .contains(&offset)
+            .then(|| (source[start..start + len].to_string(), start..start + len))
+    })
+}
+
+/// Name of the structure `field` belongs to.
+fn owner_name<'s>(field: Node, source: &'s str) -> Option<&'s str> {
+    let structure = field.parent()?.parent()?;
+    let name = structure
+        .child(0)
+        .filter(|n| n.kind() == "structure_name")?;
+    Some(text(name, source))
+}
+
+/// The renameable symbol at byte `offset` of `source`, and the range of its
+/// text there.
+pub(super) fn symbol_at(source: &str, offset: usize) -> Option<(Symbol, Range<usize>)> {
+    let tree = parse_tree_with_errors(source);
+    let mut node = tree.root_node().descendant_for_byte_range(offset, offset)?;
+    loop {
+        match node.kind() {
+            "variable" | "expression" => {
+                let (name, range) = reference_at(node, source, offset)?;
+                return Some((Symbol::Variable(name), range));
+            }
+            "field_name" => {
+                let field = node.parent()?;
+                return (owner_name(field, source)? == "set-vars").then(|| {
+                    (
+                        Symbol::Variable(text(node, source).to_string()),
+                        node.byte_range(),
+                    )
+                });
+            }
+            "field_value" if node.parent()?.kind() == "field" => {
+                let field = node.parent()?;
+                let name = text(field.child_by_field_name("name")?, source);
+                if name != "issue-id" || owner_name(field, source)? != "expected-issue" {
+                    return None;
+                }
+                let FieldValue::Scalar(value) = FieldValue::from_node(node, source)? else {
+                    return None;
+                };
+                return Some((Symbol::IssueId(value.unquoted()), value.span));
+            }
+            _ => node = node.parent()?,
+        }
+    }
+}
+
+/// Edits renaming the symbol at byte `offset` of `source` to `new_name`.
+pub(super) fn rename(
+    source: &str,
+    offset: usize,
+    new_name: &str,
+) -> Option<Result<Vec<TextEdit>, RefactorError>> {
+    Some(match symbol_at(source, offset)?.0 {
+        Symbol::Variable(name) => rename_variable(source, &name, new_name),
+        Symbol::IssueId(id) => rename_issue_id(source, &id, new_name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refactor::apply_edits;
+
+    const SOURCE: &str = "set-vars, clip=\"a.mp4\"\nplay, uri=\"file://$(clip)\", n=expr($(clip.n)+1)\nmeta, expected-issues={[expected-issue, issue-id=event::a]}\n";
+
+    fn symbol(needle: &str, delta: usize) -> Option<(Symbol, &'static str)> {
+        let offset = SOURCE.find(needle).unwrap() + delta;
+        symbol_at(SOURCE, offset).map(|(symbol, range)| (symbol, &SOURCE[range]))
+    }
+
+    #[test]
+    fn test_symbol_at() {
+        let clip = Some((Symbol::Variable("clip".to_string()), "clip"));
+        assert_eq!(symbol("clip=", 2), clip);
+        assert_eq!(symbol("$(clip)", 3), clip);
+        assert_eq!(symbol("$(clip.n", 6), clip);
+        assert_eq!(
+            symbol("event::a", 3),
+            Some((Symbol::IssueId("event::a".to_string()), "event::a"))
+        );
+        assert_eq!(symbol("uri", 0), None);
+        assert_eq!(symbol("play", 1), None);
+    }
+
+    #[test]
+    fn test_rename() {
+        let offset = SOURCE.find("$(clip)").unwrap() + 2;
+        let edits = rename(SOURCE, offset, "media").unwrap().unwrap();
+        assert_eq!(apply_edits(SOURCE, &edits), SOURCE.replace("clip", "media"));
+
+        let offset = SOURCE.find("event::a").unwrap();
+        assert!(matches!(
+            rename(SOURCE, offset, "bad id"),
+            Some(Err(RefactorError::InvalidIssueId(_)))
+        ));
+    }
+}
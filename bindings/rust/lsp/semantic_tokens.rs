@@ -0,0 +1,183 @@
+//! Semantic tokens, for finer highlighting than TextMate grammars allow.
+
+use std::ops::Range;
+
+use lsp_types::{SemanticToken, SemanticTokenType, SemanticTokensLegend};
+use tree_sitter::{Node, Tree};
+
+/// Token types, in the order of their index in the legend.
+const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    // Action names
+    SemanticTokenType::FUNCTION,
+    // Names of nested structures
+    SemanticTokenType::STRUCT,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::VARIABLE,
+    // `expr(...)` expressions
+    SemanticTokenType::MACRO,
+    // Type casts such as `(int)`
+    SemanticTokenType::TYPE,
+    SemanticTokenType::STRING,
+];
+
+const ACTION: u32 = 0;
+const STRUCTURE: u32 = 1;
+const FIELD: u32 = 2;
+const VARIABLE: u32 = 3;
+const EXPRESSION: u32 = 4;
+const TYPE: u32 = 5;
+const STRING: u32 = 6;
+
+pub(super) fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: Vec::new(),
+    }
+}
+
+/// Byte ranges and token types of the highlighted nodes under `node`, in
+/// document order.
+fn collect(node: Node, tokens: &mut Vec<(Range<usize>, u32)>) {
+    let token_type = match node.kind() {
+        "structure_name" => {
+            let name = node.child(0);
+            if name.is_some_and(|n| n.kind() == "variable") {
+                Some(VARIABLE)
+            } else if node.parent().is_some_and(|p| p.kind() == "structure") {
+                Some(ACTION)
+            } else if node.parent().is_some_and(|p| {
+                let mut cursor = p.walk();
+                let has_fields = p.children(&mut cursor).any(|c| c.kind() == "field_list");
+                has_fields
+            }) {
+                Some(STRUCTURE)
+            } else {
+                // A bare word in an array
+                None
+            }
+        }
+        "field_name" => Some(FIELD),
+        "variable" => Some(VARIABLE),
+        "expression" => Some(EXPRESSION),
+        "type_name" => Some(TYPE),
+        "string" => {
+            // The string minus the variables it references
+            let mut start = node.start_byte();
+            let mut stack = vec![node];
+            while let Some(n) = stack.pop() {
+                if n.kind() == "variable" || n.kind() == "expression" {
+                    tokens.push((start..n.start_byte(), STRING));
+                    collect(n, tokens);
+                    start = n.end_byte();
+                    continue;
+                }
+                let mut cursor = n.walk();
+                let children: Vec<_> = n.children(&mut cursor).collect();
+                stack.extend(children.into_iter().rev());
+            }
+            tokens.push((start..node.end_byte(), STRING));
+            return;
+        }
+        _ => None,
+    };
+    if let Some(token_type) = token_type {
+        tokens.push((node.byte_range(), token_type));
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, tokens);
+    }
+}
+
+/// Semantic tokens of `text`, parsed as `tree`, in the LSP relative encoding.
+pub(super) fn semantic_tokens(text: &str, tree: &Tree) -> Vec<SemanticToken> {
+    let mut ranges = Vec::new();
+    collect(tree.root_node(), &mut ranges);
+
+    let mut tokens = Vec::new();
+    let (mut previous_line, mut previous_start) = (0, 0);
+    let mut line = 0;
+    let mut line_start = 0;
+    for (range, token_type) in ranges {
+        // Tokens cannot span lines: split multi-line strings
+        let mut start = range.start;
+        while start < range.end {
+            while let Some(i) = text[line_start..start].find('\n') {
+                line += 1;
+                line_start += i + 1;
+            }
+            let end = text[start..range.end]
+                .find('\n')
+                .map_or(range.end, |i| start + i);
+            if end > start {
+                let character = text[line_start..start].encode_utf16().count() as u32;
+                let delta_line = line - previous_line;
+                tokens.push(SemanticToken {
+                    delta_line,
+                    delta_start: if delta_line == 0 {
+                        character - previous_start
+                    } else {
+                        character
+                    },
+                    length: text[start..end].encode_utf16().count() as u32,
+                    token_type,
+                    token_modifiers_bitset: 0,
+                });
+                previous_line = line;
+                previous_start = character;
+            }
+            start = end + usize::from(end < range.end);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree_with_errors;
+
+    /// Decode the tokens back to `(line, text, type)`.
+    fn decode(text: &str) -> Vec<(u32, String, u32)> {
+        let tokens = semantic_tokens(text, &parse_tree_with_errors(text));
+        let lines: Vec<Vec<u16>> = text.lines().map(|l| l.encode_utf16().collect()).collect();
+        let (mut line, mut start) = (0, 0);
+        tokens
+            .into_iter()
+            .map(|t| {
+                if t.delta_line > 0 {
+                    start = 0;
+                }
+                line += t.delta_line;
+                start += t.delta_start;
+                let units = &lines[line as usize][start as usize..(start + t.length) as usize];
+                (line, String::from_utf16(units).unwrap(), t.token_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = "seek, start=(double)1.0, x=expr(2*$(d))\nmeta, a=[b, c], i={[expected-issue, id=\"é$(v)\nz\"]}\n";
+        assert_eq!(
+            decode(text),
+            vec![
+                (0, "seek".to_string(), ACTION),
+                (0, "start".to_string(), FIELD),
+                (0, "double".to_string(), TYPE),
+                (0, "x".to_string(), FIELD),
+                (0, "expr(2*$(d))".to_string(), EXPRESSION),
+                (1, "meta".to_string(), ACTION),
+                (1, "a".to_string(), FIELD),
+                (1, "i".to_string(), FIELD),
+                (1, "expected-issue".to_string(), STRUCTURE),
+                (1, "id".to_string(), FIELD),
+                (1, "\"é".to_string(), STRING),
+                (1, "$(v)".to_string(), VARIABLE),
+                (2, "z\"".to_string(), STRING),
+            ]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Settings of the language server chosen by the client.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::flow::FlowAlign;
+use crate::format::{
+    ArrayPacking, BraceStyle, ContinuationIndent, FormatterConfig, LineEnding, Semicolon,
+};
+use crate::lint::Level;
+
+/// Settings sent by the client as `initializationOptions` or with
+/// `workspace/didChangeConfiguration`, for instance:
+///
+/// ```json
+/// {
+///   "inlayHints": { "times": true, "types": false },
+///   "format": { "indentWidth": 2, "sortExpectedIssues": true },
+///   "lint": { "actions": ["tools/actions.toml"] }
+/// }
+/// ```
+///
+/// Missing entries keep their default value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub inlay_hints: InlayHintSettings,
+    pub format: FormatSettings,
+    pub lint: LintSettings,
+}
+
+impl Settings {
+    /// Parse the settings sent by a client, which may nest them under a
+    /// `validatetest` section.
+    pub fn from_value(mut value: serde_json::Value) -> serde_json::Result<Self> {
+        if let Some(section) = value.get_mut("validatetest") {
+            value = section.take();
+        }
+        serde_json::from_value(value)
+    }
+}
+
+/// Which inlay hints are shown.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct InlayHintSettings {
+    /// Human readable durations next to nanosecond values of time fields.
+    pub times: bool,
+    /// Inferred type next to values without a type cast.
+    pub types: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        Self {
+            times: true,
+            types: true,
+        }
+    }
+}
+
+/// Formatter options, mirroring the `validatetest-fmt` command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FormatSettings {
+    pub indent_width: Option<usize>,
+    pub max_line_length: Option<usize>,
+    pub sort_expected_issues: bool,
+    /// Keep quoted structures instead of converting them to arrays.
+    pub keep_quoted_structures: bool,
+    /// Structure names converted in addition to the default ones.
+    pub quoted_structures: Vec<String>,
+    pub convert_any_quoted_structure: bool,
+    pub legacy_quoted_issues: bool,
+    pub keep_long_strings: bool,
+    pub split_long_args: bool,
+    pub line_ending: Option<LineEnding>,
+    pub array_packing: Option<ArrayPacking>,
+    pub array_packing_threshold: Option<usize>,
+    pub brace_style: Option<BraceStyle>,
+    pub no_block_trailing_comma: bool,
+    pub no_array_trailing_comma: bool,
+    pub spaces_around_equals: bool,
+    pub continuation_indent: Option<ContinuationIndent>,
+    pub max_inline_fields: Option<usize>,
+    pub preserve_breaks: bool,
+    pub minimal_diff: bool,
+    pub semicolon: Option<Semicolon>,
+    pub flow_align: Option<FlowAlign>,
+    /// Canonical field order by structure name.
+    pub field_order: BTreeMap<String, Vec<String>>,
+}
+
+impl FormatSettings {
+    pub fn to_config(&self) -> FormatterConfig {
+        let mut config = FormatterConfig::default();
+        if let Some(width) = self.indent_width {
+            config.indent_width = width;
+        }
+        if let Some(length) = self.max_line_length {
+            config.max_line_length = length;
+        }
+        config.sort_expected_issues = self.sort_expected_issues;
+        config.convert_quoted_structures = !self.keep_quoted_structures;
+        config
+            .quoted_structure_names
+            .extend(self.quoted_structures.iter().cloned());
+        config.convert_any_quoted_structure = self.convert_any_quoted_structure;
+        config.legacy_quoted_issues = self.legacy_quoted_issues;
+        config.wrap_long_strings = !self.keep_long_strings;
+        config.split_long_args = self.split_long_args;
+        if let Some(line_ending) = self.line_ending {
+            config.line_ending = line_ending;
+        }
+        if let Some(packing) = self.array_packing {
+            config.array_packing = packing;
+        }
+        if let Some(threshold) = self.array_packing_threshold {
+            config.array_packing_threshold = threshold;
+        }
+        if let Some(style) = self.brace_style {
+            config.brace_style = style;
+        }
+        config.block_trailing_comma = !self.no_block_trailing_comma;
+        config.array_trailing_comma = !self.no_array_trailing_comma;
+        config.spaces_around_equals = self.spaces_around_equals;
+        if let Some(indent) = self.continuation_indent {
+            config.continuation_indent = indent;
+        }
+        config.max_inline_fields = self.max_inline_fields;
+        config.preserve_breaks = self.preserve_breaks;
+        config.minimal_diff = self.minimal_diff;
+        if let Some(semicolon) = self.semicolon {
+            config.semicolon = semicolon;
+        }
+        if let Some(align) = self.flow_align {
+            config.flow_align = align;
+        }
+        config.field_order.extend(
+            self.field_order
+                .iter()
+                .map(|(name, order)| (name.clone(), order.clone())),
+        );
+        config
+    }
+}
+
+/// Lint options.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintSettings {
+    /// Action definition files loaded on top of the built-in registry.
+    /// Relative paths are resolved against the first workspace folder.
+    pub actions: Vec<PathBuf>,
+    /// Also report the style checks, which are otherwise only offered as
+    /// quick fixes.
+    pub style: bool,
+    /// Report documents without a `meta` structure.
+    pub require_meta: bool,
+    /// Fields required in `meta`.
+    pub meta_fields: Vec<String>,
+    /// Levels by lint code or category, like `[lint.levels]`.
+    pub levels: BTreeMap<String, Level>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_value() {
+        let value = serde_json::json!({
+            "validatetest": {
+                "inlayHints": { "types": false },
+                "format": { "indentWidth": 2, "quotedStructures": ["my-check"] },
+                "lint": { "style": true, "metaFields": ["args"], "levels": { "registry": "deny" } }
+            }
+        });
+        let settings = Settings::from_value(value).unwrap();
+        assert!(settings.inlay_hints.times);
+        assert!(!settings.inlay_hints.types);
+        assert!(settings.lint.style);
+        assert_eq!(settings.lint.meta_fields, vec!["args".to_string()]);
+        assert_eq!(settings.lint.levels["registry"], Level::Deny);
+
+        let config = settings.format.to_config();
+        assert_eq!(config.indent_width, 2);
+        assert_eq!(
+            config.max_line_length,
+            FormatterConfig::default().max_line_length
+        );
+        assert!(config.convert_quoted_structures);
+        assert!(config
+            .quoted_structure_names
+            .contains(&"my-check".to_string()));
+
+        assert_eq!(
+            Settings::from_value(serde_json::json!({})).unwrap(),
+            Settings::default()
+        );
+    }
+}
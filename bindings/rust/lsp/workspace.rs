@@ -0,0 +1,115 @@
+//! Workspace-wide diagnostics, covering the files the client has not opened.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lsp_types::{DiagnosticSeverity, NumberOrString};
+
+/// Extensions of the files scanned in workspace folders.
+const EXTENSIONS: &[&str] = &["validatetest", "scenario"];
+
+/// Directories never scanned, on top of hidden ones.
+const SKIPPED_DIRECTORIES: &[&str] = &["target", "node_modules"];
+
+fn scan(directory: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRECTORIES.contains(&name.as_ref()) {
+                scan(&path, files);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|e| EXTENSIONS.iter().any(|ext| e == *ext))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Test files under `folders`, sorted. Symbolic links to directories are
+/// not followed.
+pub(super) fn scan_folders(folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for folder in folders {
+        scan(folder, &mut files);
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Name of the scenario defined by `path`, as gst-validate-launcher names
+/// it: the file name without its extension.
+fn scenario_name(path: &Path) -> Option<&str> {
+    path.file_stem()?.to_str()
+}
+
+/// Diagnostics reported on `path` when other `files` define a scenario of the
+/// same name, which the launcher cannot tell apart.
+pub(super) fn duplicate_scenarios(path: &Path, files: &[PathBuf]) -> Vec<lsp_types::Diagnostic> {
+    let Some(name) = scenario_name(path) else {
+        return Vec::new();
+    };
+    files
+        .iter()
+        .filter(|f| f.as_path() != path && scenario_name(f) == Some(name))
+        .map(|other| lsp_types::Diagnostic {
+            range: lsp_types::Range::default(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(
+                "duplicate-scenario-name".to_string(),
+            )),
+            source: Some("validatetest".to_string()),
+            message: format!("Scenario '{}' is also defined by {}", name, other.display()),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_folders() {
+        let root = std::env::temp_dir().join(format!("validatetest-lsp-{}", std::process::id()));
+        for dir in ["a", "b/c", ".git", "target"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "a/seek.scenario",
+            "b/c/seek.validatetest",
+            "b/play.validatetest",
+            "b/notes.txt",
+            ".git/hidden.scenario",
+            "target/copy.scenario",
+        ] {
+            fs::write(root.join(file), "play\n").unwrap();
+        }
+
+        let files = scan_folders(std::slice::from_ref(&root));
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                root.join("a/seek.scenario"),
+                root.join("b/c/seek.validatetest"),
+                root.join("b/play.validatetest"),
+            ]
+        );
+
+        let duplicates = duplicate_scenarios(&files[0], &files);
+        assert_eq!(duplicates.len(), 1);
+        assert!(duplicates[0].message.contains("b/c/seek.validatetest"));
+        assert!(duplicate_scenarios(&files[2], &files).is_empty());
+    }
+}
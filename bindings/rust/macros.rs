@@ -0,0 +1,125 @@
+//! The [`validatetest!`](crate::validatetest) macro.
+
+/// Build a [`Document`](crate::Document) with ValidateTest-like syntax.
+///
+/// Each structure is its name followed by its fields between braces, which
+/// are Rust expressions converted with
+/// [`FieldValue::from`](crate::document::FieldValue): strings are quoted,
+/// floats are doubles and arrays and `Vec`s are `[...]` arrays. Names may
+/// contain `-` and `::`. Other values, such as flags or variables, are
+/// built with [`FieldValue::scalar`](crate::document::FieldValue::scalar).
+///
+/// ```
+/// use tree_sitter_validatetest::document::{FieldValue, ScalarKind};
+/// use tree_sitter_validatetest::format::{format_ast, FormatterConfig};
+/// use tree_sitter_validatetest::validatetest;
+///
+/// let document = validatetest! {
+///     meta { handles-states=true, args=["fakesrc", "fakesink"] }
+///     seek { start=1.0, flags=FieldValue::scalar(ScalarKind::Flags, "flush") }
+///     stop {}
+/// };
+/// assert_eq!(
+///     format_ast(&document, &FormatterConfig::default())?,
+///     "meta, handles-states=true, args=[\"fakesrc\", \"fakesink\"]\n\
+///      seek, start=1.0, flags=flush\n\
+///      stop\n"
+/// );
+/// # Ok::<(), tree_sitter_validatetest::ParseError>(())
+/// ```
+#[macro_export]
+macro_rules! validatetest {
+    // Structures: name tokens are gathered until the braces of the fields
+    (@structures $document:ident [$($name:tt)+] { $($fields:tt)* } $($rest:tt)*) => {
+        #[allow(unused_mut)]
+        let mut structure = $crate::document::Structure::new(concat!($(stringify!($name)),+));
+        $crate::validatetest!(@fields structure [] $($fields)*);
+        $document.structures.push(structure);
+        $crate::validatetest!(@structures $document [] $($rest)*);
+    };
+    (@structures $document:ident [$($name:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::validatetest!(@structures $document [$($name)* $next] $($rest)*);
+    };
+    (@structures $document:ident []) => {};
+
+    // Fields: name tokens are gathered until `=`
+    (@fields $structure:ident [$($name:tt)+] = $value:expr $(, $($rest:tt)*)?) => {
+        $structure = $structure.with_field(concat!($(stringify!($name)),+), $value);
+        $($crate::validatetest!(@fields $structure [] $($rest)*);)?
+    };
+    (@fields $structure:ident [$($name:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::validatetest!(@fields $structure [$($name)* $next] $($rest)*);
+    };
+    (@fields $structure:ident []) => {};
+
+    ($($structures:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut document = $crate::Document::default();
+        $crate::validatetest!(@structures document [] $($structures)*);
+        document
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::{FieldValue, ScalarKind, Structure};
+    use crate::format::{format_ast, FormatterConfig};
+    use crate::Document;
+
+    #[test]
+    fn test_validatetest_macro() {
+        let start = 2;
+        let document = validatetest! {
+            meta { handles-states=true, seek=true, }
+            set-vars { count=start * 2, name="a \"b\"" }
+            check-properties { videosink::sync=false, src::caps="video/x-raw" }
+            seek {
+                start=0.5,
+                stop=FieldValue::typed("guint64", 5),
+                flags=FieldValue::scalar(ScalarKind::Flags, "flush+accurate"),
+            }
+            stop {}
+        };
+        let expected = Document::parse(
+            "meta, handles-states=true, seek=true\n\
+             set-vars, count=4, name=\"a \\\"b\\\"\"\n\
+             check-properties, videosink::sync=false, src::caps=\"video/x-raw\"\n\
+             seek, start=0.5, stop=(guint64)5, flags=flush+accurate\n\
+             stop\n",
+        )
+        .unwrap();
+        assert!(document.semantically_eq(&expected));
+        assert!(validatetest! {}.structures.is_empty());
+    }
+
+    #[test]
+    fn test_format_ast() {
+        let issue =
+            Structure::new("expected-issue").with_field("issue-id", "runtime::not-negotiated");
+        let document = validatetest! {
+            meta { expected-issues=[issue], values=vec![1.0, -2.0, 1e20] }
+            play {}
+        };
+        let formatted = format_ast(&document, &FormatterConfig::default()).unwrap();
+        assert_eq!(
+            formatted,
+            "meta, expected-issues=[expected-issue, issue-id=\"runtime::not-negotiated\"], values=[1.0, -2.0, 100000000000000000000.0]\nplay\n"
+        );
+        assert!(Document::parse(&formatted)
+            .unwrap()
+            .semantically_eq(&document));
+
+        let mut commented = document.clone();
+        commented.structures[0].leading_comments = vec!["Known issues".to_string()];
+        commented.structures[0].fields[0].trailing_comment = Some("Sorted".to_string());
+        commented.structures[0].fields[1].leading_comments = vec!["Doubles".to_string()];
+        assert_eq!(
+            format_ast(&commented, &FormatterConfig::default()).unwrap(),
+            "# Known issues\nmeta,\n    expected-issues=[expected-issue,\n        issue-id=\"runtime::not-negotiated\"],  # Sorted\n    # Doubles\n    values=[1.0, -2.0, 100000000000000000000.0]\nplay\n"
+        );
+
+        let invalid =
+            validatetest! { seek { start=FieldValue::scalar(ScalarKind::Number, "1 2") } };
+        assert!(format_ast(&invalid, &FormatterConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+//! Generation of scenarios from a template and a parameter matrix.
+//!
+//! A template is a scenario using `@name@` placeholders, and the matrix
+//! lists the values of each parameter, as TOML or JSON:
+//!
+//! ```toml
+//! # Optional name of the generated files, relative to the output directory
+//! output = "seek_@rate@_@start@.validatetest"
+//!
+//! [parameters]
+//! start = [0.0, 5.0]
+//! rate = [1.0, 2.0, -1.0]
+//! ```
+//!
+//! One scenario is generated per combination of values, formatted.
+//!
+//! Placeholders stand for field values, or parts of them. A value replacing
+//! a whole field value is quoted when it would not read back as a single
+//! value, and escaped inside strings; one changing the structure of the
+//! scenario otherwise is refused.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::document::{FieldValue, Scalar, ScalarKind, Structure};
+use crate::format::{format_source, FormatterConfig};
+use crate::strings::{escape, quote};
+use crate::{Document, ParseError};
+
+/// Parameters and their values, with the name of the generated files.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Matrix {
+    /// File name pattern, using the placeholders of the parameters.
+    pub output: Option<String>,
+    /// Values of each parameter, in name order.
+    pub parameters: BTreeMap<String, Vec<MatrixValue>>,
+}
+
+/// A value of a matrix parameter.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum MatrixValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for MatrixValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixValue::Bool(b) => write!(f, "{}", b),
+            MatrixValue::Int(i) => write!(f, "{}", i),
+            // Keep `1.0` a double for GstStructure
+            MatrixValue::Float(x) if x.fract() == 0.0 && x.is_finite() => write!(f, "{:.1}", x),
+            MatrixValue::Float(x) => write!(f, "{}", x),
+            MatrixValue::String(s) => f.write_str(s),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MatrixError {
+    Io(PathBuf, io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// The file extension is neither `.json` nor `.toml`.
+    UnknownFormat(PathBuf),
+    /// A parameter has no value, so there is no combination.
+    EmptyParameter(String),
+    /// A parameter the template never uses, likely misspelled.
+    UnusedParameter(String),
+    /// A placeholder outside of the field values, such as in an action name.
+    Placeholder(String),
+    /// The template does not parse, its placeholders taken as values.
+    Template(ParseError),
+    /// The value of a parameter would change the structure of the scenario
+    /// generated for a combination.
+    Value(String, String),
+    /// The scenario generated for a combination does not parse.
+    Parse(String, ParseError),
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            MatrixError::Toml(e) => write!(f, "invalid matrix: {}", e),
+            MatrixError::Json(e) => write!(f, "invalid matrix: {}", e),
+            MatrixError::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown matrix format, expected .json or .toml",
+                path.display()
+            ),
+            MatrixError::EmptyParameter(name) => write!(f, "parameter '{}' has no value", name),
+            MatrixError::UnusedParameter(name) => {
+                write!(f, "parameter '{}' is not used by the template", name)
+            }
+            MatrixError::Placeholder(name) => write!(
+                f,
+                "parameter '{}' is used outside of a field value of the template",
+                name
+            ),
+            MatrixError::Template(e) => write!(f, "invalid template: {}", e),
+            MatrixError::Value(file, name) => write!(
+                f,
+                "{}: the value of parameter '{}' changes the structure of the template",
+                file, name
+            ),
+            MatrixError::Parse(name, e) => write!(f, "{}: {}", name, e),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+/// Replace the `@name@` placeholders of `pattern` with `values`, leaving
+/// other `@` untouched.
+fn replace_placeholders(pattern: &str, values: &BTreeMap<String, String>) -> String {
+    let mut result = pattern.to_string();
+    for (name, value) in values {
+        result = result.replace(&format!("@{}@", name), value);
+    }
+    result
+}
+
+/// Byte ranges of the `@name@` placeholders of `source`, with their name.
+fn placeholders<'a>(source: &str, names: &[&'a str]) -> Vec<(Range<usize>, &'a str)> {
+    let mut placeholders = Vec::new();
+    let mut start = 0;
+    while let Some(at) = source[start..].find('@').map(|i| start + i) {
+        let rest = &source[at + 1..];
+        match names
+            .iter()
+            .find(|name| rest.starts_with(**name) && rest[name.len()..].starts_with('@'))
+        {
+            Some(name) => {
+                let end = at + name.len() + 2;
+                placeholders.push((at..end, *name));
+                start = end;
+            }
+            None => start = at + 1,
+        }
+    }
+    placeholders
+}
+
+/// Whether `text` reads back as a single field value, as written.
+fn is_single_value(text: &str) -> bool {
+    let Ok(document) = Document::parse(&format!("s, v={}\n", text)) else {
+        return false;
+    };
+    match document.structures.as_slice() {
+        [structure] => matches!(
+            structure.fields.as_slice(),
+            [field] if matches!(&field.value, FieldValue::Scalar(s) if s.text == text)
+        ),
+        _ => false,
+    }
+}
+
+fn structure_scalars<'a>(structure: &'a Structure, scalars: &mut Vec<&'a Scalar>) {
+    for field in &structure.fields {
+        value_scalars(&field.value, scalars);
+    }
+}
+
+fn value_scalars<'a>(value: &'a FieldValue, scalars: &mut Vec<&'a Scalar>) {
+    match value {
+        FieldValue::Scalar(scalar) => scalars.push(scalar),
+        FieldValue::Typed { value, .. } => value_scalars(value, scalars),
+        FieldValue::Array(values) | FieldValue::ValueArray(values) | FieldValue::Block(values) => {
+            for value in values {
+                value_scalars(value, scalars);
+            }
+        }
+        FieldValue::Structure(structure) => structure_scalars(structure, scalars),
+    }
+}
+
+/// A field value of a template holding placeholders.
+struct TemplateValue<'a> {
+    span: Range<usize>,
+    string: bool,
+    placeholders: Vec<(Range<usize>, &'a str)>,
+}
+
+/// A template, with the field values its placeholders are in.
+struct Template<'a> {
+    source: &'a str,
+    values: Vec<TemplateValue<'a>>,
+}
+
+impl<'a> Template<'a> {
+    /// Find the placeholders of `names` in `source`, parsed with each
+    /// placeholder replaced by a bare value of the same length.
+    fn parse(source: &'a str, names: &[&'a str]) -> Result<Self, MatrixError> {
+        let placeholders = placeholders(source, names);
+        if let Some(name) = names
+            .iter()
+            .find(|name| placeholders.iter().all(|(_, used)| used != *name))
+        {
+            return Err(MatrixError::UnusedParameter(name.to_string()));
+        }
+        let mut bare = source.to_string();
+        for (range, _) in &placeholders {
+            bare.replace_range(range.clone(), &"_".repeat(range.len()));
+        }
+        let document = Document::parse(&bare).map_err(MatrixError::Template)?;
+        let mut scalars = Vec::new();
+        for structure in &document.structures {
+            structure_scalars(structure, &mut scalars);
+        }
+
+        let mut values: Vec<TemplateValue> = Vec::new();
+        for (range, name) in placeholders {
+            let Some(scalar) = scalars
+                .iter()
+                .find(|s| s.span.start <= range.start && range.end <= s.span.end)
+            else {
+                return Err(MatrixError::Placeholder(name.to_string()));
+            };
+            match values.last_mut() {
+                Some(value) if value.span == scalar.span => value.placeholders.push((range, name)),
+                _ => values.push(TemplateValue {
+                    span: scalar.span.clone(),
+                    string: scalar.kind == ScalarKind::String,
+                    placeholders: vec![(range, name)],
+                }),
+            }
+        }
+        Ok(Self { source, values })
+    }
+
+    /// The template with its placeholders replaced with `values`, or the
+    /// name of a parameter whose value changes the structure.
+    fn instantiate(&self, values: &BTreeMap<String, String>) -> Result<String, String> {
+        let mut result = String::with_capacity(self.source.len());
+        let mut copied = 0;
+        for value in &self.values {
+            result.push_str(&self.source[copied..value.span.start]);
+            let mut text = String::new();
+            let mut start = value.span.start;
+            for (range, name) in &value.placeholders {
+                text.push_str(&self.source[start..range.start]);
+                if value.string {
+                    text.push_str(&escape(&values[*name]));
+                } else {
+                    text.push_str(&values[*name]);
+                }
+                start = range.end;
+            }
+            text.push_str(&self.source[start..value.span.end]);
+            if !value.string && !is_single_value(&text) {
+                // A whole value can be quoted, part of one cannot
+                match value.placeholders.as_slice() {
+                    [(range, name)] if *range == value.span => text = quote(&values[*name]),
+                    [(_, name), ..] => return Err(name.to_string()),
+                    [] => unreachable!("template values hold placeholders"),
+                }
+            }
+            result.push_str(&text);
+            copied = value.span.end;
+        }
+        result.push_str(&self.source[copied..]);
+        Ok(result)
+    }
+}
+
+/// Characters other than these are replaced in generated file names.
+fn file_name_char(c: char) -> char {
+    if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+') {
+        c
+    } else {
+        '-'
+    }
+}
+
+impl Matrix {
+    pub fn from_toml(source: &str) -> Result<Self, MatrixError> {
+        toml::from_str(source).map_err(MatrixError::Toml)
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, MatrixError> {
+        serde_json::from_str(source).map_err(MatrixError::Json)
+    }
+
+    /// Load a `.toml` or `.json` matrix.
+    pub fn load(path: &Path) -> Result<Self, MatrixError> {
+        let source =
+            fs::read_to_string(path).map_err(|e| MatrixError::Io(path.to_path_buf(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&source),
+            Some("json") => Self::from_json(&source),
+            _ => Err(MatrixError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Every combination of the parameter values, the last parameter
+    /// varying fastest.
+    pub fn combinations(&self) -> Vec<BTreeMap<String, String>> {
+        let mut combinations = vec![BTreeMap::new()];
+        for (name, values) in &self.parameters {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(name.clone(), value.to_string());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+
+    /// File name of the scenario generated for `values`: the `output`
+    /// pattern, or `stem` followed by the values.
+    fn file_name(&self, stem: &str, values: &BTreeMap<String, String>) -> String {
+        match &self.output {
+            Some(pattern) => {
+                let values = values
+                    .iter()
+                    .map(|(name, value)| {
+                        (name.clone(), value.chars().map(file_name_char).collect())
+                    })
+                    .collect();
+                replace_placeholders(pattern, &values)
+            }
+            None => {
+                let mut name = stem.to_string();
+                for value in values.values() {
+                    name.push('_');
+                    name.extend(value.chars().map(file_name_char));
+                }
+                name.push_str(".validatetest");
+                name
+            }
+        }
+    }
+
+    /// Generate one formatted scenario per combination of values, as file
+    /// names and contents. Without an `output` pattern, files are named
+    /// after `stem` and the values.
+    pub fn generate(
+        &self,
+        template: &str,
+        stem: &str,
+        config: &FormatterConfig,
+    ) -> Result<Vec<(String, String)>, MatrixError> {
+        if let Some((name, _)) = self.parameters.iter().find(|(_, v)| v.is_empty()) {
+            return Err(MatrixError::EmptyParameter(name.clone()));
+        }
+        let names: Vec<&str> = self.parameters.keys().map(String::as_str).collect();
+        let template = Template::parse(template, &names)?;
+        self.combinations()
+            .into_iter()
+            .map(|values| {
+                let name = self.file_name(stem, &values);
+                let source = template
+                    .instantiate(&values)
+                    .map_err(|parameter| MatrixError::Value(name.clone(), parameter))?;
+                let source = format_source(&source, config)
+                    .map_err(|e| MatrixError::Parse(name.clone(), e))?;
+                Ok((name, source))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str =
+        "meta, handles-states=true\nseek, start=@start@, rate=@rate@, flags=flush\n";
+
+    #[test]
+    fn test_generate() {
+        let matrix =
+            Matrix::from_toml("[parameters]\nstart = [0.0, 5.5]\nrate = [1, -1]\n").unwrap();
+        let generated = matrix
+            .generate(TEMPLATE, "seek", &FormatterConfig::default())
+            .unwrap();
+        let names: Vec<&str> = generated.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "seek_1_0.0.validatetest",
+                "seek_1_5.5.validatetest",
+                "seek_-1_0.0.validatetest",
+                "seek_-1_5.5.validatetest",
+            ]
+        );
+        assert_eq!(
+            generated[3].1,
+            "meta, handles-states=true\nseek, start=5.5, rate=-1, flags=flush\n"
+        );
+
+        let matrix = Matrix::from_json(
+            r#"{"output": "rate_@rate@.validatetest", "parameters": {"rate": [0.5], "start": ["$(position)"]}}"#,
+        )
+        .unwrap();
+        let generated = matrix
+            .generate(TEMPLATE, "seek", &FormatterConfig::default())
+            .unwrap();
+        assert_eq!(generated[0].0, "rate_0.5.validatetest");
+        assert!(generated[0].1.contains("seek, start=$(position), rate=0.5"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let matrix = Matrix::from_toml("[parameters]\nstrat = [0.0]\n").unwrap();
+        assert!(matches!(
+            matrix.generate(TEMPLATE, "seek", &FormatterConfig::default()),
+            Err(MatrixError::UnusedParameter(name)) if name == "strat"
+        ));
+        let matrix = Matrix::from_toml("[parameters]\nstart = [\"a b\"]\n").unwrap();
+        assert!(matches!(
+            matrix.generate("seek, start=@start@.0\n", "seek", &FormatterConfig::default()),
+            Err(MatrixError::Value(_, name)) if name == "start"
+        ));
+        assert!(matches!(
+            matrix.generate("@start@, rate=1.0\n", "seek", &FormatterConfig::default()),
+            Err(MatrixError::Placeholder(name)) if name == "start"
+        ));
+    }
+
+    #[test]
+    fn test_values_keep_the_structure() {
+        let matrix = Matrix::from_toml(
+            "[parameters]\nuri = [\"file:///a b.mp4\", \"x, y=1\"]\nrate = [\"{\"]\n",
+        )
+        .unwrap();
+        let template = "play, uri=@uri@, rate=@rate@, description=\"plays @uri@\"\n";
+        let generated = matrix
+            .generate(template, "play", &FormatterConfig::default())
+            .unwrap();
+        assert_eq!(
+            generated[0].1,
+            "play, uri=\"file:///a b.mp4\", rate=\"{\", description=\"plays file:///a b.mp4\"\n"
+        );
+        assert_eq!(
+            generated[1].1,
+            "play, uri=\"x, y=1\", rate=\"{\", description=\"plays x, y=1\"\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! `.media_info` files describing the media a scenario plays.
+//!
+//! `gst-validate-media-check-1.0` writes them as XML, with the duration of
+//! the file and its streams:
+//!
+//! ```xml
+//! <file duration="10000000000" frame-detection="0" uri="file:///clip.mp4" seekable="true">
+//!   <streams caps="video/quicktime">
+//!     <stream type="video" caps="video/x-h264, width=(int)320" id="0">
+//! ```
+//!
+//! Only what the lints check is read; the rest of the file is ignored.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::time::ClockTime;
+
+/// A stream of the media.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// `video`, `audio`, `subtitle`...
+    pub stream_type: String,
+    pub caps: String,
+}
+
+/// What a `.media_info` file tells about a media.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaInfo {
+    pub uri: Option<String>,
+    pub duration: Option<ClockTime>,
+    pub seekable: bool,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Debug)]
+pub enum MediaInfoError {
+    Io(PathBuf, io::Error),
+    /// The text holds no `<file>` element.
+    Invalid(PathBuf),
+}
+
+impl fmt::Display for MediaInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaInfoError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            MediaInfoError::Invalid(path) => {
+                write!(f, "{}: not a media info file", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MediaInfoError {}
+
+/// Start tags named `name` in `text`, as their attribute lists.
+fn elements<'a>(text: &'a str, name: &str) -> Vec<Vec<(&'a str, String)>> {
+    let open = format!("<{}", name);
+    text.match_indices(open.as_str())
+        .map(|(i, _)| &text[i + open.len()..])
+        .filter(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/'))
+        .map(attributes)
+        .collect()
+}
+
+/// The `name="value"` attributes at the start of `tag`, up to its `>`.
+fn attributes(mut tag: &str) -> Vec<(&str, String)> {
+    let mut attributes = Vec::new();
+    loop {
+        tag = tag.trim_start();
+        let Some((name, rest)) = tag.split_once('=') else {
+            break;
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(['>', '<']) {
+            break;
+        }
+        let rest = rest.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = rest[1..].find(quote) else {
+            break;
+        };
+        attributes.push((name, unescape_xml(&rest[1..end + 1])));
+        tag = &rest[end + 2..];
+    }
+    attributes
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn attribute<'a>(attributes: &'a [(&str, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, value)| value.as_str())
+}
+
+impl MediaInfo {
+    /// Read a media info file from `text`, `None` if it has no `<file>`
+    /// element.
+    pub fn parse(text: &str) -> Option<Self> {
+        let file = elements(text, "file").into_iter().next()?;
+        Some(Self {
+            uri: attribute(&file, "uri").map(str::to_string),
+            duration: attribute(&file, "duration")
+                .and_then(|d| d.parse().ok())
+                .map(ClockTime::from_nseconds),
+            seekable: attribute(&file, "seekable").is_some_and(|s| s == "true" || s == "1"),
+            streams: elements(text, "stream")
+                .iter()
+                .map(|stream| StreamInfo {
+                    stream_type: attribute(stream, "type").unwrap_or_default().to_string(),
+                    caps: attribute(stream, "caps").unwrap_or_default().to_string(),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, MediaInfoError> {
+        let text = fs::read_to_string(path).map_err(|e| MediaInfoError::Io(path.to_owned(), e))?;
+        Self::parse(&text).ok_or_else(|| MediaInfoError::Invalid(path.to_owned()))
+    }
+
+    /// Streams of the type `switch-track` names `track_type` (`audio`,
+    /// `video` or `text`).
+    pub fn tracks(&self, track_type: &str) -> impl Iterator<Item = &StreamInfo> + '_ {
+        let track_type = track_type.to_string();
+        self.streams.iter().filter(move |s| {
+            s.stream_type == track_type
+                || (track_type == "text" && matches!(s.stream_type.as_str(), "subtitle" | "text"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"<?xml version="1.0"?>
+<file duration="10000000000" frame-detection="0" uri="file:///a&amp;b.mp4" seekable="true">
+  <streams caps="video/quicktime">
+    <stream type="video" caps="video/x-h264, stream-format=(string)avc" id="1">
+      <frame duration="33333333"/>
+    </stream>
+    <stream type="audio" caps='audio/mpeg, mpegversion=(int)4' id="2"/>
+    <stream type="subtitle" caps="text/x-raw" id="3"/>
+  </streams>
+</file>
+"#;
+        let info = MediaInfo::parse(text).unwrap();
+        assert_eq!(info.uri.as_deref(), Some("file:///a&b.mp4"));
+        assert_eq!(
+            info.duration,
+            Some(ClockTime::from_nseconds(10_000_000_000))
+        );
+        assert!(info.seekable);
+        assert_eq!(info.streams.len(), 3);
+        assert_eq!(info.streams[1].caps, "audio/mpeg, mpegversion=(int)4");
+        assert_eq!(info.tracks("text").count(), 1);
+        assert_eq!(info.tracks("audio").count(), 1);
+        assert_eq!(MediaInfo::parse("meta, args={}"), None);
+    }
+}
@@ -0,0 +1,421 @@
+//! Merging of scenario fragments.
+//!
+//! Smoke tests are often composed out of shared fragments: one holding the
+//! `meta` boilerplate, others holding sequences of actions. [`merge_fragments`]
+//! combines them into a single document: the `meta` structures are merged
+//! into one placed at the top, and everything else is concatenated in order,
+//! comments included.
+
+use std::fmt;
+
+use tree_sitter::Node;
+
+use crate::document::{parse_tree, FieldValue};
+use crate::ParseError;
+
+/// Meta fields whose `{...}` blocks are merged as a union, keeping order.
+const UNION_FIELDS: &[&str] = &["configs"];
+
+/// Meta fields whose `{...}` blocks are command lines, merged as a whole:
+/// removing one element could separate an option from its value.
+const ARGV_FIELDS: &[&str] = &["args"];
+
+/// Meta fields whose `{...}` blocks are concatenated.
+const CONCAT_FIELDS: &[&str] = &["expected-issues"];
+
+/// A named piece of scenario source.
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment<'a> {
+    /// Name used in conflict reports, typically the file path.
+    pub name: &'a str,
+    pub source: &'a str,
+}
+
+/// Two fragments giving different values to the same meta field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub field: String,
+    /// Fragment whose value was kept.
+    pub kept: String,
+    /// Fragment whose value was dropped, or appended to the kept one for
+    /// command lines.
+    pub dropped: String,
+    /// Whether the value was appended rather than dropped.
+    pub appended: bool,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.appended {
+            write!(
+                f,
+                "meta field '{}' in {} differs from {}, appending its arguments",
+                self.field, self.dropped, self.kept
+            )
+        } else {
+            write!(
+                f,
+                "meta field '{}' in {} conflicts with {}, keeping the value from {}",
+                self.field, self.dropped, self.kept, self.kept
+            )
+        }
+    }
+}
+
+/// A fragment failed to parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeError {
+    pub fragment: String,
+    pub error: ParseError,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.fragment, self.error)
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// The result of [`merge_fragments`].
+#[derive(Debug, Clone)]
+pub struct Merged {
+    /// The merged document. It is valid but not formatted; run it through
+    /// [`format_source`](crate::format::format_source) for a canonical layout.
+    pub source: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+enum MetaValue {
+    /// A value taken verbatim from one fragment.
+    Single {
+        text: String,
+        value: Option<FieldValue>,
+        fragment: String,
+    },
+    /// The items of a `{...}` block merged from several fragments, the
+    /// first of which gave it.
+    Block(Vec<(String, Option<FieldValue>)>, String),
+}
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn is_meta(node: Node, source: &str) -> bool {
+    node.kind() == "structure" && node.child(0).is_some_and(|n| text(n, source) == "meta")
+}
+
+/// The non-comment items of a `{...}` block value, if `value` is one.
+fn block_items<'t>(value: Node<'t>) -> Option<Vec<Node<'t>>> {
+    let block = value
+        .named_child(0)
+        .filter(|n| n.kind() == "nested_structure_block")?;
+    let mut cursor = block.walk();
+    let items = block
+        .named_children(&mut cursor)
+        .filter(|c| c.kind() != "comment")
+        .collect();
+    Some(items)
+}
+
+/// Merge scenario fragments into one document.
+///
+/// Fields of all `meta` structures are merged into a single `meta`:
+/// `configs` blocks are unioned, `expected-issues` blocks are concatenated,
+/// and any other field must have the same value in every fragment defining
+/// it. On conflicts, the first value wins and a [`MergeConflict`] is
+/// reported.
+///
+/// `args` blocks are command lines: one repeating or extending the merged
+/// arguments only adds its new ones at the end, while other ones are
+/// appended whole and reported as conflicts.
+pub fn merge_fragments(fragments: &[Fragment]) -> Result<Merged, MergeError> {
+    let mut meta: Vec<(String, MetaValue)> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut bodies = Vec::new();
+
+    for fragment in fragments {
+        let source = fragment.source;
+        let tree = parse_tree(source).map_err(|error| MergeError {
+            fragment: fragment.name.to_string(),
+            error,
+        })?;
+        let root = tree.root_node();
+
+        let mut body = String::new();
+        let mut pos = 0;
+        let mut cursor = root.walk();
+        for structure in root.children(&mut cursor) {
+            if !is_meta(structure, source) {
+                continue;
+            }
+            body.push_str(&source[pos..structure.start_byte()]);
+            pos = structure.end_byte();
+            if source[pos..].starts_with('\n') {
+                pos += 1;
+            }
+            merge_meta(structure, fragment, &mut meta, &mut conflicts);
+        }
+        body.push_str(&source[pos..]);
+
+        let body = body.trim_matches('\n');
+        if !body.trim().is_empty() {
+            bodies.push(body.to_string());
+        }
+    }
+
+    let mut output = String::new();
+    if !meta.is_empty() {
+        output.push_str("meta");
+        for (name, value) in &meta {
+            output.push_str(",\n    ");
+            output.push_str(name);
+            output.push('=');
+            match value {
+                MetaValue::Single { text, .. } => output.push_str(text),
+                MetaValue::Block(items, _) => {
+                    let items: Vec<&str> = items.iter().map(|(t, _)| t.as_str()).collect();
+                    output.push('{');
+                    output.push_str(&items.join(", "));
+                    output.push('}');
+                }
+            }
+        }
+        output.push('\n');
+    }
+    for body in bodies {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&body);
+        output.push('\n');
+    }
+
+    Ok(Merged {
+        source: output,
+        conflicts,
+    })
+}
+
+fn merge_meta(
+    structure: Node,
+    fragment: &Fragment,
+    meta: &mut Vec<(String, MetaValue)>,
+    conflicts: &mut Vec<MergeConflict>,
+) {
+    let source = fragment.source;
+    let mut cursor = structure.walk();
+    let Some(field_list) = structure
+        .children(&mut cursor)
+        .find(|c| c.kind() == "field_list")
+    else {
+        return;
+    };
+
+    let mut cursor = field_list.walk();
+    for field in field_list.children(&mut cursor) {
+        let (Some(name), Some(value)) = (
+            field.child_by_field_name("name"),
+            field.child_by_field_name("value"),
+        ) else {
+            continue;
+        };
+        let name = text(name, source);
+        let existing = meta.iter_mut().find(|(n, _)| n == name).map(|(_, v)| v);
+
+        let is_list = UNION_FIELDS.contains(&name)
+            || CONCAT_FIELDS.contains(&name)
+            || ARGV_FIELDS.contains(&name);
+        match (existing, block_items(value).filter(|_| is_list)) {
+            (Some(MetaValue::Block(merged, first)), Some(items)) if ARGV_FIELDS.contains(&name) => {
+                let items: Vec<(String, Option<FieldValue>)> = items
+                    .into_iter()
+                    .map(|i| {
+                        (
+                            text(i, source).to_string(),
+                            FieldValue::from_node(i, source),
+                        )
+                    })
+                    .collect();
+                let same =
+                    |a: &(String, Option<FieldValue>), b: &(String, Option<FieldValue>)| match (
+                        &a.1, &b.1,
+                    ) {
+                        (Some(a), Some(b)) => a.semantically_eq(b),
+                        _ => a.0 == b.0,
+                    };
+                let common = merged
+                    .iter()
+                    .zip(&items)
+                    .take_while(|(a, b)| same(a, b))
+                    .count();
+                if common == items.len() {
+                    // Repeated arguments
+                } else if common == merged.len() {
+                    merged.extend(items.into_iter().skip(common));
+                } else {
+                    merged.extend(items);
+                    conflicts.push(MergeConflict {
+                        field: name.to_string(),
+                        kept: first.clone(),
+                        dropped: fragment.name.to_string(),
+                        appended: true,
+                    });
+                }
+            }
+            (Some(MetaValue::Block(merged, _)), Some(items)) => {
+                for item in items {
+                    let item_value = FieldValue::from_node(item, source);
+                    let duplicate = UNION_FIELDS.contains(&name)
+                        && merged.iter().any(|(_, v)| match (v, &item_value) {
+                            (Some(a), Some(b)) => a.semantically_eq(b),
+                            _ => false,
+                        });
+                    if !duplicate {
+                        merged.push((text(item, source).to_string(), item_value));
+                    }
+                }
+            }
+            (None, Some(items)) => {
+                let items = items
+                    .into_iter()
+                    .map(|i| {
+                        (
+                            text(i, source).to_string(),
+                            FieldValue::from_node(i, source),
+                        )
+                    })
+                    .collect();
+                meta.push((
+                    name.to_string(),
+                    MetaValue::Block(items, fragment.name.to_string()),
+                ));
+            }
+            (None, None) => meta.push((
+                name.to_string(),
+                MetaValue::Single {
+                    text: text(value, source).to_string(),
+                    value: FieldValue::from_node(value, source),
+                    fragment: fragment.name.to_string(),
+                },
+            )),
+            (Some(existing), _) => {
+                let new_value = FieldValue::from_node(value, source);
+                let same = match existing {
+                    MetaValue::Single {
+                        value: Some(old), ..
+                    } => new_value.is_some_and(|new| old.semantically_eq(&new)),
+                    _ => false,
+                };
+                if !same {
+                    let kept = match existing {
+                        MetaValue::Single { fragment, .. } => fragment.clone(),
+                        MetaValue::Block(_, fragment) => fragment.clone(),
+                    };
+                    conflicts.push(MergeConflict {
+                        field: name.to_string(),
+                        kept,
+                        dropped: fragment.name.to_string(),
+                        appended: false,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(fragments: &[(&'static str, &'static str)]) -> Merged {
+        let fragments: Vec<Fragment> = fragments
+            .iter()
+            .map(|(name, source)| Fragment { name, source })
+            .collect();
+        merge_fragments(&fragments).unwrap()
+    }
+
+    #[test]
+    fn test_merge_meta_and_actions() {
+        let merged = merge(&[
+            (
+                "base",
+                "# Base pipeline\nmeta, handles-states=true, args={\"videotestsrc ! fakesink\"}\n",
+            ),
+            (
+                "seek",
+                "meta, handles-states=true,\n    args={\"videotestsrc ! fakesink\", --sync},\n    \
+                 expected-issues={[expected-issue, issue-id=a]}\n\
+                 # Seek forward\nseek, start=1.0\n",
+            ),
+            (
+                "stop",
+                "meta, expected-issues={[expected-issue, issue-id=b]}\nstop\n",
+            ),
+        ]);
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(
+            merged.source,
+            "meta,\n    handles-states=true,\n    args={\"videotestsrc ! fakesink\", --sync},\n    \
+             expected-issues={[expected-issue, issue-id=a], [expected-issue, issue-id=b]}\n\
+             \n# Base pipeline\n\n# Seek forward\nseek, start=1.0\n\nstop\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_conflicts() {
+        let merged = merge(&[
+            ("a.validatetest", "meta, seek=true\nplay\n"),
+            ("b.validatetest", "meta, seek=false\nstop\n"),
+        ]);
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                field: "seek".to_string(),
+                kept: "a.validatetest".to_string(),
+                dropped: "b.validatetest".to_string(),
+                appended: false,
+            }]
+        );
+        assert_eq!(merged.source, "meta,\n    seek=true\n\nplay\n\nstop\n");
+    }
+
+    #[test]
+    fn test_merge_args() {
+        // Options keep their values
+        let merged = merge(&[
+            ("video", "meta, args={--videosink, fakesink}\n"),
+            ("audio", "meta, args={--audiosink, fakesink}\n"),
+            ("again", "meta, args={--videosink, \"fakesink\"}\n"),
+        ]);
+        assert_eq!(
+            merged.source,
+            "meta,\n    args={--videosink, fakesink, --audiosink, fakesink}\n"
+        );
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                field: "args".to_string(),
+                kept: "video".to_string(),
+                dropped: "audio".to_string(),
+                appended: true,
+            }]
+        );
+        assert_eq!(
+            merged.conflicts[0].to_string(),
+            "meta field 'args' in audio differs from video, appending its arguments"
+        );
+    }
+
+    #[test]
+    fn test_merge_parse_error() {
+        let err = merge_fragments(&[Fragment {
+            name: "broken",
+            source: "meta, a=[",
+        }])
+        .unwrap_err();
+        assert_eq!(err.fragment, "broken");
+    }
+}
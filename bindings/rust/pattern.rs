@@ -0,0 +1,256 @@
+//! Structural patterns: partial structures such as `seek, rate=2.0`,
+//! matching every `seek` with a `rate` of 2.0, whatever its other fields
+//! and however it is written (`rate=(double)2.0`, on several lines, with
+//! comments...). Values compare as GstStructure deserializes them, so
+//! `rate="2.0"`, a string, does not match.
+//!
+//! Structures nested in arrays and blocks, and serialized in strings, are
+//! matched too.
+//!
+//! A value written as an uppercase word, such as `X` or `NEW_NAME`, is a
+//! metavariable: it matches any value, the same one wherever it appears in
+//! the pattern. A [`Template`] uses them to write what a structure is
+//! rewritten to: `set-property, property-name=X, property-value=Y`
+//! rewritten to `set-properties, X=Y`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::document::{Document, Field, FieldValue, Structure};
+use crate::lint::visit_structures;
+use crate::position::{offset_to_line_col, ColumnEncoding, LineCol};
+use crate::ParseError;
+
+/// A structure name and the fields a structure needs to match.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    structure: Structure,
+}
+
+impl FromStr for Pattern {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            structure: text.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.structure.fmt(f)
+    }
+}
+
+/// Whether `text` is a metavariable: an uppercase letter, then uppercase
+/// letters, digits and underscores.
+pub fn is_metavariable(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// The metavariable `value` is, if any.
+pub(crate) fn metavariable(value: &FieldValue) -> Option<&str> {
+    match value {
+        FieldValue::Scalar(scalar) if is_metavariable(&scalar.text) => Some(&scalar.text),
+        _ => None,
+    }
+}
+
+/// What a structure matching a [`Pattern`] holds.
+#[derive(Debug, Clone)]
+pub struct Captures<'a> {
+    /// Its fields named by the pattern.
+    pub fields: Vec<&'a Field>,
+    /// The field holding the value of each metavariable.
+    pub variables: BTreeMap<String, &'a Field>,
+}
+
+impl Pattern {
+    /// Whether `structure` has the name of the pattern and all its fields,
+    /// compared ignoring formatting and the quotes of strings.
+    pub fn matches(&self, structure: &Structure) -> bool {
+        self.captures(structure).is_some()
+    }
+
+    /// The fields of `structure` matching the pattern, and the values of
+    /// its metavariables. When a field is set twice, the last value counts,
+    /// as in GStreamer.
+    pub fn captures<'a>(&self, structure: &'a Structure) -> Option<Captures<'a>> {
+        if structure.name != self.structure.name {
+            return None;
+        }
+        let mut captures = Captures {
+            fields: Vec::new(),
+            variables: BTreeMap::new(),
+        };
+        for wanted in &self.structure.fields {
+            let field = structure.fields.iter().rfind(|f| f.name == wanted.name)?;
+            match metavariable(&wanted.value) {
+                Some(variable) => match captures.variables.get(variable) {
+                    Some(bound) if !bound.value.semantically_eq(&field.value) => return None,
+                    Some(_) => {}
+                    None => {
+                        captures.variables.insert(variable.to_string(), field);
+                    }
+                },
+                None if !field.value.semantically_eq(&wanted.value) => return None,
+                None => {}
+            }
+            captures.fields.push(field);
+        }
+        Some(captures)
+    }
+
+    fn variables(&self) -> impl Iterator<Item = &str> {
+        self.structure
+            .fields
+            .iter()
+            .filter_map(|f| metavariable(&f.value))
+    }
+}
+
+/// What structures matching a [`Pattern`] are rewritten to: a structure
+/// whose field names and values may be metavariables of the pattern, such
+/// as `set-properties, X=Y`.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub(crate) structure: Structure,
+    /// The template as written, for the text of its values.
+    source: String,
+}
+
+impl FromStr for Template {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            structure: text.parse()?,
+            source: text.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.structure.fmt(f)
+    }
+}
+
+impl Template {
+    /// The first metavariable of the template `pattern` does not bind.
+    pub fn unbound_variable(&self, pattern: &Pattern) -> Option<&str> {
+        self.structure
+            .fields
+            .iter()
+            .flat_map(|f| [Some(f.name.as_str()), metavariable(&f.value)])
+            .flatten()
+            .filter(|name| is_metavariable(name))
+            .find(|name| !pattern.variables().any(|v| v == *name))
+    }
+
+    /// The value of `field`, one of the template's, as written.
+    pub(crate) fn value_text(&self, field: &Field) -> &str {
+        let text = &self.source[field.span.clone()];
+        text.split_once('=').map_or(text, |(_, value)| value.trim())
+    }
+}
+
+/// A structure matched by a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Byte range of the structure, or of the string holding it when it
+    /// is serialized in one.
+    pub range: Range<usize>,
+    /// 0-based start and end of the range, columns in bytes.
+    pub start: LineCol,
+    pub end: LineCol,
+}
+
+/// The matches of `pattern` in `source`, in source order.
+pub fn find_matches(pattern: &Pattern, source: &str) -> Result<Vec<Match>, ParseError> {
+    let document = Document::parse(source)?;
+    let mut matches: Vec<Match> = Vec::new();
+    for structure in &document.structures {
+        visit_structures(structure, None, &mut |s, quoted_span| {
+            if !pattern.matches(s) {
+                return;
+            }
+            let range = quoted_span.unwrap_or(&s.span).clone();
+            // Several structures serialized in the same string are one match
+            if matches.last().is_some_and(|m| m.range == range) {
+                return;
+            }
+            let position = |offset| offset_to_line_col(source, offset, ColumnEncoding::Utf8);
+            matches.push(Match {
+                start: position(range.start),
+                end: position(range.end),
+                range,
+            });
+        });
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches() {
+        let pattern: Pattern = "seek, rate=2.0".parse().unwrap();
+        let source = "\
+play
+seek, start=1.0, rate=2.0, flags=flush
+seek, rate=-1.0, flags=flush
+seek,
+    # Fast forward
+    rate=(double)2.0,
+    flags=flush
+meta, checks={\"seek, rate=2.0\", [seek, rate=\"2.0\"]}
+";
+        let matches = find_matches(&pattern, source).unwrap();
+        let found: Vec<_> = matches.iter().map(|m| (m.start.line, m.end.line)).collect();
+        assert_eq!(found, vec![(1, 1), (3, 6), (7, 7)]);
+        assert_eq!(
+            &source[matches[0].range.clone()],
+            "seek, start=1.0, rate=2.0, flags=flush"
+        );
+        // The string holding the structure, not the one with a quoted rate
+        assert_eq!(&source[matches[2].range.clone()], "\"seek, rate=2.0\"");
+
+        let pattern: Pattern = "play".parse().unwrap();
+        assert_eq!(find_matches(&pattern, source).unwrap().len(), 1);
+        let pattern: Pattern = "seek, rate=X, flags=flush".parse().unwrap();
+        assert_eq!(find_matches(&pattern, source).unwrap().len(), 3);
+        let pattern: Pattern = "seek, start=X, stop=X".parse().unwrap();
+        assert_eq!(
+            find_matches(
+                &pattern,
+                "seek, start=1.0, stop=2.0\nseek, start=1.0, stop=1.0\n"
+            )
+            .unwrap()
+            .iter()
+            .map(|m| m.start.line)
+            .collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert!("seek, rate=2.0\nplay".parse::<Pattern>().is_err());
+        assert!(find_matches(&pattern, "seek, rate=\n").is_err());
+    }
+
+    #[test]
+    fn test_unbound_variable() {
+        let pattern: Pattern = "set-property, property-name=X, property-value=Y"
+            .parse()
+            .unwrap();
+        let template: Template = "set-properties, X=Y".parse().unwrap();
+        assert_eq!(template.unbound_variable(&pattern), None);
+        let template: Template = "set-properties, X=Z".parse().unwrap();
+        assert_eq!(template.unbound_variable(&pattern), Some("Z"));
+    }
+}
@@ -0,0 +1,345 @@
+//! Checking of the pipeline descriptions given in `meta` `args`.
+//!
+//! [`parse_launch`] approximates the syntax `gst_parse_launch` accepts
+//! (`videotestsrc num-buffers=1 ! video/x-raw,width=320 ! fakesink`), without
+//! linking GStreamer: it finds the element factories a description uses and
+//! the first syntax error it holds. [`installed_elements`] lists the element
+//! factories of the installed GStreamer, to report unknown ones.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::process::Command;
+
+/// An element factory used by a pipeline description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementRef {
+    pub factory: String,
+    /// Byte range of the name in the description.
+    pub range: Range<usize>,
+}
+
+/// A syntax error of a pipeline description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineError {
+    pub message: String,
+    /// Byte range of the offending text in the description.
+    pub range: Range<usize>,
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Link,
+    Open,
+    Close,
+    Element,
+    Property,
+    Caps,
+    /// `name.` or `name.pad`, a reference to a named element.
+    Reference,
+    /// A `file://` or other URI, creating its source or sink element.
+    Uri,
+    /// `-t` and other command line options of gst-validate.
+    Option,
+}
+
+/// Split `description` into words, `!` and parentheses, keeping quoted
+/// text and `$(variables)` within their word.
+fn tokenize(description: &str) -> Result<Vec<Range<usize>>, PipelineError> {
+    let mut tokens = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut chars = description.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(s) = start.take() {
+                    tokens.push(s..i);
+                }
+            }
+            '!' | '(' | ')' => {
+                if let Some(s) = start.take() {
+                    tokens.push(s..i);
+                }
+                tokens.push(i..i + 1);
+            }
+            _ => {
+                start.get_or_insert(i);
+                let closing = match c {
+                    '"' | '\'' => c,
+                    '$' if chars.peek().is_some_and(|(_, c)| *c == '(') => ')',
+                    '\\' => {
+                        chars.next();
+                        continue;
+                    }
+                    _ => continue,
+                };
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) if closing != ')' => {
+                            chars.next();
+                        }
+                        Some((_, c)) if c == closing => break,
+                        Some(_) => {}
+                        None => {
+                            return Err(PipelineError {
+                                message: "unterminated quoted text".to_string(),
+                                range: i..description.len(),
+                            })
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(s..description.len());
+    }
+    Ok(tokens)
+}
+
+fn classify(word: &str) -> Token {
+    let word = word.trim_matches('"');
+    let key = word.split('=').next().unwrap_or_default();
+    match word {
+        "!" => Token::Link,
+        "(" => Token::Open,
+        ")" => Token::Close,
+        _ if word.starts_with('-') => Token::Option,
+        // Caps such as `video/x-raw,width=320`
+        _ if key.contains('/') && !word.contains("://") => Token::Caps,
+        _ if word.contains('=') => Token::Property,
+        _ if word.contains("://") => Token::Uri,
+        _ if word.contains('/') => Token::Caps,
+        _ if word.contains('.') => Token::Reference,
+        _ => Token::Element,
+    }
+}
+
+/// Whether `name` can be an element factory name.
+fn is_factory_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+}
+
+/// Parse the pipeline `description`, returning the element factories it
+/// uses, or its first syntax error.
+///
+/// Words starting with `-` are taken as gst-validate options and skipped.
+/// Names holding `$(variables)` are substituted at run time and not
+/// returned.
+pub fn parse_launch(description: &str) -> Result<Vec<ElementRef>, PipelineError> {
+    let error = |message: String, range: Range<usize>| Err(PipelineError { message, range });
+    let mut elements = Vec::new();
+    let mut previous: Option<(Token, Range<usize>)> = None;
+    let mut bins: Vec<Range<usize>> = Vec::new();
+    let mut has_element = false;
+
+    for range in tokenize(description)? {
+        let word = &description[range.clone()];
+        let token = classify(word);
+        let last = previous.as_ref().map(|(t, _)| *t);
+        if token == Token::Option {
+            continue;
+        }
+        if let Some((Token::Caps, caps)) = &previous {
+            if token != Token::Link {
+                let caps = caps.clone();
+                return error(
+                    format!(
+                        "caps '{}' are not linked to an element",
+                        &description[caps.clone()]
+                    ),
+                    caps,
+                );
+            }
+        }
+        match token {
+            Token::Link => {
+                if !matches!(
+                    last,
+                    Some(
+                        Token::Element
+                            | Token::Property
+                            | Token::Caps
+                            | Token::Reference
+                            | Token::Uri
+                            | Token::Close
+                    )
+                ) {
+                    return error("'!' without an element to link from".to_string(), range);
+                }
+            }
+            Token::Property => {
+                if !matches!(last, Some(Token::Element | Token::Property | Token::Uri)) {
+                    return error(
+                        format!("property '{}' does not follow an element", word),
+                        range,
+                    );
+                }
+                if word.starts_with('=') {
+                    return error(format!("property '{}' has no name", word), range);
+                }
+            }
+            Token::Caps => {
+                if last != Some(Token::Link) {
+                    return error(
+                        format!("caps '{}' are not linked to an element", word),
+                        range,
+                    );
+                }
+            }
+            Token::Open => bins.push(range.clone()),
+            Token::Close => {
+                if bins.pop().is_none() {
+                    return error("')' without a matching '('".to_string(), range);
+                }
+            }
+            Token::Element | Token::Uri | Token::Reference => {
+                has_element = true;
+                if token == Token::Element && !word.contains("$(") {
+                    if !is_factory_name(word) {
+                        return error(format!("'{}' is not a valid element name", word), range);
+                    }
+                    elements.push(ElementRef {
+                        factory: word.to_string(),
+                        range: range.clone(),
+                    });
+                }
+            }
+            Token::Option => unreachable!(),
+        }
+        previous = Some((token, range));
+    }
+
+    match previous {
+        Some((Token::Link, range)) => error("'!' without an element to link to".to_string(), range),
+        Some((Token::Caps, range)) => error(
+            format!(
+                "caps '{}' are not linked to an element",
+                &description[range.clone()]
+            ),
+            range,
+        ),
+        _ if !bins.is_empty() => error(
+            "'(' without a matching ')'".to_string(),
+            bins.pop().unwrap(),
+        ),
+        _ if !has_element => error(
+            "no element in the pipeline".to_string(),
+            0..description.len(),
+        ),
+        _ => Ok(elements),
+    }
+}
+
+/// Names of the element factories of the installed GStreamer, as listed by
+/// `gst-inspect-1.0`.
+pub fn installed_elements() -> io::Result<BTreeSet<String>> {
+    let output = Command::new("gst-inspect-1.0").output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(parse_inspect_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Factory names of `gst-inspect-1.0` output, whose lines are
+/// `plugin:  factory: description`. Type finders are listed the same way
+/// and end up included, which only makes the check more lenient.
+fn parse_inspect_output(output: &str) -> BTreeSet<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let (_plugin, factory, _description) = (parts.next()?, parts.next()?, parts.next()?);
+            let factory = factory.trim();
+            is_factory_name(factory).then(|| factory.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factories(description: &str) -> Vec<String> {
+        parse_launch(description)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.factory)
+            .collect()
+    }
+
+    fn error(description: &str) -> String {
+        parse_launch(description).unwrap_err().message
+    }
+
+    #[test]
+    fn test_parse_launch() {
+        assert_eq!(
+            factories("videotestsrc num-buffers=1 ! video/x-raw,width=320 ! tee name=t t. ! queue ! fakesink t.src_1 ! fakesink"),
+            vec!["videotestsrc", "tee", "queue", "fakesink", "fakesink"]
+        );
+        assert_eq!(
+            factories("-t playbin3 uri=\"file:///a b.mp4\" video-sink=\"$(videosink) sync=false\""),
+            vec!["playbin3"]
+        );
+        assert_eq!(
+            factories("( audiotestsrc ! $(audiosink) ) file:///media/a.mp4 ! decodebin"),
+            vec!["audiotestsrc", "decodebin"]
+        );
+    }
+
+    #[test]
+    fn test_parse_launch_errors() {
+        assert_eq!(
+            error("videotestsrc ! ! fakesink"),
+            "'!' without an element to link from"
+        );
+        assert_eq!(error("videotestsrc !"), "'!' without an element to link to");
+        assert_eq!(
+            error("num-buffers=1 videotestsrc"),
+            "property 'num-buffers=1' does not follow an element"
+        );
+        assert_eq!(
+            error("( videotestsrc ! fakesink"),
+            "'(' without a matching ')'"
+        );
+        assert_eq!(
+            error("videotestsrc pattern=\"ball"),
+            "unterminated quoted text"
+        );
+        assert_eq!(
+            error("videotestsrc ! video/x-raw fakesink"),
+            "caps 'video/x-raw' are not linked to an element"
+        );
+        assert_eq!(error(""), "no element in the pipeline");
+
+        let err = parse_launch("videotestsrc ! fake*sink").unwrap_err();
+        assert_eq!(err.message, "'fake*sink' is not a valid element name");
+        assert_eq!(err.range, 15..24);
+    }
+
+    #[test]
+    fn test_parse_inspect_output() {
+        let output = "coreelements:  fakesink: Fake Sink\nvideotestsrc:  videotestsrc: Video test source\n\nTotal count: 2 plugins, 3 features\n";
+        assert_eq!(
+            parse_inspect_output(output),
+            BTreeSet::from(["fakesink".to_string(), "videotestsrc".to_string()])
+        );
+    }
+}
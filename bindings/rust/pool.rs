@@ -0,0 +1,174 @@
+//! Reusable parsers, for parsing many files concurrently.
+//!
+//! A tree-sitter [`Parser`] parses one file at a time and costs an
+//! allocation and the loading of the grammar to create. A [`ParserPool`]
+//! keeps the parsers it creates: a thread takes one for as long as it
+//! parses, and hands it back for the next file when the [`PooledParser`] is
+//! dropped. The pool never holds more parsers than there were threads
+//! parsing at once.
+//!
+//! The parsing functions of this crate, such as [`Document::parse`], use
+//! [`ParserPool::global`], so batch jobs running them on several threads
+//! already reuse parsers. Embedders parsing trees themselves can share that
+//! pool or create their own.
+//!
+//! ```
+//! use tree_sitter_validatetest::pool::ParserPool;
+//! use tree_sitter_validatetest::Document;
+//!
+//! let pool = ParserPool::new();
+//! let sources = ["play\n", "seek, start=1.0\n"];
+//! std::thread::scope(|scope| {
+//!     for source in sources {
+//!         let pool = &pool;
+//!         scope.spawn(move || {
+//!             let tree = pool.parse(source);
+//!             Document::from_node(tree.root_node(), source)
+//!         });
+//!     }
+//! });
+//! ```
+//!
+//! [`Document::parse`]: crate::Document::parse
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use tree_sitter::{Parser, Tree};
+
+use crate::LANGUAGE;
+
+/// Parsers set up for ValidateTest files, shared between threads.
+#[derive(Default)]
+pub struct ParserPool {
+    parsers: Mutex<Vec<Parser>>,
+}
+
+impl std::fmt::Debug for ParserPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParserPool")
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// A parser taken from a [`ParserPool`], returned to it when dropped.
+pub struct PooledParser<'p> {
+    pool: &'p ParserPool,
+    parser: Option<Parser>,
+}
+
+impl ParserPool {
+    /// An empty pool, creating parsers as they are needed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pool used by the parsing functions of this crate.
+    pub fn global() -> &'static ParserPool {
+        static POOL: OnceLock<ParserPool> = OnceLock::new();
+        POOL.get_or_init(ParserPool::new)
+    }
+
+    /// Take a parser out of the pool, or create one if all are in use.
+    pub fn get(&self) -> PooledParser<'_> {
+        let idle = self.lock().pop();
+        #[cfg(feature = "tracing")]
+        if idle.is_some() {
+            tracing::trace!("reusing an idle parser");
+        }
+        let parser = idle.unwrap_or_else(|| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("creating a parser");
+            let mut parser = Parser::new();
+            parser
+                .set_language(&LANGUAGE.into())
+                .expect("Error loading ValidateTest parser");
+            parser
+        });
+        PooledParser {
+            pool: self,
+            parser: Some(parser),
+        }
+    }
+
+    /// Parse `source` into a tree, keeping error nodes.
+    pub fn parse(&self, source: &str) -> Tree {
+        self.reparse(source, None)
+    }
+
+    /// Parse `source`, reusing the unchanged parts of `old_tree` once it has
+    /// been updated with [`Tree::edit`].
+    pub fn reparse(&self, source: &str, old_tree: Option<&Tree>) -> Tree {
+        self.get()
+            .parse(source, old_tree)
+            .expect("Parser has a language")
+    }
+
+    /// Number of parsers waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Parser>> {
+        // The parsers stay valid if a thread panicked while holding the lock
+        self.parsers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Deref for PooledParser<'_> {
+    type Target = Parser;
+
+    fn deref(&self) -> &Parser {
+        self.parser.as_ref().expect("parser taken on drop only")
+    }
+}
+
+impl DerefMut for PooledParser<'_> {
+    fn deref_mut(&mut self) -> &mut Parser {
+        self.parser.as_mut().expect("parser taken on drop only")
+    }
+}
+
+impl Drop for PooledParser<'_> {
+    fn drop(&mut self) {
+        if let Some(parser) = self.parser.take() {
+            self.pool.lock().push(parser);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parser_pool() {
+        let pool = ParserPool::new();
+        {
+            let first = pool.get();
+            let _second = pool.get();
+            assert_eq!(pool.idle(), 0);
+            drop(first);
+            assert_eq!(pool.idle(), 1);
+        }
+        assert_eq!(pool.idle(), 2);
+
+        let tree = pool.parse("seek, start=1.0\n");
+        assert!(!tree.root_node().has_error());
+        assert_eq!(pool.idle(), 2);
+
+        // Shared between threads, each parsing with its own parser
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let tree = pool.parse("play\nstop\n");
+                        assert_eq!(tree.root_node().named_child_count(), 2);
+                    }
+                });
+            }
+        });
+        assert!((2..=4).contains(&pool.idle()));
+    }
+}
@@ -0,0 +1,163 @@
+//! Conversions between byte offsets and line/column positions.
+//!
+//! The library works with byte offsets into UTF-8 text. Editors count
+//! columns differently: LSP clients in UTF-16 code units by default, others
+//! in bytes or in characters. Lines are 0-based and end at `\n`; a `\r`
+//! before it counts as the last column of its line.
+
+/// The unit columns are counted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnEncoding {
+    /// Bytes of UTF-8, like tree-sitter points.
+    Utf8,
+    /// UTF-16 code units, the LSP default: characters outside the Basic
+    /// Multilingual Plane, such as emoji, take two.
+    #[default]
+    Utf16,
+    /// Unicode scalar values, one per `char`.
+    Utf32,
+}
+
+impl ColumnEncoding {
+    /// Length of `text` in this unit.
+    pub fn len(self, text: &str) -> usize {
+        match self {
+            ColumnEncoding::Utf8 => text.len(),
+            ColumnEncoding::Utf16 => text.encode_utf16().count(),
+            ColumnEncoding::Utf32 => text.chars().count(),
+        }
+    }
+
+    fn char_len(self, c: char) -> usize {
+        match self {
+            ColumnEncoding::Utf8 => c.len_utf8(),
+            ColumnEncoding::Utf16 => c.len_utf16(),
+            ColumnEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// A 0-based line and column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineCol {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Position of the byte `offset` of `text`, clamped to its length.
+///
+/// An offset inside a multi-byte character is moved back to its start.
+pub fn offset_to_line_col(text: &str, offset: usize, encoding: ColumnEncoding) -> LineCol {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    LineCol {
+        line: before.matches('\n').count(),
+        column: encoding.len(&before[line_start..]),
+    }
+}
+
+/// Byte offset of `position` in `text`.
+///
+/// Columns past the end of their line are clamped to it, lines past the
+/// end of the text to its length. A column inside a character, such as
+/// the second UTF-16 unit of an emoji, maps to the end of that character.
+pub fn line_col_to_offset(text: &str, position: LineCol, encoding: ColumnEncoding) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |i| line_start + i);
+
+    let mut units = 0;
+    for (i, c) in text[line_start..line_end].char_indices() {
+        if units >= position.column {
+            return line_start + i;
+        }
+        units += encoding.char_len(c);
+    }
+    line_end
+}
+
+/// Convert a `column` of `line` of `text` from one encoding to another.
+pub fn convert_column(
+    text: &str,
+    position: LineCol,
+    from: ColumnEncoding,
+    to: ColumnEncoding,
+) -> LineCol {
+    offset_to_line_col(text, line_col_to_offset(text, position, from), to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let text = "meta, summary=\"é😀\", a=1\r\nplay\n";
+        let offset = text.find(", a").unwrap();
+        for (encoding, column) in [
+            (ColumnEncoding::Utf8, 22),
+            (ColumnEncoding::Utf16, 19),
+            (ColumnEncoding::Utf32, 18),
+        ] {
+            let position = offset_to_line_col(text, offset, encoding);
+            assert_eq!(position, LineCol::new(0, column), "{:?}", encoding);
+            assert_eq!(line_col_to_offset(text, position, encoding), offset);
+        }
+        assert_eq!(
+            convert_column(
+                text,
+                LineCol::new(0, 22),
+                ColumnEncoding::Utf8,
+                ColumnEncoding::Utf16
+            ),
+            LineCol::new(0, 19)
+        );
+
+        // Inside the emoji
+        let emoji = text.find('😀').unwrap();
+        assert_eq!(
+            offset_to_line_col(text, emoji + 2, ColumnEncoding::Utf16),
+            LineCol::new(0, 16)
+        );
+        assert_eq!(
+            line_col_to_offset(text, LineCol::new(0, 17), ColumnEncoding::Utf16),
+            emoji + 4
+        );
+
+        // Clamping, and the `\r` ending the first line
+        let play = text.find("play").unwrap();
+        assert_eq!(
+            line_col_to_offset(text, LineCol::new(0, 99), ColumnEncoding::Utf16),
+            play - 1
+        );
+        assert_eq!(
+            line_col_to_offset(text, LineCol::new(1, 2), ColumnEncoding::Utf8),
+            play + 2
+        );
+        assert_eq!(
+            line_col_to_offset(text, LineCol::new(9, 0), ColumnEncoding::Utf8),
+            text.len()
+        );
+        assert_eq!(
+            offset_to_line_col(text, 999, ColumnEncoding::Utf8),
+            LineCol::new(2, 0)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Tree-sitter queries over ValidateTest sources, to extract what test
+//! suites do, such as every seek rate or every expected issue-id:
+//!
+//! ```scheme
+//! (structure
+//!   (structure_name) @action (#eq? @action "seek")
+//!   (field_list (field (field_name) @field (#eq? @field "rate") (_) @rate)))
+//! ```
+//!
+//! See the `node-types.json` of the grammar, or the trees printed by
+//! `validatetest-fmt --dump-cst`, for the node kinds to query.
+
+use std::ops::Range;
+
+use tree_sitter::{Query, QueryCursor, QueryError, StreamingIterator};
+
+use crate::document::parse_tree_with_errors;
+use crate::position::LineCol;
+use crate::LANGUAGE;
+
+/// A node captured by a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    /// Index of the pattern of the query that matched.
+    pub pattern: usize,
+    /// Name of the capture, without its `@`.
+    pub name: String,
+    pub text: String,
+    /// Byte range of the node.
+    pub range: Range<usize>,
+    /// 0-based start and end of the node, columns in bytes.
+    pub start: LineCol,
+    pub end: LineCol,
+}
+
+/// Compile the query `source`, to run with [`captures`].
+pub fn compile(source: &str) -> Result<Query, QueryError> {
+    Query::new(&LANGUAGE.into(), source)
+}
+
+/// The captures of `query` in `source`, in source order. Sources that do
+/// not parse are queried too, their errors being `ERROR` nodes.
+pub fn captures(query: &Query, source: &str) -> Vec<Capture> {
+    let tree = parse_tree_with_errors(source);
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.captures(query, tree.root_node(), source.as_bytes());
+    let mut captures = Vec::new();
+    while let Some((query_match, index)) = matches.next() {
+        let capture = query_match.captures[*index];
+        let node = capture.node;
+        let point = |p: tree_sitter::Point| LineCol::new(p.row, p.column);
+        captures.push(Capture {
+            pattern: query_match.pattern_index,
+            name: query.capture_names()[capture.index as usize].to_string(),
+            text: source[node.byte_range()].to_string(),
+            range: node.byte_range(),
+            start: point(node.start_position()),
+            end: point(node.end_position()),
+        });
+    }
+    captures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captures() {
+        let query = compile(
+            r#"(structure
+  (structure_name) @action (#eq? @action "seek")
+  (field_list (field (field_name) @field (#eq? @field "rate") (_) @rate)))"#,
+        )
+        .unwrap();
+        let source = "play\nseek, start=1.0, rate=2.0\nseek, rate=-1.0\n";
+        let captures = captures(&query, source);
+        let rates: Vec<_> = captures
+            .iter()
+            .filter(|c| c.name == "rate")
+            .map(|c| (c.text.as_str(), c.start))
+            .collect();
+        assert_eq!(
+            rates,
+            vec![("2.0", LineCol::new(1, 22)), ("-1.0", LineCol::new(2, 11))]
+        );
+        assert_eq!(captures[0].name, "action");
+        assert_eq!(&source[captures[0].range.clone()], "seek");
+
+        let error = compile("(no_such_node) @x").unwrap_err();
+        assert_eq!(error.row, 0);
+    }
+}
@@ -0,0 +1,1560 @@
+//! Source-preserving refactorings.
+//!
+//! Refactorings work on the original text rather than on the formatter
+//! output: they compute a list of [`TextEdit`]s touching only the bytes that
+//! need to change, so comments and hand-made layout elsewhere in the file
+//! are left alone. Use [`apply_edits`] to get the resulting text.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use serde::Deserialize;
+use tree_sitter::Node;
+
+use crate::document::{parse_tree, Document, Field, FieldValue, ScalarKind, Structure};
+use crate::lint::{is_valid_issue_id, literal, visit_structures};
+use crate::pattern::{is_metavariable, metavariable, Captures, Pattern, Template};
+use crate::strings::{quote, unescape};
+use crate::time::ClockTime;
+use crate::ParseError;
+
+/// Replace the bytes in `range` with `new_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+/// Error returned by refactorings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefactorError {
+    /// The source could not be parsed.
+    Parse(ParseError),
+    /// The requested name is not a valid variable name.
+    InvalidName(String),
+    /// The requested issue-id is not of the `area::name` form.
+    InvalidIssueId(String),
+    /// The new name is already used by another variable.
+    AlreadyDefined(String),
+    /// The literal to extract does not appear in the source.
+    LiteralNotFound(String),
+    /// The variable has no `set-vars` definition, or more than one.
+    NoSingleDefinition(String),
+    /// The variable is used in a way the refactoring cannot handle.
+    Unsupported(String),
+    /// A metavariable of a rewrite template is not bound by its pattern.
+    UnboundVariable(String),
+}
+
+impl fmt::Display for RefactorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => e.fmt(f),
+            Self::InvalidName(name) => write!(f, "'{}' is not a valid variable name", name),
+            Self::InvalidIssueId(id) => write!(f, "'{}' is not a valid issue-id", id),
+            Self::AlreadyDefined(name) => write!(f, "variable '{}' is already defined", name),
+            Self::LiteralNotFound(literal) => write!(f, "value '{}' not found", literal),
+            Self::NoSingleDefinition(name) => {
+                write!(f, "variable '{}' must be defined exactly once", name)
+            }
+            Self::Unsupported(reason) => f.write_str(reason),
+            Self::UnboundVariable(name) => {
+                write!(
+                    f,
+                    "metavariable '{}' of the template is not in the pattern",
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RefactorError {}
+
+impl From<ParseError> for RefactorError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+/// Apply non-overlapping edits to `source`.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|e| e.range.start);
+
+    let mut result = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in sorted {
+        result.push_str(&source[pos..edit.range.start]);
+        result.push_str(&edit.new_text);
+        pos = edit.range.end;
+    }
+    result.push_str(&source[pos..]);
+    result
+}
+
+/// Whether `name` can be used as a variable name (`$(name)`).
+pub fn is_valid_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A place in the source where a variable is defined or referenced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableOccurrence {
+    /// Byte range of the variable name itself (without `$(` and `)`).
+    pub range: Range<usize>,
+    /// Whether this is the `set-vars` field defining the variable.
+    pub is_definition: bool,
+}
+
+/// Find every definition (`set-vars` field) and reference (`$(name)`) of a variable.
+///
+/// References to sub-fields such as `$(name.field)` count as references to `name`.
+pub fn find_variable_occurrences(root: Node, source: &str, name: &str) -> Vec<VariableOccurrence> {
+    let mut occurrences = Vec::new();
+    collect_occurrences(root, source, name, &mut occurrences);
+    occurrences.sort_by_key(|o| o.range.start);
+    occurrences
+}
+
+fn collect_occurrences(
+    node: Node,
+    source: &str,
+    name: &str,
+    occurrences: &mut Vec<VariableOccurrence>,
+) {
+    match node.kind() {
+        "structure" if structure_name(node, source) == Some("set-vars") => {
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                if child.kind() != "field_list" {
+                    continue;
+                }
+                let mut field_cursor = child.walk();
+                for field in child.children(&mut field_cursor) {
+                    if let Some(field_name) = field.child_by_field_name("name") {
+                        if field.kind() == "field" && text(field_name, source) == name {
+                            occurrences.push(VariableOccurrence {
+                                range: field_name.byte_range(),
+                                is_definition: true,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        // Variables and expressions are single tokens; look for references in their text
+        "variable" | "expression" => {
+            let start = node.start_byte();
+            for range in variable_references_in(text(node, source), name) {
+                occurrences.push(VariableOccurrence {
+                    range: start + range.start..start + range.end,
+                    is_definition: false,
+                });
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_occurrences(child, source, name, occurrences);
+    }
+}
+
+/// Byte ranges of `name` in every `$(name)` or `$(name.sub)` reference inside `text`.
+fn variable_references_in(text: &str, name: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find("$(") {
+        let start = offset + pos + 2;
+        let rest = &text[start..];
+        if rest.starts_with(name) && matches!(rest[name.len()..].chars().next(), Some(')' | '.')) {
+            ranges.push(start..start + name.len());
+        }
+        offset = start;
+    }
+    ranges
+}
+
+fn text<'s>(node: Node, source: &'s str) -> &'s str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn structure_name<'s>(node: Node, source: &'s str) -> Option<&'s str> {
+    let mut cursor = node.walk();
+    let name = node
+        .children(&mut cursor)
+        .find(|c| c.kind() == "structure_name")
+        .map(|c| text(c, source));
+    name
+}
+
+/// Compute the edits renaming variable `old` to `new` in `source`.
+///
+/// Renames the `set-vars` definition and every `$(old)` reference, including
+/// those inside strings and `expr(...)` expressions.
+pub fn rename_variable(source: &str, old: &str, new: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    for name in [old, new] {
+        if !is_valid_variable_name(name) {
+            return Err(RefactorError::InvalidName(name.to_string()));
+        }
+    }
+
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    if old != new
+        && find_variable_occurrences(root, source, new)
+            .iter()
+            .any(|o| o.is_definition)
+    {
+        return Err(RefactorError::AlreadyDefined(new.to_string()));
+    }
+
+    Ok(find_variable_occurrences(root, source, old)
+        .into_iter()
+        .map(|o| TextEdit {
+            range: o.range,
+            new_text: new.to_string(),
+        })
+        .collect())
+}
+
+/// Compute the edits renaming issue-id `old` to `new` in every
+/// `expected-issue` of `source`.
+///
+/// Expected issues written as quoted structures are renamed too, unless the
+/// string uses escapes.
+pub fn rename_issue_id(source: &str, old: &str, new: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    if !is_valid_issue_id(new) {
+        return Err(RefactorError::InvalidIssueId(new.to_string()));
+    }
+
+    let tree = parse_tree(source)?;
+    let document = Document::from_node(tree.root_node(), source);
+    let mut edits = Vec::new();
+    for structure in &document.structures {
+        visit_source_structures(structure, 0, &mut |s, base| {
+            if s.name != "expected-issue" {
+                return;
+            }
+            for field in s.fields.iter().filter(|f| f.name == "issue-id") {
+                if let FieldValue::Scalar(scalar) = &field.value {
+                    if scalar.kind != ScalarKind::Variable && scalar.unquoted() == old {
+                        edits.push(TextEdit {
+                            range: base + scalar.span.start..base + scalar.span.end,
+                            new_text: if scalar.kind == ScalarKind::String {
+                                quote(new)
+                            } else {
+                                new.to_string()
+                            },
+                        });
+                    }
+                }
+            }
+        });
+    }
+    edits.sort_by_key(|e| e.range.start);
+    Ok(edits)
+}
+
+/// New names of actions and of their fields, read from a mapping file keyed
+/// by the names gst-validate deprecated:
+///
+/// ```toml
+/// [set-feature-rank]
+/// name = "set-feature-ranks"
+///
+/// # Only renaming fields
+/// [emit-signal]
+/// fields = { signal-name = "signal" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct ActionRenames(pub BTreeMap<String, ActionRename>);
+
+/// What an action of [`ActionRenames`] is renamed to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionRename {
+    /// New name of the action, when it changes.
+    pub name: Option<String>,
+    /// New names of its fields, by old name.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl ActionRenames {
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+}
+
+/// Compute the edits renaming the actions of `source`, and their fields,
+/// as `renames` tells.
+///
+/// Actions nested in blocks and arrays, such as the `actions` of `foreach`,
+/// are renamed too, as are those serialized in strings with fields, unless
+/// the string uses escapes. Renaming a field to one its action already has
+/// is an error.
+pub fn rename_actions(
+    source: &str,
+    renames: &ActionRenames,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    for rename in renames.0.values() {
+        if let Some(name) = rename.name.as_ref().filter(|n| !is_structure_name(n)) {
+            return Err(RefactorError::Unsupported(format!(
+                "'{}' is not a valid action name",
+                name
+            )));
+        }
+        if let Some(name) = rename.fields.values().find(|n| !is_field_name(n)) {
+            return Err(RefactorError::Unsupported(format!(
+                "'{}' is not a valid field name",
+                name
+            )));
+        }
+    }
+
+    let document = Document::parse(source)?;
+    let mut edits = Vec::new();
+    let mut error = None;
+    for structure in &document.structures {
+        visit_source_structures(structure, 0, &mut |s, base| {
+            let Some(rename) = renames.0.get(&s.name) else {
+                return;
+            };
+            // A quoted word is more likely a string than an action
+            if base > 0 && s.fields.is_empty() {
+                return;
+            }
+            let start = base + s.span.start;
+            if let Some(name) = rename.name.as_ref().filter(|n| **n != s.name) {
+                edits.push(TextEdit {
+                    range: start..start + s.name.len(),
+                    new_text: name.clone(),
+                });
+            }
+            for field in &s.fields {
+                let Some(new) = rename.fields.get(&field.name) else {
+                    continue;
+                };
+                if *new != field.name && s.fields.iter().any(|f| f.name == *new) {
+                    error.get_or_insert(RefactorError::Unsupported(format!(
+                        "{} at byte {} already has a field '{}'",
+                        s.name, start, new
+                    )));
+                }
+                let field_start = base + field.span.start;
+                edits.push(TextEdit {
+                    range: field_start..field_start + field.name.len(),
+                    new_text: new.clone(),
+                });
+            }
+        });
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    edits.sort_by_key(|e| e.range.start);
+    Ok(edits)
+}
+
+/// Whether `name` parses as the name of a structure without fields.
+fn is_structure_name(name: &str) -> bool {
+    name.parse::<Structure>()
+        .is_ok_and(|s| s.name == name && s.fields.is_empty())
+}
+
+/// Whether `name` parses as the name of a field.
+fn is_field_name(name: &str) -> bool {
+    format!("s, {}=0", name)
+        .parse::<Structure>()
+        .is_ok_and(|s| s.fields.len() == 1 && s.fields[0].name == name)
+}
+
+/// Call `f` with `structure` and every structure nested in it, along with
+/// the byte of the source their spans are relative to, `base` for
+/// `structure`. Structures serialized in strings are visited unless the
+/// string uses escapes, their spans then not matching the source.
+fn visit_source_structures(
+    structure: &Structure,
+    base: usize,
+    f: &mut impl FnMut(&Structure, usize),
+) {
+    fn visit_value(value: &FieldValue, base: usize, f: &mut impl FnMut(&Structure, usize)) {
+        match value {
+            FieldValue::Structure(s) => visit_source_structures(s, base, f),
+            FieldValue::Typed { value, .. } => visit_value(value, base, f),
+            FieldValue::Array(values)
+            | FieldValue::ValueArray(values)
+            | FieldValue::Block(values) => {
+                for value in values {
+                    visit_value(value, base, f);
+                }
+            }
+            FieldValue::Scalar(scalar) if scalar.kind == ScalarKind::String => {
+                let content = scalar.unquoted();
+                if scalar.text.len() < 2 || content != scalar.text[1..scalar.text.len() - 1] {
+                    return;
+                }
+                if let Ok(document) = Document::parse(&content) {
+                    for structure in &document.structures {
+                        visit_source_structures(structure, base + scalar.span.start + 1, f);
+                    }
+                }
+            }
+            FieldValue::Scalar(_) => {}
+        }
+    }
+
+    f(structure, base);
+    for field in &structure.fields {
+        visit_value(&field.value, base, f);
+    }
+}
+
+/// Undo GstStructure string quoting, returning the content of a `string` or
+/// `unquoted_string` node.
+fn literal_content(node: Node, source: &str) -> String {
+    let text = text(node, source);
+    if node.kind() == "string" && text.len() >= 2 {
+        let content = &text[1..text.len() - 1];
+        unescape(content).unwrap_or_else(|_| content.to_string())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Collect string and bare-word values, skipping `set-vars` definitions.
+fn collect_literals<'t>(node: Node<'t>, source: &str, literals: &mut Vec<Node<'t>>) {
+    match node.kind() {
+        "string" | "unquoted_string" => {
+            literals.push(node);
+            return;
+        }
+        "structure" if structure_name(node, source) == Some("set-vars") => return,
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_literals(child, source, literals);
+    }
+}
+
+/// String values appearing at least `min_occurrences` times, most repeated first.
+///
+/// Candidates for [`extract_variable`]; values already defined through
+/// `set-vars` are not counted.
+pub fn find_repeated_literals(
+    source: &str,
+    min_occurrences: usize,
+) -> Result<Vec<(String, usize)>, RefactorError> {
+    let tree = parse_tree(source)?;
+    let mut literals = Vec::new();
+    collect_literals(tree.root_node(), source, &mut literals);
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for node in literals.into_iter().filter(|n| n.kind() == "string") {
+        let content = literal_content(node, source);
+        if content.is_empty() {
+            continue;
+        }
+        match counts.iter_mut().find(|(c, _)| *c == content) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((content, 1)),
+        }
+    }
+    counts.retain(|(_, count)| *count >= min_occurrences);
+    // Stable sort keeps first-seen order between equally repeated values
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    Ok(counts)
+}
+
+/// Compute the edits replacing every occurrence of the value `literal` with
+/// `$(name)` and defining `name` in a `set-vars` structure.
+///
+/// `literal` is the unquoted value: both `"file:///a.mp4"` and `file:///a.mp4`
+/// in the source match `file:///a.mp4`. The definition is appended to a
+/// `set-vars` structure preceding the first occurrence if there is one,
+/// otherwise a new `set-vars` line is inserted before that structure.
+pub fn extract_variable(
+    source: &str,
+    literal: &str,
+    name: &str,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    if !is_valid_variable_name(name) {
+        return Err(RefactorError::InvalidName(name.to_string()));
+    }
+
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    if find_variable_occurrences(root, source, name)
+        .iter()
+        .any(|o| o.is_definition)
+    {
+        return Err(RefactorError::AlreadyDefined(name.to_string()));
+    }
+
+    let mut literals = Vec::new();
+    collect_literals(root, source, &mut literals);
+    let occurrences: Vec<Node> = literals
+        .into_iter()
+        .filter(|n| literal_content(*n, source) == literal)
+        .collect();
+    let Some(first) = occurrences.first() else {
+        return Err(RefactorError::LiteralNotFound(literal.to_string()));
+    };
+
+    let mut edits: Vec<TextEdit> = occurrences
+        .iter()
+        .map(|n| TextEdit {
+            range: n.byte_range(),
+            new_text: format!("$({})", name),
+        })
+        .collect();
+
+    let definition = format!("{}={}", name, quote(literal));
+    let mut cursor = root.walk();
+    let top_level: Vec<Node> = root
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "structure")
+        .collect();
+    let owner = top_level
+        .iter()
+        .find(|s| s.end_byte() >= first.end_byte())
+        .copied()
+        .unwrap_or(root);
+    let set_vars = top_level
+        .iter()
+        .take_while(|s| s.start_byte() < owner.start_byte())
+        .filter(|s| structure_name(**s, source) == Some("set-vars"))
+        .last();
+
+    edits.push(match set_vars {
+        Some(set_vars) => append_field(*set_vars, &definition),
+        None => {
+            let line_start = source[..owner.start_byte()]
+                .rfind('\n')
+                .map_or(0, |p| p + 1);
+            TextEdit {
+                range: line_start..line_start,
+                new_text: format!("set-vars, {}\n", definition),
+            }
+        }
+    });
+    Ok(edits)
+}
+
+/// Insert `field` after the last field of `structure`, following its layout.
+fn append_field(structure: Node, field: &str) -> TextEdit {
+    let mut cursor = structure.walk();
+    let field_list = structure
+        .children(&mut cursor)
+        .find(|c| c.kind() == "field_list");
+    let Some(field_list) = field_list else {
+        let name_end = structure
+            .child(0)
+            .map_or(structure.end_byte(), |n| n.end_byte());
+        return TextEdit {
+            range: name_end..name_end,
+            new_text: format!(", {}", field),
+        };
+    };
+
+    let mut cursor = field_list.walk();
+    let fields: Vec<Node> = field_list
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "field")
+        .collect();
+    let last = fields[fields.len() - 1];
+    let multiline = structure.start_position().row != last.start_position().row;
+    let separator = if multiline {
+        format!(",\n{}", " ".repeat(last.start_position().column))
+    } else {
+        ", ".to_string()
+    };
+    TextEdit {
+        range: last.end_byte()..last.end_byte(),
+        new_text: format!("{}{}", separator, field),
+    }
+}
+
+/// Compute the edits replacing every `$(name)` reference with the variable's
+/// value and removing its `set-vars` definition.
+///
+/// Standalone references get the value as written (quotes included), while
+/// references embedded in strings or expressions get the unquoted content.
+/// When the definition is the only field of its `set-vars`, the whole
+/// structure is removed.
+pub fn inline_variable(source: &str, name: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    let occurrences = find_variable_occurrences(root, source, name);
+
+    let mut definitions = occurrences.iter().filter(|o| o.is_definition);
+    let (Some(definition), None) = (definitions.next(), definitions.next()) else {
+        return Err(RefactorError::NoSingleDefinition(name.to_string()));
+    };
+
+    let mut field = root.descendant_for_byte_range(definition.range.start, definition.range.end);
+    while let Some(node) = field.filter(|n| n.kind() != "field") {
+        field = node.parent();
+    }
+    let field = field.ok_or_else(|| RefactorError::NoSingleDefinition(name.to_string()))?;
+    let value = field
+        .child_by_field_name("value")
+        .ok_or_else(|| RefactorError::NoSingleDefinition(name.to_string()))?;
+    let value_text = text(value, source);
+    let embedded_text = match value.child(0).and_then(|v| v.child(0)) {
+        Some(string) if string.kind() == "string" => {
+            let text = text(string, source);
+            &text[1..text.len() - 1]
+        }
+        _ => value_text,
+    };
+
+    let mut edits = Vec::new();
+    for occurrence in occurrences.iter().filter(|o| !o.is_definition) {
+        let reference = occurrence.range.start - 2..occurrence.range.end + 1;
+        if source.as_bytes()[occurrence.range.end] == b'.' {
+            return Err(RefactorError::Unsupported(format!(
+                "cannot inline sub-field reference at byte {}",
+                reference.start
+            )));
+        }
+        let token = root.descendant_for_byte_range(occurrence.range.start, occurrence.range.end);
+        let embedded = token.is_some_and(|t| {
+            t.kind() == "expression" || t.parent().is_some_and(|p| p.kind() == "string_inner")
+        });
+        edits.push(TextEdit {
+            range: reference,
+            new_text: if embedded { embedded_text } else { value_text }.to_string(),
+        });
+    }
+
+    edits.push(remove_field(field, source));
+    Ok(edits)
+}
+
+/// Remove `field` and its separating comma, or its whole structure (and
+/// line) when it is the only field.
+fn remove_field(field: Node, source: &str) -> TextEdit {
+    let field_list = field.parent().expect("fields live in a field list");
+    let mut cursor = field_list.walk();
+    let fields: Vec<Node> = field_list
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "field")
+        .collect();
+    let index = fields
+        .iter()
+        .position(|f| f.id() == field.id())
+        .expect("field is part of its field list");
+
+    let range = if fields.len() == 1 {
+        let structure = field_list
+            .parent()
+            .expect("field lists live in a structure");
+        let mut end = structure.end_byte();
+        if source[end..].starts_with('\n') {
+            end += 1;
+        }
+        structure.start_byte()..end
+    } else if index == 0 {
+        field.start_byte()..fields[1].start_byte()
+    } else {
+        fields[index - 1].end_byte()..field.end_byte()
+    };
+    TextEdit {
+        range,
+        new_text: String::new(),
+    }
+}
+
+/// Remove the fields of `structure` selected by `remove`, with their
+/// separating commas. Removing every field leaves the bare structure name.
+pub(crate) fn remove_fields(
+    structure: Node,
+    mut remove: impl FnMut(Node) -> bool,
+) -> Vec<TextEdit> {
+    let mut cursor = structure.walk();
+    let Some(field_list) = structure
+        .children(&mut cursor)
+        .find(|c| c.kind() == "field_list")
+    else {
+        return Vec::new();
+    };
+    let mut cursor = field_list.walk();
+    let fields: Vec<Node> = field_list
+        .children(&mut cursor)
+        .filter(|c| c.kind() == "field")
+        .collect();
+    let removed: Vec<bool> = fields.iter().map(|f| remove(*f)).collect();
+
+    if removed.iter().all(|r| *r) {
+        let name_end = structure
+            .child(0)
+            .map_or(field_list.start_byte(), |n| n.end_byte());
+        return vec![TextEdit {
+            range: name_end..field_list.end_byte(),
+            new_text: String::new(),
+        }];
+    }
+
+    let first_kept = removed.iter().position(|r| !r).unwrap_or(0);
+    fields
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| removed[*i])
+        .map(|(i, field)| TextEdit {
+            // Before the first kept field, eat the comma following the field;
+            // after it, eat the comma preceding the field.
+            range: if i < first_kept {
+                field.start_byte()..fields[i + 1].start_byte()
+            } else {
+                fields[i - 1].end_byte()..field.end_byte()
+            },
+            new_text: String::new(),
+        })
+        .collect()
+}
+
+/// Compute the edits sorting every `expected-issues` list by issue-id and
+/// removing exact duplicates.
+///
+/// Entries may be written as `[expected-issue, ...]` arrays, quoted
+/// structures or bare structures; duplicates are detected semantically so
+/// both spellings of the same expectation are merged, keeping the first one.
+/// When every entry sits on its own lines, comments on the lines above an
+/// entry move with it.
+pub fn sort_expected_issues(source: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    let tree = parse_tree(source)?;
+    Ok(expected_issues_edits(tree.root_node(), source))
+}
+
+/// [`sort_expected_issues`] on an already parsed tree.
+pub(crate) fn expected_issues_edits(root: Node, source: &str) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    collect_expected_issues_edits(root, source, &mut edits);
+    edits
+}
+
+fn collect_expected_issues_edits(node: Node, source: &str, edits: &mut Vec<TextEdit>) {
+    if node.kind() == "field" {
+        let is_expected_issues = node
+            .child_by_field_name("name")
+            .is_some_and(|n| text(n, source) == "expected-issues");
+        let list = node
+            .child_by_field_name("value")
+            .and_then(|v| v.named_child(0))
+            .filter(|v| matches!(v.kind(), "nested_structure_block" | "array"));
+        if let (true, Some(list)) = (is_expected_issues, list) {
+            edits.extend(sort_list_edit(list, source));
+            return;
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_expected_issues_edits(child, source, edits);
+    }
+}
+
+/// An entry of a block or array together with the comments preceding it.
+struct ListEntry<'t> {
+    node: Node<'t>,
+    leading_comments: Vec<Node<'t>>,
+}
+
+fn list_entries<'t>(list: Node<'t>) -> Vec<ListEntry<'t>> {
+    let mut entries = Vec::new();
+    let mut comments = Vec::new();
+    let mut previous_row = list.start_position().row;
+    let mut cursor = list.walk();
+    for child in list.children(&mut cursor) {
+        let node = match child.kind() {
+            // Comments trailing the previous entry stay on its line
+            "comment" if child.start_position().row == previous_row => continue,
+            "comment" => {
+                comments.push(child);
+                continue;
+            }
+            "{" | "}" | "[" | "]" | "," => continue,
+            // Array elements carry their trailing comma; keep only the value
+            "array_element" => match child.named_child(0) {
+                Some(value) => value,
+                None => continue,
+            },
+            _ => child,
+        };
+        previous_row = node.end_position().row;
+        entries.push(ListEntry {
+            node,
+            leading_comments: std::mem::take(&mut comments),
+        });
+    }
+    entries
+}
+
+/// Byte range of the lines holding `span`, extended upwards over the comment
+/// lines directly above it.
+pub(crate) fn action_lines(source: &str, span: &Range<usize>) -> Range<usize> {
+    let line_start = |offset: usize| source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let mut start = line_start(span.start);
+    while start > 0 {
+        let previous = line_start(start - 1);
+        if !source[previous..start].trim_start().starts_with('#') {
+            break;
+        }
+        start = previous;
+    }
+    let end = source[span.end..]
+        .find('\n')
+        .map_or(source.len(), |i| span.end + i);
+    start..end
+}
+
+/// Edit sorting a run of consecutive timed actions by playback time.
+///
+/// Each action moves along with the comments directly above it, while the
+/// text between actions (blank lines) stays in place.
+pub(crate) fn sort_actions_edit(source: &str, run: &[(&Structure, ClockTime)]) -> Option<TextEdit> {
+    let chunks: Vec<Range<usize>> = run
+        .iter()
+        .map(|(s, _)| action_lines(source, &s.span))
+        .collect();
+    // Actions sharing lines cannot be moved independently
+    if chunks.windows(2).any(|w| w[0].end >= w[1].start) {
+        return None;
+    }
+
+    let mut order: Vec<usize> = (0..run.len()).collect();
+    order.sort_by_key(|&i| run[i].1);
+
+    let range = chunks[0].start..chunks[chunks.len() - 1].end;
+    let mut new_text = String::new();
+    for (i, &from) in order.iter().enumerate() {
+        new_text.push_str(&source[chunks[from].clone()]);
+        if let Some(next) = chunks.get(i + 1) {
+            new_text.push_str(&source[chunks[i].end..next.start]);
+        }
+    }
+    Some(TextEdit { range, new_text })
+}
+
+/// Compute the edits sorting the actions of `source` by `playback-time`.
+///
+/// Only runs of consecutive actions with a literal playback time are sorted,
+/// as [`Document::sort_actions_by_time`] does, so actions without one keep
+/// running when they did. Each action moves along with the comments directly
+/// above it; runs where actions share lines are left alone.
+pub fn sort_actions_by_time(source: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    let document = Document::parse(source)?;
+    Ok(document
+        .timed_runs()
+        .into_iter()
+        .filter_map(|run| {
+            let run: Vec<(&Structure, ClockTime)> = document.structures[run]
+                .iter()
+                .map(|s| (s, s.playback_time().unwrap()))
+                .collect();
+            if run.windows(2).all(|w| w[0].1 <= w[1].1) {
+                return None;
+            }
+            sort_actions_edit(source, &run)
+        })
+        .collect())
+}
+
+fn line_start(source: &str, pos: usize) -> usize {
+    source[..pos].rfind('\n').map_or(0, |p| p + 1)
+}
+
+/// The byte range of the lines holding `entry`, if it owns them exclusively:
+/// nothing but indentation before it (or its leading comments), and only a
+/// separator and a trailing comment after it.
+fn entry_lines(entry: &ListEntry, source: &str) -> Option<Range<usize>> {
+    let first = entry.leading_comments.first().unwrap_or(&entry.node);
+    let start = line_start(source, first.start_byte());
+    if !source[start..first.start_byte()].trim().is_empty() {
+        return None;
+    }
+    let mut previous_end = start;
+    for comment in &entry.leading_comments {
+        if !source[previous_end..comment.start_byte()].trim().is_empty() {
+            return None;
+        }
+        previous_end = comment.end_byte();
+    }
+
+    let rest = &source[entry.node.end_byte()..];
+    let after = rest.trim_start_matches([' ', '\t']);
+    let after = after.strip_prefix(',').unwrap_or(after);
+    let after = after.trim_start_matches([' ', '\t']);
+    let after = if after.starts_with('#') {
+        after.trim_start_matches(|c| c != '\n')
+    } else {
+        after
+    };
+    let after = after.strip_prefix('\n')?;
+    Some(start..source.len() - after.len())
+}
+
+fn sort_list_edit(list: Node, source: &str) -> Option<TextEdit> {
+    let entries = list_entries(list);
+    if entries.len() < 2 {
+        return None;
+    }
+
+    let values: Vec<Option<FieldValue>> = entries
+        .iter()
+        .map(|e| FieldValue::from_node(e.node, source))
+        .collect();
+    let keys: Vec<String> = values
+        .iter()
+        .map(|v| {
+            v.as_ref()
+                .and_then(|v| v.as_structure())
+                .and_then(|s| s.field("issue-id").map(|f| value_text(&f.value)))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    // Stable sort by issue-id, then drop later duplicates
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by(|&a, &b| keys[a].cmp(&keys[b]));
+    let mut kept: Vec<usize> = Vec::new();
+    for i in order {
+        let duplicate = kept.iter().any(|&k| match (&values[k], &values[i]) {
+            (Some(a), Some(b)) => a.semantically_eq(b),
+            _ => false,
+        });
+        if !duplicate {
+            kept.push(i);
+        }
+    }
+    if kept.iter().copied().eq(0..entries.len()) {
+        return None;
+    }
+
+    let lines: Option<Vec<Range<usize>>> = entries.iter().map(|e| entry_lines(e, source)).collect();
+    let last_node = entries[entries.len() - 1].node;
+    let (range, new_text) = match lines {
+        Some(lines)
+            if list.start_position().row < entries[0].node.start_position().row
+                && list.end_position().row > last_node.end_position().row =>
+        {
+            let mut new_text = String::new();
+            for &i in &kept {
+                let node = entries[i].node;
+                let line = &lines[i];
+                new_text.push_str(&source[line.start..node.end_byte()]);
+                if !source[node.end_byte()..].trim_start().starts_with(',') {
+                    new_text.push(',');
+                }
+                new_text.push_str(&source[node.end_byte()..line.end]);
+            }
+            (lines[0].start..lines[lines.len() - 1].end, new_text)
+        }
+        _ => {
+            // Entries share lines: only safe without comments in between
+            let range = entries[0].node.start_byte()..last_node.end_byte();
+            let mut cursor = list.walk();
+            if list
+                .children(&mut cursor)
+                .any(|c| c.kind() == "comment" && range.contains(&c.start_byte()))
+            {
+                return None;
+            }
+            let texts: Vec<&str> = kept
+                .iter()
+                .map(|&i| text(entries[i].node, source))
+                .collect();
+            (range, texts.join(", "))
+        }
+    };
+    Some(TextEdit { range, new_text })
+}
+
+fn value_text(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Scalar(s) => s.unquoted(),
+        FieldValue::Typed { value, .. } => value_text(value),
+        _ => String::new(),
+    }
+}
+
+/// Compute the edits rewriting the structures of `source` matching
+/// `pattern` to `template`.
+///
+/// Only the name and the fields named by the pattern change: the fields of
+/// the template take the place of the first of them, one per line with its
+/// indentation when it is on a line of its own. Other fields, comments and
+/// layout are kept as written. Structures serialized in strings are left
+/// alone, as are those nested in a rewritten structure.
+pub fn rewrite(
+    source: &str,
+    pattern: &Pattern,
+    template: &Template,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    if let Some(variable) = template.unbound_variable(pattern) {
+        return Err(RefactorError::UnboundVariable(variable.to_string()));
+    }
+
+    let tree = parse_tree(source)?;
+    let root = tree.root_node();
+    let document = Document::from_node(root, source);
+    let mut edits = Vec::new();
+    let mut error = None;
+    let mut rewritten: Option<Range<usize>> = None;
+    for structure in &document.structures {
+        visit_structures(structure, None, &mut |s, quoted_span| {
+            if quoted_span.is_some()
+                || rewritten
+                    .as_ref()
+                    .is_some_and(|r| r.contains(&s.span.start))
+            {
+                return;
+            }
+            let Some(captures) = pattern.captures(s) else {
+                return;
+            };
+            let mut node = root.descendant_for_byte_range(s.span.start, s.span.end);
+            while let Some(n) =
+                node.filter(|n| !matches!(n.kind(), "structure" | "array_structure"))
+            {
+                node = n.parent();
+            }
+            let Some(node) = node.filter(|n| n.start_byte() == s.span.start) else {
+                return;
+            };
+            match rewrite_structure(node, source, &captures, template) {
+                Ok(structure_edits) => edits.extend(structure_edits),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            rewritten = Some(s.span.clone());
+        });
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    edits.sort_by_key(|e| e.range.start);
+    Ok(edits)
+}
+
+fn rewrite_structure(
+    structure: Node,
+    source: &str,
+    captures: &Captures,
+    template: &Template,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    let mut cursor = structure.walk();
+    let children: Vec<Node> = structure.children(&mut cursor).collect();
+    let Some(name) = children.iter().find(|c| c.kind() == "structure_name") else {
+        return Ok(Vec::new());
+    };
+    let fields: Vec<Node> = children
+        .iter()
+        .filter(|c| c.kind() == "field_list")
+        .flat_map(|list| {
+            let mut cursor = list.walk();
+            list.children(&mut cursor)
+                .filter(|c| c.kind() == "field")
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let field_node = |field: &Field| fields.iter().find(|n| n.start_byte() == field.span.start);
+    let matched: Vec<Node> = fields
+        .iter()
+        .filter(|n| {
+            captures
+                .fields
+                .iter()
+                .any(|f| f.span.start == n.start_byte())
+        })
+        .copied()
+        .collect();
+
+    let mut new_fields = Vec::new();
+    for field in &template.structure.fields {
+        let field_name = match captures.variables.get(&field.name) {
+            Some(bound) if is_metavariable(&field.name) => literal(&bound.value)
+                .filter(|name| {
+                    !name.is_empty()
+                        && !name.contains(|c: char| c.is_whitespace() || "=,;\"".contains(c))
+                })
+                .ok_or_else(|| {
+                    RefactorError::Unsupported(format!(
+                        "the value of {} at byte {} is not a field name",
+                        field.name, bound.span.start
+                    ))
+                })?,
+            _ => field.name.clone(),
+        };
+        let value = match metavariable(&field.value).map(|v| captures.variables[v]) {
+            Some(bound) => field_node(bound)
+                .and_then(|n| n.child_by_field_name("value"))
+                .map_or("", |value| text(value, source)),
+            None => template.value_text(field),
+        };
+        new_fields.push(format!("{}={}", field_name, value));
+    }
+
+    let mut edits = Vec::new();
+    if text(*name, source) != template.structure.name {
+        edits.push(TextEdit {
+            range: name.byte_range(),
+            new_text: template.structure.name.clone(),
+        });
+    }
+    match matched.split_first() {
+        None if new_fields.is_empty() => {}
+        None => edits.push(TextEdit {
+            range: name.end_byte()..name.end_byte(),
+            new_text: format!(", {}", new_fields.join(", ")),
+        }),
+        Some(_) if new_fields.is_empty() => {
+            edits.extend(remove_fields(structure, |f| matched.contains(&f)));
+        }
+        Some((first, rest)) => {
+            let start = line_start(source, first.start_byte());
+            let indent = &source[start..first.start_byte()];
+            let separator = if start > structure.start_byte() && indent.trim().is_empty() {
+                format!(",\n{}", indent)
+            } else {
+                ", ".to_string()
+            };
+            edits.push(TextEdit {
+                range: first.byte_range(),
+                new_text: new_fields.join(&separator),
+            });
+            edits.extend(remove_fields_keeping_comments(&fields, rest, source));
+        }
+    }
+    Ok(edits)
+}
+
+/// Remove the `removed` fields of `fields`, the fields of a structure in
+/// order, with their separating commas, keeping the comments between them.
+/// At least one field before the removed ones is kept.
+fn remove_fields_keeping_comments(
+    fields: &[Node],
+    removed: &[Node],
+    source: &str,
+) -> Vec<TextEdit> {
+    let Some(last_kept) = fields.iter().rposition(|f| !removed.contains(f)) else {
+        return Vec::new();
+    };
+    let mut edits = Vec::new();
+    let mut remove = |range: Range<usize>| {
+        if !range.is_empty() {
+            edits.push(TextEdit {
+                range,
+                new_text: String::new(),
+            });
+        }
+    };
+
+    // Before the last kept field, a field goes with the comma following it
+    for field in fields[..last_kept].iter().filter(|f| removed.contains(f)) {
+        let end = match field.next_sibling().filter(|n| n.kind() == ",") {
+            Some(comma) => comma
+                .next_sibling()
+                .map_or(comma.end_byte(), |n| n.start_byte()),
+            None => field.end_byte(),
+        };
+        remove(field.start_byte()..end);
+    }
+
+    // After it, everything up to the last field goes but the comments
+    let Some(last) = fields[last_kept + 1..].last() else {
+        return edits;
+    };
+    let mut start = fields[last_kept].end_byte();
+    let mut node = fields[last_kept].next_sibling();
+    while let Some(n) = node.filter(|n| n.start_byte() < last.end_byte()) {
+        if n.kind() == "comment" {
+            remove(start..start + source[start..n.start_byte()].trim_end().len());
+            start = n.end_byte();
+        }
+        node = n.next_sibling();
+    }
+    remove(start..last.end_byte());
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(source: &str, old: &str, new: &str) -> String {
+        let edits = rename_variable(source, old, new).unwrap();
+        apply_edits(source, &edits)
+    }
+
+    #[test]
+    fn test_rename_definition_and_references() {
+        let source = "set-vars, uri=\"file:///tmp/a.mp4\"  # the clip\n\
+                      # keep $(uri) in comments untouched\n\
+                      open-uri,\n    uri=$(uri)\n\
+                      check, path=\"$(uri.path)/x\", v=expr($(uri) + 1), other=$(uri2)\n";
+        let expected = "set-vars, media=\"file:///tmp/a.mp4\"  # the clip\n\
+                        # keep $(uri) in comments untouched\n\
+                        open-uri,\n    uri=$(media)\n\
+                        check, path=\"$(media.path)/x\", v=expr($(media) + 1), other=$(uri2)\n";
+        assert_eq!(rename(source, "uri", "media"), expected);
+    }
+
+    #[test]
+    fn test_rename_only_set_vars_fields() {
+        let source = "seek, start=1.0\nset-vars, start=2.0\n";
+        assert_eq!(
+            rename(source, "start", "begin"),
+            "seek, start=1.0\nset-vars, begin=2.0\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_rejects_conflicts() {
+        assert_eq!(
+            rename_variable("set-vars, a=1, b=2", "a", "b"),
+            Err(RefactorError::AlreadyDefined("b".to_string()))
+        );
+        assert_eq!(
+            rename_variable("set-vars, a=1", "a", "not valid"),
+            Err(RefactorError::InvalidName("not valid".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rename_issue_id() {
+        let source = "meta,\n    expected-issues={\n        [expected-issue, issue-id=event::a, level=critical],\n        \"expected-issue, issue-id=event::a\",\n        [expected-issue, issue-id=\"event::a\", details=\"event::a\"],\n        [expected-issue, issue-id=event::b],\n    }\n";
+        let edits = rename_issue_id(source, "event::a", "event::c").unwrap();
+        assert_eq!(
+            apply_edits(source, &edits),
+            "meta,\n    expected-issues={\n        [expected-issue, issue-id=event::c, level=critical],\n        \"expected-issue, issue-id=event::c\",\n        [expected-issue, issue-id=\"event::c\", details=\"event::a\"],\n        [expected-issue, issue-id=event::b],\n    }\n"
+        );
+        assert_eq!(
+            rename_issue_id(source, "event::a", "nope"),
+            Err(RefactorError::InvalidIssueId("nope".to_string()))
+        );
+    }
+
+    fn extract(source: &str, literal: &str, name: &str) -> String {
+        let edits = extract_variable(source, literal, name).unwrap();
+        apply_edits(source, &edits)
+    }
+
+    #[test]
+    fn test_extract_variable_new_set_vars() {
+        let source = "meta, args={\"playbin\"}\n\
+                      # Open the clip\n\
+                      set-property, uri=\"file:///a.mp4\"\n\
+                      check, uri=file:///a.mp4, other=\"file:///b.mp4\"\n";
+        let expected = "meta, args={\"playbin\"}\n\
+                        # Open the clip\n\
+                        set-vars, clip=\"file:///a.mp4\"\n\
+                        set-property, uri=$(clip)\n\
+                        check, uri=$(clip), other=\"file:///b.mp4\"\n";
+        assert_eq!(extract(source, "file:///a.mp4", "clip"), expected);
+    }
+
+    #[test]
+    fn test_extract_variable_existing_set_vars() {
+        let source = "set-vars,\n    a=1\nfoo, x=\"some value\"\nbar, y=\"some value\"\n";
+        assert_eq!(
+            extract(source, "some value", "v"),
+            "set-vars,\n    a=1,\n    v=\"some value\"\nfoo, x=$(v)\nbar, y=$(v)\n"
+        );
+    }
+
+    #[test]
+    fn test_find_repeated_literals() {
+        let source = "a, x=\"u\", y=\"w\"\nb, x=\"w\"\nc, x=\"w\", z=\"u\"\nd, x=\"once\"\n";
+        assert_eq!(
+            find_repeated_literals(source, 2).unwrap(),
+            vec![("w".to_string(), 3), ("u".to_string(), 2)]
+        );
+        assert_eq!(
+            extract_variable(source, "missing", "v"),
+            Err(RefactorError::LiteralNotFound("missing".to_string()))
+        );
+    }
+
+    fn inline(source: &str, name: &str) -> String {
+        let edits = inline_variable(source, name).unwrap();
+        apply_edits(source, &edits)
+    }
+
+    #[test]
+    fn test_inline_variable() {
+        let source = "set-vars, clip=\"file:///a.mp4\", rate=2.0\n\
+                      open, uri=$(clip), msg=\"opening $(clip)\"\n\
+                      seek, rate=$(rate), stop=expr($(rate) * 2)\n";
+        assert_eq!(
+            inline(source, "clip"),
+            "set-vars, rate=2.0\n\
+             open, uri=\"file:///a.mp4\", msg=\"opening file:///a.mp4\"\n\
+             seek, rate=$(rate), stop=expr($(rate) * 2)\n"
+        );
+        assert_eq!(
+            inline(source, "rate"),
+            "set-vars, clip=\"file:///a.mp4\"\n\
+             open, uri=$(clip), msg=\"opening $(clip)\"\n\
+             seek, rate=2.0, stop=expr(2.0 * 2)\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_variable_drops_empty_set_vars() {
+        let source = "meta, a=1\nset-vars, v=5\nfoo, x=$(v)\n";
+        assert_eq!(inline(source, "v"), "meta, a=1\nfoo, x=5\n");
+    }
+
+    #[test]
+    fn test_inline_variable_errors() {
+        assert_eq!(
+            inline_variable("foo, x=$(v)", "v"),
+            Err(RefactorError::NoSingleDefinition("v".to_string()))
+        );
+        assert!(matches!(
+            inline_variable("set-vars, v=1\nfoo, x=$(v.sub)", "v"),
+            Err(RefactorError::Unsupported(_))
+        ));
+    }
+
+    fn sort_issues(source: &str) -> String {
+        let edits = sort_expected_issues(source).unwrap();
+        apply_edits(source, &edits)
+    }
+
+    #[test]
+    fn test_sort_expected_issues_multiline() {
+        let source = r#"meta,
+    expected-issues={
+        # Second
+        [expected-issue,
+            issue-id=b,
+        ],
+        "expected-issue, issue-id=a, details=\"x\"",  # first
+        [expected-issue, issue-id=b],
+        [expected-issue, issue-id=a, details="x"]
+    }
+"#;
+        let expected = r#"meta,
+    expected-issues={
+        "expected-issue, issue-id=a, details=\"x\"",  # first
+        # Second
+        [expected-issue,
+            issue-id=b,
+        ],
+    }
+"#;
+        assert_eq!(sort_issues(source), expected);
+    }
+
+    #[test]
+    fn test_sort_expected_issues_inline() {
+        let source =
+            "meta, expected-issues={[expected-issue, issue-id=z], [expected-issue, issue-id=y]}\n";
+        assert_eq!(
+            sort_issues(source),
+            "meta, expected-issues={[expected-issue, issue-id=y], [expected-issue, issue-id=z]}\n"
+        );
+        let sorted =
+            "meta, expected-issues={[expected-issue, issue-id=a], [expected-issue, issue-id=b]}";
+        assert!(sort_expected_issues(sorted).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sort_actions_by_time() {
+        let source = "meta, handles-states=true\n\n\
+                      # Seek back\n\
+                      seek, playback-time=2.0, start=0.0\n\n\
+                      pause, playback-time=1.0 # Pause first\n\
+                      wait, duration=1.0\n\
+                      play, playback-time=\"0:00:00.5\"\n\
+                      stop, playback-time=$(end)\n";
+        let edits = sort_actions_by_time(source).unwrap();
+        assert_eq!(
+            apply_edits(source, &edits),
+            "meta, handles-states=true\n\n\
+             pause, playback-time=1.0 # Pause first\n\n\
+             # Seek back\n\
+             seek, playback-time=2.0, start=0.0\n\
+             wait, duration=1.0\n\
+             play, playback-time=\"0:00:00.5\"\n\
+             stop, playback-time=$(end)\n"
+        );
+
+        let mut document = Document::parse(source).unwrap();
+        document.sort_actions_by_time();
+        assert!(document.semantically_eq(&Document::parse(&apply_edits(source, &edits)).unwrap()));
+
+        assert!(sort_actions_by_time(&apply_edits(source, &edits))
+            .unwrap()
+            .is_empty());
+        // Actions on the same line are not moved
+        assert!(
+            sort_actions_by_time("play, playback-time=2; pause, playback-time=1;")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_remove_fields() {
+        let remove = |source: &str, names: &[&str]| {
+            let tree = parse_tree(source).unwrap();
+            let structure = tree.root_node().child(0).unwrap();
+            let edits = remove_fields(structure, |f| {
+                names.contains(&text(f.child_by_field_name("name").unwrap(), source))
+            });
+            apply_edits(source, &edits)
+        };
+        assert_eq!(remove("s, a=1, b=2, c=3, d=4", &["a", "b", "d"]), "s, c=3");
+        assert_eq!(remove("s,\n    a=1,\n    b=2\n", &["b"]), "s,\n    a=1\n");
+        assert_eq!(remove("s, a=1, b=2;", &["a", "b"]), "s;");
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rewrite = |source: &str, pattern: &str, template: &str| {
+            let edits = rewrite(
+                source,
+                &pattern.parse().unwrap(),
+                &template.parse().unwrap(),
+            )?;
+            Ok::<_, RefactorError>(apply_edits(source, &edits))
+        };
+        let pattern = "set-property, property-name=X, property-value=Y";
+        let template = "set-properties, X=Y";
+        assert_eq!(
+            rewrite(
+                "play # Start\nset-property, target-element-name=sink, property-name=sync, property-value=false;\n",
+                pattern,
+                template
+            )
+            .unwrap(),
+            "play # Start\nset-properties, target-element-name=sink, sync=false;\n"
+        );
+        assert_eq!(
+            rewrite(
+                "set-property,\n    property-name=\"sync\",\n    # Not synced\n    property-value=(boolean)false,\n    on-all-instances=true\n",
+                pattern,
+                template
+            )
+            .unwrap(),
+            "set-properties,\n    sync=(boolean)false,\n    # Not synced\n    on-all-instances=true\n"
+        );
+
+        assert_eq!(
+            rewrite("s, a=1,\n    # Then\n    b=2\n", "s, a=X, b=Y", "s, a=Y").unwrap(),
+            "s, a=2\n    # Then\n"
+        );
+
+        // Fields are added after the name, and removed
+        assert_eq!(
+            rewrite("seek, start=1.0\n", "seek", "seek, flags=flush").unwrap(),
+            "seek, flags=flush, start=1.0\n"
+        );
+        assert_eq!(
+            rewrite(
+                "seek, start=1.0, flags=accurate\n",
+                "seek, flags=accurate",
+                "seek"
+            )
+            .unwrap(),
+            "seek, start=1.0\n"
+        );
+
+        // Quoted structures are left alone, nested ones rewritten
+        assert_eq!(
+            rewrite(
+                "meta, expected-issues={[expected-issue, id=a], \"expected-issue, id=b\"}\n",
+                "expected-issue, id=X",
+                "expected-issue, issue-id=X"
+            )
+            .unwrap(),
+            "meta, expected-issues={[expected-issue, issue-id=a], \"expected-issue, id=b\"}\n"
+        );
+
+        assert_eq!(
+            rewrite("s, a=1\n", "s, a=X", "s, b=Y"),
+            Err(RefactorError::UnboundVariable("Y".to_string()))
+        );
+        assert!(matches!(
+            rewrite("s, a=$(name)\n", "s, a=X", "s, X=1"),
+            Err(RefactorError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_rename_actions() {
+        let renames = ActionRenames::from_toml(
+            r#"
+[set-feature-rank]
+name = "set-feature-ranks"
+fields = { feature-name = "name" }
+
+[emit-signal]
+fields = { signal-name = "signal" }
+"#,
+        )
+        .unwrap();
+        let source = "\
+set-feature-rank, feature-name=avdec_h264, rank=0 # Software
+foreach, i=[0, 2],
+    actions={
+        \"set-feature-rank, feature-name=avdec_h265, rank=0\",
+        [emit-signal, target-element-name=src, signal-name=need-data],
+    }
+set-vars, action=\"set-feature-rank\"
+";
+        let edits = rename_actions(source, &renames).unwrap();
+        assert_eq!(
+            apply_edits(source, &edits),
+            "\
+set-feature-ranks, name=avdec_h264, rank=0 # Software
+foreach, i=[0, 2],
+    actions={
+        \"set-feature-ranks, name=avdec_h265, rank=0\",
+        [emit-signal, target-element-name=src, signal=need-data],
+    }
+set-vars, action=\"set-feature-rank\"
+"
+        );
+
+        assert!(matches!(
+            rename_actions("emit-signal, signal-name=a, signal=b\n", &renames),
+            Err(RefactorError::Unsupported(_))
+        ));
+        let renames = ActionRenames::from_toml("[seek]\nname = \"bad name\"\n").unwrap();
+        assert!(matches!(
+            rename_actions("seek\n", &renames),
+            Err(RefactorError::Unsupported(_))
+        ));
+        assert!(ActionRenames::from_toml("[seek]\nnew-name = \"x\"\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_edits_unordered() {
+        let edits = vec![
+            TextEdit {
+                range: 4..5,
+                new_text: "E".to_string(),
+            },
+            TextEdit {
+                range: 0..1,
+                new_text: "A".to_string(),
+            },
+        ];
+        assert_eq!(apply_edits("abcdef", &edits), "AbcdEf");
+    }
+}
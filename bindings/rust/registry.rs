@@ -0,0 +1,353 @@
+//! Definitions of the actions gst-validate scenarios can use.
+//!
+//! The [`ActionRegistry`] describes each action type and its parameters, and
+//! the enum and flags types values can be cast to. It drives the
+//! registry-based lints and editor features such as completion.
+//! Definitions can come from three sources, from lowest to highest precedence:
+//!
+//! 1. the baseline shipped with the crate ([`ActionRegistry::builtin`]),
+//! 2. a JSON dump of the action types of the installed gst-validate,
+//! 3. TOML files maintained alongside the test suite.
+//!
+//! A definition from a higher precedence source replaces the whole definition
+//! of the action with the same name.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+const BUILTIN_ACTIONS: &str = include_str!("actions.toml");
+
+/// A parameter of an action.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ParameterDef {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Whether the action fails without this parameter.
+    #[serde(default)]
+    pub mandatory: bool,
+    /// Human readable description of the accepted types.
+    #[serde(default)]
+    pub types: String,
+    /// Value used when the parameter is not given.
+    #[serde(default, alias = "default-value")]
+    pub default: Option<String>,
+}
+
+/// An action type, such as `seek`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ActionDef {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<ParameterDef>,
+}
+
+impl ActionDef {
+    /// Look up a parameter by name.
+    pub fn parameter(&self, name: &str) -> Option<&ParameterDef> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+}
+
+/// An enum or flags type, whose values are checked in `(Type)value` casts.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TypeDef {
+    #[serde(default)]
+    pub name: String,
+    /// Whether values combine several nicks with `+`.
+    #[serde(default)]
+    pub flags: bool,
+    /// Nicks of the values.
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+impl TypeDef {
+    /// Whether `value`, a nick or a number, is a value of this type.
+    pub fn accepts(&self, value: &str) -> bool {
+        let is_nick = |nick: &str| {
+            let nick = nick.trim();
+            nick.parse::<i64>().is_ok() || self.values.iter().any(|v| v == nick)
+        };
+        if self.flags {
+            value.split(['+', '|']).all(is_nick)
+        } else {
+            is_nick(value)
+        }
+    }
+}
+
+/// Error returned when action definitions cannot be loaded.
+#[derive(Debug)]
+pub enum RegistryError {
+    Io(PathBuf, io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// The file extension is neither `.json` nor `.toml`.
+    UnknownFormat(PathBuf),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            RegistryError::Toml(e) => write!(f, "invalid action definitions: {}", e),
+            RegistryError::Json(e) => write!(f, "invalid action dump: {}", e),
+            RegistryError::UnknownFormat(path) => write!(
+                f,
+                "{}: unknown action definitions format, expected .json or .toml",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Layout of the TOML files.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TomlRegistry {
+    #[serde(default)]
+    common_parameters: Vec<ParameterDef>,
+    #[serde(default)]
+    actions: BTreeMap<String, ActionDef>,
+    #[serde(default)]
+    types: BTreeMap<String, TypeDef>,
+}
+
+/// Layout of the gst-validate JSON dumps: either a list of action types or
+/// an object holding it as `action-types`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonRegistry {
+    List(Vec<ActionDef>),
+    Object {
+        #[serde(rename = "action-types")]
+        action_types: Vec<ActionDef>,
+    },
+}
+
+/// A set of action definitions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionRegistry {
+    actions: BTreeMap<String, ActionDef>,
+    common_parameters: Vec<ParameterDef>,
+    types: BTreeMap<String, TypeDef>,
+}
+
+impl ActionRegistry {
+    /// The baseline definitions shipped with the crate.
+    pub fn builtin() -> Self {
+        Self::from_toml(BUILTIN_ACTIONS).expect("built-in action definitions are valid")
+    }
+
+    /// Parse definitions from the TOML format used by the built-in baseline:
+    ///
+    /// ```toml
+    /// [actions.seek]
+    /// description = "Seek into the stream."
+    /// parameters = [
+    ///     { name = "start", mandatory = true, types = "double" },
+    /// ]
+    ///
+    /// [types.GstSeekFlags]
+    /// flags = true
+    /// values = ["flush", "accurate"]
+    /// ```
+    pub fn from_toml(source: &str) -> Result<Self, RegistryError> {
+        let registry: TomlRegistry = toml::from_str(source).map_err(RegistryError::Toml)?;
+        let actions = registry
+            .actions
+            .into_iter()
+            .map(|(name, mut action)| {
+                action.name = name.clone();
+                (name, action)
+            })
+            .collect();
+        let types = registry
+            .types
+            .into_iter()
+            .map(|(name, mut value_type)| {
+                value_type.name = name.clone();
+                (name, value_type)
+            })
+            .collect();
+        Ok(Self {
+            actions,
+            common_parameters: registry.common_parameters,
+            types,
+        })
+    }
+
+    /// Parse a JSON dump of the action types of gst-validate.
+    pub fn from_json(source: &str) -> Result<Self, RegistryError> {
+        let action_types = match serde_json::from_str(source).map_err(RegistryError::Json)? {
+            JsonRegistry::List(action_types) => action_types,
+            JsonRegistry::Object { action_types } => action_types,
+        };
+        Ok(Self {
+            actions: action_types
+                .into_iter()
+                .map(|action| (action.name.clone(), action))
+                .collect(),
+            common_parameters: Vec::new(),
+            types: BTreeMap::new(),
+        })
+    }
+
+    /// Read definitions from a `.json` dump or a `.toml` file.
+    pub fn from_file(path: &Path) -> Result<Self, RegistryError> {
+        let source =
+            fs::read_to_string(path).map_err(|e| RegistryError::Io(path.to_path_buf(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json(&source),
+            Some("toml") => Self::from_toml(&source),
+            _ => Err(RegistryError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Build a registry from the built-in baseline and `paths`, applying the
+    /// module precedence: JSON dumps override the baseline and TOML files
+    /// override both, whatever the order of `paths`. Files of the same
+    /// format are applied in order.
+    pub fn load(paths: &[impl AsRef<Path>]) -> Result<Self, RegistryError> {
+        let mut registry = Self::builtin();
+        registry.merge_files(paths)?;
+        Ok(registry)
+    }
+
+    /// Add the definitions of `paths` with the precedence of [`Self::load`].
+    pub fn merge_files(&mut self, paths: &[impl AsRef<Path>]) -> Result<(), RegistryError> {
+        let (toml, other): (Vec<&Path>, Vec<&Path>) = paths
+            .iter()
+            .map(AsRef::as_ref)
+            .partition(|p| p.extension().is_some_and(|e| e == "toml"));
+        for path in other.into_iter().chain(toml) {
+            self.merge(Self::from_file(path)?);
+        }
+        Ok(())
+    }
+
+    /// Add the definitions of `other`, replacing actions and types defined
+    /// in both.
+    pub fn merge(&mut self, other: ActionRegistry) {
+        self.actions.extend(other.actions);
+        self.types.extend(other.types);
+        for parameter in other.common_parameters {
+            self.common_parameters.retain(|p| p.name != parameter.name);
+            self.common_parameters.push(parameter);
+        }
+    }
+
+    /// Look up an action by name.
+    pub fn get(&self, name: &str) -> Option<&ActionDef> {
+        self.actions.get(name)
+    }
+
+    /// All actions, sorted by name.
+    pub fn actions(&self) -> impl Iterator<Item = &ActionDef> {
+        self.actions.values()
+    }
+
+    /// Look up an enum or flags type by name, such as `GstSeekFlags`.
+    pub fn value_type(&self, name: &str) -> Option<&TypeDef> {
+        self.types.get(name)
+    }
+
+    /// Parameters accepted by every action, such as `playback-time`.
+    pub fn common_parameters(&self) -> &[ParameterDef] {
+        &self.common_parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        let registry = ActionRegistry::builtin();
+        let seek = registry.get("seek").unwrap();
+        assert_eq!(seek.name, "seek");
+        assert!(seek.parameter("start").unwrap().mandatory);
+        assert!(!seek.parameter("rate").unwrap().mandatory);
+        assert!(registry
+            .common_parameters()
+            .iter()
+            .any(|p| p.name == "playback-time"));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let dump = r#"{"action-types": [
+            {"name": "seek", "description": "Seeks", "parameters": [
+                {"name": "start", "mandatory": true, "types": "double", "possible-variables": "duration"},
+                {"name": "rate", "mandatory": false, "default-value": "1.0"}
+            ]},
+            {"name": "custom-action"}
+        ]}"#;
+        let registry = ActionRegistry::from_json(dump).unwrap();
+        assert_eq!(
+            registry
+                .get("seek")
+                .unwrap()
+                .parameter("rate")
+                .unwrap()
+                .default,
+            Some("1.0".to_string())
+        );
+        assert!(registry.get("custom-action").is_some());
+
+        assert!(ActionRegistry::from_json("[]")
+            .unwrap()
+            .actions()
+            .next()
+            .is_none());
+        assert!(ActionRegistry::from_json("{").is_err());
+    }
+
+    #[test]
+    fn test_merge_precedence() {
+        let mut registry = ActionRegistry::builtin();
+        registry.merge(
+            ActionRegistry::from_toml(
+                "[actions.seek]\ndescription = \"Project seek\"\n\
+                 [actions.my-action]\nparameters = [{ name = \"target\", mandatory = true }]\n",
+            )
+            .unwrap(),
+        );
+        let seek = registry.get("seek").unwrap();
+        assert_eq!(seek.description, "Project seek");
+        // Definitions are replaced as a whole
+        assert!(seek.parameters.is_empty());
+        assert!(registry.get("my-action").unwrap().parameters[0].mandatory);
+        assert!(registry.get("play").is_some());
+    }
+
+    #[test]
+    fn test_load_applies_toml_last() {
+        let dir =
+            std::env::temp_dir().join(format!("validatetest-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let toml = dir.join("actions.toml");
+        let json = dir.join("dump.json");
+        fs::write(&toml, "[actions.play]\ndescription = \"From TOML\"\n").unwrap();
+        fs::write(&json, r#"[{"name": "play", "description": "From JSON"}]"#).unwrap();
+
+        let registry = ActionRegistry::load(&[&toml, &json]).unwrap();
+        assert_eq!(registry.get("play").unwrap().description, "From TOML");
+
+        let err = ActionRegistry::load(&[dir.join("actions.yaml")]).unwrap_err();
+        assert!(matches!(err, RegistryError::Io(..)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
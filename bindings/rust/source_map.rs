@@ -0,0 +1,159 @@
+//! Mapping of locations between a file and its formatted version.
+//!
+//! Formatting moves text around, so a diagnostic computed on the original
+//! file points elsewhere in the formatted one, and the other way around. A
+//! [`SourceMap`] pairs the tokens the two versions have in common: names,
+//! values, punctuation and comments. Locations inside a paired token map to
+//! the same place in its counterpart; locations in the whitespace between
+//! tokens, or in tokens the formatter rewrote (such as a converted quoted
+//! structure), map to the end of the preceding paired token.
+
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use crate::diff::common_runs;
+use crate::document::parse_tree_with_errors;
+
+/// A token present in both versions, as its byte ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub input: Range<usize>,
+    pub output: Range<usize>,
+}
+
+/// Pairs of tokens of an input and output text, in order of both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    segments: Vec<Segment>,
+}
+
+/// Ranges of the tokens of `source`: the leaves of its parse tree, with
+/// strings and comments taken whole.
+fn tokens(source: &str) -> Vec<Range<usize>> {
+    fn collect(node: Node, tokens: &mut Vec<Range<usize>>) {
+        if node.child_count() == 0 || matches!(node.kind(), "string" | "comment") {
+            if !node.byte_range().is_empty() {
+                tokens.push(node.byte_range());
+            }
+            return;
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            collect(child, tokens);
+        }
+    }
+
+    let tree = parse_tree_with_errors(source);
+    let mut tokens = Vec::new();
+    collect(tree.root_node(), &mut tokens);
+    tokens
+}
+
+/// The offset in the counterpart of a segment of `offset`, given the
+/// segments' ranges on its side and on the other.
+fn map_offset(
+    segments: &[Segment],
+    offset: usize,
+    side: fn(&Segment) -> &Range<usize>,
+    other: fn(&Segment) -> &Range<usize>,
+) -> usize {
+    let index = segments.partition_point(|s| side(s).start <= offset);
+    let Some(segment) = index.checked_sub(1).map(|i| &segments[i]) else {
+        return 0;
+    };
+    let (range, target) = (side(segment), other(segment));
+    if offset < range.end {
+        (target.start + offset - range.start).min(target.end)
+    } else {
+        target.end
+    }
+}
+
+impl SourceMap {
+    /// Pair the tokens of `input` and of its formatted version `output`.
+    /// Either may have syntax errors, their tokens are then paired around
+    /// them.
+    pub fn new(input: &str, output: &str) -> Self {
+        let (input_tokens, output_tokens) = (tokens(input), tokens(output));
+        let a: Vec<&str> = input_tokens.iter().map(|t| &input[t.clone()]).collect();
+        let b: Vec<&str> = output_tokens.iter().map(|t| &output[t.clone()]).collect();
+
+        let segments = common_runs(&a, &b)
+            .into_iter()
+            .flat_map(|(i, j, len)| (0..len).map(move |k| (i + k, j + k)))
+            .map(|(i, j)| Segment {
+                input: input_tokens[i].clone(),
+                output: output_tokens[j].clone(),
+            })
+            .collect();
+        Self { segments }
+    }
+
+    /// The paired tokens, in order.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Offset of the output corresponding to the input `offset`.
+    pub fn to_output(&self, offset: usize) -> usize {
+        map_offset(&self.segments, offset, |s| &s.input, |s| &s.output)
+    }
+
+    /// Offset of the input corresponding to the output `offset`.
+    pub fn to_input(&self, offset: usize) -> usize {
+        map_offset(&self.segments, offset, |s| &s.output, |s| &s.input)
+    }
+
+    /// Range of the output corresponding to the input `range`.
+    pub fn output_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.to_output(range.start);
+        start..self.to_output(range.end).max(start)
+    }
+
+    /// Range of the input corresponding to the output `range`.
+    pub fn input_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.to_input(range.start);
+        start..self.to_input(range.end).max(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{format_source, FormatterConfig};
+
+    #[test]
+    fn test_source_map() {
+        let input = "seek,start=1.0 ,   flags=flush # go\nplay\n";
+        let output = format_source(input, &FormatterConfig::default()).unwrap();
+        assert_eq!(output, "seek, start=1.0, flags=flush  # go\nplay\n");
+        let map = SourceMap::new(input, &output);
+
+        let flags = input.find("flags").unwrap();
+        let range = map.output_range(flags + 1..flags + 5);
+        assert_eq!(&output[range.clone()], "lags");
+        assert_eq!(map.input_range(range), flags + 1..flags + 5);
+        assert_eq!(
+            &output[map.output_range(input.find("# go").unwrap()..input.len() - 1)],
+            "# go\nplay"
+        );
+        // Whitespace maps after the preceding token
+        assert_eq!(
+            map.to_output(input.find("   ").unwrap()),
+            output.find(", flags").unwrap() + 1
+        );
+    }
+
+    #[test]
+    fn test_rewritten_tokens() {
+        let input = "meta, expected-issues={\"expected-issue, issue-id=a::b\"}\nplay\n";
+        let output = format_source(input, &FormatterConfig::default()).unwrap();
+        let map = SourceMap::new(input, &output);
+        // The converted string maps to the end of `{`
+        let string = input.find('"').unwrap();
+        assert_eq!(map.to_output(string + 3), output.find('{').unwrap() + 1);
+        let play = input.find("play").unwrap();
+        assert_eq!(map.to_output(play), output.find("play").unwrap());
+    }
+}
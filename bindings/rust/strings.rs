@@ -0,0 +1,172 @@
+//! GstStructure string escaping.
+//!
+//! The quoted strings of ValidateTest files are read by
+//! `gst_structure_from_string`: a backslash stands for the character after
+//! it, so `\"` is a quote and `\\` a backslash, except for three octal
+//! digits starting with `0` to `3`, which stand for a byte (`\303\251` is
+//! `é`). [`unescape`] and [`unquote`] read strings that way; [`escape`] and
+//! [`quote`] write text that reads back the same, only escaping what they
+//! have to.
+//!
+//! A structure serialized in a string is escaped once more at each level:
+//!
+//! ```
+//! use tree_sitter_validatetest::strings::{quote, unquote};
+//!
+//! let issue = format!("expected-issue, details={}", quote("said \"no\""));
+//! assert_eq!(issue, r#"expected-issue, details="said \"no\"""#);
+//! let field = quote(&issue);
+//! assert_eq!(field, r#""expected-issue, details=\"said \\\"no\\\"\"""#);
+//! assert_eq!(unquote(&field).unwrap(), issue);
+//! ```
+
+use std::fmt;
+
+/// Why [`unescape`] rejected a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnescapeError {
+    /// A `"` not escaped by a backslash, at this byte offset, which would end
+    /// the string.
+    UnescapedQuote(usize),
+    /// A backslash ending the text.
+    TrailingBackslash,
+    /// An octal escape at this byte offset with fewer than three digits.
+    InvalidOctal(usize),
+    /// The escaped bytes are not UTF-8.
+    InvalidUtf8,
+    /// [`unquote`] was given a text not enclosed in double quotes.
+    NotQuoted,
+}
+
+impl fmt::Display for UnescapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnescapeError::UnescapedQuote(offset) => {
+                write!(f, "unescaped quote at byte {}", offset)
+            }
+            UnescapeError::TrailingBackslash => write!(f, "string ending with a backslash"),
+            UnescapeError::InvalidOctal(offset) => {
+                write!(f, "invalid octal escape at byte {}", offset)
+            }
+            UnescapeError::InvalidUtf8 => write!(f, "escaped bytes are not valid UTF-8"),
+            UnescapeError::NotQuoted => write!(f, "string not enclosed in double quotes"),
+        }
+    }
+}
+
+impl std::error::Error for UnescapeError {}
+
+/// The text `content`, written between the quotes of a string, stands for.
+pub fn unescape(content: &str) -> Result<String, UnescapeError> {
+    let bytes = content.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Err(UnescapeError::UnescapedQuote(i)),
+            b'\\' => {
+                let Some(&next) = bytes.get(i + 1) else {
+                    return Err(UnescapeError::TrailingBackslash);
+                };
+                if (b'0'..=b'3').contains(&next) {
+                    let digits = bytes.get(i + 1..i + 4).filter(|digits| {
+                        digits[1..]
+                            .iter()
+                            .all(|digit| (b'0'..=b'7').contains(digit))
+                    });
+                    let Some(digits) = digits else {
+                        return Err(UnescapeError::InvalidOctal(i));
+                    };
+                    result.push(
+                        digits
+                            .iter()
+                            .fold(0, |byte, digit| byte << 3 | (digit - b'0')),
+                    );
+                    i += 4;
+                } else {
+                    // The escaped character may be several bytes long; they
+                    // are copied by the next iterations
+                    result.push(next);
+                    i += 2;
+                }
+            }
+            byte => {
+                result.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(result).map_err(|_| UnescapeError::InvalidUtf8)
+}
+
+/// `text` escaped to be written between the quotes of a string.
+///
+/// Quotes and backslashes get a backslash, and control characters an octal
+/// escape, which keeps the string on one line. GStreamer itself escapes
+/// more characters when serializing, such as spaces, but reads both alike.
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_ascii_control() => result.push_str(&format!("\\{:03o}", c as u8)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// `text` as a quoted string.
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+/// The text the quoted string `quoted` stands for.
+pub fn unquote(quoted: &str) -> Result<String, UnescapeError> {
+    let content = quoted
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or(UnescapeError::NotQuoted)?;
+    unescape(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r#"a \"b\" c\\d"#).unwrap(), r#"a "b" c\d"#);
+        // Any other escaped character stands for itself
+        assert_eq!(unescape(r"\n\t\é\$(x)").unwrap(), "nté$(x)");
+        assert_eq!(unescape(r"\303\251t\303\251 \0411\4").unwrap(), "été !14");
+
+        assert_eq!(unescape("a\"b"), Err(UnescapeError::UnescapedQuote(1)));
+        assert_eq!(unescape("a\\"), Err(UnescapeError::TrailingBackslash));
+        assert_eq!(unescape(r"a\38"), Err(UnescapeError::InvalidOctal(1)));
+        assert_eq!(unescape(r"\30"), Err(UnescapeError::InvalidOctal(0)));
+        assert_eq!(unescape(r"\377"), Err(UnescapeError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"a "b" c\d"#), r#"a \"b\" c\\d"#);
+        assert_eq!(escape("line\nnext\ttab\u{7f}"), r"line\012next\011tab\177");
+        assert_eq!(escape("été $(x), [a]"), "été $(x), [a]");
+
+        for text in ["", "a \"b\"", "\\", "\\\"", "\n\r", "\u{1}é", "\\303"] {
+            assert_eq!(unescape(&escape(text)).unwrap(), text);
+            assert_eq!(unquote(&quote(text)).unwrap(), text);
+            // Nested in a serialized structure
+            assert_eq!(
+                unquote(&unquote(&quote(&quote(text))).unwrap()).unwrap(),
+                text
+            );
+        }
+        assert_eq!(unquote("abc"), Err(UnescapeError::NotQuoted));
+        assert_eq!(unquote("\""), Err(UnescapeError::NotQuoted));
+    }
+}
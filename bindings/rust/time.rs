@@ -0,0 +1,226 @@
+//! GstClockTime values: parsing, serializing and arithmetic.
+//!
+//! Times are written in scenarios as integers of nanoseconds
+//! (`(GstClockTime)5000000000`), as floating point seconds (`5.0`) or as
+//! `H:MM:SS.nnnnnnnnn` clock strings (`"0:00:05.000000000"`). [`ClockTime`]
+//! reads all of them and keeps nanoseconds, so times compare exactly.
+
+use std::fmt;
+use std::ops::{Add, AddAssign};
+
+/// A time or duration, in nanoseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClockTime(u64);
+
+impl ClockTime {
+    pub const ZERO: ClockTime = ClockTime(0);
+    pub const NSECOND: u64 = 1;
+    pub const USECOND: u64 = 1_000;
+    pub const MSECOND: u64 = 1_000_000;
+    pub const SECOND: u64 = 1_000_000_000;
+
+    pub const fn from_nseconds(nseconds: u64) -> Self {
+        Self(nseconds)
+    }
+
+    /// `seconds` as a time, or `None` if negative or out of range.
+    pub fn from_seconds_f64(seconds: f64) -> Option<Self> {
+        let nseconds = (seconds * Self::SECOND as f64).round();
+        (seconds.is_finite() && nseconds >= 0.0 && nseconds <= u64::MAX as f64)
+            .then_some(Self(nseconds as u64))
+    }
+
+    pub const fn nseconds(self) -> u64 {
+        self.0
+    }
+
+    pub fn seconds_f64(self) -> f64 {
+        self.0 as f64 / Self::SECOND as f64
+    }
+
+    /// Parse a time: an integer of nanoseconds, floating point seconds or a
+    /// `H:MM:SS.fraction` clock string.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Ok(nseconds) = text.parse::<u64>() {
+            return Some(Self(nseconds));
+        }
+        Self::parse_clock(text).or_else(|| Self::parse_seconds(text))
+    }
+
+    /// Parse a time where numbers are seconds, whether integer or not, as
+    /// for `playback-time`, or a `H:MM:SS.fraction` clock string.
+    pub fn parse_seconds(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(time) = Self::parse_clock(text) {
+            return Some(time);
+        }
+        decimal_nseconds(text).map(Self).or_else(|| {
+            // Exponents and other forms only floating point parsing reads
+            Self::from_seconds_f64(text.parse().ok()?)
+        })
+    }
+
+    /// Parse a `H:MM:SS.fraction` clock string.
+    pub fn parse_clock(text: &str) -> Option<Self> {
+        let mut parts = text.splitn(3, ':');
+        let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+        let hours: u64 = hours.parse().ok()?;
+        let minutes: u64 = minutes.parse().ok()?;
+        let seconds = decimal_nseconds(seconds)?;
+        if minutes >= 60 || seconds >= 60 * Self::SECOND {
+            return None;
+        }
+        hours
+            .checked_mul(3600)?
+            .checked_add(minutes * 60)?
+            .checked_mul(Self::SECOND)?
+            .checked_add(seconds)
+            .map(Self)
+    }
+
+    /// The time as floating point seconds without trailing zeros, such as
+    /// `1.5`.
+    pub fn to_seconds_string(self) -> String {
+        let seconds = self.0 / Self::SECOND;
+        let fraction = self.0 % Self::SECOND;
+        if fraction == 0 {
+            return format!("{}.0", seconds);
+        }
+        let fraction = format!("{:09}", fraction);
+        format!("{}.{}", seconds, fraction.trim_end_matches('0'))
+    }
+
+    /// The time in the largest unit keeping it readable, such as `5 s` or
+    /// `1.5 ms`.
+    pub fn to_duration_string(self) -> String {
+        let units = [
+            (Self::SECOND, "s"),
+            (Self::MSECOND, "ms"),
+            (Self::USECOND, "µs"),
+        ];
+        for (scale, unit) in units {
+            if self.0 >= scale {
+                let value = format!("{:.9}", self.0 as f64 / scale as f64);
+                let value = value.trim_end_matches('0').trim_end_matches('.');
+                return format!("{} {}", value, unit);
+            }
+        }
+        format!("{} ns", self.0)
+    }
+
+    pub fn checked_add(self, duration: ClockTime) -> Option<Self> {
+        self.0.checked_add(duration.0).map(Self)
+    }
+
+    pub fn checked_sub(self, duration: ClockTime) -> Option<Self> {
+        self.0.checked_sub(duration.0).map(Self)
+    }
+
+    /// Time elapsed from `earlier` to `self`, zero if `earlier` is later.
+    pub fn saturating_sub(self, earlier: ClockTime) -> Self {
+        Self(self.0.saturating_sub(earlier.0))
+    }
+}
+
+/// Nanoseconds of non-negative decimal seconds such as `12` or `1.5`,
+/// without going through floating point. Digits past nanoseconds are
+/// truncated.
+fn decimal_nseconds(text: &str) -> Option<u64> {
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if integer.is_empty() && fraction.is_empty()
+        || !integer.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let integer: u64 = if integer.is_empty() {
+        0
+    } else {
+        integer.parse().ok()?
+    };
+    let fraction = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+    integer
+        .checked_mul(ClockTime::SECOND)?
+        .checked_add(fraction.parse().ok()?)
+}
+
+/// The `H:MM:SS.nnnnnnnnn` clock string, as `GST_TIME_FORMAT` prints it.
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.0 / Self::SECOND;
+        write!(
+            f,
+            "{}:{:02}:{:02}.{:09}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            self.0 % Self::SECOND
+        )
+    }
+}
+
+impl Add for ClockTime {
+    type Output = ClockTime;
+
+    fn add(self, duration: ClockTime) -> ClockTime {
+        self.checked_add(duration)
+            .expect("overflow when adding clock times")
+    }
+}
+
+impl AddAssign for ClockTime {
+    fn add_assign(&mut self, duration: ClockTime) {
+        *self = *self + duration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let time = |s| ClockTime::parse(s).map(ClockTime::nseconds);
+        assert_eq!(time("5000000000"), Some(5_000_000_000));
+        assert_eq!(time("1.5"), Some(1_500_000_000));
+        assert_eq!(time("0:01:02.5"), Some(62_500_000_000));
+        assert_eq!(time("1:00:00.000000001"), Some(3_600_000_000_001));
+        assert_eq!(time("0:61:00"), None);
+        assert_eq!(time("-1.0"), None);
+        assert_eq!(time("later"), None);
+
+        let seconds = |s| ClockTime::parse_seconds(s).map(ClockTime::nseconds);
+        assert_eq!(seconds("5"), Some(5_000_000_000));
+        assert_eq!(seconds("1e-3"), Some(1_000_000));
+        assert_eq!(seconds("0.1234567891"), Some(123_456_789));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let time = ClockTime::from_nseconds(3_723_500_000_000);
+        assert_eq!(time.to_string(), "1:02:03.500000000");
+        assert_eq!(ClockTime::parse(&time.to_string()), Some(time));
+        assert_eq!(time.to_seconds_string(), "3723.5");
+        assert_eq!(ClockTime::ZERO.to_seconds_string(), "0.0");
+        let duration = |ns| ClockTime::from_nseconds(ns).to_duration_string();
+        assert_eq!(duration(5_000_000_000), "5 s");
+        assert_eq!(duration(1_500_000), "1.5 ms");
+        assert_eq!(duration(2_000), "2 µs");
+        assert_eq!(duration(12), "12 ns");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut time = ClockTime::parse("1.5").unwrap();
+        time += ClockTime::parse("0.5").unwrap();
+        assert_eq!(time, ClockTime::parse("0:00:02").unwrap());
+        assert!(time > ClockTime::parse("1999999999").unwrap());
+        assert_eq!(
+            ClockTime::ZERO.checked_sub(time),
+            None,
+            "clock times are never negative"
+        );
+        assert_eq!(ClockTime::ZERO.saturating_sub(time), ClockTime::ZERO);
+    }
+}
@@ -0,0 +1,237 @@
+//! Comments and blank lines attached to the nodes they belong with.
+//!
+//! The grammar allows comments anywhere, as extras, so the parse tree puts
+//! them wherever they happen to be: between two fields, array elements or
+//! structures. [`Trivia`] attaches each of them to one owner, the item of a
+//! list (a structure of the file, a field, a block or array element) they
+//! are written next to:
+//!
+//! - a comment on the line of the end of an item *trails* it,
+//! - other comments *lead* the item following them,
+//! - comments after the last item *dangle* in the list itself.
+//!
+//! A comment written inside an item, such as between a field name and its
+//! value, leads that item. Every comment gets exactly one owner, which is
+//! what lets the formatter guarantee that it writes them all back.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+/// Nodes whose children are lists of items, one per line when split.
+const LIST_KINDS: &[&str] = &[
+    "source_file",
+    "field_list",
+    "nested_structure_block",
+    "array",
+    "angle_bracket_array",
+];
+
+/// A comment attached to a node.
+#[derive(Debug, Clone, Copy)]
+pub struct Comment<'tree> {
+    pub node: Node<'tree>,
+    /// Blank lines between the comment and what precedes it.
+    pub blank_lines_before: usize,
+}
+
+/// The comments and blank lines of a parse tree, by owner node.
+#[derive(Debug, Clone, Default)]
+pub struct Trivia<'tree> {
+    leading: HashMap<usize, Vec<Comment<'tree>>>,
+    trailing: HashMap<usize, Comment<'tree>>,
+    dangling: HashMap<usize, Vec<Comment<'tree>>>,
+    blank_lines: HashMap<usize, usize>,
+    /// Start bytes of all comments, sorted.
+    starts: Vec<usize>,
+}
+
+/// Whether `node` is an item of the list it is a child of.
+fn is_item(node: Node) -> bool {
+    node.is_named() && !matches!(node.kind(), "comment" | "line_continuation")
+}
+
+/// Blank lines in `source` between the byte offsets `end` and `start`.
+fn blank_lines_between(source: &str, end: usize, start: usize) -> usize {
+    if start <= end {
+        return 0;
+    }
+    let newlines = source[end..start].matches('\n').count();
+    newlines.saturating_sub(1)
+}
+
+impl<'tree> Trivia<'tree> {
+    /// Attach the comments under `root` of `source`.
+    pub fn new(root: Node<'tree>, source: &str) -> Self {
+        let mut trivia = Self::default();
+        trivia.visit(root, source);
+        trivia.starts.sort_unstable();
+        trivia
+    }
+
+    fn visit(&mut self, node: Node<'tree>, source: &str) {
+        let mut cursor = node.walk();
+        let children: Vec<Node<'tree>> = node.children(&mut cursor).collect();
+        if LIST_KINDS.contains(&node.kind()) {
+            self.attach_list(node, &children, source);
+        } else {
+            // Comments outside of lists lead the item holding them, or the
+            // first item of a list following them, such as the first field
+            // after `seek,`
+            for (i, child) in children.iter().enumerate() {
+                if child.kind() != "comment" {
+                    continue;
+                }
+                let owner = children[i + 1..]
+                    .iter()
+                    .find(|c| is_item(**c))
+                    .filter(|c| LIST_KINDS.contains(&c.kind()))
+                    .and_then(|list| {
+                        let mut cursor = list.walk();
+                        let first = list.named_children(&mut cursor).find(|c| is_item(*c));
+                        first
+                    })
+                    .unwrap_or_else(|| self.owner(node));
+                self.starts.push(child.start_byte());
+                self.leading.entry(owner.id()).or_default().push(Comment {
+                    node: *child,
+                    blank_lines_before: 0,
+                });
+            }
+        }
+        for child in children.iter().filter(|c| c.kind() != "comment") {
+            self.visit(*child, source);
+        }
+    }
+
+    /// The item of a list `node` is part of, or the root.
+    fn owner(&self, node: Node<'tree>) -> Node<'tree> {
+        let mut owner = node;
+        while let Some(parent) = owner.parent() {
+            if LIST_KINDS.contains(&parent.kind()) {
+                return owner;
+            }
+            owner = parent;
+        }
+        owner
+    }
+
+    fn attach_list(&mut self, list: Node<'tree>, children: &[Node<'tree>], source: &str) {
+        let mut previous: Option<Node<'tree>> = None;
+        // End of the last item or comment, for counting blank lines
+        let mut end = children.first().map_or(0, |c| c.start_byte());
+        let mut pending: Vec<Comment<'tree>> = Vec::new();
+        for child in children {
+            let blank_lines_before = blank_lines_between(source, end, child.start_byte());
+            if child.kind() == "comment" {
+                self.starts.push(child.start_byte());
+                let comment = Comment {
+                    node: *child,
+                    blank_lines_before,
+                };
+                let trails = previous.is_some_and(|p| {
+                    p.end_position().row == child.start_position().row
+                        && !self.trailing.contains_key(&p.id())
+                });
+                match previous {
+                    Some(previous) if trails && pending.is_empty() => {
+                        self.trailing.insert(previous.id(), comment);
+                    }
+                    _ => pending.push(comment),
+                }
+                end = child.end_byte();
+            } else if is_item(*child) {
+                self.blank_lines.insert(child.id(), blank_lines_before);
+                if !pending.is_empty() {
+                    self.leading
+                        .entry(child.id())
+                        .or_default()
+                        .append(&mut pending);
+                }
+                previous = Some(*child);
+                end = child.end_byte();
+            }
+        }
+        if !pending.is_empty() {
+            self.dangling.insert(list.id(), pending);
+        }
+    }
+
+    /// Comments written before `node`, each on its own line.
+    pub fn leading(&self, node: Node) -> &[Comment<'tree>] {
+        self.leading.get(&node.id()).map_or(&[], Vec::as_slice)
+    }
+
+    /// The comment ending the last line of `node`.
+    pub fn trailing(&self, node: Node) -> Option<&Comment<'tree>> {
+        self.trailing.get(&node.id())
+    }
+
+    /// Comments after the last item of the list `node`.
+    pub fn dangling(&self, node: Node) -> &[Comment<'tree>] {
+        self.dangling.get(&node.id()).map_or(&[], Vec::as_slice)
+    }
+
+    /// Blank lines before the item `node`, after its leading comments.
+    pub fn blank_lines_before(&self, node: Node) -> usize {
+        self.blank_lines.get(&node.id()).copied().unwrap_or(0)
+    }
+
+    /// Whether a comment is written within the range of `node`.
+    pub fn has_comments(&self, node: Node) -> bool {
+        let range: Range<usize> = node.byte_range();
+        let index = self.starts.partition_point(|s| *s < range.start);
+        self.starts.get(index).is_some_and(|s| *s < range.end)
+    }
+
+    /// Number of comments of the tree.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse_tree;
+
+    #[test]
+    fn test_attach() {
+        let source = "# header\n\nseek, start=1.0, \\\n  # flags\n  flags=flush  # trailing\n\nplay\n# end\n";
+        let tree = parse_tree(source).unwrap();
+        let root = tree.root_node();
+        let trivia = Trivia::new(root, source);
+        let text = |c: &Comment| &source[c.node.byte_range()];
+        assert_eq!(trivia.len(), 4);
+
+        let seek = root.named_child(1).unwrap();
+        assert_eq!(seek.kind(), "structure");
+        assert_eq!(trivia.leading(seek).len(), 1);
+        assert_eq!(text(&trivia.leading(seek)[0]), "# header");
+        assert_eq!(trivia.blank_lines_before(seek), 1);
+        assert_eq!(trivia.leading(seek)[0].blank_lines_before, 0);
+        assert!(trivia.has_comments(seek));
+
+        let fields = seek.named_child(1).unwrap();
+        let mut cursor = fields.walk();
+        let flags = fields
+            .named_children(&mut cursor)
+            .filter(|c| c.kind() == "field")
+            .nth(1)
+            .unwrap();
+        assert_eq!(text(&trivia.leading(flags)[0]), "# flags");
+        // The structure ends with the line
+        assert!(trivia.trailing(flags).is_none());
+        assert_eq!(text(trivia.trailing(seek).unwrap()), "# trailing");
+
+        let play = root.named_child(3).unwrap();
+        assert_eq!(trivia.blank_lines_before(play), 1);
+        assert!(!trivia.has_comments(play));
+        assert_eq!(text(&trivia.dangling(root)[0]), "# end");
+    }
+}
@@ -0,0 +1,749 @@
+//! Field values parsed the way GstStructure deserializes them.
+//!
+//! [`Value::from_field_value`] turns a [`FieldValue`] as written in a
+//! document into typed data: `5` is a [`Value::Int`], `(double)5` a
+//! [`Value::Double`], `30000/1001` a [`Value::Fraction`] and `{a, b}` a
+//! [`Value::List`] of strings. Variables and expressions are only resolved
+//! by gst-validate at runtime and stay strings.
+//!
+//! [`Structure::get`] reads a field straight into a Rust type:
+//!
+//! ```
+//! use tree_sitter_validatetest::time::ClockTime;
+//! use tree_sitter_validatetest::Document;
+//!
+//! let document = Document::parse("seek, playback-time=1.5, start=(GstClockTime)0, rate=2\n")?;
+//! let seek = &document.structures[0];
+//! assert_eq!(seek.get::<f64>("playback-time")?, 1.5);
+//! assert_eq!(seek.get_clock_time("start")?, ClockTime::ZERO);
+//! assert_eq!(seek.get::<f64>("rate")?, 2.0);
+//! assert!(seek.get_str("rate").is_err());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+
+use crate::document::{FieldValue, Scalar, ScalarKind, Structure};
+use crate::time::ClockTime;
+
+/// A typed field value.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    /// A value cast to `uint`, `uint64` or one of their aliases.
+    UInt(u64),
+    Double(f64),
+    Bool(bool),
+    Str(String),
+    Fraction(i32, i32),
+    /// A value cast to `GstClockTime`.
+    ClockTime(ClockTime),
+    /// A `{...}` list, or a `[...]` array of values.
+    List(Vec<Value>),
+    /// A `<...>` GstValueArray.
+    Array(Vec<Value>),
+    Structure(Structure),
+    /// A value cast to a type whose values are not interpreted here, such as
+    /// an enum or `GstCaps`, as written without quotes.
+    Other {
+        type_name: String,
+        text: String,
+    },
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::UInt(a), Value::UInt(b)) => a == b,
+            (Value::Double(a), Value::Double(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Fraction(an, ad), Value::Fraction(bn, bd)) => an == bn && ad == bd,
+            (Value::ClockTime(a), Value::ClockTime(b)) => a == b,
+            (Value::List(a), Value::List(b)) | (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Structure(a), Value::Structure(b)) => a.semantically_eq(b),
+            (
+                Value::Other { type_name, text },
+                Value::Other {
+                    type_name: other_type,
+                    text: other_text,
+                },
+            ) => type_name == other_type && text == other_text,
+            _ => false,
+        }
+    }
+}
+
+/// Error returned for a value that is not valid for its type cast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    pub type_name: String,
+    pub text: String,
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is not a valid {}", self.text, self.type_name)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// Error returned by [`Structure::get`] and the other field accessors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    /// The structure has no field with this name.
+    Missing(String),
+    /// The value is not valid for its type cast.
+    Invalid(ValueError),
+    /// The value has a type that does not convert to the requested one.
+    WrongType {
+        field: String,
+        expected: &'static str,
+        found: String,
+    },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::Missing(field) => write!(f, "missing field '{}'", field),
+            FieldError::Invalid(error) => error.fmt(f),
+            FieldError::WrongType {
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "field '{}' has type {}, expected {}",
+                field, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+impl From<ValueError> for FieldError {
+    fn from(error: ValueError) -> Self {
+        FieldError::Invalid(error)
+    }
+}
+
+/// Types GstStructure deserializes values to, see [`ValueType::from_name`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueType {
+    Int,
+    UInt,
+    Int64,
+    UInt64,
+    Double,
+    Boolean,
+    String,
+    Fraction,
+    ClockTime,
+    Flags,
+    Structure,
+}
+
+/// Type names accepted in `(type)value` casts, with their aliases.
+const VALUE_TYPES: &[(ValueType, &[&str])] = &[
+    (ValueType::Int, &["int", "i", "gint"]),
+    (ValueType::UInt, &["uint", "u", "guint"]),
+    (ValueType::Int64, &["int64", "gint64"]),
+    (ValueType::UInt64, &["uint64", "guint64"]),
+    (
+        ValueType::Double,
+        &["double", "d", "gdouble", "float", "f", "gfloat"],
+    ),
+    (ValueType::Boolean, &["boolean", "bool", "b", "gboolean"]),
+    (ValueType::String, &["string", "str", "s", "gchararray"]),
+    (ValueType::Fraction, &["fraction", "GstFraction"]),
+    (ValueType::ClockTime, &["GstClockTime"]),
+    (ValueType::Flags, &["flags"]),
+    (ValueType::Structure, &["structure", "GstStructure"]),
+];
+
+/// Parse an integer, possibly hexadecimal, as GstStructure does.
+fn parse_integer(value: &str) -> Option<i128> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let number = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    Some(if negative { -number } else { number })
+}
+
+fn parse_boolean(value: &str) -> Option<bool> {
+    if ["true", "yes", "t", "1"]
+        .iter()
+        .any(|b| b.eq_ignore_ascii_case(value))
+    {
+        Some(true)
+    } else if ["false", "no", "f", "0"]
+        .iter()
+        .any(|b| b.eq_ignore_ascii_case(value))
+    {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// The fraction closest to `value`, by continued fractions, as
+/// `gst_util_double_to_fraction` does.
+fn double_to_fraction(value: f64) -> Option<(i32, i32)> {
+    if !value.is_finite() || value.abs() > i32::MAX as f64 {
+        return None;
+    }
+    let (mut n0, mut d0, mut n1, mut d1) = (0i64, 1i64, 1i64, 0i64);
+    let mut rest = value.abs();
+    for _ in 0..30 {
+        let a = rest.floor() as i64;
+        let (n2, d2) = (a * n1 + n0, a * d1 + d0);
+        if n2 > i32::MAX as i64 || d2 > i32::MAX as i64 {
+            break;
+        }
+        (n0, d0, n1, d1) = (n1, d1, n2, d2);
+        let fraction = rest - a as f64;
+        if fraction.abs() < 1e-10 || (n1 as f64 / d1 as f64 - value.abs()).abs() < 1e-10 {
+            break;
+        }
+        rest = 1.0 / fraction;
+    }
+    let numerator = if value < 0.0 { -n1 } else { n1 };
+    Some((numerator as i32, d1 as i32))
+}
+
+fn parse_fraction(value: &str) -> Option<(i32, i32)> {
+    match value.split_once('/') {
+        Some((n, d)) => {
+            let n = i32::try_from(parse_integer(n.trim())?).ok()?;
+            let d = i32::try_from(parse_integer(d.trim())?).ok()?;
+            (d != 0).then_some((n, d))
+        }
+        None => double_to_fraction(value.parse().ok()?),
+    }
+}
+
+impl ValueType {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        VALUE_TYPES
+            .iter()
+            .find(|(_, names)| names.contains(&name))
+            .map(|(value_type, _)| *value_type)
+    }
+
+    /// `text`, the unquoted value, deserialized as this type.
+    pub(crate) fn parse(self, type_name: &str, text: &str) -> Option<Value> {
+        let integer = |min: i128, max: i128| parse_integer(text).filter(|n| *n >= min && *n <= max);
+        Some(match self {
+            ValueType::Int => Value::Int(integer(i32::MIN.into(), i32::MAX.into())? as i64),
+            ValueType::Int64 => Value::Int(integer(i64::MIN.into(), i64::MAX.into())? as i64),
+            ValueType::UInt => Value::UInt(integer(0, u32::MAX.into())? as u64),
+            ValueType::UInt64 => Value::UInt(integer(0, u64::MAX.into())? as u64),
+            ValueType::ClockTime => {
+                Value::ClockTime(ClockTime::from_nseconds(integer(0, u64::MAX.into())? as u64))
+            }
+            ValueType::Double => Value::Double(
+                text.parse::<f64>()
+                    .ok()
+                    .or_else(|| parse_integer(text).map(|n| n as f64))?,
+            ),
+            ValueType::Boolean => Value::Bool(parse_boolean(text)?),
+            ValueType::String => Value::Str(text.to_string()),
+            ValueType::Fraction => {
+                let (n, d) = parse_fraction(text)?;
+                Value::Fraction(n, d)
+            }
+            ValueType::Flags => {
+                let valid = text.split(['+', '|']).all(|flag| {
+                    let flag = flag.trim();
+                    parse_integer(flag).is_some()
+                        || (!flag.is_empty()
+                            && flag
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+                });
+                if !valid {
+                    return None;
+                }
+                Value::Other {
+                    type_name: type_name.to_string(),
+                    text: text.to_string(),
+                }
+            }
+            ValueType::Structure => {
+                let mut document = crate::Document::parse(text).ok()?;
+                if document.structures.len() != 1 {
+                    return None;
+                }
+                Value::Structure(document.structures.pop()?)
+            }
+        })
+    }
+}
+
+/// Untyped scalar: quoted strings, variables and expressions stay strings,
+/// other values go through [`Value::parse_untyped`].
+fn untyped_scalar(scalar: &Scalar) -> Value {
+    match scalar.kind {
+        ScalarKind::Number
+        | ScalarKind::HexNumber
+        | ScalarKind::Fraction
+        | ScalarKind::Boolean
+        | ScalarKind::Unquoted => Value::parse_untyped(&scalar.text),
+        _ => Value::Str(scalar.unquoted()),
+    }
+}
+
+/// A scalar cast to `type_name`.
+fn typed_scalar(type_name: &str, scalar: &Scalar) -> Result<Value, ValueError> {
+    let text = scalar.unquoted();
+    // Only resolved at runtime
+    if matches!(scalar.kind, ScalarKind::Variable | ScalarKind::Expression) || text.contains("$(") {
+        return Ok(Value::Str(text));
+    }
+    match ValueType::from_name(type_name) {
+        Some(value_type) => value_type.parse(type_name, &text).ok_or(ValueError {
+            type_name: type_name.to_string(),
+            text,
+        }),
+        None => Ok(Value::Other {
+            type_name: type_name.to_string(),
+            text,
+        }),
+    }
+}
+
+fn typed_value(type_name: &str, value: &FieldValue) -> Result<Value, ValueError> {
+    let typed_all = |values: &[FieldValue]| {
+        values
+            .iter()
+            .map(|v| typed_value(type_name, v))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    match value {
+        FieldValue::Scalar(scalar) => typed_scalar(type_name, scalar),
+        // A cast applies to each element of a list
+        FieldValue::Array(values) | FieldValue::Block(values) => {
+            Ok(Value::List(typed_all(values)?))
+        }
+        FieldValue::ValueArray(values) => Ok(Value::Array(typed_all(values)?)),
+        // The innermost cast wins
+        FieldValue::Typed { type_name, value } => typed_value(type_name, value),
+        FieldValue::Structure(structure) => Ok(Value::Structure(structure.clone())),
+    }
+}
+
+impl Value {
+    /// `text` deserialized without a type cast, as the first type it is
+    /// valid for, as GstStructure does: int, double, fraction, boolean, then
+    /// string.
+    pub fn parse_untyped(text: &str) -> Value {
+        if let Some(n) = parse_integer(text).and_then(|n| i64::try_from(n).ok()) {
+            return Value::Int(n);
+        }
+        // Unlike GStreamer, Rust also reads words such as `inf` as doubles
+        let numeric = text
+            .bytes()
+            .next()
+            .is_some_and(|b| b.is_ascii_digit() || b"+-.".contains(&b));
+        if numeric {
+            if let Ok(d) = text.parse::<f64>() {
+                return Value::Double(d);
+            }
+            if let Some((n, d)) = text.contains('/').then(|| parse_fraction(text)).flatten() {
+                return Value::Fraction(n, d);
+            }
+        }
+        match parse_boolean(text) {
+            Some(b) => Value::Bool(b),
+            None => Value::Str(text.to_string()),
+        }
+    }
+
+    /// Parse `value` following the GstStructure deserialization rules.
+    pub fn from_field_value(value: &FieldValue) -> Result<Self, ValueError> {
+        let all = |values: &[FieldValue]| {
+            values
+                .iter()
+                .map(Value::from_field_value)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match value {
+            FieldValue::Scalar(scalar) => Ok(untyped_scalar(scalar)),
+            FieldValue::Typed { type_name, value } => typed_value(type_name, value),
+            FieldValue::Array(values) => match values.as_slice() {
+                [FieldValue::Structure(structure)] => Ok(Value::Structure(structure.clone())),
+                values => Ok(Value::List(all(values)?)),
+            },
+            FieldValue::Block(values) => Ok(Value::List(all(values)?)),
+            FieldValue::ValueArray(values) => Ok(Value::Array(all(values)?)),
+            FieldValue::Structure(structure) => Ok(Value::Structure(structure.clone())),
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            Value::UInt(n) => i64::try_from(*n).ok(),
+            Value::ClockTime(time) => i64::try_from(time.nseconds()).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a double, converting integers and fractions.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Double(d) => Some(*d),
+            Value::Int(n) => Some(*n as f64),
+            Value::UInt(n) => Some(*n as f64),
+            Value::ClockTime(time) => Some(time.nseconds() as f64),
+            Value::Fraction(n, d) => Some(*n as f64 / *d as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Rust types a [`Value`] converts to, for [`Structure::get`].
+///
+/// Conversions are those GValue transforms between GstStructure types
+/// without losing information: integers of any type fit in `i64` or `u64`
+/// when in range, and in `f64`, as do fractions. Times follow gst-validate,
+/// which reads integers as nanoseconds, doubles as seconds and strings as
+/// `H:MM:SS.fraction` clock times.
+pub trait FromValue: Sized {
+    /// The type as named in errors.
+    const TYPE_NAME: &'static str;
+
+    /// `value` converted, or `None` if it has another type.
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    const TYPE_NAME: &'static str = "value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+macro_rules! integer_from_value {
+    ($($type:ty => $name:literal),*) => {
+        $(impl FromValue for $type {
+            const TYPE_NAME: &'static str = $name;
+
+            fn from_value(value: &Value) -> Option<Self> {
+                match value {
+                    Value::Int(n) => (*n).try_into().ok(),
+                    Value::UInt(n) => (*n).try_into().ok(),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+
+integer_from_value!(i32 => "int", i64 => "int64", u32 => "uint", u64 => "uint64");
+
+impl FromValue for f64 {
+    const TYPE_NAME: &'static str = "double";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::ClockTime(_) => None,
+            value => value.as_f64(),
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl FromValue for ClockTime {
+    const TYPE_NAME: &'static str = "GstClockTime";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::ClockTime(time) => Some(*time),
+            Value::Int(n) => u64::try_from(*n).ok().map(ClockTime::from_nseconds),
+            Value::UInt(n) => Some(ClockTime::from_nseconds(*n)),
+            Value::Double(seconds) => ClockTime::from_seconds_f64(*seconds),
+            Value::Str(text) => ClockTime::parse_clock(text),
+            _ => None,
+        }
+    }
+}
+
+/// A fraction, as numerator and denominator.
+impl FromValue for (i32, i32) {
+    const TYPE_NAME: &'static str = "fraction";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Fraction(n, d) => Some((*n, *d)),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Structure {
+    const TYPE_NAME: &'static str = "structure";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Structure(structure) => Some(structure.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// A `{...}` list or a `<...>` array, each element converted.
+impl<T: FromValue> FromValue for Vec<T> {
+    const TYPE_NAME: &'static str = "list";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(values) | Value::Array(values) => {
+                values.iter().map(T::from_value).collect()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// Name of the type of the value, as written in casts.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Int(_) => "int",
+            Value::UInt(_) => "uint",
+            Value::Double(_) => "double",
+            Value::Bool(_) => "boolean",
+            Value::Str(_) => "string",
+            Value::Fraction(..) => "fraction",
+            Value::ClockTime(_) => "GstClockTime",
+            Value::List(_) => "list",
+            Value::Array(_) => "array",
+            Value::Structure(_) => "structure",
+            Value::Other { type_name, .. } => type_name,
+        }
+    }
+}
+
+impl Structure {
+    /// The value of the field `name`, converted to `T`.
+    ///
+    /// Variables and expressions are strings, resolved by gst-validate at
+    /// runtime only: reading `$(position)` as a number is a
+    /// [`FieldError::WrongType`].
+    pub fn get<T: FromValue>(&self, name: &str) -> Result<T, FieldError> {
+        self.get_optional(name)?
+            .ok_or_else(|| FieldError::Missing(name.to_string()))
+    }
+
+    /// Like [`Structure::get`], with `None` when the field is missing.
+    pub fn get_optional<T: FromValue>(&self, name: &str) -> Result<Option<T>, FieldError> {
+        let Some(field) = self.field(name) else {
+            return Ok(None);
+        };
+        let value = Value::from_field_value(&field.value)?;
+        T::from_value(&value)
+            .map(Some)
+            .ok_or_else(|| FieldError::WrongType {
+                field: name.to_string(),
+                expected: T::TYPE_NAME,
+                found: value.type_name().to_string(),
+            })
+    }
+
+    pub fn get_str(&self, name: &str) -> Result<String, FieldError> {
+        self.get(name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Result<bool, FieldError> {
+        self.get(name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Result<i64, FieldError> {
+        self.get(name)
+    }
+
+    pub fn get_double(&self, name: &str) -> Result<f64, FieldError> {
+        self.get(name)
+    }
+
+    pub fn get_clock_time(&self, name: &str) -> Result<ClockTime, FieldError> {
+        self.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+
+    fn values(source: &str) -> Vec<Result<Value, ValueError>> {
+        let document = Document::parse(source).unwrap();
+        document.structures[0]
+            .fields
+            .iter()
+            .map(|f| Value::from_field_value(&f.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_untyped() {
+        assert_eq!(
+            values(
+                "s, a=5, b=-2.5, c=0x10, d=30000/1001, e=true, f=yes, g=\"a \\\"b\\\"\", h=foo, i=$(var)"
+            ),
+            vec![
+                Ok(Value::Int(5)),
+                Ok(Value::Double(-2.5)),
+                Ok(Value::Int(16)),
+                Ok(Value::Fraction(30000, 1001)),
+                Ok(Value::Bool(true)),
+                Ok(Value::Bool(true)),
+                Ok(Value::Str("a \"b\"".to_string())),
+                Ok(Value::Str("foo".to_string())),
+                Ok(Value::Str("$(var)".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typed() {
+        assert_eq!(
+            values(
+                "s, a=(double)5, b=(uint)7, c=(GstClockTime)1000, d=(fraction)0.5, e=(string)5, \
+                 f=(int)<1, 2>, g=(GstState)playing, h=(int)$(n)"
+            ),
+            vec![
+                Ok(Value::Double(5.0)),
+                Ok(Value::UInt(7)),
+                Ok(Value::ClockTime(ClockTime::from_nseconds(1000))),
+                Ok(Value::Fraction(1, 2)),
+                Ok(Value::Str("5".to_string())),
+                Ok(Value::Array(vec![Value::Int(1), Value::Int(2)])),
+                Ok(Value::Other {
+                    type_name: "GstState".to_string(),
+                    text: "playing".to_string()
+                }),
+                Ok(Value::Str("$(n)".to_string())),
+            ]
+        );
+        assert_eq!(
+            values("s, a=(int)foo, b=(uint)-1")
+                .into_iter()
+                .map(|v| v.unwrap_err().to_string())
+                .collect::<Vec<_>>(),
+            vec!["'foo' is not a valid int", "'-1' is not a valid uint"]
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let [list, structure] =
+            values("s, l={a, (int)1, <b, c>}, nested=[expected-issue, issue-id=a::b]")
+                .try_into()
+                .unwrap();
+        assert_eq!(
+            list.unwrap(),
+            Value::List(vec![
+                Value::Str("a".to_string()),
+                Value::Int(1),
+                Value::Array(vec![
+                    Value::Str("b".to_string()),
+                    Value::Str("c".to_string())
+                ]),
+            ])
+        );
+        let Ok(Value::Structure(structure)) = structure else {
+            panic!("expected a structure");
+        };
+        assert_eq!(structure.name, "expected-issue");
+    }
+
+    #[test]
+    fn test_get() {
+        let document = Document::parse(
+            "s, a=5, b=(uint64)18446744073709551615, c=0.5, d=\"0:00:01.5\", e=(int)x, \
+             f={1, 2}, g=<1, a>, h=[check, i=1], j=30/1, k=$(n), l=true\n",
+        )
+        .unwrap();
+        let s = &document.structures[0];
+        assert_eq!(s.get::<i32>("a"), Ok(5));
+        assert_eq!(s.get_int("a"), Ok(5));
+        assert_eq!(s.get_double("a"), Ok(5.0));
+        assert_eq!(s.get::<u64>("b"), Ok(u64::MAX));
+        assert_eq!(s.get_double("c"), Ok(0.5));
+        assert_eq!(
+            s.get_clock_time("c"),
+            Ok(ClockTime::from_nseconds(500_000_000))
+        );
+        assert_eq!(s.get_str("d").unwrap(), "0:00:01.5");
+        assert_eq!(
+            s.get_clock_time("d"),
+            Ok(ClockTime::from_nseconds(1_500_000_000))
+        );
+        assert_eq!(s.get::<Vec<i64>>("f"), Ok(vec![1, 2]));
+        assert_eq!(s.get::<Structure>("h").unwrap().get_int("i"), Ok(1));
+        assert_eq!(s.get::<(i32, i32)>("j"), Ok((30, 1)));
+        assert_eq!(s.get_double("j"), Ok(30.0));
+        assert_eq!(s.get_bool("l"), Ok(true));
+        assert_eq!(s.get_optional::<bool>("m"), Ok(None));
+
+        assert_eq!(
+            s.get::<i64>("b").unwrap_err().to_string(),
+            "field 'b' has type uint, expected int64"
+        );
+        assert_eq!(
+            s.get_int("c"),
+            Err(FieldError::WrongType {
+                field: "c".to_string(),
+                expected: "int64",
+                found: "double".to_string()
+            })
+        );
+        assert!(matches!(s.get_int("e"), Err(FieldError::Invalid(_))));
+        assert!(s.get::<Vec<i64>>("g").is_err());
+        assert!(s.get_int("k").is_err());
+        assert_eq!(s.get_bool("m"), Err(FieldError::Missing("m".to_string())));
+    }
+}
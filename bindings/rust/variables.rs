@@ -0,0 +1,169 @@
+//! Variables of scenarios: those `set-vars` and `foreach` define, and the
+//! built-in ones gst-validate provides.
+//!
+//! `$(name)` references are substituted when gst-validate runs an action.
+//! Besides user variables, it defines directories of the run and of the
+//! file being run, and the playback state in `expr()` expressions.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::document::{Field, FieldValue, Structure};
+use crate::Document;
+
+/// A variable gst-validate defines without `set-vars`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinVariable {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+const fn builtin(name: &'static str, description: &'static str) -> BuiltinVariable {
+    BuiltinVariable { name, description }
+}
+
+/// Variables gst-validate and gst-validate-launcher define.
+pub const BUILTIN_VARIABLES: &[BuiltinVariable] = &[
+    builtin("TMPDIR", "The temporary directory of the system."),
+    builtin(
+        "LOGSDIR",
+        "The directory logs are written to, `$GST_VALIDATE_LOGSDIR` or the temporary directory.",
+    ),
+    builtin("tmpdir", "The temporary directory of the system."),
+    builtin("logsdir", "The directory logs are written to."),
+    builtin("gst_api_version", "The GStreamer API version, such as `1.0`."),
+    builtin("test_dir", "The directory of the `.validatetest` file."),
+    builtin("test_name", "The name of the test, from its file name."),
+    builtin(
+        "test_name_dir",
+        "The test name as a path, dots replaced with directory separators.",
+    ),
+    builtin("test_path", "The path of the `.validatetest` file."),
+    builtin("scenario_dir", "The directory of the scenario file."),
+    builtin("scenario_name", "The name of the scenario, from its file name."),
+    builtin("scenario_path", "The path of the scenario file."),
+    builtin("__dir__", "The directory of the file being run."),
+    builtin("__file__", "The path of the file being run."),
+    builtin("__filename__", "The file name of the file being run."),
+    builtin(
+        "validateflow",
+        "The `validateflow` configuration of gst-validate-launcher, with its expectation and result directories.",
+    ),
+    builtin("position", "The current playback position, in seconds."),
+    builtin("duration", "The duration of the media, in seconds."),
+];
+
+/// The built-in variable named `name`.
+pub fn builtin_variable(name: &str) -> Option<&'static BuiltinVariable> {
+    BUILTIN_VARIABLES.iter().find(|v| v.name == name)
+}
+
+/// A `$(name)` reference: `$(name.field)` references `name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableReference {
+    pub name: String,
+    /// Byte range of the name, without `$(` and `)`.
+    pub range: Range<usize>,
+}
+
+/// References in `text`, which starts at byte `base` of the source.
+fn references_in(text: &str, base: usize, references: &mut Vec<VariableReference>) {
+    for (i, _) in text.match_indices("$(") {
+        let start = i + 2;
+        let len = text[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(text.len() - start);
+        if len > 0 {
+            references.push(VariableReference {
+                name: text[start..start + len].to_string(),
+                range: base + start..base + start + len,
+            });
+        }
+    }
+}
+
+fn value_references(value: &FieldValue, references: &mut Vec<VariableReference>) {
+    match value {
+        FieldValue::Scalar(scalar) => references_in(&scalar.text, scalar.span.start, references),
+        FieldValue::Typed { value, .. } => value_references(value, references),
+        FieldValue::Array(values) | FieldValue::ValueArray(values) | FieldValue::Block(values) => {
+            for value in values {
+                value_references(value, references);
+            }
+        }
+        FieldValue::Structure(structure) => structure_references(structure, references),
+    }
+}
+
+fn structure_references(structure: &Structure, references: &mut Vec<VariableReference>) {
+    // `$(name)` can also name a structure
+    if structure.name.starts_with("$(") {
+        references_in(&structure.name, structure.span.start, references);
+    }
+    for field in &structure.fields {
+        value_references(&field.value, references);
+    }
+}
+
+/// Every variable reference of `document`, in source order, including the
+/// ones in strings and expressions.
+pub fn references(document: &Document) -> Vec<VariableReference> {
+    let mut references = Vec::new();
+    for structure in &document.structures {
+        structure_references(structure, &mut references);
+    }
+    references.sort_by_key(|r| r.range.start);
+    references
+}
+
+/// The iterators of a `foreach` loop: its fields other than `actions` and
+/// `playback-time`. gst-validate only allows one.
+pub(crate) fn foreach_iterators(foreach: &Structure) -> impl Iterator<Item = &Field> {
+    foreach
+        .fields
+        .iter()
+        .filter(|f| f.name != "actions" && f.name != "playback-time")
+}
+
+/// Variables `document` defines, with the range of their first definition:
+/// the fields of `set-vars` and the iterators of `foreach` loops.
+pub fn definitions(document: &Document) -> BTreeMap<String, Range<usize>> {
+    let mut definitions = BTreeMap::new();
+    for structure in &document.structures {
+        let fields: Box<dyn Iterator<Item = _>> = match structure.name.as_str() {
+            "set-vars" => Box::new(structure.fields.iter()),
+            "foreach" => Box::new(foreach_iterators(structure)),
+            _ => continue,
+        };
+        for field in fields {
+            definitions
+                .entry(field.name.clone())
+                .or_insert(field.span.start..field.span.start + field.name.len());
+        }
+    }
+    definitions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_definitions() {
+        let source = "set-vars, clip=\"$(test_dir)/a.mp4\", unused=1\nforeach, i=[0, 2], actions={\"seek, start=$(i)\"}\nplay, uri=\"file://$(clip.path)\", n=expr($(position)+1)\n";
+        let document = Document::parse(source).unwrap();
+        let names: Vec<&str> = references(&document)
+            .iter()
+            .map(|r| &source[r.range.clone()])
+            .collect();
+        assert_eq!(names, vec!["test_dir", "i", "clip", "position"]);
+        let definitions = definitions(&document);
+        assert_eq!(
+            definitions.keys().collect::<Vec<_>>(),
+            vec!["clip", "i", "unused"]
+        );
+        assert_eq!(&source[definitions["i"].clone()], "i");
+        assert!(builtin_variable("TMPDIR").is_some());
+        assert!(builtin_variable("clip").is_none());
+    }
+}
@@ -0,0 +1,214 @@
+//! Lint baselines: the diagnostics a suite already has, written by
+//! `lint --generate-baseline` and left out of the reports of
+//! `lint --baseline`, so that only new ones are reported.
+//!
+//! Diagnostics are recorded by file, lint code and message rather than by
+//! position, so that editing other parts of a file does not report them
+//! again. A file having more diagnostics of the same code and message than
+//! its baseline gets the extra ones reported.
+//!
+//! Files are recorded by their path relative to the directory of the
+//! baseline, so that it applies whatever the directory `lint` runs from and
+//! however the files are spelled on the command line.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tree_sitter_validatetest::lint::Diagnostic;
+
+/// Version of the baseline format, bumped on incompatible changes.
+const VERSION: u32 = 1;
+
+/// Diagnostics found in a file, counted by code and message.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+struct Entry {
+    code: String,
+    message: String,
+    count: usize,
+}
+
+/// The known diagnostics of a suite, by file relative to the baseline.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Baseline {
+    version: u32,
+    files: BTreeMap<String, Vec<Entry>>,
+    /// Absolute directory of the baseline file.
+    #[serde(skip)]
+    directory: PathBuf,
+}
+
+/// `path` made absolute, with its `.` and `..` components resolved
+/// without following symbolic links.
+fn normalize(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+impl Baseline {
+    /// An empty baseline, to be written at `path`.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            version: VERSION,
+            files: BTreeMap::new(),
+            directory: normalize(path)
+                .parent()
+                .map(Path::to_owned)
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut baseline: Self =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if baseline.version != VERSION {
+            return Err(format!(
+                "{}: unsupported baseline version {}, generate it again",
+                path.display(),
+                baseline.version
+            ));
+        }
+        baseline.directory = Self::new(path).directory;
+        Ok(baseline)
+    }
+
+    /// How `file` is recorded: its path relative to the directory of the
+    /// baseline, with `/` separators.
+    fn key(&self, file: &str) -> String {
+        let file = normalize(Path::new(file));
+        let mut base = self.directory.components().peekable();
+        let mut rest = file.components().peekable();
+        while base.peek().is_some() && base.peek() == rest.peek() {
+            base.next();
+            rest.next();
+        }
+        let parents = base.map(|_| "..".to_string());
+        let names = rest.map(|c| c.as_os_str().to_string_lossy().into_owned());
+        parents.chain(names).collect::<Vec<_>>().join("/")
+    }
+
+    /// Record the `diagnostics` of `file`.
+    pub fn add(&mut self, file: &str, diagnostics: &[Diagnostic]) {
+        let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for diagnostic in diagnostics {
+            *counts
+                .entry((diagnostic.code, &diagnostic.message))
+                .or_default() += 1;
+        }
+        if counts.is_empty() {
+            return;
+        }
+        let entries = counts
+            .into_iter()
+            .map(|((code, message), count)| Entry {
+                code: code.to_string(),
+                message: message.to_string(),
+                count,
+            })
+            .collect();
+        self.files.insert(self.key(file), entries);
+    }
+
+    /// The `diagnostics` of `file` its baseline does not hold.
+    pub fn new_diagnostics(&self, file: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let Some(entries) = self.files.get(&self.key(file)) else {
+            return diagnostics;
+        };
+        let mut left: Vec<usize> = entries.iter().map(|e| e.count).collect();
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                let known = entries
+                    .iter()
+                    .position(|e| e.code == diagnostic.code && e.message == diagnostic.message);
+                match known {
+                    Some(index) if left[index] > 0 => {
+                        left[index] -= 1;
+                        false
+                    }
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Number of diagnostics recorded.
+    pub fn count(&self) -> usize {
+        self.files.values().flatten().map(|e| e.count).sum()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("baselines serialize to JSON");
+        json.push('\n');
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tree_sitter_validatetest::lint::Severity;
+
+    fn diagnostic(code: &'static str) -> Diagnostic {
+        Diagnostic::new(0..1, Severity::Warning, code, "message")
+    }
+
+    #[test]
+    fn test_baseline_paths() {
+        let root =
+            std::env::temp_dir().join(format!("validatetest-baseline-{}", std::process::id()));
+        let tests = root.join("suite/tests");
+        fs::create_dir_all(&tests).unwrap();
+        let path = root.join("suite/baseline.json");
+        let file = tests.join("a.validatetest");
+        let file = file.to_str().unwrap();
+
+        let mut baseline = Baseline::new(&root.join("suite/./baseline.json"));
+        baseline.add(file, &[diagnostic("a"), diagnostic("a"), diagnostic("b")]);
+        baseline.add(
+            root.join("other.validatetest").to_str().unwrap(),
+            &[diagnostic("a")],
+        );
+        fs::write(&path, baseline.to_json()).unwrap();
+        let loaded = Baseline::load(&root.join("suite/tests/../baseline.json"));
+        fs::remove_dir_all(&root).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(
+            loaded.files.keys().collect::<Vec<_>>(),
+            ["../other.validatetest", "tests/a.validatetest"]
+        );
+        assert_eq!(loaded.count(), 4);
+        // However the file is spelled
+        let spelled = format!("{}/../tests/./a.validatetest", tests.display());
+        let left = loaded.new_diagnostics(
+            &spelled,
+            vec![
+                diagnostic("a"),
+                diagnostic("b"),
+                diagnostic("a"),
+                diagnostic("a"),
+            ],
+        );
+        assert_eq!(left.iter().map(|d| d.code).collect::<Vec<_>>(), ["a"]);
+        let other = tests.join("b.validatetest");
+        assert_eq!(
+            loaded
+                .new_diagnostics(other.to_str().unwrap(), vec![diagnostic("a")])
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,589 @@
+//! Command line of `validatetest-fmt`.
+
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+
+/// Formatter for GStreamer ValidateTest files
+///
+/// Without a subcommand, `validatetest-fmt [OPTIONS] [FILE]...` runs `fmt`,
+/// as it did before there were subcommands.
+///
+/// The settings of the formatter and of the lints are read from
+/// `.validatetest-fmt.toml` files, looked up from the directory of each file
+/// upwards until one sets `root = true`. The closest files take precedence,
+/// and the command line options over them all.
+#[derive(Debug, Parser)]
+#[command(name = "validatetest-fmt", version, after_long_help = ENVIRONMENT)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Environment variables and exit status, at the end of the long help.
+const ENVIRONMENT: &str = "\
+NO_COLOR, when set and not empty, turns off the colors of --color auto. \
+RUST_LOG, such as RUST_LOG=debug or RUST_LOG=tree_sitter_validatetest=trace, \
+selects the logs instead of --verbose.
+
+The exit status is 0 on success, 1 when files need formatting, have lint \
+errors, or cannot be read, parsed or written, and 2 on an invalid command line.";
+
+/// Options accepted by every subcommand.
+#[derive(Debug, Args)]
+pub struct GlobalArgs {
+    /// Color diffs, errors and notices; auto colors terminals unless
+    /// NO_COLOR is set
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    /// Do not show the progress of, nor summarize, runs on several files,
+    /// nor list the files written
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Log the time taken by each file to stderr; -vv also logs what is
+    /// split over several lines and why, -vvv everything, such as parser
+    /// reuse. RUST_LOG (e.g. RUST_LOG=debug) takes precedence
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Read the settings from PATH instead of looking up
+    /// .validatetest-fmt.toml files
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Process N files at once (default: the number of CPUs)
+    #[arg(short, long, global = true, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+    /// Read files with invalid UTF-8, replacing the invalid bytes with
+    /// U+FFFD
+    #[arg(long, global = true)]
+    pub lossy: bool,
+    /// Write errors, lint diagnostics and the files to format to stderr as
+    /// explained text, as file:line:column: lines for editors (short), or
+    /// as one JSON object per line
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
+}
+
+/// Layout of the messages on stderr, as given to `--message-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+    Human,
+    Short,
+    Json,
+}
+
+/// When to color the output, as given to `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Format files, or stdin to stdout
+    Fmt(FmtArgs),
+    /// Check that files are formatted, exit 1 if not
+    ///
+    /// The files to format are listed on stderr; nothing is written. Like
+    /// `fmt --check`.
+    Check(CheckArgs),
+    /// Print the changes formatting would make as unified diffs, exit 1 if
+    /// there are any
+    ///
+    /// With colors, the words changed within lines are highlighted. Like
+    /// `fmt --diff`.
+    Diff(CheckArgs),
+    /// Report likely mistakes, exit 1 if any is an error
+    ///
+    /// Diagnostics are printed on stderr as
+    /// `file:line:column: severity[code]: message`. Actions are checked
+    /// against the definitions shipped with the tool, those of --actions
+    /// taking precedence, and the `[lint]` table of the configuration files
+    /// sets the conventions of the project, such as the fields required in
+    /// `meta`.
+    Lint(LintArgs),
+    /// Convert between .scenario and .validatetest layouts
+    ///
+    /// Files are converted to the other layout, by their extension, and
+    /// printed unless -i is given. Converting to .scenario drops the meta
+    /// fields .scenario files do not support, with a warning.
+    Convert(ConvertArgs),
+    /// Rename a set-vars variable and its references
+    RenameVar(RenameVarArgs),
+    /// Move a repeated string literal into a set-vars variable
+    ExtractVar(ExtractVarArgs),
+    /// Replace $(NAME) references with the variable's value and drop it
+    InlineVar(InlineVarArgs),
+    /// Rewrite the structures matching a pattern
+    ///
+    /// The pattern is a partial structure whose uppercase words are
+    /// metavariables matching any value, and the template what matching
+    /// structures become, such as `set-property, property-name=X,
+    /// property-value=Y` and `set-properties, X=Y`. Only the name and the
+    /// fields of the pattern change, the other fields and the comments are
+    /// kept as written.
+    Rewrite(RewriteArgs),
+    /// Rename actions and their fields as a mapping file tells
+    ///
+    /// The mapping is a TOML file with a table per action to rename, named
+    /// after it, giving its new `name` and the new names of its `fields`,
+    /// such as `[emit-signal]` then `fields = { signal-name = "signal" }`.
+    /// Actions nested in blocks, arrays and strings are renamed too.
+    RenameActions(RenameActionsArgs),
+    /// Print the actions gst-validate runs, with foreach loops and repeats
+    /// unrolled and set-vars variables replaced
+    Expand(ExpandArgs),
+    /// Write one scenario per combination of the parameter values of a
+    /// matrix
+    ///
+    /// The matrix lists the values of each parameter in a `[parameters]`
+    /// table, as TOML or JSON, and optionally the name of the generated
+    /// files in `output`, such as `seek_@rate@.validatetest`. By default
+    /// they are named after the template and the values.
+    Generate(GenerateArgs),
+    /// Run a tree-sitter query over files and print what it captures
+    ///
+    /// Each capture is printed as `file:line:column: @name: text`, its
+    /// newlines escaped, or with --format json as one JSON object per line
+    /// with the file, capture name, pattern index, text and range. Files
+    /// that do not parse are queried too, their errors being ERROR nodes.
+    Query(QueryArgs),
+    /// Write the man pages of validatetest-fmt and of each subcommand to a
+    /// directory, for packagers
+    #[command(hide = true)]
+    GenerateMan(GenerateManArgs),
+}
+
+/// Formatter settings, overriding those of the configuration files.
+#[derive(Debug, Default, Args)]
+#[command(next_help_heading = "Formatting")]
+pub struct FormatArgs {
+    /// Indentation width (default: 4)
+    #[arg(long, value_name = "N")]
+    pub indent: Option<usize>,
+    /// Maximum line length (default: 120)
+    #[arg(long, value_name = "N")]
+    pub line_length: Option<usize>,
+    /// Sort expected-issues by issue-id and drop duplicates
+    #[arg(long)]
+    pub sort_expected_issues: bool,
+    /// Do not rewrite quoted structures to [name, ...] arrays
+    #[arg(long)]
+    pub keep_quoted_structures: bool,
+    /// Also rewrite quoted NAME structures (default: expected-issue,
+    /// change-severity)
+    #[arg(long, value_name = "NAME")]
+    pub quoted_structure: Vec<String>,
+    /// Rewrite any quoted string holding a structure with fields
+    #[arg(long)]
+    pub convert_quoted_structures: bool,
+    /// Write [expected-issue, ...] entries as quoted strings
+    #[arg(long)]
+    pub legacy_quoted_issues: bool,
+    /// Do not wrap string values overflowing the line length
+    #[arg(long)]
+    pub keep_long_strings: bool,
+    /// Split args = { "..." } strings overflowing the line length at spaces
+    #[arg(long)]
+    pub split_long_args: bool,
+    /// Drop the UTF-8 byte order mark instead of keeping it
+    #[arg(long)]
+    pub strip_bom: bool,
+    /// Line ending to write (default: lf); auto keeps the file's dominant
+    /// one
+    #[arg(long, value_parser = ["lf", "crlf", "native", "auto"])]
+    pub line_ending: Option<String>,
+    /// Layout of split arrays and blocks: as many values per line as fit
+    /// (default), one per line, or one per line up to
+    /// --array-packing-threshold values
+    #[arg(long, value_parser = ["packed", "one-per-line", "auto"])]
+    pub array_packing: Option<String>,
+    /// Values up to which auto writes one per line (default: 8)
+    #[arg(long, value_name = "N")]
+    pub array_packing_threshold: Option<usize>,
+    /// Put the { of split nested blocks after the = of their field
+    /// (default) or on its own line
+    #[arg(long, value_parser = ["same-line", "next-line"])]
+    pub brace_style: Option<String>,
+    /// Do not end the last value of nested blocks with a comma
+    #[arg(long)]
+    pub no_block_trailing_comma: bool,
+    /// Do not end the last element of split arrays with a comma
+    #[arg(long)]
+    pub no_array_trailing_comma: bool,
+    /// Write fields as name = value instead of name=value
+    #[arg(long)]
+    pub spaces_around_equals: bool,
+    /// Split structures with more than N fields even when they fit on one
+    /// line
+    #[arg(long, value_name = "N")]
+    pub max_inline_fields: Option<usize>,
+    /// Keep fields and values on the lines they are written on, only
+    /// normalizing indentation and spacing
+    #[arg(long)]
+    pub preserve_breaks: bool,
+    /// Only reformat the structures, through edits of the lines that
+    /// change, keeping comments and blank lines between them as written
+    #[arg(long)]
+    pub minimal_diff: bool,
+    /// End top-level structures with a ; as written (default), always or
+    /// never
+    #[arg(long, value_parser = ["preserve", "always", "never"])]
+    pub semicolon: Option<String>,
+    /// Write the fields of NAME structures in this order, the others after
+    /// them as written
+    #[arg(long, value_name = "NAME:FIELD,...", value_parser = field_order)]
+    pub field_order: Vec<(String, Vec<String>)>,
+    /// Indentation of the values of a field split over several lines: the
+    /// indent width (default), N columns, or under the = of the field
+    #[arg(long, value_name = "block|N|equals", value_parser = continuation_indent)]
+    pub continuation_indent: Option<String>,
+    /// Format the files as scenarios, media info or validateflow
+    /// expectations, rather than as told by their name (.media_info and
+    /// .media_info.push files are media info, log-*-expected files and those
+    /// of flow-expectations directories are validateflow expectations)
+    #[arg(long, value_parser = DIALECTS)]
+    pub dialect: Option<String>,
+    /// Align the records of validateflow expectations: not at all (default),
+    /// their bodies after the longest prefix, or also their items in columns.
+    /// Aligned files no longer match the validateflow logs
+    #[arg(long, value_parser = ["none", "prefixes", "columns"])]
+    pub flow_align: Option<String>,
+}
+
+/// The `--dialect` values.
+const DIALECTS: [&str; 3] = ["scenario", "media-info", "validateflow"];
+
+/// A `--continuation-indent` value.
+fn continuation_indent(value: &str) -> Result<String, String> {
+    if value == "block" || value == "equals" || value.parse::<usize>().is_ok() {
+        Ok(value.to_string())
+    } else {
+        Err("expected block, equals or a number of columns".to_string())
+    }
+}
+
+/// A `--field-order` value, `seek:start,stop`.
+fn field_order(value: &str) -> Result<(String, Vec<String>), String> {
+    let (name, fields) = value
+        .split_once(':')
+        .ok_or("expected NAME:FIELD,... such as seek:start,stop")?;
+    Ok((
+        name.trim().to_string(),
+        fields.split(',').map(|f| f.trim().to_string()).collect(),
+    ))
+}
+
+/// Options of the summary of runs on several files.
+#[derive(Debug, Default, Args)]
+#[command(next_help_heading = "Report")]
+pub struct ReportArgs {
+    /// Summarize the run in a single document: the status, duration and
+    /// errors of each file, and the lint counts
+    #[arg(long, value_name = "FORMAT", requires = "files")]
+    pub report: Option<ReportFormat>,
+    /// Write the report to PATH; without it, the report is all that is
+    /// written to stdout
+    #[arg(long, value_name = "PATH", requires = "report")]
+    pub report_file: Option<PathBuf>,
+}
+
+/// Format of `--report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+}
+
+/// Language of the source files `--embedded` formats the scenarios of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Embedded {
+    C,
+    Python,
+}
+
+/// Layout of the parse trees printed by `--dump-cst`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CstFormat {
+    /// Indented, with the ranges and text of nodes
+    Pretty,
+    /// As an S-expression
+    Sexp,
+}
+
+/// Arguments of `fmt`, and of the invocation without a subcommand.
+#[derive(Debug, Default, Args)]
+#[command(
+    after_help = "If no FILE is given, reads from stdin and writes to stdout. \
+Several FILEs written to stdout are each preceded by a \"==> FILE <==\" header."
+)]
+pub struct FmtArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    /// Check if files are formatted (exit 1 if not), like the check
+    /// subcommand
+    #[arg(short, long)]
+    pub check: bool,
+    /// Like --check, also printing the changes as a unified diff
+    #[arg(short, long)]
+    pub diff: bool,
+    /// Check that formatting keeps the meaning of files and that formatting
+    /// twice changes nothing (exit 1 if not)
+    #[arg(long, conflicts_with_all = ["embedded", "report"])]
+    pub verify: bool,
+    /// Print a hash of the meaning of each file, ignoring formatting and
+    /// comments, to find duplicates
+    #[arg(long, conflicts_with = "report")]
+    pub fingerprint: bool,
+    /// Check the content staged in git of the changed .validatetest files,
+    /// like --check
+    #[arg(long, conflicts_with_all = ["files", "in_place", "output", "embedded"])]
+    pub staged: bool,
+    /// Only format the structures of the .validatetest and .scenario files
+    /// whose lines the unified diff PATCH changes ("-" for stdin)
+    #[arg(
+        long,
+        value_name = "PATCH",
+        conflicts_with_all = ["files", "staged", "output", "embedded"]
+    )]
+    pub patch_from: Option<String>,
+    /// With -i, rewrite the target of symbolic links instead of refusing
+    /// them
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    /// With -i, keep the modification time of files whose content is only
+    /// reformatted
+    #[arg(long)]
+    pub preserve_mtime: bool,
+    /// Write the result to PATH, or under the directory PATH when
+    /// formatting several files
+    #[arg(short, long, value_name = "PATH", conflicts_with_all = ["in_place", "check", "diff"])]
+    pub output: Option<PathBuf>,
+    /// Print the settings applying to each FILE and the configuration files
+    /// they come from
+    #[arg(long, conflicts_with = "report")]
+    pub print_config: bool,
+    /// Print the parse tree of each FILE
+    #[arg(long, value_name = "FORMAT", conflicts_with = "report")]
+    pub dump_cst: Option<CstFormat>,
+    /// Print the structures, fields and values of each FILE as JSON, with
+    /// their byte ranges
+    #[arg(long, conflicts_with = "report")]
+    pub dump_ast: bool,
+    /// Only format the scenarios held by the string literals of C or Python
+    /// source files
+    #[arg(long, value_name = "LANGUAGE")]
+    pub embedded: Option<Embedded>,
+    #[command(flatten)]
+    pub format: FormatArgs,
+    #[command(flatten)]
+    pub report: ReportArgs,
+    /// Files to format; stdin when none is given
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Arguments of `check` and `diff`.
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    /// Check the content staged in git of the changed .validatetest files
+    #[arg(long, conflicts_with_all = ["files", "embedded"])]
+    pub staged: bool,
+    /// Only check the structures of the .validatetest and .scenario files
+    /// whose lines the unified diff PATCH changes ("-" for stdin)
+    #[arg(
+        long,
+        value_name = "PATCH",
+        conflicts_with_all = ["files", "staged", "embedded"]
+    )]
+    pub patch_from: Option<String>,
+    /// Only check the scenarios held by the string literals of C or Python
+    /// source files
+    #[arg(long, value_name = "LANGUAGE")]
+    pub embedded: Option<Embedded>,
+    #[command(flatten)]
+    pub format: FormatArgs,
+    #[command(flatten)]
+    pub report: ReportArgs,
+    /// Files to check; stdin when none is given
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// Apply the automatic fixes, in place, or to stdout when reading stdin
+    #[arg(long)]
+    pub fix: bool,
+    /// Also report the style checks
+    #[arg(long)]
+    pub style: bool,
+    /// Do not report LINT, a lint code or a category: correctness, style
+    /// or registry
+    #[arg(short = 'A', long = "allow", value_name = "LINT")]
+    pub allow: Vec<String>,
+    /// Report LINT, a lint code or a category, as a warning
+    #[arg(short = 'W', long = "warn", value_name = "LINT")]
+    pub warn: Vec<String>,
+    /// Report LINT, a lint code or a category, as an error
+    ///
+    /// Levels given on the command line override those of the
+    /// configuration files. A lint given by its code takes precedence over
+    /// its category, and -D over -W and -A for the same lint.
+    #[arg(short = 'D', long = "deny", value_name = "LINT")]
+    pub deny: Vec<String>,
+    /// Only report the diagnostics missing from the baseline at PATH
+    #[arg(long, value_name = "PATH")]
+    pub baseline: Option<PathBuf>,
+    /// Write the diagnostics found to the baseline at PATH instead of
+    /// reporting them
+    ///
+    /// Files are recorded by their path relative to the directory of the
+    /// baseline, so --baseline works from any directory.
+    #[arg(long, value_name = "PATH", requires = "files", conflicts_with_all = ["fix", "baseline"])]
+    pub generate_baseline: Option<PathBuf>,
+    /// Load action definitions from a gst-validate .json dump or a .toml
+    /// file
+    #[arg(long = "actions", value_name = "DEFINITIONS")]
+    pub actions: Vec<String>,
+    /// Check seeks and tracks against the .media_info file of the media
+    #[arg(long, value_name = "FILE")]
+    pub media_info: Option<PathBuf>,
+    /// Lint the files as scenarios or as media info, rather than as told by
+    /// their name
+    #[arg(long, value_parser = DIALECTS)]
+    pub dialect: Option<String>,
+    #[command(flatten)]
+    pub report: ReportArgs,
+    /// Files to lint; stdin when none is given
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Target layout of `convert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConvertTarget {
+    Scenario,
+    #[value(name = "validatetest")]
+    ValidateTest,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// Write each FILE next to it, with the extension of the new layout
+    #[arg(short, long)]
+    pub in_place: bool,
+    /// Layout to convert to (default: the other one; required with stdin)
+    #[arg(long)]
+    pub to: Option<ConvertTarget>,
+    /// Pipeline argument of the meta of converted .scenario files
+    #[arg(long = "arg", value_name = "ARG")]
+    pub pipeline: Vec<String>,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RenameVarArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    pub old: String,
+    pub new: String,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExtractVarArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    /// Literal to extract (default: the most repeated one)
+    #[arg(long)]
+    pub value: Option<String>,
+    pub name: String,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct InlineVarArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    pub name: String,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RewriteArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    pub pattern: String,
+    pub template: String,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RenameActionsArgs {
+    /// Edit files in place
+    #[arg(short, long)]
+    pub in_place: bool,
+    /// The TOML file mapping the old names to the new ones
+    pub mapping: PathBuf,
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExpandArgs {
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateManArgs {
+    /// Where to write the pages, such as $pkgdir/usr/share/man/man1
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    /// The .scm file holding the query
+    pub query: PathBuf,
+    /// Print the captures as text lines or JSON objects
+    #[arg(long, value_enum, default_value_t = QueryFormat::Text)]
+    pub format: QueryFormat,
+    /// Files to query; stdin when none is given
+    #[arg(value_name = "FILE")]
+    pub files: Vec<String>,
+}
+
+/// Output of `query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QueryFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    /// Parameter values, as TOML or JSON
+    #[arg(long)]
+    pub matrix: PathBuf,
+    /// Directory of the generated files (default: that of the template)
+    #[arg(short, long, value_name = "DIR")]
+    pub output: Option<PathBuf>,
+    /// Scenarios whose @name@ placeholders are replaced
+    #[arg(required = true)]
+    pub templates: Vec<String>,
+}
@@ -0,0 +1,1834 @@
+//! Formatter for GStreamer ValidateTest files
+//!
+//! Usage: validatetest-fmt [COMMAND] [OPTIONS] <FILE>...
+//!
+//! Without a command, the files are formatted as `fmt` does. The commands
+//! are defined in `cli.rs`; `validatetest-fmt --help` lists them, and
+//! `validatetest-fmt help COMMAND` their options.
+
+mod baseline;
+mod cli;
+mod report;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use clap::{CommandFactory, Parser};
+use serde::de::value::StrDeserializer;
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+use tree_sitter_validatetest::config::{self, FileConfig};
+use tree_sitter_validatetest::convert::{scenario_to_validatetest, validatetest_to_scenario};
+use tree_sitter_validatetest::dialect::Dialect;
+use tree_sitter_validatetest::diff::{colored_unified_diff, touched_lines, unified_diff};
+use tree_sitter_validatetest::dump::{ast_json, cst_pretty, cst_sexp};
+use tree_sitter_validatetest::embedded::{format_embedded, HostLanguage};
+use tree_sitter_validatetest::encoding::decode;
+use tree_sitter_validatetest::expand::expand;
+use tree_sitter_validatetest::format::{
+    format_lines, format_source, verify, FormatterConfig, VerifyError,
+};
+use tree_sitter_validatetest::lint::{
+    apply_fixes, line_col, lint_with, Diagnostic, Level, LintConfig, Severity,
+};
+use tree_sitter_validatetest::matrix::Matrix;
+use tree_sitter_validatetest::media_info::MediaInfo;
+use tree_sitter_validatetest::pattern::{Pattern, Template};
+use tree_sitter_validatetest::position::LineCol;
+use tree_sitter_validatetest::query::{captures, compile as compile_query};
+use tree_sitter_validatetest::refactor::{
+    apply_edits, extract_variable, find_repeated_literals, inline_variable, rename_actions,
+    rename_variable, rewrite, ActionRenames, RefactorError,
+};
+use tree_sitter_validatetest::registry::ActionRegistry;
+use tree_sitter_validatetest::{Document, ParseError};
+
+use baseline::Baseline;
+use cli::{
+    CheckArgs, Cli, ColorChoice, ConvertArgs, ConvertTarget, CstFormat, Embedded, ExpandArgs,
+    ExtractVarArgs, FmtArgs, FormatArgs, GenerateArgs, GlobalArgs, InlineVarArgs, LintArgs,
+    MessageFormat, QueryArgs, QueryFormat, RenameActionsArgs, RenameVarArgs, ReportArgs,
+    RewriteArgs,
+};
+use report::{ErrorReport, FileReport, LintCounts, Report, Status};
+
+impl ColorChoice {
+    /// Whether to color what is written to `stream`: with `auto`, only
+    /// terminals are, unless the `NO_COLOR` environment variable is set.
+    fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                stream.is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+/// Whether standard output and standard error are colored.
+#[derive(Debug, Clone, Copy)]
+struct Colors {
+    stdout: bool,
+    stderr: bool,
+}
+
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+impl Colors {
+    /// `text` in `style` when standard error is colored.
+    fn stderr(&self, text: &str, style: &str) -> String {
+        paint(text, style, self.stderr)
+    }
+
+    /// The unified diff formatting `source` into `formatted`, for standard
+    /// output.
+    fn diff(&self, source: &str, formatted: &str, name: &str) -> String {
+        let formatted_name = format!("{} (formatted)", name);
+        if self.stdout {
+            colored_unified_diff(source, formatted, name, &formatted_name, 3)
+        } else {
+            unified_diff(source, formatted, name, &formatted_name, 3)
+        }
+    }
+}
+
+fn paint(text: &str, style: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `error` followed by the line of `source` it is on, with a caret under its
+/// column.
+fn parse_error_message(source: &str, error: &ParseError, color: bool) -> String {
+    let position = error.position;
+    let Some(line) = source.split('\n').nth(position.row) else {
+        return error.to_string();
+    };
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let mut column = position.column.min(line.len());
+    while !line.is_char_boundary(column) {
+        column -= 1;
+    }
+    // Tabs are kept so the caret lines up with them
+    let padding: String = line[..column]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let number = (position.row + 1).to_string();
+    let gutter = " ".repeat(number.len());
+    format!(
+        "{}\n{} {}\n{} {}{}",
+        error,
+        paint(&format!("{} |", number), BLUE, color),
+        line,
+        paint(&format!("{} |", gutter), BLUE, color),
+        padding,
+        paint("^", RED, color)
+    )
+}
+
+/// A problem found in a file, at a 0-based line and column.
+struct Message<'a> {
+    file: &'a str,
+    line: usize,
+    column: usize,
+    severity: Severity,
+    /// The lint code, none for other problems.
+    code: Option<&'static str>,
+    text: &'a str,
+}
+
+impl<'a> Message<'a> {
+    fn syntax(file: &'a str, error: &ParseError) -> Self {
+        Self {
+            file,
+            line: error.position.row,
+            column: error.position.column,
+            severity: Severity::Error,
+            code: None,
+            text: "syntax error",
+        }
+    }
+
+    fn lint(file: &'a str, line: usize, column: usize, diagnostic: &'a Diagnostic) -> Self {
+        Self {
+            file,
+            line,
+            column,
+            severity: diagnostic.severity,
+            code: Some(diagnostic.code),
+            text: &diagnostic.message,
+        }
+    }
+
+    /// `file` needs formatting from the 0-based `line` on.
+    fn needs_formatting(file: &'a str, line: usize) -> Self {
+        Self {
+            file,
+            line,
+            column: 0,
+            severity: Severity::Error,
+            code: None,
+            text: "needs formatting",
+        }
+    }
+}
+
+/// A [`Message`] as written by `--message-format json`, with a 1-based line
+/// and column.
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+    file: &'a str,
+    line: usize,
+    column: usize,
+    severity: String,
+    code: Option<&'static str>,
+    message: &'a str,
+}
+
+impl MessageFormat {
+    /// Write `message` to stderr, as `human` says for human messages.
+    fn emit(self, message: &Message, human: impl FnOnce() -> String) {
+        match self {
+            MessageFormat::Human => eprintln!("{}", human()),
+            // The layout of compilers, that editors jump to
+            MessageFormat::Short => eprintln!(
+                "{}:{}:{}: {}: {}{}",
+                message.file,
+                message.line + 1,
+                message.column + 1,
+                message.severity,
+                message.text,
+                message
+                    .code
+                    .map(|code| format!(" [{}]", code))
+                    .unwrap_or_default()
+            ),
+            MessageFormat::Json => {
+                let json = JsonMessage {
+                    file: message.file,
+                    line: message.line + 1,
+                    column: message.column + 1,
+                    severity: message.severity.to_string(),
+                    code: message.code,
+                    message: message.text,
+                };
+                eprintln!(
+                    "{}",
+                    serde_json::to_string(&json).expect("messages serialize to JSON")
+                );
+            }
+        }
+    }
+}
+
+/// The 0-based line of `source` where `formatted` starts to differ.
+fn first_changed_line(source: &str, formatted: &str) -> usize {
+    let common = source
+        .bytes()
+        .zip(formatted.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    source.as_bytes()[..common]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+}
+
+/// Log to standard error what `RUST_LOG` selects or else, with `verbosity`
+/// `-v` flags, the time taken by each file (1), the layout decisions (2) and
+/// everything, such as the reuse of parsers (3). Returns whether anything is logged.
+fn init_logging(verbosity: usize, color: bool) -> bool {
+    let filter = match env::var("RUST_LOG") {
+        Ok(filter) if !filter.is_empty() => EnvFilter::new(filter),
+        _ => match verbosity {
+            0 => return false,
+            1 => EnvFilter::new("info"),
+            2 => EnvFilter::new("debug"),
+            _ => EnvFilter::new("trace"),
+        },
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(color)
+        .with_target(false)
+        .init();
+    true
+}
+
+/// A progress bar on standard error, redrawn as files are done.
+struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    enabled: bool,
+}
+
+impl Progress {
+    const WIDTH: usize = 30;
+
+    /// A bar over `total` files, only drawn when `enabled`.
+    fn new(total: usize, enabled: bool) -> Self {
+        Progress {
+            total,
+            done: AtomicUsize::new(0),
+            enabled,
+        }
+    }
+
+    /// Count one more file done and redraw the bar.
+    fn advance(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.enabled {
+            let filled = Self::WIDTH * done / self.total.max(1);
+            eprint!(
+                "\r[{}{}] {}/{}",
+                "=".repeat(filled),
+                " ".repeat(Self::WIDTH - filled),
+                done,
+                self.total
+            );
+        }
+    }
+
+    /// Erase the bar, before printing the results.
+    fn finish(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// What happened to the files of a batch, for the final summary.
+#[derive(Debug, Default)]
+struct Summary {
+    changed: usize,
+    unchanged: usize,
+    errors: usize,
+}
+
+impl Summary {
+    /// Print the counts, `changed` naming the files that were (or, when
+    /// checking, would be) rewritten.
+    fn print(&self, changed: &str, unchanged: &str) {
+        let files = |count: usize| if count == 1 { "file" } else { "files" };
+        eprintln!(
+            "{} {} {}, {} {} {}, {} {}",
+            self.changed,
+            files(self.changed),
+            changed,
+            self.unchanged,
+            files(self.unchanged),
+            unchanged,
+            self.errors,
+            if self.errors == 1 { "error" } else { "errors" }
+        );
+    }
+}
+
+/// `map` applied to each of `files` on `jobs` threads, the results in the
+/// order of `files`.
+///
+/// The threads parse with the parsers of the library's shared pool, so each
+/// creates one parser at most.
+fn map_files<T: Send>(files: &[String], jobs: usize, map: impl Fn(&String) -> T + Sync) -> Vec<T> {
+    let jobs = jobs.clamp(1, files.len().max(1));
+    if jobs == 1 {
+        return files.iter().map(map).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else {
+                            break done;
+                        };
+                        done.push((index, map(file)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("formatting thread panicked"))
+            .collect()
+    });
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// How to format a batch of files.
+struct Batch<'a> {
+    settings: &'a Settings,
+    embedded: Option<HostLanguage>,
+    lossy: bool,
+    /// Number of threads.
+    jobs: usize,
+    colors: Colors,
+    progress: Progress,
+    /// The lines of the files of `--patch-from`, the only ones formatted.
+    touched: HashMap<String, Vec<Range<usize>>>,
+}
+
+impl Batch<'_> {
+    /// Format `source`, the content of `file`.
+    fn format(&self, source: &str, file: &str) -> Result<String, FormatError> {
+        let config = self.settings.resolve(Some(file));
+        match self.touched.get(file) {
+            Some(lines) => format_lines(source, lines, &config)
+                .map_err(|error| FormatError::new(source, error, self.colors)),
+            None => format_file(source, &config, self.embedded, self.colors),
+        }
+    }
+}
+
+/// A source, its formatting and the time they took to read and format.
+type FormatResult = (String, Result<String, FormatError>, Duration);
+
+/// Read and format `files` on `jobs` threads.
+fn format_files(files: &[String], batch: &Batch) -> Vec<FormatResult> {
+    map_files(files, batch.jobs, |file| {
+        let _span = tracing::info_span!("file", path = %file).entered();
+        let start = Instant::now();
+        let source = read_source(Some(file), batch.lossy);
+        let formatted = batch.format(&source, file);
+        let elapsed = start.elapsed();
+        tracing::info!(?elapsed, "formatted");
+        batch.progress.advance();
+        (source, formatted, elapsed)
+    })
+}
+
+/// What [`check_files`] found in a file.
+type CheckResult = (Result<Option<(usize, String)>, FormatError>, Duration);
+
+/// Check on `jobs` threads which of `files` need formatting.
+///
+/// Each result is `None` for a formatted file, else the first line to
+/// change and the diff to format it, empty unless `show_diff`, with the
+/// time the check took. Only the diffs are kept, not the sources or
+/// formatted texts.
+fn check_files(files: &[String], batch: &Batch, show_diff: bool) -> Vec<CheckResult> {
+    map_files(files, batch.jobs, |file| {
+        let _span = tracing::info_span!("file", path = %file).entered();
+        let start = Instant::now();
+        let result = with_source(file, batch.lossy, |source| {
+            let formatted = batch.format(source, file)?;
+            Ok((formatted != source).then(|| {
+                let diff = if show_diff {
+                    batch.colors.diff(source, &formatted, file)
+                } else {
+                    String::new()
+                };
+                (first_changed_line(source, &formatted), diff)
+            }))
+        });
+        let elapsed = start.elapsed();
+        tracing::info!(?elapsed, "checked");
+        batch.progress.advance();
+        (result, elapsed)
+    })
+}
+
+/// A file that does not parse.
+struct FormatError {
+    error: ParseError,
+    /// The error and the line it is on, for standard error.
+    message: String,
+}
+
+impl FormatError {
+    fn new(source: &str, error: ParseError, colors: Colors) -> Self {
+        Self {
+            message: parse_error_message(source, &error, colors.stderr),
+            error,
+        }
+    }
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Format `source`, or only the scenarios it embeds in string literals when
+/// it is written in an `embedded` host language.
+fn format_file(
+    source: &str,
+    config: &FormatterConfig,
+    embedded: Option<HostLanguage>,
+    colors: Colors,
+) -> Result<String, FormatError> {
+    match embedded {
+        Some(language) => Ok(format_embedded(source, language, config)),
+        None => {
+            format_source(source, config).map_err(|error| FormatError::new(source, error, colors))
+        }
+    }
+}
+
+/// Where the formatter settings come from, in increasing precedence: the
+/// defaults, the configuration files and the command line.
+struct Settings {
+    /// Configuration file replacing the lookup of `.validatetest-fmt.toml`.
+    config_file: Option<PathBuf>,
+    /// Settings given on the command line.
+    overrides: FileConfig,
+}
+
+impl Settings {
+    /// Configuration files applying to `file`, stdin without one, outermost
+    /// first. Exits on invalid files.
+    fn config_files(&self, file: Option<&str>) -> Vec<(PathBuf, FileConfig)> {
+        let files = match &self.config_file {
+            Some(path) => FileConfig::load(path).map(|config| vec![(path.clone(), config)]),
+            None => {
+                let dir = file
+                    .and_then(|f| Path::new(f).parent())
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                config::discover(dir)
+            }
+        };
+        files.unwrap_or_else(|e| {
+            eprintln!("Error: invalid configuration {}", e);
+            process::exit(1);
+        })
+    }
+
+    /// The configuration files of `file` merged with the command line.
+    fn merged(&self, file: Option<&str>) -> FileConfig {
+        let mut merged = FileConfig::default();
+        for (_, config) in self.config_files(file) {
+            merged.merge(&config);
+        }
+        merged.merge(&self.overrides);
+        merged
+    }
+
+    /// Formatter settings for `file`, stdin without one.
+    fn resolve(&self, file: Option<&str>) -> FormatterConfig {
+        let mut config = defaults(file);
+        self.merged(file).apply(&mut config);
+        config
+    }
+}
+
+/// The default settings of `file`, in the dialect told by its extension.
+fn defaults(file: Option<&str>) -> FormatterConfig {
+    FormatterConfig {
+        dialect: file
+            .map(|f| Dialect::from_path(Path::new(f)))
+            .unwrap_or_default(),
+        ..FormatterConfig::default()
+    }
+}
+
+/// Print the settings applying to `files`, or to stdin, preceded by the
+/// configuration files they come from.
+fn run_print_config(files: &[String], settings: &Settings) {
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    for (index, file) in inputs.iter().enumerate() {
+        if files.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("==> {} <==", file.unwrap_or("<stdin>"));
+        }
+        for (path, _) in settings.config_files(*file) {
+            println!("# {}", path.display());
+        }
+        let merged = settings.merged(*file);
+        let mut config = defaults(*file);
+        merged.apply(&mut config);
+        let effective = FileConfig {
+            lint: merged.lint,
+            ..FileConfig::effective(&config)
+        };
+        print!("{}", effective.to_toml());
+    }
+}
+
+/// Read `file`, or stdin without one, exiting on failure.
+///
+/// Files that are not valid UTF-8 are rejected with the position of the
+/// first invalid sequence, unless `lossy` replaces them with U+FFFD.
+fn read_source(file: Option<&str>, lossy: bool) -> String {
+    let name = file.unwrap_or("stdin");
+    let bytes = match file {
+        Some(file) => fs::read(file),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+        }
+    };
+    let bytes = bytes.unwrap_or_else(|e| {
+        eprintln!("Error reading {}: {}", name, e);
+        process::exit(1);
+    });
+    match String::from_utf8(bytes) {
+        Ok(source) => source,
+        Err(e) => decode_source(name, e.as_bytes(), lossy).into_owned(),
+    }
+}
+
+/// Call `f` with the text of `file`, exiting on failure like [`read_source`].
+///
+/// Built with the `mmap` feature, the file is mapped into memory instead of
+/// read, so its text is only copied if it is not valid UTF-8 and `lossy`.
+fn with_source<T>(file: &str, lossy: bool, f: impl FnOnce(&str) -> T) -> T {
+    #[cfg(feature = "mmap")]
+    {
+        // SAFETY: the map is only read; a file truncated by another process
+        // meanwhile is the caller's race, as with any mapped input
+        let map = fs::File::open(file).and_then(|opened| unsafe { memmap2::Mmap::map(&opened) });
+        let map = map.unwrap_or_else(|e| {
+            eprintln!("Error reading {}: {}", file, e);
+            process::exit(1);
+        });
+        f(&decode_source(file, &map, lossy))
+    }
+    #[cfg(not(feature = "mmap"))]
+    f(&read_source(Some(file), lossy))
+}
+
+/// `bytes` read from `name` as UTF-8, exiting on failure unless `lossy`.
+fn decode_source<'a>(name: &str, bytes: &'a [u8], lossy: bool) -> Cow<'a, str> {
+    match decode(bytes) {
+        Ok(source) => Cow::Borrowed(source),
+        Err(e) if lossy => {
+            eprintln!("Warning: {}: {}, replacing invalid bytes", name, e);
+            String::from_utf8_lossy(bytes)
+        }
+        Err(e) => {
+            eprintln!("Error reading {}: {}", name, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Replace the content of `file` with `content`, keeping its permissions.
+///
+/// The content goes to a temporary file renamed over the original, so an
+/// interrupted run never leaves a truncated file. Symbolic links are refused
+/// unless `follow_symlinks`, in which case their target is rewritten. With a
+/// `modified` time, it is restored on the rewritten file.
+fn write_in_place(
+    file: &str,
+    content: &str,
+    follow_symlinks: bool,
+    modified: Option<SystemTime>,
+) -> io::Result<()> {
+    let mut path = PathBuf::from(file);
+    if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+        if !follow_symlinks {
+            return Err(io::Error::other(
+                "is a symbolic link (use --follow-symlinks to rewrite its target)",
+            ));
+        }
+        path = fs::canonicalize(&path)?;
+    }
+    let permissions = fs::metadata(&path)?.permissions();
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.validatetest-fmt", name));
+    let written = fs::write(&temporary, content)
+        .and_then(|()| fs::set_permissions(&temporary, permissions))
+        .and_then(|()| match modified {
+            Some(modified) => fs::File::options()
+                .write(true)
+                .open(&temporary)?
+                .set_modified(modified),
+            None => Ok(()),
+        })
+        .and_then(|()| fs::rename(&temporary, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Print the fingerprint of each file (or stdin), like `sha256sum` does, so
+/// that duplicate scenarios sort next to each other.
+fn run_fingerprint(files: &[String], lossy: bool) {
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    let mut failed = false;
+    for file in inputs {
+        let name = file.unwrap_or("-");
+        match Document::parse(&read_source(file, lossy)) {
+            Ok(document) => println!("{:016x}  {}", document.fingerprint(), name),
+            Err(e) => {
+                eprintln!("Error parsing {}: {}", name, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Print the parse tree of each file, or of stdin, as an S-expression or
+/// an indented tree. Syntax errors are part of the tree.
+fn run_dump_cst(files: &[String], sexp: bool, lossy: bool) {
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    for (index, file) in inputs.iter().enumerate() {
+        if files.len() > 1 {
+            if index > 0 {
+                println!();
+            }
+            println!("==> {} <==", file.unwrap_or("<stdin>"));
+        }
+        let source = read_source(*file, lossy);
+        if sexp {
+            print!("{}", cst_sexp(&source));
+        } else {
+            print!("{}", cst_pretty(&source));
+        }
+    }
+}
+
+/// Print the typed document of each file, or of stdin, as JSON, one
+/// document per file. Exits with an error if any does not parse.
+fn run_dump_ast(files: &[String], lossy: bool) {
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    let mut failed = false;
+    for file in inputs {
+        let name = file.unwrap_or("-");
+        match Document::parse(&read_source(file, lossy)) {
+            Ok(document) => print!("{}", ast_json(&document)),
+            Err(e) => {
+                eprintln!("Error parsing {}: {}", name, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Check that formatting each file (or stdin) is safe, without writing
+/// anything, and exit with an error if any is not.
+fn run_verify(files: &[String], settings: &Settings, lossy: bool, messages: MessageFormat) {
+    let inputs: Vec<Option<&str>> = if files.is_empty() {
+        vec![None]
+    } else {
+        files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    let mut failed = false;
+    for file in inputs {
+        let source = read_source(file, lossy);
+        if let Err(e) = verify(&source, &settings.resolve(file)) {
+            let name = file.unwrap_or("<stdin>");
+            // Errors of the output are not at a place of the file
+            let (line, column) = match &e {
+                VerifyError::Input(error) => (error.position.row, error.position.column),
+                VerifyError::Changed { line } => (*line, 0),
+                _ => (0, 0),
+            };
+            let text = e.to_string();
+            let message = Message {
+                file: name,
+                line,
+                column,
+                severity: Severity::Error,
+                code: None,
+                text: &text,
+            };
+            messages.emit(&message, || format!("{}: {}", name, e));
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Output of `git` run with `args`, exiting on failure.
+fn git(args: &[&str]) -> Vec<u8> {
+    let output = Command::new("git").args(args).output().unwrap_or_else(|e| {
+        eprintln!("Error: cannot run git: {}", e);
+        process::exit(1);
+    });
+    if !output.status.success() {
+        eprintln!(
+            "Error: git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        process::exit(1);
+    }
+    output.stdout
+}
+
+/// Check the staged content of the `.validatetest` files added or modified
+/// in the git index, rather than their working tree content, exiting with 1
+/// if any needs formatting.
+fn run_staged(
+    settings: &Settings,
+    show_diff: bool,
+    lossy: bool,
+    colors: Colors,
+    messages: MessageFormat,
+) {
+    let root = String::from_utf8_lossy(&git(&["rev-parse", "--show-toplevel"]))
+        .trim_end()
+        .to_string();
+    let names = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "--diff-filter=ACMR",
+        "-z",
+        "--",
+        ":(top,glob)**/*.validatetest",
+    ]);
+    let mut any_diff = false;
+    for name in names.split(|b| *b == 0).filter(|n| !n.is_empty()) {
+        let name = String::from_utf8_lossy(name);
+        let bytes = git(&["show", &format!(":{}", name)]);
+        let source = decode_source(&name, &bytes, lossy);
+        // Configuration files are looked up from the working tree
+        let path = Path::new(&root).join(name.as_ref());
+        let config = settings.resolve(Some(&path.to_string_lossy()));
+        match format_file(&source, &config, None, colors) {
+            Ok(formatted) if formatted != source => {
+                let line = first_changed_line(&source, &formatted);
+                messages.emit(&Message::needs_formatting(&name, line), || {
+                    format!("{}: {}", name, colors.stderr("needs formatting", YELLOW))
+                });
+                if show_diff {
+                    print!("{}", colors.diff(&source, &formatted, &name));
+                }
+                any_diff = true;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                messages.emit(&Message::syntax(&name, &e.error), || {
+                    format!("{} {}: {}", colors.stderr("Error formatting", RED), name, e)
+                });
+                any_diff = true;
+            }
+        }
+    }
+    if any_diff {
+        process::exit(1);
+    }
+}
+
+/// Modification time of `file`, if `formatted` only changes the layout of its
+/// `source`. Sorting or converting structures is not a layout change.
+fn unchanged_mtime(file: &str, source: &str, formatted: &str) -> Option<SystemTime> {
+    let before = Document::parse(source).ok()?;
+    if !before.semantically_eq(&Document::parse(formatted).ok()?) {
+        return None;
+    }
+    fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Write `content` to `path`, creating its parent directories, exiting on
+/// failure.
+fn write_output(path: &Path, content: &str) {
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, content));
+    if let Err(e) = written {
+        eprintln!("Error writing {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+/// Write `report` if `args` ask for it, to stdout without `--report-file`.
+fn write_report(report: &Report, args: &ReportArgs) {
+    if args.report.is_none() {
+        return;
+    }
+    match &args.report_file {
+        Some(path) => write_output(path, &report.to_json()),
+        None => print!("{}", report.to_json()),
+    }
+}
+
+/// Path of `file` under the output directory `dir`: relative inputs keep
+/// their relative path, absolute ones only their file name. Relative inputs
+/// going up with `..` have no place under `dir`.
+fn mirrored_path(dir: &Path, file: &Path) -> Option<PathBuf> {
+    if file.is_absolute() {
+        return Some(dir.join(file.file_name()?));
+    }
+    let mut relative = PathBuf::new();
+    for component in file.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(dir.join(relative))
+}
+
+/// The path under `dir` of each of `files`, exiting when one has none or
+/// when two would be written to the same one.
+fn mirrored_paths(dir: &Path, files: &[String]) -> HashMap<String, PathBuf> {
+    let mut paths: HashMap<String, PathBuf> = HashMap::new();
+    for file in files {
+        let Some(path) = mirrored_path(dir, Path::new(file)) else {
+            eprintln!(
+                "Error: cannot write {} under {}, give it without '..'",
+                file,
+                dir.display()
+            );
+            process::exit(2);
+        };
+        if let Some((other, _)) = paths
+            .iter()
+            .find(|(other, p)| **p == path && *other != file)
+        {
+            eprintln!(
+                "Error: {} and {} would both be written to {}",
+                other,
+                file,
+                path.display()
+            );
+            process::exit(2);
+        }
+        paths.insert(file.clone(), path);
+    }
+    paths
+}
+
+/// Run a source-to-source `edit` on stdin or on each file, either printing
+/// the result or rewriting the files in place.
+fn run_edit(
+    files: &[String],
+    in_place: bool,
+    global: &GlobalArgs,
+    edit: impl Fn(&str) -> Result<String, String>,
+) {
+    if files.is_empty() {
+        let source = read_source(None, global.lossy);
+        match edit(&source) {
+            Ok(edited) => print!("{}", edited),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    for file in files {
+        let source = read_source(Some(file), global.lossy);
+
+        match edit(&source) {
+            Ok(edited) => {
+                if !in_place {
+                    print!("{}", edited);
+                } else if edited != source {
+                    if let Err(e) = write_in_place(file, &edited, true, None) {
+                        eprintln!("Error writing {}: {}", file, e);
+                        process::exit(1);
+                    }
+                    if !global.quiet {
+                        eprintln!("Edited: {}", file);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Error editing {}: {}", file, e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn run_rename_var(args: RenameVarArgs, global: &GlobalArgs) {
+    run_edit(&args.files, args.in_place, global, |source| {
+        rename_variable(source, &args.old, &args.new)
+            .map(|edits| apply_edits(source, &edits))
+            .map_err(|e| e.to_string())
+    });
+}
+
+fn run_extract_var(args: ExtractVarArgs, global: &GlobalArgs) {
+    run_edit(&args.files, args.in_place, global, |source| {
+        // Without an explicit value, extract the most repeated string
+        let literal = match &args.value {
+            Some(value) => value.clone(),
+            None => find_repeated_literals(source, 2)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .next()
+                .map(|(literal, _)| literal)
+                .ok_or_else(|| "no repeated value to extract".to_string())?,
+        };
+        extract_variable(source, &literal, &args.name)
+            .map(|edits| apply_edits(source, &edits))
+            .map_err(|e| e.to_string())
+    });
+}
+
+fn run_inline_var(args: InlineVarArgs, global: &GlobalArgs) {
+    run_edit(&args.files, args.in_place, global, |source| {
+        inline_variable(source, &args.name)
+            .map(|edits| apply_edits(source, &edits))
+            .map_err(|e| e.to_string())
+    });
+}
+
+fn run_rewrite(args: RewriteArgs, global: &GlobalArgs) {
+    let pattern: Pattern = args.pattern.parse().unwrap_or_else(|e| {
+        eprintln!("Error: invalid pattern '{}': {}", args.pattern, e);
+        process::exit(2);
+    });
+    let template: Template = args.template.parse().unwrap_or_else(|e| {
+        eprintln!("Error: invalid template '{}': {}", args.template, e);
+        process::exit(2);
+    });
+    if let Some(variable) = template.unbound_variable(&pattern) {
+        let error = RefactorError::UnboundVariable(variable.to_string());
+        eprintln!("Error: {}", error);
+        process::exit(2);
+    }
+    run_edit(&args.files, args.in_place, global, |source| {
+        rewrite(source, &pattern, &template)
+            .map(|edits| apply_edits(source, &edits))
+            .map_err(|e| e.to_string())
+    });
+}
+
+fn run_rename_actions(args: RenameActionsArgs, global: &GlobalArgs) {
+    let renames = fs::read_to_string(&args.mapping)
+        .map_err(|e| e.to_string())
+        .and_then(|text| ActionRenames::from_toml(&text).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: {}: {}", args.mapping.display(), e);
+            process::exit(1);
+        });
+    run_edit(&args.files, args.in_place, global, |source| {
+        rename_actions(source, &renames)
+            .map(|edits| apply_edits(source, &edits))
+            .map_err(|e| e.to_string())
+    });
+}
+
+impl ConvertTarget {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Scenario => "scenario",
+            Self::ValidateTest => "validatetest",
+        }
+    }
+}
+
+fn run_expand(args: ExpandArgs, global: &GlobalArgs) {
+    let settings = Settings {
+        config_file: global.config.clone(),
+        overrides: FileConfig::default(),
+    };
+    // Each file is formatted with its own settings
+    let expand_with = |config: FormatterConfig| {
+        move |source: &str| {
+            let expansion = expand(source, &config).map_err(|e| e.to_string())?;
+            for warning in &expansion.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            Ok(expansion.source)
+        }
+    };
+    if args.files.is_empty() {
+        run_edit(&[], false, global, expand_with(settings.resolve(None)));
+    }
+    for file in &args.files {
+        let config = settings.resolve(Some(file));
+        run_edit(
+            std::slice::from_ref(file),
+            false,
+            global,
+            expand_with(config),
+        );
+    }
+}
+
+/// A line and column, 1-based, and the byte offset of a capture bound.
+#[derive(Serialize)]
+struct JsonPoint {
+    line: usize,
+    column: usize,
+    byte: usize,
+}
+
+#[derive(Serialize)]
+struct JsonRange {
+    start: JsonPoint,
+    end: JsonPoint,
+}
+
+/// A query capture as written by `query --format json`.
+#[derive(Serialize)]
+struct JsonCapture<'a> {
+    file: &'a str,
+    capture: &'a str,
+    pattern: usize,
+    text: &'a str,
+    range: JsonRange,
+}
+
+fn run_query(args: QueryArgs, global: &GlobalArgs) {
+    let text = fs::read_to_string(&args.query).unwrap_or_else(|e| {
+        eprintln!("Error reading {}: {}", args.query.display(), e);
+        process::exit(1);
+    });
+    let query = compile_query(&text).unwrap_or_else(|e| {
+        eprintln!("Error: {}: {}", args.query.display(), e);
+        process::exit(1);
+    });
+
+    let inputs: Vec<Option<&str>> = if args.files.is_empty() {
+        vec![None]
+    } else {
+        args.files.iter().map(|f| Some(f.as_str())).collect()
+    };
+    for file in inputs {
+        let source = read_source(file, global.lossy);
+        let name = file.unwrap_or("<stdin>");
+        for capture in captures(&query, &source) {
+            match args.format {
+                QueryFormat::Text => println!(
+                    "{}:{}:{}: @{}: {}",
+                    name,
+                    capture.start.line + 1,
+                    capture.start.column + 1,
+                    capture.name,
+                    capture.text.replace('\n', "\\n")
+                ),
+                QueryFormat::Json => {
+                    let point = |position: LineCol, byte| JsonPoint {
+                        line: position.line + 1,
+                        column: position.column + 1,
+                        byte,
+                    };
+                    let json = JsonCapture {
+                        file: name,
+                        capture: &capture.name,
+                        pattern: capture.pattern,
+                        text: &capture.text,
+                        range: JsonRange {
+                            start: point(capture.start, capture.range.start),
+                            end: point(capture.end, capture.range.end),
+                        },
+                    };
+                    println!(
+                        "{}",
+                        serde_json::to_string(&json).expect("captures serialize to JSON")
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn run_generate(args: GenerateArgs, global: &GlobalArgs) {
+    let matrix = Matrix::load(&args.matrix).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    let settings = Settings {
+        config_file: global.config.clone(),
+        overrides: FileConfig::default(),
+    };
+
+    for template in &args.templates {
+        let path = Path::new(template);
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(template);
+        let stem = file_name.strip_suffix(".in").unwrap_or(file_name);
+        let stem = stem.strip_suffix(".validatetest").unwrap_or(stem);
+        let directory = match &args.output {
+            Some(dir) => dir.clone(),
+            None => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        // The settings of the generated files, where they are written
+        let output = directory.join(format!("{}.validatetest", stem));
+        let config = settings.resolve(Some(&output.to_string_lossy()));
+        let source = read_source(Some(template), global.lossy);
+        let generated = matrix.generate(&source, stem, &config).unwrap_or_else(|e| {
+            eprintln!("Error generating from {}: {}", template, e);
+            process::exit(1);
+        });
+        if let Err(e) = fs::create_dir_all(&directory) {
+            eprintln!("Error creating {}: {}", directory.display(), e);
+            process::exit(1);
+        }
+        for (name, scenario) in generated {
+            let output = directory.join(name);
+            if let Err(e) = fs::write(&output, scenario) {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                process::exit(1);
+            }
+            if !global.quiet {
+                eprintln!("Generated: {}", output.display());
+            }
+        }
+    }
+}
+
+/// Write the man pages, `validatetest-fmt.1`, `validatetest-fmt-check.1`...,
+/// to `dir`.
+fn run_generate_man(dir: &Path) {
+    let written =
+        fs::create_dir_all(dir).and_then(|()| clap_mangen::generate_to(Cli::command(), dir));
+    if let Err(e) = written {
+        eprintln!("Error writing the man pages to {}: {}", dir.display(), e);
+        process::exit(1);
+    }
+}
+
+fn run_convert(args: ConvertArgs, global: &GlobalArgs) {
+    let forced_target = args.to;
+    let pipeline = &args.pipeline;
+
+    let convert = |source: &str, target: ConvertTarget| {
+        let converted = match target {
+            ConvertTarget::ValidateTest => scenario_to_validatetest(source, pipeline),
+            ConvertTarget::Scenario => validatetest_to_scenario(source),
+        }
+        .map_err(|e| e.to_string())?;
+        for warning in &converted.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        Ok::<_, String>(converted.source)
+    };
+
+    if args.files.is_empty() {
+        let target = forced_target.unwrap_or_else(|| {
+            eprintln!("Error: --to is required when reading from stdin");
+            process::exit(1);
+        });
+        return run_edit(&[], false, global, |source| convert(source, target));
+    }
+
+    for file in &args.files {
+        let path = Path::new(file);
+        // By default convert to the other format
+        let target =
+            forced_target.unwrap_or(if path.extension().is_some_and(|e| e == "scenario") {
+                ConvertTarget::ValidateTest
+            } else {
+                ConvertTarget::Scenario
+            });
+        let source = read_source(Some(file), global.lossy);
+        let converted = convert(&source, target).unwrap_or_else(|e| {
+            eprintln!("Error converting {}: {}", file, e);
+            process::exit(1);
+        });
+
+        if args.in_place {
+            // Write next to the input, with the extension of the new format
+            let output = path.with_extension(target.extension());
+            if let Err(e) = fs::write(&output, &converted) {
+                eprintln!("Error writing {}: {}", output.display(), e);
+                process::exit(1);
+            }
+            if !global.quiet {
+                eprintln!("Converted: {} -> {}", file, output.display());
+            }
+        } else {
+            print!("{}", converted);
+        }
+    }
+}
+
+/// Print a diagnostic as `file:line:column: severity[code]: message`.
+fn report_diagnostic(
+    file: &str,
+    line: usize,
+    column: usize,
+    diagnostic: &Diagnostic,
+    messages: MessageFormat,
+) {
+    // With --fix on stdin, stdout holds the fixed source
+    messages.emit(&Message::lint(file, line, column, diagnostic), || {
+        format!(
+            "{}:{}:{}: {}[{}]: {}",
+            file,
+            line + 1,
+            column + 1,
+            diagnostic.severity,
+            diagnostic.code,
+            diagnostic.message
+        )
+    });
+}
+
+fn run_lint(args: LintArgs, global: &GlobalArgs) {
+    let fix = args.fix;
+    let definitions: Vec<&str> = args.actions.iter().map(String::as_str).collect();
+    #[allow(unused_mut)]
+    let mut base_config = LintConfig {
+        registry: ActionRegistry::load(&definitions).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }),
+        style: args.style,
+        media_info: args.media_info.as_ref().map(|path| {
+            MediaInfo::load(path).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            })
+        }),
+        ..Default::default()
+    };
+    #[cfg(feature = "gstreamer")]
+    match tree_sitter_validatetest::pipeline::installed_elements() {
+        Ok(elements) => base_config.elements = Some(elements),
+        Err(e) => eprintln!(
+            "Warning: cannot list the installed GStreamer elements ({}), element names are not checked",
+            e
+        ),
+    }
+    let levels: Vec<(String, Level)> = [
+        (&args.allow, Level::Allow),
+        (&args.warn, Level::Warn),
+        (&args.deny, Level::Deny),
+    ]
+    .into_iter()
+    .flat_map(|(names, level)| names.iter().map(move |name| (name.clone(), level)))
+    .collect();
+    let baseline = args.baseline.as_ref().map(|path| {
+        Baseline::load(path).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        })
+    });
+    let mut generated = args.generate_baseline.as_deref().map(Baseline::new);
+    let settings = Settings {
+        config_file: global.config.clone(),
+        overrides: FileConfig {
+            dialect: args.dialect.as_deref().map(setting),
+            ..FileConfig::default()
+        },
+    };
+
+    let mut inputs = Vec::new();
+    if args.files.is_empty() {
+        inputs.push(("<stdin>".to_string(), read_source(None, global.lossy)));
+    }
+    for file in &args.files {
+        inputs.push((file.clone(), read_source(Some(file), global.lossy)));
+    }
+
+    let mut has_errors = false;
+    let mut report = Report::new("lint");
+    for (file, source) in inputs {
+        let start = Instant::now();
+        let path = (!args.files.is_empty()).then_some(file.as_str());
+        let mut config = base_config.clone();
+        config.require_meta = file.ends_with(".validatetest");
+        let merged = settings.merged(path);
+        merged.lint.apply(&mut config);
+        config.add_levels(levels.iter().cloned());
+        // Names are checked once the rules of the configuration are known
+        if let Some(name) = config.unknown_levels().next() {
+            eprintln!("Error: unknown lint or category '{}'", name);
+            process::exit(2);
+        }
+        config.dialect = merged.dialect.unwrap_or(defaults(path).dialect);
+
+        let mut source = source;
+        let mut diagnostics = match lint_with(&source, &config) {
+            Ok(diagnostics) => diagnostics,
+            Err(e) => {
+                global
+                    .message_format
+                    .emit(&Message::syntax(&file, &e), || format!("{}: {}", file, e));
+                has_errors = true;
+                report.push(
+                    FileReport::new(&file, Status::Error, start.elapsed())
+                        .with_errors(vec![ErrorReport::syntax(&e)]),
+                );
+                continue;
+            }
+        };
+
+        if fix && diagnostics.iter().any(|d| !d.fix.is_empty()) {
+            source = apply_fixes(&source, &diagnostics);
+            if !args.files.is_empty() {
+                if let Err(e) = write_in_place(&file, &source, true, None) {
+                    eprintln!("Error writing {}: {}", file, e);
+                    process::exit(1);
+                }
+                if !global.quiet {
+                    eprintln!("Fixed: {}", file);
+                }
+            }
+            // Report what is left
+            diagnostics = lint_with(&source, &config).unwrap_or_default();
+        }
+        if fix && args.files.is_empty() {
+            print!("{}", source);
+        }
+        if let Some(generated) = &mut generated {
+            generated.add(&file, &diagnostics);
+            report.push(FileReport::new(&file, Status::Passed, start.elapsed()));
+            continue;
+        }
+        if let Some(baseline) = &baseline {
+            diagnostics = baseline.new_diagnostics(&file, diagnostics);
+        }
+
+        let mut counts = LintCounts::default();
+        let mut errors = Vec::new();
+        for diagnostic in diagnostics {
+            let (line, column) = line_col(&source, diagnostic.range.start);
+            report_diagnostic(&file, line, column, &diagnostic, global.message_format);
+            counts.add(&diagnostic);
+            if diagnostic.severity == Severity::Error {
+                errors.push(ErrorReport::lint(&diagnostic, line, column));
+            }
+        }
+        has_errors |= !errors.is_empty();
+        let status = if errors.is_empty() {
+            Status::Passed
+        } else {
+            Status::Failed
+        };
+        let mut file_report = FileReport::new(&file, status, start.elapsed()).with_errors(errors);
+        file_report.lint = Some(counts);
+        report.push(file_report);
+    }
+    if let (Some(path), Some(generated)) = (&args.generate_baseline, &generated) {
+        write_output(path, &generated.to_json());
+        if !global.quiet {
+            eprintln!(
+                "Wrote a baseline of {} diagnostics to {}",
+                generated.count(),
+                path.display()
+            );
+        }
+    }
+    write_report(&report, &args.report);
+
+    if has_errors {
+        process::exit(1);
+    }
+}
+
+/// The settings given by the formatting options.
+fn overrides(args: FormatArgs) -> FileConfig {
+    FileConfig {
+        indent: args.indent,
+        line_length: args.line_length,
+        sort_expected_issues: args.sort_expected_issues.then_some(true),
+        keep_quoted_structures: args.keep_quoted_structures.then_some(true),
+        quoted_structures: args.quoted_structure,
+        convert_quoted_structures: args.convert_quoted_structures.then_some(true),
+        legacy_quoted_issues: args.legacy_quoted_issues.then_some(true),
+        keep_long_strings: args.keep_long_strings.then_some(true),
+        split_long_args: args.split_long_args.then_some(true),
+        line_ending: args.line_ending.as_deref().map(setting),
+        strip_bom: args.strip_bom.then_some(true),
+        array_packing: args.array_packing.as_deref().map(setting),
+        array_packing_threshold: args.array_packing_threshold,
+        brace_style: args.brace_style.as_deref().map(setting),
+        no_block_trailing_comma: args.no_block_trailing_comma.then_some(true),
+        no_array_trailing_comma: args.no_array_trailing_comma.then_some(true),
+        spaces_around_equals: args.spaces_around_equals.then_some(true),
+        continuation_indent: args.continuation_indent.map(|value| {
+            value
+                .parse()
+                .expect("clap only accepts valid continuation indents")
+        }),
+        max_inline_fields: args.max_inline_fields,
+        preserve_breaks: args.preserve_breaks.then_some(true),
+        minimal_diff: args.minimal_diff.then_some(true),
+        semicolon: args.semicolon.as_deref().map(setting),
+        dialect: args.dialect.as_deref().map(setting),
+        flow_align: args.flow_align.as_deref().map(setting),
+        field_order: args.field_order.into_iter().collect(),
+        ..FileConfig::default()
+    }
+}
+
+/// An option value among those clap let through, read like the same
+/// setting in configuration files.
+fn setting<T: DeserializeOwned>(value: &str) -> T {
+    let deserializer: StrDeserializer<'_, serde::de::value::Error> = value.into_deserializer();
+    T::deserialize(deserializer).expect("clap only accepts the values of the setting")
+}
+
+impl From<Embedded> for HostLanguage {
+    fn from(embedded: Embedded) -> Self {
+        match embedded {
+            Embedded::C => HostLanguage::C,
+            Embedded::Python => HostLanguage::Python,
+        }
+    }
+}
+
+/// `check`, or `diff` with `show_diff`, as the `fmt` options they stand for.
+fn check_args(args: CheckArgs, show_diff: bool) -> FmtArgs {
+    FmtArgs {
+        check: true,
+        diff: show_diff,
+        staged: args.staged,
+        patch_from: args.patch_from,
+        embedded: args.embedded,
+        format: args.format,
+        report: args.report,
+        files: args.files,
+        ..FmtArgs::default()
+    }
+}
+
+fn run_fmt(args: FmtArgs, global: &GlobalArgs, colors: Colors, logging: bool) {
+    let files = args.files;
+    let (in_place, output, lossy, quiet) = (args.in_place, args.output, global.lossy, global.quiet);
+    let messages = global.message_format;
+    let embedded = args.embedded.map(HostLanguage::from);
+    // --diff implies --check
+    let (check_only, show_diff) = (args.check || args.diff, args.diff);
+    let command = match (args.check, args.diff) {
+        (_, true) => "diff",
+        (true, false) => "check",
+        (false, false) => "fmt",
+    };
+    // Without a report file, the report replaces the output
+    let quiet_stdout = args.report.report.is_some() && args.report.report_file.is_none();
+    let settings = Settings {
+        config_file: global.config.clone(),
+        overrides: overrides(args.format),
+    };
+    if args.print_config {
+        run_print_config(&files, &settings);
+        return;
+    }
+
+    if args.staged {
+        run_staged(&settings, show_diff, lossy, colors, messages);
+        return;
+    }
+
+    if args.fingerprint {
+        run_fingerprint(&files, lossy);
+        return;
+    }
+
+    if let Some(format) = args.dump_cst {
+        run_dump_cst(&files, format == CstFormat::Sexp, lossy);
+        return;
+    }
+
+    if args.dump_ast {
+        run_dump_ast(&files, lossy);
+        return;
+    }
+
+    if args.verify {
+        run_verify(&files, &settings, lossy, messages);
+        return;
+    }
+
+    // A patch names the files to format, and the lines to format in them
+    let mut touched = HashMap::new();
+    let files = match &args.patch_from {
+        Some(patch) => {
+            let patch = read_source((patch != "-").then_some(patch.as_str()), lossy);
+            let mut files = Vec::new();
+            for (file, lines) in touched_lines(&patch) {
+                let extension = Path::new(&file).extension();
+                if extension.is_some_and(|e| e == "validatetest" || e == "scenario") {
+                    files.push(file.clone());
+                    touched.insert(file, lines);
+                }
+            }
+            if files.is_empty() {
+                return;
+            }
+            files
+        }
+        None => files,
+    };
+
+    // Several inputs, or one and an existing directory, go to a directory
+    let output_dir = output
+        .as_ref()
+        .filter(|output| files.len() > 1 || output.is_dir());
+    // Checked before anything is written
+    let mirrored = output_dir.map(|dir| mirrored_paths(dir, &files));
+
+    // Read from stdin if no files provided
+    if files.is_empty() {
+        let _span = tracing::info_span!("file", path = "<stdin>").entered();
+        let start = Instant::now();
+        let source = read_source(None, lossy);
+        let config = settings.resolve(None);
+        let result = format_file(&source, &config, embedded, colors);
+        tracing::info!(elapsed = ?start.elapsed(), "formatted");
+
+        match result {
+            Ok(formatted) => {
+                if check_only {
+                    if formatted != source {
+                        if show_diff {
+                            print!("{}", colors.diff(&source, &formatted, "<stdin>"));
+                        }
+                        process::exit(1);
+                    }
+                } else if let Some(output) = &output {
+                    write_output(output, &formatted);
+                } else {
+                    print!("{}", formatted);
+                }
+            }
+            Err(e) => {
+                messages.emit(&Message::syntax("<stdin>", &e.error), || {
+                    format!("{} {}", colors.stderr("Error:", RED), e)
+                });
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let jobs = global
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    // Only batches get a progress bar and a summary, and only the summary of
+    // checks and in-place runs: other runs write the files to stdout. Short
+    // and JSON messages are left alone on stderr for the tools reading them
+    let summarize = files.len() > 1 && !quiet && messages == MessageFormat::Human;
+    let batch = Batch {
+        settings: &settings,
+        embedded,
+        lossy,
+        jobs,
+        colors,
+        // Log lines would break the bar
+        progress: Progress::new(
+            files.len(),
+            summarize && !logging && io::stderr().is_terminal(),
+        ),
+        touched,
+    };
+    let mut summary = Summary::default();
+    let mut report = Report::new(command);
+    if check_only {
+        let results = check_files(&files, &batch, show_diff && !quiet_stdout);
+        batch.progress.finish();
+        for (file, (result, elapsed)) in files.iter().zip(results) {
+            let status = match result {
+                Ok(None) => {
+                    summary.unchanged += 1;
+                    FileReport::new(file, Status::Unchanged, elapsed)
+                }
+                Ok(Some((line, diff))) => {
+                    messages.emit(&Message::needs_formatting(file, line), || {
+                        format!("{}: {}", file, colors.stderr("needs formatting", YELLOW))
+                    });
+                    print!("{}", diff);
+                    summary.changed += 1;
+                    FileReport::new(file, Status::NeedsFormatting, elapsed)
+                }
+                Err(e) => {
+                    messages.emit(&Message::syntax(file, &e.error), || {
+                        format!("{} {}: {}", colors.stderr("Error formatting", RED), file, e)
+                    });
+                    summary.errors += 1;
+                    FileReport::new(file, Status::Error, elapsed)
+                        .with_errors(vec![ErrorReport::syntax(&e.error)])
+                }
+            };
+            report.push(status);
+        }
+        if summarize {
+            summary.print("to format", "already formatted");
+        }
+        write_report(&report, &args.report);
+        if summary.changed > 0 || summary.errors > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let results = format_files(&files, &batch);
+    batch.progress.finish();
+    for (index, (file, (source, result, elapsed))) in files.iter().zip(results).enumerate() {
+        let status = match &result {
+            Ok(formatted) if *formatted == source => Status::Unchanged,
+            Ok(_) => Status::Formatted,
+            Err(_) => Status::Error,
+        };
+        let mut file_report = FileReport::new(file, status, elapsed);
+        match result {
+            Ok(formatted) => {
+                if in_place {
+                    if formatted == source {
+                        summary.unchanged += 1;
+                        report.push(file_report);
+                        continue;
+                    }
+                    let modified = args
+                        .preserve_mtime
+                        .then(|| unchanged_mtime(file, &source, &formatted))
+                        .flatten();
+                    if let Err(e) = write_in_place(file, &formatted, args.follow_symlinks, modified)
+                    {
+                        eprintln!("Error writing {}: {}", file, e);
+                        summary.errors += 1;
+                        file_report.status = Status::Error;
+                        report.push(file_report.with_errors(vec![ErrorReport::write(&e)]));
+                        continue;
+                    }
+                    if !quiet {
+                        eprintln!("{} {}", colors.stderr("Formatted:", GREEN), file);
+                    }
+                    summary.changed += 1;
+                } else if let Some(mirrored) = &mirrored {
+                    write_output(&mirrored[file], &formatted);
+                } else if let Some(output) = &output {
+                    write_output(output, &formatted);
+                } else if !quiet_stdout {
+                    // Tell the outputs apart, the way `head` does
+                    if files.len() > 1 {
+                        if index > 0 {
+                            println!();
+                        }
+                        println!("==> {} <==", file);
+                    }
+                    print!("{}", formatted);
+                }
+            }
+            Err(e) => {
+                messages.emit(&Message::syntax(file, &e.error), || {
+                    format!("{} {}: {}", colors.stderr("Error formatting", RED), file, e)
+                });
+                summary.errors += 1;
+                file_report.errors.push(ErrorReport::syntax(&e.error));
+            }
+        }
+        report.push(file_report);
+    }
+    if summarize && in_place {
+        summary.print("formatted", "unchanged");
+    }
+    write_report(&report, &args.report);
+    if summary.errors > 0 {
+        process::exit(1);
+    }
+}
+
+/// `args` with `fmt` inserted when they are those of the
+/// `validatetest-fmt [OPTIONS] [FILE]...` invocation, which predates the
+/// subcommands.
+fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut command = Cli::command();
+    command.build();
+    let fmt = command.find_subcommand("fmt").expect("fmt is a subcommand");
+    // Whether `option`, without its leading `-`, is followed by its value
+    let takes_value = |option: &str| {
+        let mut arguments = command.get_arguments().chain(fmt.get_arguments());
+        match option.strip_prefix('-') {
+            Some(long) => {
+                arguments.any(|a| a.get_long() == Some(long) && a.get_action().takes_values())
+            }
+            None => option.chars().last().is_some_and(|short| {
+                arguments.any(|a| a.get_short() == Some(short) && a.get_action().takes_values())
+            }),
+        }
+    };
+
+    let mut i = 1;
+    while let Some(arg) = args.get(i).map(|arg| arg.to_string_lossy()) {
+        if arg == "--" {
+            break;
+        }
+        match arg.strip_prefix('-').filter(|option| !option.is_empty()) {
+            Some("h" | "-help" | "V" | "-version") => return args,
+            Some(option) => {
+                if !option.contains('=') && takes_value(option) {
+                    i += 1;
+                }
+            }
+            None if command.find_subcommand(arg.as_ref()).is_some() => return args,
+            None => break,
+        }
+        i += 1;
+    }
+    args.insert(1, OsString::from("fmt"));
+    args
+}
+
+fn main() {
+    let cli = Cli::parse_from(with_default_subcommand(env::args_os().collect()));
+    let global = cli.global;
+    let colors = Colors {
+        stdout: global.color.enabled(&io::stdout()),
+        stderr: global.color.enabled(&io::stderr()),
+    };
+    let logging = init_logging(global.verbose.into(), colors.stderr);
+
+    match cli.command {
+        cli::Command::Fmt(args) => run_fmt(args, &global, colors, logging),
+        cli::Command::Check(args) => run_fmt(check_args(args, false), &global, colors, logging),
+        cli::Command::Diff(args) => run_fmt(check_args(args, true), &global, colors, logging),
+        cli::Command::Lint(args) => run_lint(args, &global),
+        cli::Command::Convert(args) => run_convert(args, &global),
+        cli::Command::RenameVar(args) => run_rename_var(args, &global),
+        cli::Command::ExtractVar(args) => run_extract_var(args, &global),
+        cli::Command::InlineVar(args) => run_inline_var(args, &global),
+        cli::Command::Rewrite(args) => run_rewrite(args, &global),
+        cli::Command::RenameActions(args) => run_rename_actions(args, &global),
+        cli::Command::Expand(args) => run_expand(args, &global),
+        cli::Command::Generate(args) => run_generate(args, &global),
+        cli::Command::Query(args) => run_query(args, &global),
+        cli::Command::GenerateMan(args) => run_generate_man(&args.dir),
+    }
+}
@@ -0,0 +1,206 @@
+//! The summary of a run written by `--report json`, meant to be archived by
+//! CI and compared from one run to the next.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tree_sitter_validatetest::lint::{Diagnostic, Severity};
+use tree_sitter_validatetest::ParseError;
+
+/// What a run did with a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Already formatted.
+    Unchanged,
+    /// Formatted by `fmt`.
+    Formatted,
+    /// Found not formatted by `check` or `diff`.
+    NeedsFormatting,
+    /// Linted without errors, maybe with warnings.
+    Passed,
+    /// Linted with errors.
+    Failed,
+    /// Not processed, as it does not parse or could not be written.
+    Error,
+}
+
+/// An error found in a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    /// 1-based line and column, none for write errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// The lint code, none for syntax and write errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    pub message: String,
+}
+
+impl ErrorReport {
+    pub fn syntax(error: &ParseError) -> Self {
+        Self {
+            line: Some(error.position.row + 1),
+            column: Some(error.position.column + 1),
+            code: None,
+            message: "syntax error".to_string(),
+        }
+    }
+
+    /// A file that could not be written.
+    pub fn write(error: &std::io::Error) -> Self {
+        Self {
+            line: None,
+            column: None,
+            code: None,
+            message: error.to_string(),
+        }
+    }
+
+    /// `diagnostic`, found at the 0-based `line` and `column`.
+    pub fn lint(diagnostic: &Diagnostic, line: usize, column: usize) -> Self {
+        Self {
+            line: Some(line + 1),
+            column: Some(column + 1),
+            code: Some(diagnostic.code),
+            message: diagnostic.message.clone(),
+        }
+    }
+}
+
+/// Diagnostics by severity and by lint code.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub codes: BTreeMap<&'static str, usize>,
+}
+
+impl LintCounts {
+    pub fn add(&mut self, diagnostic: &Diagnostic) {
+        match diagnostic.severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        *self.codes.entry(diagnostic.code).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: &LintCounts) {
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        for (code, count) in &other.codes {
+            *self.codes.entry(code).or_default() += count;
+        }
+    }
+}
+
+/// What happened to a file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub status: Status,
+    #[serde(rename = "duration_ms", serialize_with = "milliseconds")]
+    pub duration: Duration,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorReport>,
+    /// The diagnostics of `lint`, errors and warnings alike.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lint: Option<LintCounts>,
+}
+
+impl FileReport {
+    pub fn new(path: &str, status: Status, duration: Duration) -> Self {
+        Self {
+            path: path.to_string(),
+            status,
+            duration,
+            errors: Vec::new(),
+            lint: None,
+        }
+    }
+
+    pub fn with_errors(mut self, errors: Vec<ErrorReport>) -> Self {
+        self.errors = errors;
+        self
+    }
+}
+
+fn milliseconds<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// The files of a run, in the order given.
+#[derive(Debug)]
+pub struct Report {
+    command: &'static str,
+    start: Instant,
+    files: Vec<FileReport>,
+}
+
+/// The totals of a [`Report`].
+#[derive(Serialize)]
+struct Summary {
+    files: usize,
+    statuses: BTreeMap<Status, usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lint: Option<LintCounts>,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    tool: &'static str,
+    version: &'static str,
+    command: &'static str,
+    #[serde(rename = "duration_ms", serialize_with = "milliseconds")]
+    duration: Duration,
+    summary: Summary,
+    files: &'a [FileReport],
+}
+
+impl Report {
+    /// Start timing a run of the `command` subcommand.
+    pub fn new(command: &'static str) -> Self {
+        Self {
+            command,
+            start: Instant::now(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    /// The report as a JSON document, timed until now.
+    pub fn to_json(&self) -> String {
+        let mut statuses = BTreeMap::new();
+        let mut lint: Option<LintCounts> = None;
+        for file in &self.files {
+            *statuses.entry(file.status).or_default() += 1;
+            if let Some(counts) = &file.lint {
+                lint.get_or_insert_with(LintCounts::default).merge(counts);
+            }
+        }
+        let document = Document {
+            tool: env!("CARGO_BIN_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            command: self.command,
+            duration: self.start.elapsed(),
+            summary: Summary {
+                files: self.files.len(),
+                statuses,
+                lint,
+            },
+            files: &self.files,
+        };
+        let mut json = serde_json::to_string_pretty(&document).expect("reports serialize to JSON");
+        json.push('\n');
+        json
+    }
+}
@@ -0,0 +1,166 @@
+//! Search GStreamer ValidateTest files for structures matching a pattern
+//!
+//! Usage: validatetest-grep [OPTIONS] <PATTERN> <FILE>...
+//!
+//! Options:
+//!   -l, --files-with-matches  Print only the names of the files with matches
+//!   -c, --count               Print only the number of matches of each file
+//!   -A, --after-context <N>   Print N lines after each match
+//!   -B, --before-context <N>  Print N lines before each match
+//!   -C, --context <N>         Print N lines before and after each match
+
+use std::fs;
+use std::process;
+
+use clap::Parser;
+use tree_sitter_validatetest::encoding::decode;
+use tree_sitter_validatetest::pattern::{find_matches, Match, Pattern};
+
+/// Search GStreamer ValidateTest files for structures matching a pattern
+///
+/// Prints the structures of the files matching PATTERN, a partial
+/// structure: 'seek, rate=2.0' matches every seek with a rate of 2.0,
+/// whatever its other fields and however it is written. Exits with 1 when
+/// nothing matches, and 2 on errors.
+#[derive(Debug, Parser)]
+#[command(name = "validatetest-grep", version)]
+struct Cli {
+    /// Print only the names of the files with matches
+    #[arg(short = 'l', long, conflicts_with = "count")]
+    files_with_matches: bool,
+    /// Print only the number of matches of each file
+    #[arg(short, long)]
+    count: bool,
+    /// Print N lines after each match
+    #[arg(short = 'A', long, value_name = "N")]
+    after_context: Option<usize>,
+    /// Print N lines before each match
+    #[arg(short = 'B', long, value_name = "N")]
+    before_context: Option<usize>,
+    /// Print N lines before and after each match
+    #[arg(short = 'C', long, value_name = "N")]
+    context: Option<usize>,
+    /// The partial structure to look for
+    pattern: String,
+    /// Files to search
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Lines,
+    FilesWithMatches,
+    Count,
+}
+
+/// Print the lines of `matches` with `before` and `after` lines of context,
+/// as grep does: `:` after the line numbers of matches, `-` after those of
+/// context lines, and `--` between groups of lines that are not adjacent.
+fn print_lines(prefix: Option<&str>, source: &str, matches: &[Match], before: usize, after: usize) {
+    let lines: Vec<&str> = source.lines().collect();
+    // Line ranges to print, the lines of matches flagged
+    let mut matched = vec![false; lines.len()];
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for m in matches {
+        // A range ending at the start of a line does not include it
+        let last = if m.end.column == 0 && m.end.line > m.start.line {
+            m.end.line - 1
+        } else {
+            m.end.line
+        };
+        for flag in &mut matched[m.start.line..=last.min(lines.len() - 1)] {
+            *flag = true;
+        }
+        let first = m.start.line.saturating_sub(before);
+        let last = (last + after).min(lines.len() - 1);
+        match groups.last_mut() {
+            Some(group) if first <= group.1 + 1 => group.1 = group.1.max(last),
+            _ => groups.push((first, last)),
+        }
+    }
+
+    for (i, &(first, last)) in groups.iter().enumerate() {
+        if i > 0 && (before > 0 || after > 0) {
+            println!("--");
+        }
+        for (line, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+            let separator = if matched[line] { ':' } else { '-' };
+            match prefix {
+                Some(file) => println!("{}{}{}{}{}", file, separator, line + 1, separator, text),
+                None => println!("{}{}{}", line + 1, separator, text),
+            }
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let mode = if cli.files_with_matches {
+        Mode::FilesWithMatches
+    } else if cli.count {
+        Mode::Count
+    } else {
+        Mode::Lines
+    };
+    // -A and -B take precedence over -C, as in grep
+    let before = cli.before_context.or(cli.context).unwrap_or(0);
+    let after = cli.after_context.or(cli.context).unwrap_or(0);
+    let files = cli.files;
+    let pattern: Pattern = cli.pattern.parse().unwrap_or_else(|e| {
+        eprintln!("Error: invalid pattern '{}': {}", cli.pattern, e);
+        process::exit(2);
+    });
+
+    // Like grep, name the files only when there are several
+    let with_names = files.len() > 1;
+    let mut found = false;
+    let mut failed = false;
+    for file in &files {
+        let source = match fs::read(file) {
+            Ok(bytes) => match decode(&bytes) {
+                Ok(source) => source.to_string(),
+                Err(e) => {
+                    eprintln!("Error reading {}: {}", file, e);
+                    failed = true;
+                    continue;
+                }
+            },
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file, e);
+                failed = true;
+                continue;
+            }
+        };
+        let matches = match find_matches(&pattern, &source) {
+            Ok(matches) => matches,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                failed = true;
+                continue;
+            }
+        };
+        found |= !matches.is_empty();
+
+        let prefix = with_names.then_some(file.as_str());
+        match mode {
+            Mode::FilesWithMatches if !matches.is_empty() => println!("{}", file),
+            Mode::FilesWithMatches => {}
+            Mode::Count => match prefix {
+                Some(file) => println!("{}:{}", file, matches.len()),
+                None => println!("{}", matches.len()),
+            },
+            Mode::Lines if !matches.is_empty() => {
+                print_lines(prefix, &source, &matches, before, after)
+            }
+            Mode::Lines => {}
+        }
+    }
+
+    if failed {
+        process::exit(2);
+    }
+    if !found {
+        process::exit(1);
+    }
+}
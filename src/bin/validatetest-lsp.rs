@@ -0,0 +1,61 @@
+//! Language server for GStreamer ValidateTest files
+//!
+//! Usage: validatetest-lsp [OPTIONS]
+//!
+//! Options:
+//!   --actions <PATH>  Load action definitions from a .json dump or .toml file
+
+use std::env;
+use std::process;
+
+use tree_sitter_validatetest::lsp;
+use tree_sitter_validatetest::registry::ActionRegistry;
+
+fn print_usage() {
+    eprintln!("Usage: validatetest-lsp [OPTIONS]");
+    eprintln!();
+    eprintln!("Serves the Language Server Protocol on stdin/stdout.");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --actions <PATH>  Load action definitions from a gst-validate .json dump or");
+    eprintln!("                    a .toml file (repeatable)");
+    eprintln!("  -h, --help        Show this help message");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut definitions: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-h" | "--help" => {
+                print_usage();
+                process::exit(0);
+            }
+            "--actions" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --actions requires a value");
+                    process::exit(1);
+                }
+                definitions.push(args[i].clone());
+            }
+            arg => {
+                eprintln!("Error: unknown argument {}", arg);
+                process::exit(1);
+            }
+        }
+        i += 1;
+    }
+
+    let registry = ActionRegistry::load(&definitions).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    if let Err(e) = lsp::run(registry) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Merge GStreamer ValidateTest scenario fragments
+//!
+//! Usage: validatetest-merge [OPTIONS] <FILE>...
+//!
+//! Options:
+//!   -o, --output <PATH>  Write the merged scenario to PATH instead of stdout
+//!   --strict             Exit with an error when meta fields conflict
+//!   --config <PATH>      Read the formatter settings from PATH
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::Parser;
+use tree_sitter_validatetest::config::{self, FileConfig};
+use tree_sitter_validatetest::dialect::Dialect;
+use tree_sitter_validatetest::encoding::decode;
+use tree_sitter_validatetest::format::{format_source, FormatterConfig};
+use tree_sitter_validatetest::merge::{merge_fragments, Fragment};
+
+/// Merge GStreamer ValidateTest scenario fragments
+///
+/// Combines scenario fragments into one: meta structures are merged
+/// (configs unioned, args and expected-issues concatenated) and actions are
+/// concatenated in the order the files are given. The result is formatted
+/// with the settings of the `.validatetest-fmt.toml` files applying to the
+/// output, looked up as validatetest-fmt does.
+#[derive(Debug, Parser)]
+#[command(name = "validatetest-merge", version)]
+struct Cli {
+    /// Write the merged scenario to PATH instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Exit with an error when meta fields conflict
+    #[arg(long)]
+    strict: bool,
+    /// Read the formatter settings from PATH instead of looking up
+    /// .validatetest-fmt.toml files
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Fragments to merge, in order
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<String>,
+}
+
+/// The formatter settings of `output`, stdout without one: those of
+/// `config_file`, or of the configuration files of its directory.
+fn formatter_config(config_file: Option<&Path>, output: Option<&Path>) -> FormatterConfig {
+    let files = match config_file {
+        Some(path) => FileConfig::load(path).map(|config| vec![(path.to_path_buf(), config)]),
+        None => {
+            let dir = output
+                .and_then(Path::parent)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            config::discover(dir)
+        }
+    };
+    let files = files.unwrap_or_else(|e| {
+        eprintln!("Error: invalid configuration {}", e);
+        process::exit(1);
+    });
+    let mut merged = FileConfig::default();
+    for (_, config) in &files {
+        merged.merge(config);
+    }
+    let mut config = FormatterConfig {
+        dialect: output.map(Dialect::from_path).unwrap_or_default(),
+        ..FormatterConfig::default()
+    };
+    merged.apply(&mut config);
+    config
+}
+
+fn main() {
+    let Cli {
+        output,
+        strict,
+        config,
+        files,
+    } = Cli::parse();
+    let config = formatter_config(config.as_deref(), output.as_deref());
+
+    let sources: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let bytes = fs::read(file).unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {}", file, e);
+                process::exit(1);
+            });
+            decode(&bytes).map(str::to_string).unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {}", file, e);
+                process::exit(1);
+            })
+        })
+        .collect();
+    let fragments: Vec<Fragment> = files
+        .iter()
+        .zip(&sources)
+        .map(|(name, source)| Fragment { name, source })
+        .collect();
+
+    let merged = merge_fragments(&fragments).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+
+    for conflict in &merged.conflicts {
+        eprintln!("{}: {}", if strict { "Error" } else { "Warning" }, conflict);
+    }
+    if strict && !merged.conflicts.is_empty() {
+        process::exit(1);
+    }
+
+    let formatted = format_source(&merged.source, &config).unwrap_or_else(|e| {
+        eprintln!("Error formatting merged scenario: {}", e);
+        process::exit(1);
+    });
+
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, &formatted) {
+                eprintln!("Error writing {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", formatted),
+    }
+}
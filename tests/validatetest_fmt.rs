@@ -0,0 +1,428 @@
+//! Runs of the `validatetest-fmt` binary on files of temporary directories.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const UNFORMATTED: &str = "meta,handles-states=true\nplay\n";
+const FORMATTED: &str = "meta, handles-states=true\nplay\n";
+
+/// A directory removed once the test is over.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("validatetest-fmt-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.0.join(name)).unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run `validatetest-fmt` with `args` from `dir`.
+fn run(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_validatetest-fmt"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_check_and_diff_exit_codes() {
+    let dir = TempDir::new("check");
+    dir.write("formatted.validatetest", FORMATTED);
+    dir.write("unformatted.validatetest", UNFORMATTED);
+    dir.write("invalid.validatetest", "meta, handles-states=\n");
+
+    assert_eq!(
+        run(&dir.0, &["--check", "formatted.validatetest"])
+            .status
+            .code(),
+        Some(0)
+    );
+    let output = run(&dir.0, &["--check", "unformatted.validatetest"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("unformatted.validatetest: needs formatting"));
+    assert!(stdout(&output).is_empty());
+
+    let output = run(&dir.0, &["--diff", "unformatted.validatetest"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("-meta,handles-states=true\n+meta, handles-states=true\n"));
+    assert_eq!(
+        run(&dir.0, &["--diff", "formatted.validatetest"])
+            .status
+            .code(),
+        Some(0)
+    );
+
+    assert_eq!(
+        run(&dir.0, &["--check", "invalid.validatetest"])
+            .status
+            .code(),
+        Some(1)
+    );
+    assert_eq!(run(&dir.0, &["--check", "--bogus"]).status.code(), Some(2));
+    // Nothing is written
+    assert_eq!(dir.read("unformatted.validatetest"), UNFORMATTED);
+}
+
+#[test]
+fn test_message_formats() {
+    let dir = TempDir::new("messages");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("invalid.validatetest", "meta, handles-states=\n");
+
+    let output = run(
+        &dir.0,
+        &[
+            "--check",
+            "--message-format",
+            "short",
+            "invalid.validatetest",
+            "a.validatetest",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "invalid.validatetest:1:22: error: syntax error\n\
+         a.validatetest:1:1: error: needs formatting\n"
+    );
+
+    let output = run(
+        &dir.0,
+        &["--check", "--message-format", "json", "a.validatetest"],
+    );
+    let message: serde_json::Value = serde_json::from_str(stderr(&output).trim()).unwrap();
+    assert_eq!(message["file"], "a.validatetest");
+    assert_eq!(message["message"], "needs formatting");
+}
+
+#[test]
+fn test_in_place() {
+    let dir = TempDir::new("in-place");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("b.validatetest", FORMATTED);
+
+    let output = run(&dir.0, &["-i", "a.validatetest", "b.validatetest"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(dir.read("a.validatetest"), FORMATTED);
+    assert_eq!(dir.read("b.validatetest"), FORMATTED);
+    // No temporary file is left behind
+    assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_in_place_keeps_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("permissions");
+    let path = dir.write("a.validatetest", UNFORMATTED);
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+    assert_eq!(
+        run(&dir.0, &["-i", "a.validatetest"]).status.code(),
+        Some(0)
+    );
+    assert_eq!(dir.read("a.validatetest"), FORMATTED);
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_in_place_symlinks() {
+    let dir = TempDir::new("symlinks");
+    dir.write("target.validatetest", UNFORMATTED);
+    let link = dir.0.join("link.validatetest");
+    std::os::unix::fs::symlink("target.validatetest", &link).unwrap();
+
+    let output = run(&dir.0, &["-i", "link.validatetest"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("is a symbolic link"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(dir.read("target.validatetest"), UNFORMATTED);
+
+    let output = run(&dir.0, &["-i", "--follow-symlinks", "link.validatetest"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(dir.read("target.validatetest"), FORMATTED);
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
+
+#[test]
+fn test_in_place_preserve_mtime() {
+    let dir = TempDir::new("mtime");
+    let path = dir.write("a.validatetest", UNFORMATTED);
+    let sorted = dir.write(
+        "b.validatetest",
+        "meta, expected-issues={[expected-issue, issue-id=b], [expected-issue, issue-id=a]}\n",
+    );
+    let past = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    for file in [&path, &sorted] {
+        fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+    }
+
+    let output = run(
+        &dir.0,
+        &[
+            "-i",
+            "--preserve-mtime",
+            "--sort-expected-issues",
+            "a.validatetest",
+            "b.validatetest",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(dir.read("a.validatetest"), FORMATTED);
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), past);
+    // Sorting is not a layout change
+    assert_ne!(fs::metadata(&sorted).unwrap().modified().unwrap(), past);
+}
+
+#[test]
+fn test_several_files() {
+    let dir = TempDir::new("several");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("tests/b.validatetest", FORMATTED);
+
+    let output = run(&dir.0, &["a.validatetest", "tests/b.validatetest"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        format!("==> a.validatetest <==\n{FORMATTED}\n==> tests/b.validatetest <==\n{FORMATTED}")
+    );
+    // Nothing is written
+    assert_eq!(dir.read("a.validatetest"), UNFORMATTED);
+}
+
+#[test]
+fn test_output() {
+    let dir = TempDir::new("output");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("tests/b.validatetest", FORMATTED);
+
+    let output = run(
+        &dir.0,
+        &["-o", "out", "a.validatetest", "tests/b.validatetest"],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(dir.read("out/a.validatetest"), FORMATTED);
+    assert_eq!(dir.read("out/tests/b.validatetest"), FORMATTED);
+
+    // Inputs that would be written outside of the directory, or to the
+    // same file, are refused before anything is written
+    let output = run(
+        &dir.0.join("tests"),
+        &["-o", "out", "../a.validatetest", "b.validatetest"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("without '..'"),
+        "{}",
+        stderr(&output)
+    );
+    dir.write("b.validatetest", FORMATTED);
+    let absolute = |name: &str| dir.0.join(name).to_string_lossy().into_owned();
+    let output = run(
+        &dir.0,
+        &[
+            "-o",
+            "flat",
+            &absolute("tests/b.validatetest"),
+            &absolute("b.validatetest"),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("would both be written to"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!dir.0.join("tests/out").exists());
+    assert!(!dir.0.join("flat").exists());
+
+    let output = run(&dir.0, &["-o", "single.validatetest", "a.validatetest"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(dir.read("single.validatetest"), FORMATTED);
+}
+
+#[test]
+fn test_summary() {
+    let dir = TempDir::new("summary");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("b.validatetest", FORMATTED);
+
+    let output = run(&dir.0, &["-i", "a.validatetest", "b.validatetest"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("1 file formatted, 1 file unchanged, 0 errors"));
+
+    dir.write("a.validatetest", UNFORMATTED);
+    let output = run(&dir.0, &["-i", "--quiet", "a.validatetest"]);
+    assert!(stderr(&output).is_empty());
+}
+
+#[test]
+fn test_report() {
+    let dir = TempDir::new("report");
+    dir.write("a.validatetest", UNFORMATTED);
+    dir.write("b.validatetest", FORMATTED);
+
+    let output = run(
+        &dir.0,
+        &["--report", "json", "a.validatetest", "b.validatetest"],
+    );
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["command"], "fmt");
+    assert_eq!(report["summary"]["files"], 2);
+    assert_eq!(report["files"][0]["path"], "a.validatetest");
+    assert_eq!(report["files"][0]["status"], "formatted");
+    assert_eq!(report["files"][1]["status"], "unchanged");
+}
+
+#[test]
+fn test_lint_fix_twice() {
+    let dir = TempDir::new("lint-fix");
+    dir.write(
+        "a.validatetest",
+        "meta, handles-states=true\nplay\neos\nwait, duration=1.0\nstop\n",
+    );
+
+    let output = run(&dir.0, &["lint", "--fix", "a.validatetest"]);
+    assert!(stderr(&output).contains("Fixed: a.validatetest"));
+    let fixed = dir.read("a.validatetest");
+    assert_eq!(
+        fixed,
+        "meta, handles-states=true\nplay\nwait, duration=1.0\nstop\n"
+    );
+    // Fixing again finds nothing to fix or report
+    let output = run(&dir.0, &["lint", "--fix", "a.validatetest"]);
+    assert!(!stderr(&output).contains("Fixed"), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("unreachable-action"));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    assert_eq!(dir.read("a.validatetest"), fixed);
+}
+
+#[test]
+fn test_expand_and_generate_settings() {
+    let dir = TempDir::new("expand-settings");
+    dir.write(".validatetest-fmt.toml", "spaces-around-equals = true\n");
+    dir.write("a.validatetest", "set-vars, s=1.0\nseek, start=$(s)\n");
+    dir.write("seek.validatetest.in", "seek, start=@start@\n");
+    dir.write("matrix.toml", "[parameters]\nstart = [1.0]\n");
+
+    let output = run(&dir.0, &["expand", "a.validatetest"]);
+    assert_eq!(stdout(&output), "seek, start = 1.0\n");
+    let output = run(
+        &dir.0,
+        &[
+            "generate",
+            "--matrix",
+            "matrix.toml",
+            "-o",
+            "out",
+            "seek.validatetest.in",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(dir.read("out/seek_1.0.validatetest"), "seek, start = 1.0\n");
+}
+
+#[test]
+fn test_generate_man() {
+    let dir = TempDir::new("man");
+    let output = run(&dir.0, &["generate-man", "man1"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(dir
+        .read("man1/validatetest-fmt.1")
+        .contains("validatetest\\-fmt"));
+    assert!(dir.0.join("man1/validatetest-fmt-lint.1").exists());
+    // The command itself has no page, nor is it listed
+    assert!(!dir.0.join("man1/validatetest-fmt-generate-man.1").exists());
+    assert!(!stdout(&run(&dir.0, &["--help"])).contains("generate-man"));
+}
+
+/// Run `git` with `args` in `dir`.
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(["-c", "commit.gpgsign=false"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn test_staged() {
+    let dir = TempDir::new("staged");
+    git(&dir.0, &["init", "-q"]);
+    dir.write("tests/a.validatetest", FORMATTED);
+    dir.write("tests/b.validatetest", FORMATTED);
+    git(&dir.0, &["add", "."]);
+    git(&dir.0, &["commit", "-q", "-m", "initial"]);
+
+    // Unformatted in the index, formatted in the working tree
+    dir.write("tests/a.validatetest", UNFORMATTED);
+    git(&dir.0, &["add", "tests/a.validatetest"]);
+    dir.write("tests/a.validatetest", FORMATTED);
+    // Only unformatted in the working tree
+    dir.write("tests/b.validatetest", &format!("{FORMATTED}# comment\n"));
+    git(&dir.0, &["add", "tests/b.validatetest"]);
+    dir.write("tests/b.validatetest", UNFORMATTED);
+
+    let output = run(&dir.0.join("tests"), &["--staged", "--diff"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        "tests/a.validatetest: needs formatting\n",
+        "{}",
+        stdout(&output)
+    );
+    assert!(stdout(&output).contains("-meta,handles-states=true\n+meta, handles-states=true\n"));
+    // Nothing is written back
+    assert_eq!(dir.read("tests/a.validatetest"), FORMATTED);
+    assert_eq!(dir.read("tests/b.validatetest"), UNFORMATTED);
+
+    git(&dir.0, &["add", "tests/a.validatetest"]);
+    assert_eq!(run(&dir.0, &["check", "--staged"]).status.code(), Some(0));
+}